@@ -0,0 +1,275 @@
+//! The throughput test backends and the http transport of the measurement
+//! the settings are passed in by the app
+
+mod speedtest;
+
+pub use speedtest::{SpeedtestClient, SpeedtestServer};
+
+use anyhow::{bail, Result};
+use futures::{future::BoxFuture, stream::BoxStream};
+use std::time::Duration;
+
+/// the throughput test urls of `Cloudflare`
+const CLOUDFLARE_DOWNLOAD_URL: &str = "https://speed.cloudflare.com/__down?bytes=50000000";
+
+/// the body is discarded
+const CLOUDFLARE_UPLOAD_URL: &str = "https://speed.cloudflare.com/__up";
+
+/// what the throughput is measured against
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct MeasureTarget {
+    /// none if the throughput is not measured
+    pub download_url: Option<String>,
+
+    pub upload_url: Option<String>,
+
+    /// the speedtest server of the urls
+    pub server: Option<SpeedtestServer>,
+
+    /// the exit of the proxy seen by speedtest.net
+    pub client: Option<SpeedtestClient>,
+}
+
+/// the server of the throughput test, by `measure_download_backend`
+pub trait MeasurementBackend: Send + Sync {
+    /// the target for the proxy, which may be looked up through the proxy
+    fn target<'a>(&'a self, proxy: &'a str) -> BoxFuture<'a, Result<MeasureTarget>>;
+}
+
+/// the client seen through the proxy and the speedtest server picked for it
+pub type PickServer = fn(&str) -> BoxFuture<'_, Result<(SpeedtestClient, SpeedtestServer)>>;
+
+/// the settings of the app picking the backend
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct BackendSettings {
+    /// `cloudflare`, `speedtest`, `custom` or `latency`, default is `cloudflare`
+    pub backend: Option<String>,
+
+    /// required by `custom`
+    pub download_url: Option<String>,
+
+    pub upload_url: Option<String>,
+}
+
+pub struct Cloudflare;
+
+impl MeasurementBackend for Cloudflare {
+    fn target<'a>(&'a self, _: &'a str) -> BoxFuture<'a, Result<MeasureTarget>> {
+        Box::pin(async move {
+            Ok(MeasureTarget {
+                download_url: Some(CLOUDFLARE_DOWNLOAD_URL.into()),
+                upload_url: Some(CLOUDFLARE_UPLOAD_URL.into()),
+                ..MeasureTarget::default()
+            })
+        })
+    }
+}
+
+/// the speedtest.net server picked by the app, such as the nearest one
+pub struct Ookla {
+    pub pick_server: PickServer,
+}
+
+impl MeasurementBackend for Ookla {
+    fn target<'a>(&'a self, proxy: &'a str) -> BoxFuture<'a, Result<MeasureTarget>> {
+        Box::pin(async move {
+            let (client, server) = (self.pick_server)(proxy).await?;
+            Ok(MeasureTarget {
+                download_url: Some(server.download_url()),
+                upload_url: Some(server.url.clone()),
+                server: Some(server),
+                client: Some(client),
+            })
+        })
+    }
+}
+
+/// `measure_download_url` and `measure_upload_url`
+pub struct Custom {
+    pub download_url: String,
+
+    pub upload_url: Option<String>,
+}
+
+impl MeasurementBackend for Custom {
+    fn target<'a>(&'a self, _: &'a str) -> BoxFuture<'a, Result<MeasureTarget>> {
+        Box::pin(async move {
+            Ok(MeasureTarget {
+                download_url: Some(self.download_url.clone()),
+                upload_url: self.upload_url.clone(),
+                ..MeasureTarget::default()
+            })
+        })
+    }
+}
+
+/// the full tests are downgraded to the latency only
+pub struct LatencyOnly;
+
+impl MeasurementBackend for LatencyOnly {
+    fn target<'a>(&'a self, _: &'a str) -> BoxFuture<'a, Result<MeasureTarget>> {
+        Box::pin(async move { Ok(MeasureTarget::default()) })
+    }
+}
+
+/// the backend of the settings, `pick_server` is used by `speedtest`
+pub fn from_settings(
+    settings: BackendSettings,
+    pick_server: PickServer,
+) -> Result<Box<dyn MeasurementBackend>> {
+    match settings.backend.as_deref() {
+        Some("speedtest") => Ok(Box::new(Ookla { pick_server })),
+        Some("custom") => match settings.download_url {
+            Some(download_url) => Ok(Box::new(Custom {
+                download_url,
+                upload_url: settings.upload_url,
+            })),
+            None => bail!("the custom download url is not set"),
+        },
+        Some("latency") => Ok(Box::new(LatencyOnly)),
+        _ => Ok(Box::new(Cloudflare)),
+    }
+}
+
+/// the body of the download, replaced by a mock in the tests
+pub trait ChunkSource: Send {
+    /// the length of the next chunk, none at the end
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<usize>>>;
+}
+
+impl ChunkSource for reqwest::Response {
+    fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<usize>>> {
+        Box::pin(async move { Ok(self.chunk().await?.map(|chunk| chunk.len())) })
+    }
+}
+
+/// the chunks streamed by the upload
+pub type UploadBody = BoxStream<'static, std::io::Result<&'static [u8]>>;
+
+/// the http requests of the measurement, replaced by a mock in the tests
+pub trait HttpTransport: Send + Sync {
+    /// through the measure listener if proxied, or without the proxy
+    /// return the negotiated http version and the body after the response headers
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        proxied: bool,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<(String, Box<dyn ChunkSource>)>>;
+
+    /// through the measure listener, the response is discarded
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        body: UploadBody,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// a new connection for each request, the settings are read for each one
+pub struct ReqwestTransport {
+    /// the client with the headers, through the measure listener if proxied
+    pub builder: fn(bool) -> Result<reqwest::ClientBuilder>,
+
+    /// `auto`, `http1` or `http2`, default is `auto`
+    pub http_version: fn() -> Option<String>,
+}
+
+impl ReqwestTransport {
+    fn client(&self, proxied: bool, timeout: Duration) -> Result<reqwest::Client> {
+        let builder = (self.builder)(proxied)?;
+        let builder = match (self.http_version)().as_deref() {
+            None | Some("auto") => builder,
+            Some("http1") => builder.http1_only(),
+            Some("http2") => builder.http2_prior_knowledge(),
+            Some(version) => bail!("unsupported http version \"{version}\""),
+        };
+
+        Ok(builder.timeout(timeout).build()?)
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn get<'a>(
+        &'a self,
+        url: &'a str,
+        proxied: bool,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<(String, Box<dyn ChunkSource>)>> {
+        Box::pin(async move {
+            let resp = self.client(proxied, timeout)?.get(url).send().await?;
+            if !resp.status().is_success() {
+                bail!("failed to download with status \"{}\"", resp.status());
+            }
+
+            let version = format!("{:?}", resp.version());
+            Ok((version, Box::new(resp) as Box<dyn ChunkSource>))
+        })
+    }
+
+    fn post<'a>(
+        &'a self,
+        url: &'a str,
+        body: UploadBody,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let body = reqwest::Body::wrap_stream(body);
+            self.client(true, timeout)?
+                .post(url)
+                .body(body)
+                .send()
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+#[test]
+fn test_backend_target() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let target = runtime.block_on(Cloudflare.target("de")).unwrap();
+    assert_eq!(
+        target.download_url.as_deref(),
+        Some(CLOUDFLARE_DOWNLOAD_URL)
+    );
+    assert!(target.server.is_none());
+
+    let custom = Custom {
+        download_url: "http://mirror/100mb.bin".into(),
+        upload_url: None,
+    };
+    let target = runtime.block_on(custom.target("de")).unwrap();
+    assert_eq!(
+        target.download_url.as_deref(),
+        Some("http://mirror/100mb.bin")
+    );
+    assert!(target.upload_url.is_none());
+
+    let target = runtime.block_on(LatencyOnly.target("de")).unwrap();
+    assert_eq!(target, MeasureTarget::default());
+
+    fn pick_server(_: &str) -> BoxFuture<'_, Result<(SpeedtestClient, SpeedtestServer)>> {
+        Box::pin(async move {
+            let server = SpeedtestServer {
+                url: "http://a/speedtest/upload.php".into(),
+                ..SpeedtestServer::default()
+            };
+            Ok((SpeedtestClient::default(), server))
+        })
+    }
+    let settings = |backend: &str| BackendSettings {
+        backend: Some(backend.into()),
+        ..BackendSettings::default()
+    };
+
+    let backend = from_settings(settings("speedtest"), pick_server).unwrap();
+    let target = runtime.block_on(backend.target("de")).unwrap();
+    assert_eq!(
+        target.download_url.as_deref(),
+        Some("http://a/speedtest/random4000x4000.jpg")
+    );
+    assert!(target.client.is_some());
+    assert!(from_settings(settings("custom"), pick_server).is_err());
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+/// the client seen by speedtest.net
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct SpeedtestClient {
+    pub ip: Option<String>,
+
+    pub isp: Option<String>,
+
+    /// the iso code, such as `DE`
+    pub country: Option<String>,
+
+    pub lat: Option<f64>,
+
+    pub lon: Option<f64>,
+}
+
+impl SpeedtestClient {
+    /// `(lat, lon)`
+    pub fn location(&self) -> Option<(f64, f64)> {
+        Some((self.lat?, self.lon?))
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct SpeedtestServer {
+    pub id: String,
+
+    /// the hosting company, such as `Hetzner`
+    pub sponsor: String,
+
+    /// the city
+    pub name: String,
+
+    pub country: String,
+
+    pub lat: f64,
+
+    pub lon: f64,
+
+    /// the upload url, such as `http://host:8080/speedtest/upload.php`
+    pub url: String,
+
+    /// `host:port`
+    pub host: String,
+
+    /// the distance from the client (km)
+    pub distance: Option<f64>,
+}
+
+impl SpeedtestServer {
+    /// the large image next to the upload url, served by all the servers
+    pub fn download_url(&self) -> String {
+        match self.url.rsplit_once('/') {
+            Some((base, _)) => format!("{base}/random4000x4000.jpg"),
+            None => format!("http://{}/speedtest/random4000x4000.jpg", self.host),
+        }
+    }
+}
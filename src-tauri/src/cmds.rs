@@ -2,19 +2,24 @@ use crate::{
     config::*,
     core::*,
     feat,
-    utils::{dirs, help},
+    utils::{
+        authz::{self, Capability},
+        backup, dirs,
+        error::CmdError,
+        help, kv, resolve, startup,
+    },
 };
-use crate::{ret_err, wrap_err};
+use crate::{log_err, ret_err, wrap_err};
 use anyhow::{Context, Result};
 use serde_yaml::Mapping;
 use std::collections::{HashMap, VecDeque};
 use sysproxy::Sysproxy;
 
-type CmdResult<T = ()> = Result<T, String>;
+type CmdResult<T = ()> = Result<T, CmdError>;
 
 #[tauri::command]
 pub fn get_profiles() -> CmdResult<IProfiles> {
-    Ok(Config::profiles().data().clone())
+    Ok(Config::profiles().latest().clone())
 }
 
 #[tauri::command]
@@ -24,16 +29,68 @@ pub async fn enhance_profiles() -> CmdResult {
     Ok(())
 }
 
+/// return the count of the nodes dropped by `filter_nodes`
 #[tauri::command]
-pub async fn import_profile(url: String, option: Option<PrfOption>) -> CmdResult {
+pub async fn import_profile(url: String, option: Option<PrfOption>) -> CmdResult<usize> {
     let item = wrap_err!(PrfItem::from_url(&url, None, None, option).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    let filtered = item.filtered.unwrap_or(0);
+    let tx = Config::profiles().begin().await;
+    wrap_err!({ tx.draft().append_item(item) })?;
+    wrap_err!(tx.commit())?;
+    Ok(filtered)
+}
+
+/// the deep links waiting for the confirmation
+#[tauri::command]
+pub fn get_deep_link_imports() -> CmdResult<Vec<link_import::LinkImport>> {
+    Ok(link_import::LinkImports::global().get_pending())
+}
+
+/// import and activate the profile of the deep link, or drop it
+#[tauri::command]
+pub async fn confirm_deep_link_import(id: String, accept: bool) -> CmdResult {
+    wrap_err!(
+        link_import::LinkImports::global()
+            .confirm(&id, accept)
+            .await
+    )
+}
+
+/// import the dropped file as a local profile
+#[tauri::command]
+pub async fn import_profile_file(path: String) -> CmdResult {
+    let item = wrap_err!(PrfItem::from_file(&path))?;
+    let tx = Config::profiles().begin().await;
+    wrap_err!({ tx.draft().append_item(item) })?;
+    wrap_err!(tx.commit())
 }
 
 #[tauri::command]
 pub async fn create_profile(item: PrfItem, file_data: Option<String>) -> CmdResult {
     let item = wrap_err!(PrfItem::from(item, file_data).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    let tx = Config::profiles().begin().await;
+    wrap_err!({ tx.draft().append_item(item) })?;
+    wrap_err!(tx.commit())
+}
+
+/// the yaml of the profile generated from the share links
+/// create it by `create_profile`
+#[tauri::command]
+pub fn generate_basic_profile(nodes: String, template: String) -> CmdResult<String> {
+    wrap_err!(crate::config::generate_basic_profile(&nodes, &template))
+}
+
+/// the same nodes in the imported profiles
+#[tauri::command]
+pub fn find_duplicate_nodes() -> CmdResult<Vec<DuplicateNodes>> {
+    wrap_err!(crate::config::find_duplicate_nodes())
+}
+
+/// the yaml of the profile with all the unique nodes
+/// create it by `create_profile`
+#[tauri::command]
+pub fn generate_unique_profile() -> CmdResult<String> {
+    wrap_err!(crate::config::generate_unique_profile())
 }
 
 #[tauri::command]
@@ -43,7 +100,10 @@ pub async fn update_profile(index: String, option: Option<PrfOption>) -> CmdResu
 
 #[tauri::command]
 pub async fn delete_profile(index: String) -> CmdResult {
-    let should_update = wrap_err!({ Config::profiles().data().delete_item(index) })?;
+    let tx = Config::profiles().begin().await;
+    let should_update = wrap_err!({ tx.draft().delete_item(index.clone()) })?;
+    wrap_err!(tx.commit())?;
+    log_err!(measure_cache::MeasureCache::global().remove_profile(&index));
     if should_update {
         wrap_err!(CoreManager::global().update_config().await)?;
         handle::Handle::refresh_clash();
@@ -55,27 +115,43 @@ pub async fn delete_profile(index: String) -> CmdResult {
 /// 修改profiles的
 #[tauri::command]
 pub async fn patch_profiles_config(profiles: IProfiles) -> CmdResult {
-    wrap_err!({ Config::profiles().draft().patch_config(profiles) })?;
-
-    match CoreManager::global().update_config().await {
+    let switched = profiles.current.is_some();
+    // refuse the broken profile before leaving the working one
+    if let Some(uid) = profiles.current.as_ref() {
+        wrap_err!(preflight::require_preflight(uid))?;
+    }
+    let tx = Config::profiles().begin().await;
+    wrap_err!({ tx.draft().patch_config(profiles) })?;
+
+    // the switch is rolled back if the new profile does not work
+    let activated = match switched {
+        true => activation::activate().await,
+        false => CoreManager::global().update_config().await,
+    };
+    match activated {
         Ok(_) => {
             handle::Handle::refresh_clash();
-            Config::profiles().apply();
-            wrap_err!(Config::profiles().data().save_file())?;
+            wrap_err!(tx.commit())?;
+            if switched {
+                log_err!(handle::Handle::update_systray());
+                hook::fire_profile_switched();
+            }
             Ok(())
         }
+        // the draft is discarded with the transaction
         Err(err) => {
-            Config::profiles().discard();
             log::error!(target: "app", "{err}");
-            Err(format!("{err}"))
+            Err(err.into())
         }
     }
 }
 
 /// 修改某个profile item的
 #[tauri::command]
-pub fn patch_profile(index: String, profile: PrfItem) -> CmdResult {
-    wrap_err!(Config::profiles().data().patch_item(index, profile))?;
+pub async fn patch_profile(index: String, profile: PrfItem) -> CmdResult {
+    let tx = Config::profiles().begin().await;
+    wrap_err!({ tx.draft().patch_item(index, profile) })?;
+    wrap_err!(tx.commit())?;
     wrap_err!(timer::Timer::global().refresh())
 }
 
@@ -144,6 +220,42 @@ pub fn get_runtime_exists() -> CmdResult<Vec<String>> {
     Ok(Config::runtime().latest().exists_keys.clone())
 }
 
+/// the memory and the cpu of the core and the app
+#[tauri::command]
+pub fn get_resource_usage() -> CmdResult<usage::ResourceUsage> {
+    Ok(usage::ResourceMonitor::global().get_usage())
+}
+
+/// the status of the core, the profile, the proxies and the traffic in one call
+#[tauri::command]
+pub async fn get_app_status() -> CmdResult<app_status::AppStatus> {
+    Ok(app_status::get_app_status().await)
+}
+
+/// the profiles, the verge config and the selected nodes,
+/// their changes are emitted as the `verge://state-*` events after it
+#[tauri::command]
+pub async fn subscribe_state() -> CmdResult<state_events::StateSnapshot> {
+    Ok(state_events::StateEvents::global().subscribe().await)
+}
+
+/// check the profile by `clash -t` without applying it, empty if passed
+#[tauri::command]
+pub fn test_config(uid: String) -> CmdResult<Vec<clash_api::CoreCheckIssue>> {
+    wrap_err!(CoreManager::global().test_config(&uid))
+}
+
+/// the checks run before the profile is activated
+#[tauri::command]
+pub fn preflight_profile(uid: String) -> CmdResult<preflight::PreflightReport> {
+    wrap_err!(preflight::preflight(&uid))
+}
+
+#[tauri::command]
+pub async fn get_effective_config() -> CmdResult<effective::EffectiveConfig> {
+    wrap_err!(effective::get_effective_config().await)
+}
+
 #[tauri::command]
 pub fn get_runtime_logs() -> CmdResult<HashMap<String, Vec<(String, String)>>> {
     Ok(Config::runtime().latest().chain_logs.clone())
@@ -156,14 +268,356 @@ pub async fn patch_clash_config(payload: Mapping) -> CmdResult {
 
 #[tauri::command]
 pub fn get_verge_config() -> CmdResult<IVerge> {
-    Ok(Config::verge().data().clone())
+    Ok(Config::verge().latest().clone())
 }
 
 #[tauri::command]
 pub async fn patch_verge_config(payload: IVerge) -> CmdResult {
+    let (auto_launch, hooks) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.enable_auto_launch,
+            verge.hooks.clone().unwrap_or_default(),
+        )
+    };
+    if payload.enable_auto_launch.is_some() && payload.enable_auto_launch != auto_launch {
+        wrap_err!(authz::require(Capability::Autostart, "patch_verge_config"))?;
+    }
+    if let Some(patch) = payload.hooks.as_ref() {
+        if !authz::new_hook_commands(&hooks, patch).is_empty() {
+            wrap_err!(authz::require(Capability::Hooks, "patch_verge_config"))?;
+        }
+    }
     wrap_err!(feat::patch_verge(payload).await)
 }
 
+/// turn off the system proxy for the seconds and turn it back on after
+/// the tun mode is paused too with `tun`
+#[tauri::command]
+pub async fn pause_proxy(duration: u64, tun: Option<bool>) -> CmdResult<pause::PauseStatus> {
+    let pause = pause::ProxyPause::global();
+    wrap_err!(pause.pause(duration, tun.unwrap_or(false)).await)
+}
+
+#[tauri::command]
+pub async fn resume_proxy() -> CmdResult {
+    wrap_err!(pause::ProxyPause::global().resume().await)
+}
+
+/// null if not paused
+#[tauri::command]
+pub fn get_proxy_pause() -> CmdResult<Option<pause::PauseStatus>> {
+    Ok(pause::ProxyPause::global().status())
+}
+
+/// the shareable settings in json
+#[tauri::command]
+pub fn export_settings_preset() -> CmdResult<String> {
+    wrap_err!(ISettingsPreset::export().to_json())
+}
+
+#[tauri::command]
+pub async fn apply_settings_preset(json: String) -> CmdResult {
+    let preset = wrap_err!(ISettingsPreset::from_json(&json))?;
+    wrap_err!(feat::apply_settings_preset(preset).await)
+}
+
+/// the rules of the core logs, the built-in ones if not set
+#[tauri::command]
+pub fn get_log_alerts() -> CmdResult<Vec<IVergeLogAlert>> {
+    Ok(log_alert::get_rules())
+}
+
+#[tauri::command]
+pub fn get_schedule() -> CmdResult<Vec<IVergeSchedule>> {
+    Ok(Config::verge()
+        .latest()
+        .mode_schedule
+        .clone()
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_autostart_status() -> CmdResult<sysopt::AutostartStatus> {
+    wrap_err!(sysopt::Sysopt::global().autostart_status())
+}
+
+#[tauri::command]
+pub async fn set_autostart(
+    enable: bool,
+    start_minimized: Option<bool>,
+    delay: Option<u64>,
+) -> CmdResult<sysopt::AutostartStatus> {
+    wrap_err!(authz::require(Capability::Autostart, "set_autostart"))?;
+    wrap_err!(
+        feat::patch_verge(IVerge {
+            enable_auto_launch: Some(enable),
+            enable_silent_start: start_minimized,
+            auto_launch_delay: delay,
+            ..IVerge::default()
+        })
+        .await
+    )?;
+
+    let status = wrap_err!(sysopt::Sysopt::global().autostart_status())?;
+    if status.registered != enable {
+        ret_err!("the startup entry is not updated");
+    }
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn set_schedule(schedule: Vec<IVergeSchedule>) -> CmdResult {
+    wrap_err!(schedule::Schedule::check(&schedule))?;
+    wrap_err!(
+        feat::patch_verge(IVerge {
+            mode_schedule: Some(schedule),
+            ..IVerge::default()
+        })
+        .await
+    )
+}
+
+#[tauri::command]
+pub async fn test_proxy_delay(name: String, url: Option<String>) -> CmdResult<u64> {
+    wrap_err!(delay::test_delay(&name, url).await)
+}
+
+#[tauri::command]
+pub async fn compare_proxies(
+    a: String,
+    b: String,
+    mode: measure::MeasureMode,
+) -> CmdResult<measure::MeasureCompare> {
+    wrap_err!(measure::Measure::global().compare(&a, &b, mode).await)
+}
+
+#[tauri::command]
+pub async fn benchmark_direct_vs_proxy(
+    mode: measure::MeasureMode,
+) -> CmdResult<measure::MeasureBenchmark> {
+    wrap_err!(measure::Measure::global().benchmark(mode).await)
+}
+
+#[tauri::command]
+pub async fn measure_proxy(
+    name: String,
+    mode: measure::MeasureMode,
+) -> CmdResult<measure::MeasureInfo> {
+    let queue = measure_queue::MeasureQueue::global();
+    wrap_err!(
+        queue
+            .measure(&name, mode, measure_queue::MeasurePriority::Single)
+            .await
+    )
+}
+
+#[tauri::command]
+pub async fn measure_proxies(
+    names: Vec<String>,
+    mode: measure::MeasureMode,
+) -> CmdResult<Vec<measure::MeasureInfo>> {
+    let batch = wrap_err!(
+        measure_batch::MeasureBatches::global()
+            .start(names, mode)
+            .await
+    )?;
+    let infos = batch.ordered_results();
+
+    let failed = infos.iter().filter(|info| info.error.is_some()).count();
+    let body = format!("{} proxies measured, {failed} failed", infos.len());
+    notification::notify(notification::NotifyTrigger::Measurement, "Hiddify", &body);
+
+    Ok(infos)
+}
+
+/// measure the rest of the interrupted batch, the latest one by default
+#[tauri::command]
+pub async fn resume_measurement_batch(
+    id: Option<String>,
+) -> CmdResult<measure_batch::MeasureBatch> {
+    wrap_err!(measure_batch::MeasureBatches::global().resume(id).await)
+}
+
+/// measure the proxies failed or slower than the threshold (ms) in the batch again
+#[tauri::command]
+pub async fn retest_failed(
+    batch_id: String,
+    latency_threshold: Option<u64>,
+) -> CmdResult<measure_batch::MeasureBatch> {
+    wrap_err!(
+        measure_batch::MeasureBatches::global()
+            .retest_failed(&batch_id, latency_threshold)
+            .await
+    )
+}
+
+#[tauri::command]
+pub fn get_measure_data_usage() -> CmdResult<data_budget::DataUsage> {
+    Ok(data_budget::DataBudget::global().get_usage())
+}
+
+#[tauri::command]
+pub fn get_measurement_batches() -> CmdResult<Vec<measure_batch::MeasureBatch>> {
+    Ok(measure_batch::MeasureBatches::global().get_all())
+}
+
+/// the qr content to pair the companion app
+#[tauri::command]
+pub fn start_remote_pairing() -> CmdResult<remote_control::PairingInfo> {
+    wrap_err!(remote_control::RemoteControl::global().start_pairing())
+}
+
+#[tauri::command]
+pub fn get_remote_devices() -> CmdResult<Vec<remote_control::PairedDevice>> {
+    Ok(remote_control::RemoteControl::global().get_devices())
+}
+
+#[tauri::command]
+pub fn remove_remote_device(id: String) -> CmdResult {
+    wrap_err!(remote_control::RemoteControl::global().remove_device(&id))
+}
+
+/// the markdown table or the png data url of the best proxies of the batch
+#[tauri::command]
+pub fn render_scan_summary(
+    batch_id: String,
+    format: scan_summary::SummaryFormat,
+) -> CmdResult<String> {
+    wrap_err!(scan_summary::render_scan_summary(&batch_id, format))
+}
+
+#[tauri::command]
+pub async fn get_cached_measurements(profile: String) -> CmdResult<Vec<measure::MeasureInfo>> {
+    // the core only probes the proxies of the current profile
+    let current = { Config::profiles().latest().get_current() };
+    if current.as_deref() == Some(profile.as_str()) {
+        log_err!(measure_cache::sync_core_history().await);
+    }
+    Ok(measure_cache::MeasureCache::global().get(&profile))
+}
+
+/// the measured proxies of the current profile, the best first
+#[tauri::command]
+pub fn rank_proxies(strategy: rank::RankStrategy) -> CmdResult<Vec<rank::RankedProxy>> {
+    Ok(rank::rank_proxies(strategy))
+}
+
+/// select the node and record it in the recent nodes
+#[tauri::command]
+pub async fn select_proxy(selector: String, name: String) -> CmdResult {
+    wrap_err!(recent::RecentNodes::global().select(&selector, &name).await)
+}
+
+#[tauri::command]
+pub fn get_recent_nodes(selector: String) -> CmdResult<Vec<recent::RecentNode>> {
+    Ok(recent::RecentNodes::global().get(&selector))
+}
+
+/// the selector switched last is used by default
+#[tauri::command]
+pub async fn switch_to_previous_node(selector: Option<String>) -> CmdResult<String> {
+    let name = wrap_err!(
+        recent::RecentNodes::global()
+            .switch_to_previous(selector)
+            .await
+    )?;
+    handle::Handle::refresh_clash();
+    Ok(name)
+}
+
+/// the favorites, notes and aliases of the nodes in the current profile
+#[tauri::command]
+pub fn get_node_meta() -> CmdResult<HashMap<String, node_meta::NodeMeta>> {
+    Ok(node_meta::NodeMetaStore::global().get_current())
+}
+
+#[tauri::command]
+pub fn set_node_meta(name: String, meta: node_meta::NodeMeta) -> CmdResult {
+    wrap_err!(node_meta::NodeMetaStore::global().set(&name, meta))
+}
+
+/// the proxies of the core with the node metadata
+#[tauri::command]
+pub async fn get_proxies_with_meta() -> CmdResult<serde_json::Value> {
+    wrap_err!(node_meta::get_proxies().await)
+}
+
+/// a page of the nodes filtered and sorted by the backend, for the large subscriptions
+#[tauri::command]
+pub async fn query_proxies(
+    filter: Option<proxy_query::ProxyFilter>,
+    sort: Option<proxy_query::ProxySort>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> CmdResult<proxy_query::ProxyPage> {
+    wrap_err!(
+        proxy_query::query_proxies(
+            filter.unwrap_or_default(),
+            sort.unwrap_or_default(),
+            offset.unwrap_or(0),
+            limit.unwrap_or(proxy_query::DEFAULT_LIMIT),
+        )
+        .await
+    )
+}
+
+#[tauri::command]
+pub async fn check_unlock(names: Vec<String>) -> CmdResult<Vec<unlock::UnlockMatrix>> {
+    Ok(unlock::check_unlock(names).await)
+}
+
+#[tauri::command]
+pub async fn check_reachability() -> CmdResult<reach::ReachMatrix> {
+    Ok(reach::check_matrix().await)
+}
+
+/// test the panel and the proxy server without the proxy
+#[tauri::command]
+pub async fn test_direct(uid: String, proxy: Option<String>) -> CmdResult<direct::DirectReport> {
+    wrap_err!(direct::test_direct(&uid, proxy.as_deref()).await)
+}
+
+/// tcp connect to the proxy servers of the profile without the core
+#[tauri::command]
+pub async fn tcping_nodes(profile: String) -> CmdResult<Vec<direct::TcpingResult>> {
+    wrap_err!(direct::tcping_nodes(&profile).await)
+}
+
+#[tauri::command]
+pub async fn get_connection_chains() -> CmdResult<Vec<chains::ConnectionChain>> {
+    wrap_err!(chains::get_connection_chains().await)
+}
+
+#[tauri::command]
+pub async fn trace_domain(domain: String) -> CmdResult<trace::DomainTrace> {
+    wrap_err!(trace::trace_domain(&domain).await)
+}
+
+#[tauri::command]
+pub fn check_tun_routes() -> CmdResult<route_check::TunRouteReport> {
+    Ok(route_check::check_tun_routes())
+}
+
+#[tauri::command]
+pub async fn get_network_info() -> CmdResult<netinfo::NetworkInfo> {
+    Ok(netinfo::get_network_info().await)
+}
+
+/// the nearest speedtest.net servers in different metro areas
+/// the location is `speedtest_location` by default, `proxy_exit` requires the proxy
+#[tauri::command]
+pub async fn get_speedtest_servers(
+    count: Option<usize>,
+    location: Option<speedtest::LocationStrategy>,
+    proxy: Option<String>,
+) -> CmdResult<Vec<speedtest::SpeedtestServer>> {
+    let count = count.unwrap_or(speedtest::DEFAULT_SERVER_COUNT);
+    let location = location.unwrap_or_else(speedtest::LocationStrategy::from_verge);
+    wrap_err!(speedtest::get_servers(count, location, proxy.as_deref()).await)
+}
+
 #[tauri::command]
 pub async fn change_clash_core(clash_core: Option<String>) -> CmdResult {
     wrap_err!(CoreManager::global().change_core(clash_core).await)
@@ -177,6 +631,11 @@ pub async fn restart_sidecar() -> CmdResult {
 
 #[tauri::command]
 pub fn grant_permission(core: String) -> CmdResult {
+    wrap_err!(authz::require(
+        Capability::CorePermission,
+        "grant_permission"
+    ))?;
+
     #[cfg(any(target_os = "macos", target_os = "linux"))]
     return wrap_err!(manager::grant_permission(core));
 
@@ -200,17 +659,275 @@ pub fn get_sys_proxy() -> CmdResult<Mapping> {
     Ok(map)
 }
 
+#[tauri::command]
+pub fn list_processes() -> CmdResult<Vec<String>> {
+    Ok(help::list_process_names())
+}
+
+#[tauri::command]
+pub fn get_dns_presets() -> CmdResult<Vec<DnsPresetInfo>> {
+    Ok(dns_presets())
+}
+
+#[tauri::command]
+pub async fn apply_dns_preset(preset: IDnsPreset) -> CmdResult {
+    wrap_err!(feat::apply_dns_preset(preset).await)
+}
+
+/// the bundled routing presets and their rules
+#[tauri::command]
+pub fn get_routing_presets() -> CmdResult<Vec<RoutingPresetInfo>> {
+    Ok(routing_presets())
+}
+
+/// the rules added to and removed from the override by the preset
+#[tauri::command]
+pub fn preview_routing_preset(name: String) -> CmdResult<RoutingPlan> {
+    let current = { Config::overrides().latest().get_seq("prepend-rules") };
+    wrap_err!(plan_routing_preset(&name, &current))
+}
+
+#[tauri::command]
+pub async fn apply_routing_preset(name: String) -> CmdResult<RoutingPlan> {
+    wrap_err!(feat::apply_routing_preset(name).await)
+}
+
+/// the static mappings of `hosts` in the override
+#[tauri::command]
+pub fn get_hosts() -> CmdResult<Mapping> {
+    Ok(Config::overrides().latest().get_map("hosts"))
+}
+
+#[tauri::command]
+pub async fn add_host(domain: String, ip: String) -> CmdResult {
+    wrap_err!(feat::patch_hosts(vec![(domain, ip)], vec![]).await)
+}
+
+#[tauri::command]
+pub async fn remove_host(domain: String) -> CmdResult {
+    wrap_err!(feat::patch_hosts(vec![], vec![domain]).await)
+}
+
+/// import the valid mappings of a hosts file, return the count
+#[tauri::command]
+pub async fn import_hosts_file(path: String) -> CmdResult<usize> {
+    let text = wrap_err!(std::fs::read_to_string(&path))?;
+    let hosts = parse_hosts_file(&text);
+    if hosts.is_empty() {
+        ret_err!("no valid host in the file");
+    }
+
+    let count = hosts.len();
+    wrap_err!(feat::patch_hosts(hosts, vec![]).await)?;
+    Ok(count)
+}
+
+/// the keyword filters of the groups in the override
+#[tauri::command]
+pub fn get_group_filters() -> CmdResult<Vec<IGroupFilter>> {
+    Ok(Config::overrides().latest().get_group_filters())
+}
+
+#[tauri::command]
+pub async fn set_group_filters(filters: Vec<IGroupFilter>) -> CmdResult {
+    wrap_err!(feat::set_group_filters(filters).await)
+}
+
+/// create the `load-balance` group of the fastest nodes by the last measurements
+/// return the members and their weights
+#[tauri::command]
+pub async fn create_load_balance(
+    name: String,
+    strategy: Option<String>,
+    size: Option<usize>,
+) -> CmdResult<Vec<load_balance::BalanceMember>> {
+    wrap_err!(feat::create_load_balance(name, strategy, size).await)
+}
+
+#[tauri::command]
+pub async fn remove_load_balance() -> CmdResult {
+    wrap_err!(feat::remove_load_balance().await)
+}
+
+/// the profile transformed by its own script
+#[tauri::command]
+pub fn preview_script_output(uid: String) -> CmdResult<crate::enhance::ScriptPreview> {
+    wrap_err!(crate::enhance::preview_profile_script(&uid))
+}
+
+/// the node names of the profile before and after the rename rules
+#[tauri::command]
+pub fn preview_node_rename(
+    uid: String,
+    rules: Option<Vec<IVergeRenameRule>>,
+) -> CmdResult<Vec<(String, String)>> {
+    wrap_err!(crate::enhance::preview_profile_rename(&uid, rules))
+}
+
+/// the known pitfalls of the runtime config
+#[tauri::command]
+pub fn lint_config() -> CmdResult<Vec<LintIssue>> {
+    Ok(lint_runtime_config())
+}
+
+#[tauri::command]
+pub async fn apply_lint_fix(rule: String) -> CmdResult {
+    wrap_err!(feat::apply_lint_fix(rule).await)
+}
+
+#[tauri::command]
+pub async fn add_process_rule(process: String, target: String) -> CmdResult {
+    wrap_err!(feat::add_process_rule(process, target).await)
+}
+
+/// the custom rules, the `prepend-rules` of the override
+#[tauri::command]
+pub fn get_custom_rules() -> CmdResult<Vec<String>> {
+    let rules = { Config::overrides().latest().get_seq("prepend-rules") };
+    Ok(rules
+        .iter()
+        .filter_map(|rule| rule.as_str().map(String::from))
+        .collect())
+}
+
+/// insert the rule before the index, or after it if `after`, append if no index
+#[tauri::command]
+pub async fn add_custom_rule(rule: String, index: Option<usize>, after: Option<bool>) -> CmdResult {
+    let after = after.unwrap_or(false);
+    wrap_err!(feat::edit_custom_rules(RuleEdit::Add { rule, index, after }).await)
+}
+
+#[tauri::command]
+pub async fn update_custom_rule(index: usize, rule: String) -> CmdResult {
+    wrap_err!(feat::edit_custom_rules(RuleEdit::Update { index, rule }).await)
+}
+
+#[tauri::command]
+pub async fn move_custom_rule(from: usize, to: usize) -> CmdResult {
+    wrap_err!(feat::edit_custom_rules(RuleEdit::Move { from, to }).await)
+}
+
+#[tauri::command]
+pub async fn delete_custom_rule(index: usize) -> CmdResult {
+    wrap_err!(feat::edit_custom_rules(RuleEdit::Delete { index }).await)
+}
+
+#[tauri::command]
+pub async fn create_node_listener(node: String, port: u16) -> CmdResult {
+    wrap_err!(feat::create_node_listener(node, port).await)
+}
+
+#[tauri::command]
+pub async fn remove_node_listener(port: u16) -> CmdResult {
+    wrap_err!(feat::remove_node_listener(port).await)
+}
+
+#[tauri::command]
+pub fn get_bypass_list() -> CmdResult<Vec<String>> {
+    Ok(sysopt::get_bypass_list())
+}
+
+#[tauri::command]
+pub async fn set_bypass_list(bypass: Vec<String>) -> CmdResult {
+    wrap_err!(feat::patch_bypass(bypass).await)
+}
+
 #[tauri::command]
 pub fn get_clash_logs() -> CmdResult<VecDeque<String>> {
     Ok(logger::Logger::global().get_log())
 }
 
+/// the reminder days of the config if not set
+#[tauri::command]
+pub fn get_expiring_profiles(days: Option<u32>) -> CmdResult<Vec<expiry::ExpiringProfile>> {
+    let days = days.unwrap_or(expiry::reminder_days());
+    Ok(expiry::expiring_profiles(days))
+}
+
+#[tauri::command]
+pub fn test_notification() -> CmdResult {
+    wrap_err!(notification::test())
+}
+
+#[tauri::command]
+pub async fn test_hook(hook: IVergeHook, dry_run: bool) -> CmdResult<hook::HookResult> {
+    if hook.command.is_some() && !dry_run {
+        wrap_err!(authz::require(Capability::Hooks, "test_hook"))?;
+    }
+    wrap_err!(hook::test(hook, dry_run).await)
+}
+
+#[tauri::command]
+pub async fn check_app_update(channel: Option<String>) -> CmdResult<Option<updater::AppUpdate>> {
+    let current = updater::update_channel();
+    let channel = channel.unwrap_or(current.clone());
+    let update = wrap_err!(updater::check_update(&channel).await)?;
+
+    // persist the channel once its feed is reachable
+    if channel != current {
+        wrap_err!(
+            feat::patch_verge(IVerge {
+                update_channel: Some(channel),
+                ..IVerge::default()
+            })
+            .await
+        )?;
+    }
+
+    Ok(update)
+}
+
+#[tauri::command]
+pub async fn install_app_update(app_handle: tauri::AppHandle) -> CmdResult {
+    wrap_err!(updater::install_update(app_handle).await)
+}
+
+/// move the data from the default dir to the portable one, then restart
+#[tauri::command]
+pub fn migrate_data_dir(app_handle: tauri::AppHandle) -> CmdResult {
+    wrap_err!(dirs::migrate_home_dir())?;
+
+    // the configs in memory are outdated
+    resolve::resolve_reset();
+    tauri::api::process::restart(&app_handle.env());
+    Ok(())
+}
+
 #[tauri::command]
 pub fn open_app_dir() -> CmdResult<()> {
     let app_dir = wrap_err!(dirs::app_home_dir())?;
     wrap_err!(open::that(app_dir))
 }
 
+/// the config files restored or reset on this startup
+#[tauri::command]
+pub fn get_recovered_files() -> CmdResult<Vec<backup::RecoveredFile>> {
+    Ok(backup::recovered_files())
+}
+
+/// the time of each step of this startup
+#[tauri::command]
+pub fn get_startup_report() -> CmdResult<startup::StartupReport> {
+    Ok(startup::startup_report())
+}
+
+/// the value in the key-value store, null if not set
+#[tauri::command]
+pub fn kv_get(namespace: String, key: String) -> CmdResult<Option<serde_json::Value>> {
+    wrap_err!(kv::KvStore::global().get(&namespace, &key))
+}
+
+#[tauri::command]
+pub fn kv_set(namespace: String, key: String, value: serde_json::Value) -> CmdResult {
+    wrap_err!(kv::KvStore::global().set(&namespace, &key, &value))
+}
+
+/// false if the key is not set
+#[tauri::command]
+pub fn kv_delete(namespace: String, key: String) -> CmdResult<bool> {
+    wrap_err!(kv::KvStore::global().delete(&namespace, &key))
+}
+
 #[tauri::command]
 pub fn open_core_dir() -> CmdResult<()> {
     let core_dir = wrap_err!(tauri::utils::platform::current_exe())?;
@@ -241,11 +958,13 @@ pub mod service {
 
     #[tauri::command]
     pub async fn install_service() -> CmdResult {
+        wrap_err!(authz::require(Capability::Service, "install_service"))?;
         wrap_err!(win_service::install_service().await)
     }
 
     #[tauri::command]
     pub async fn uninstall_service() -> CmdResult {
+        wrap_err!(authz::require(Capability::Service, "uninstall_service"))?;
         wrap_err!(win_service::uninstall_service().await)
     }
 }
@@ -1,4 +1,6 @@
-use crate::utils::{dirs, help};
+use super::draft::{check_ports, ConfigData, ConfigKind};
+use crate::log_err;
+use crate::utils::{dirs, help, secret};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_yaml::{Mapping, Value};
@@ -7,13 +9,20 @@ use std::{
     str::FromStr,
 };
 
+/// the controller socket of clash meta, used by the app instead of the tcp port
+/// the `external-controller` is kept for the dashboard
+#[cfg(not(target_os = "windows"))]
+pub const CONTROLLER_SOCKET_KEY: &str = "external-controller-unix";
+#[cfg(target_os = "windows")]
+pub const CONTROLLER_SOCKET_KEY: &str = "external-controller-pipe";
+
 #[derive(Default, Debug, Clone)]
 pub struct IClashTemp(pub Mapping);
 
 impl IClashTemp {
     pub fn new() -> Self {
         match dirs::clash_path().and_then(|path| help::read_merge_mapping(&path)) {
-            Ok(map) => Self(Self::guard(map)),
+            Ok(map) => Self(Self::guard(map)).use_keyring_secret(),
             Err(err) => {
                 log::error!(target: "app", "{err}");
                 Self::template()
@@ -21,6 +30,29 @@ impl IClashTemp {
         }
     }
 
+    /// the secret is kept in the keyring instead of the yaml
+    /// the plaintext one is moved to the keyring, and kept if the keyring is unavailable
+    fn use_keyring_secret(mut self) -> Self {
+        match self.0.get("secret").and_then(|value| value.as_str()) {
+            Some("") => self.generate_secret(),
+            Some(_) => log_err!(self.save_config()),
+            None => match secret::get_secret() {
+                Some(secret) => {
+                    self.0.insert("secret".into(), secret.into());
+                }
+                None => self.generate_secret(),
+            },
+        }
+        self
+    }
+
+    /// the controller is never left without the secret
+    fn generate_secret(&mut self) {
+        log::info!(target: "app", "generate the secret of the external controller");
+        self.0.insert("secret".into(), secret::generate_secret().into());
+        log_err!(self.save_config());
+    }
+
     pub fn template() -> Self {
         let mut map = Mapping::new();
 
@@ -29,12 +61,15 @@ impl IClashTemp {
         map.insert("allow-lan".into(), true.into());
         map.insert("mode".into(), "rule".into());
         map.insert("external-controller".into(), "127.0.0.1:9090".into());
-        map.insert("secret".into(), "".into());
+        let secret = secret::get_secret().unwrap_or_else(secret::generate_secret);
+        map.insert("secret".into(), secret.into());
 
         Self(map)
     }
 
     fn guard(mut config: Mapping) -> Mapping {
+        Self::guard_socket_ctrl(&mut config);
+
         let port = Self::guard_mixed_port(&config);
         let ctrl = Self::guard_server_ctrl(&config);
 
@@ -50,9 +85,23 @@ impl IClashTemp {
     }
 
     pub fn save_config(&self) -> Result<()> {
+        let mut config = self.0.clone();
+
+        // 密钥保存在系统的keyring里
+        match config.get("secret").and_then(|value| value.as_str()) {
+            Some("") => log_err!(secret::delete_secret()),
+            Some(value) => match secret::set_secret(value) {
+                Ok(_) => {
+                    config.remove("secret");
+                }
+                Err(err) => log::warn!(target: "app", "keep the secret in the yaml, {err}"),
+            },
+            None => {}
+        }
+
         help::save_yaml(
             &dirs::clash_path()?,
-            &self.0,
+            &config,
             Some("# Generated by Hiddify Clash Desktop"),
         )
     }
@@ -73,7 +122,33 @@ impl IClashTemp {
                 Value::Number(val_num) => Some(val_num.to_string()),
                 _ => None,
             }),
+            socket: config
+                .get(CONTROLLER_SOCKET_KEY)
+                .and_then(|value| value.as_str())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty()),
+        }
+    }
+
+    /// move the socket in `external-controller` to its own key,
+    /// such as `unix:///tmp/clash.sock` or `\\.\pipe\clash`
+    fn guard_socket_ctrl(config: &mut Mapping) {
+        let ctrl = config
+            .get("external-controller")
+            .and_then(|value| value.as_str())
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default();
+
+        let (key, socket) = match ctrl.strip_prefix("unix://") {
+            Some(path) => ("external-controller-unix", path),
+            None if ctrl.starts_with(r"\\.\pipe\") => ("external-controller-pipe", ctrl.as_str()),
+            None => return,
+        };
+
+        if config.get(key).is_none() {
+            config.insert(key.into(), socket.into());
         }
+        config.remove("external-controller");
     }
 
     pub fn guard_mixed_port(config: &Mapping) -> u16 {
@@ -126,6 +201,29 @@ impl IClashTemp {
     }
 }
 
+impl ConfigData for IClashTemp {
+    const KIND: ConfigKind = ConfigKind::Clash;
+
+    fn validate(&self) -> Result<()> {
+        let port = |key: &str| {
+            let port = self.0.get(key).and_then(|value| value.as_u64());
+            port.and_then(|port| u16::try_from(port).ok()).unwrap_or(0)
+        };
+
+        check_ports(&[
+            ("mixed-port", self.get_mixed_port()),
+            ("port", port("port")),
+            ("socks-port", port("socks-port")),
+            ("redir-port", port("redir-port")),
+            ("tproxy-port", port("tproxy-port")),
+        ])
+    }
+
+    fn save(&self) -> Result<()> {
+        self.save_config()
+    }
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct ClashInfo {
     /// clash core port
@@ -134,6 +232,8 @@ pub struct ClashInfo {
     pub server: String,
     /// clash secret
     pub secret: Option<String>,
+    /// same as `external-controller-unix` or `external-controller-pipe`
+    pub socket: Option<String>,
 }
 
 #[test]
@@ -151,6 +251,7 @@ fn test_clash_info() {
             port,
             server: server.into(),
             secret: None,
+            socket: None,
         }
     }
 
@@ -197,6 +298,27 @@ fn test_clash_info() {
         get_case(8888, "192.168.1.1:80800"),
         get_result(8888, "127.0.0.1:9090")
     );
+
+    let mut map = Mapping::new();
+    map.insert(
+        "external-controller".into(),
+        "unix:///tmp/clash.sock".into(),
+    );
+    let map = IClashTemp::guard(map);
+    assert_eq!(
+        map.get("external-controller-unix"),
+        Some(&"/tmp/clash.sock".into())
+    );
+    assert_eq!(
+        map.get("external-controller"),
+        Some(&"127.0.0.1:9090".into())
+    );
+
+    #[cfg(not(target_os = "windows"))]
+    assert_eq!(
+        IClashTemp(map).get_client_info().socket,
+        Some("/tmp/clash.sock".into())
+    );
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -1,4 +1,4 @@
-use super::{Draft, IClashTemp, IProfiles, IRuntime, IVerge};
+use super::{Draft, IClashTemp, IOverride, IProfiles, IRuntime, IVerge};
 use crate::{
     enhance,
     utils::{dirs, help},
@@ -9,12 +9,14 @@ use std::{env::temp_dir, path::PathBuf};
 
 pub const RUNTIME_CONFIG: &str = "hiddify-desktop.yaml";
 pub const CHECK_CONFIG: &str = "hiddify-desktop-check.yaml";
+pub const TEST_CONFIG: &str = "hiddify-desktop-test.yaml";
 
 pub struct Config {
     clash_config: Draft<IClashTemp>,
     verge_config: Draft<IVerge>,
     profiles_config: Draft<IProfiles>,
     runtime_config: Draft<IRuntime>,
+    override_config: Draft<IOverride>,
 }
 
 impl Config {
@@ -26,6 +28,7 @@ impl Config {
             verge_config: Draft::from(IVerge::new()),
             profiles_config: Draft::from(IProfiles::new()),
             runtime_config: Draft::from(IRuntime::new()),
+            override_config: Draft::from(IOverride::new()),
         })
     }
 
@@ -45,6 +48,10 @@ impl Config {
         Self::global().runtime_config.clone()
     }
 
+    pub fn overrides() -> Draft<IOverride> {
+        Self::global().override_config.clone()
+    }
+
     /// 初始化配置
     pub fn init_config() -> Result<()> {
         crate::log_err!(Self::generate());
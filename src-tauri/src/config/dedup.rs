@@ -0,0 +1,191 @@
+use super::{generator::build_profile, Config};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// the name of the merged profile
+pub const UNIQUE_PROFILE_NAME: &str = "All unique nodes";
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct NodeLocation {
+    /// the profile uid
+    pub uid: String,
+
+    /// the profile name
+    pub profile: String,
+
+    /// the proxy name
+    pub name: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DuplicateNodes {
+    /// `type|server|port|credential|transport`
+    pub fingerprint: String,
+
+    /// the same node in the profiles, at least two
+    pub nodes: Vec<NodeLocation>,
+}
+
+/// the nodes with the same fingerprint in all the imported profiles
+pub fn find_duplicate_nodes() -> Result<Vec<DuplicateNodes>> {
+    Ok(find_duplicates(&collect_nodes()))
+}
+
+/// the yaml of the profile with the first one of each node
+/// create it by `create_profile`
+pub fn generate_unique_profile() -> Result<String> {
+    let mut seen = vec![];
+    let mut proxies = vec![];
+    for (_, proxy) in collect_nodes().into_iter() {
+        if let Some(fingerprint) = node_fingerprint(&proxy) {
+            if !seen.contains(&fingerprint) {
+                seen.push(fingerprint);
+                proxies.push(proxy);
+            }
+        }
+    }
+
+    if proxies.is_empty() {
+        bail!("no node found in the profiles");
+    }
+
+    let config = serde_yaml::to_string(&build_profile(proxies, "global"))?;
+    Ok(format!(
+        "# Profile generated by Hiddify Clash Desktop ({UNIQUE_PROFILE_NAME})\n\n{config}"
+    ))
+}
+
+/// the proxies of the local and remote profiles, in the order of the profiles
+fn collect_nodes() -> Vec<(NodeLocation, Mapping)> {
+    let items = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles.get_items().cloned().unwrap_or_default()
+    };
+
+    let mut nodes = vec![];
+    for item in items.iter() {
+        if !matches!(item.itype.as_deref(), Some("local" | "remote")) {
+            continue;
+        }
+
+        let config = match item.read_file() {
+            Ok(data) => match serde_yaml::from_str::<Mapping>(&data) {
+                Ok(config) => config,
+                Err(err) => {
+                    log::warn!(target: "app", "skip the invalid profile \"{}\", {err}", item.uid.as_deref().unwrap_or_default());
+                    continue;
+                }
+            },
+            Err(_) => continue,
+        };
+
+        let proxies = config
+            .get("proxies")
+            .and_then(|val| val.as_sequence())
+            .cloned()
+            .unwrap_or_default();
+        for proxy in proxies.into_iter().filter_map(|p| p.as_mapping().cloned()) {
+            let location = NodeLocation {
+                uid: item.uid.clone().unwrap_or_default(),
+                profile: item.name.clone().unwrap_or_default(),
+                name: proxy
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default()
+                    .into(),
+            };
+            nodes.push((location, proxy));
+        }
+    }
+    nodes
+}
+
+fn find_duplicates(nodes: &[(NodeLocation, Mapping)]) -> Vec<DuplicateNodes> {
+    let mut order = vec![];
+    let mut groups: HashMap<String, Vec<NodeLocation>> = HashMap::new();
+    for (location, proxy) in nodes.iter() {
+        if let Some(fingerprint) = node_fingerprint(proxy) {
+            if !groups.contains_key(&fingerprint) {
+                order.push(fingerprint.clone());
+            }
+            groups
+                .entry(fingerprint)
+                .or_default()
+                .push(location.clone());
+        }
+    }
+
+    order
+        .into_iter()
+        .filter_map(|fingerprint| {
+            let nodes = groups.remove(&fingerprint)?;
+            match nodes.len() > 1 {
+                true => Some(DuplicateNodes { fingerprint, nodes }),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// the same server with the same credential and transport is the same node,
+/// whatever the name is
+pub fn node_fingerprint(proxy: &Mapping) -> Option<String> {
+    let text = |key: &str| match proxy.get(key) {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    };
+
+    let server = text("server")?.to_lowercase();
+    let port = text("port")?;
+    let ptype = text("type").unwrap_or_default().to_lowercase();
+    let credential = ["uuid", "password", "auth-str", "auth", "username"]
+        .into_iter()
+        .find_map(text)
+        .unwrap_or_default();
+    let transport = text("network").unwrap_or_else(|| "tcp".into());
+
+    Some(format!("{ptype}|{server}|{port}|{credential}|{transport}"))
+}
+
+#[test]
+fn test_find_duplicates() {
+    let proxy = |yaml: &str| serde_yaml::from_str::<Mapping>(yaml).unwrap();
+    let location = |uid: &str, name: &str| NodeLocation {
+        uid: uid.into(),
+        profile: uid.into(),
+        name: name.into(),
+    };
+
+    let nodes = vec![
+        (
+            location("r1", "de"),
+            proxy("{name: de, type: vless, server: A.com, port: 443, uuid: u1, network: ws}"),
+        ),
+        (
+            location("r2", "germany"),
+            proxy(
+                "{name: germany, type: vless, server: a.com, port: '443', uuid: u1, network: ws}",
+            ),
+        ),
+        (
+            location("r2", "grpc"),
+            proxy("{name: grpc, type: vless, server: a.com, port: 443, uuid: u1, network: grpc}"),
+        ),
+        (
+            location("r2", "invalid"),
+            proxy("{name: invalid, type: ss}"),
+        ),
+    ];
+
+    let duplicates = find_duplicates(&nodes);
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].fingerprint, "vless|a.com|443|u1|ws");
+    assert_eq!(
+        duplicates[0].nodes,
+        vec![location("r1", "de"), location("r2", "germany")]
+    );
+}
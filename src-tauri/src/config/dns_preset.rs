@@ -0,0 +1,167 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+/// resolve the doh servers, reachable without the proxy
+const BOOTSTRAP_NAMESERVERS: [&str; 2] = ["1.1.1.1", "8.8.8.8"];
+
+/// kept out of the fake ip, the domestic and the banking (`shaparak.ir`) sites
+/// check the real ip of the visitor
+const FAKE_IP_FILTER: [&str; 11] = [
+    "*.lan",
+    "+.local",
+    "+.ir",
+    "+.digikala.com",
+    "+.aparat.com",
+    "+.snapp.taxi",
+    "+.msftconnecttest.com",
+    "+.msftncsi.com",
+    "time.*.com",
+    "ntp.*.com",
+    "+.stun.*.*",
+];
+
+/// the choice of `apply_dns_preset`, saved as `dns_preset` of the verge config
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IDnsPreset {
+    /// `default`, `anti_sanction`, `cloudflare`, `quad9` or `custom`
+    /// `none` removes the dns of the override
+    pub preset: String,
+
+    /// `fake-ip` or `redir-host`, default is `fake-ip`
+    pub mode: Option<String>,
+
+    /// the nameservers of `custom`
+    pub nameservers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DnsPresetInfo {
+    pub preset: String,
+
+    pub name: String,
+
+    pub nameservers: Vec<String>,
+}
+
+/// the built-in presets, the `custom` is not listed
+pub fn dns_presets() -> Vec<DnsPresetInfo> {
+    [
+        ("default", "Default"),
+        ("anti_sanction", "Anti-sanction DoH"),
+        ("cloudflare", "Cloudflare"),
+        ("quad9", "Quad9"),
+    ]
+    .into_iter()
+    .map(|(preset, name)| DnsPresetInfo {
+        preset: preset.into(),
+        name: name.into(),
+        nameservers: preset_nameservers(preset)
+            .unwrap_or_default()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    })
+    .collect()
+}
+
+fn preset_nameservers(preset: &str) -> Option<Vec<&'static str>> {
+    Some(match preset {
+        "default" => vec!["8.8.8.8", "1.1.1.1"],
+        "anti_sanction" => vec![
+            "https://free.shecan.ir/dns-query",
+            "https://dns.403.online/dns-query",
+        ],
+        "cloudflare" => vec!["https://1.1.1.1/dns-query", "https://1.0.0.1/dns-query"],
+        "quad9" => vec![
+            "https://dns.quad9.net/dns-query",
+            "https://9.9.9.9/dns-query",
+        ],
+        _ => return None,
+    })
+}
+
+impl IDnsPreset {
+    /// the `dns` of the override, none for `none`
+    pub fn to_dns(&self) -> Result<Option<Mapping>> {
+        let nameservers = match self.preset.as_str() {
+            "none" => return Ok(None),
+            "custom" => {
+                let nameservers = self.nameservers.clone().unwrap_or_default();
+                let nameservers = nameservers
+                    .into_iter()
+                    .map(|ns| ns.trim().to_string())
+                    .filter(|ns| !ns.is_empty())
+                    .collect::<Vec<String>>();
+                if nameservers.is_empty() {
+                    bail!("the nameservers of the custom dns preset are empty");
+                }
+                nameservers
+            }
+            preset => match preset_nameservers(preset) {
+                Some(nameservers) => nameservers.into_iter().map(String::from).collect(),
+                None => bail!("invalid dns preset \"{preset}\""),
+            },
+        };
+
+        let mode = self.mode.clone().unwrap_or("fake-ip".into());
+        if mode != "fake-ip" && mode != "redir-host" {
+            bail!("invalid dns mode \"{mode}\"");
+        }
+
+        let seq =
+            |items: Vec<String>| Value::Sequence(items.into_iter().map(Value::from).collect());
+
+        let mut dns = Mapping::new();
+        dns.insert("enable".into(), true.into());
+        dns.insert("ipv6".into(), false.into());
+        dns.insert("enhanced-mode".into(), mode.clone().into());
+        if mode == "fake-ip" {
+            let filter = FAKE_IP_FILTER.iter().map(|f| f.to_string()).collect();
+            dns.insert("fake-ip-range".into(), "198.18.0.1/16".into());
+            dns.insert("fake-ip-filter".into(), seq(filter));
+        }
+
+        let bootstrap = BOOTSTRAP_NAMESERVERS.iter().map(|ns| ns.to_string());
+        dns.insert("default-nameserver".into(), seq(bootstrap.collect()));
+        dns.insert("nameserver".into(), seq(nameservers));
+        Ok(Some(dns))
+    }
+}
+
+#[test]
+fn test_dns_preset() {
+    let preset = |preset: &str, mode: Option<&str>| IDnsPreset {
+        preset: preset.into(),
+        mode: mode.map(String::from),
+        nameservers: None,
+    };
+
+    let dns = preset("cloudflare", None).to_dns().unwrap().unwrap();
+    assert_eq!(dns["enhanced-mode"], "fake-ip");
+    assert_eq!(dns["nameserver"][0], "https://1.1.1.1/dns-query");
+    assert!(dns["fake-ip-filter"]
+        .as_sequence()
+        .unwrap()
+        .contains(&"+.ir".into()));
+
+    let dns = preset("quad9", Some("redir-host"))
+        .to_dns()
+        .unwrap()
+        .unwrap();
+    assert_eq!(dns["enhanced-mode"], "redir-host");
+    assert!(dns.get("fake-ip-filter").is_none());
+
+    assert!(preset("none", None).to_dns().unwrap().is_none());
+    assert!(preset("custom", None).to_dns().is_err());
+    assert!(preset("google", None).to_dns().is_err());
+    assert!(preset("default", Some("mapping")).to_dns().is_err());
+
+    let custom = IDnsPreset {
+        nameservers: Some(vec![" tls://9.9.9.9 ".into(), "".into()]),
+        ..preset("custom", None)
+    };
+    let dns = custom.to_dns().unwrap().unwrap();
+    assert_eq!(dns["nameserver"], Value::from(vec!["tls://9.9.9.9"]));
+    assert_eq!(dns_presets().len(), 4);
+}
@@ -1,17 +1,131 @@
-use super::{IClashTemp, IProfiles, IRuntime, IVerge};
+use super::{IClashTemp, IOverride, IProfiles, IRuntime, IVerge};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
 use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
 use std::sync::Arc;
+use tokio::sync::{Mutex as WriterLock, OwnedMutexGuard};
 
 #[derive(Debug, Clone)]
 pub struct Draft<T: Clone + ToOwned> {
     inner: Arc<Mutex<(T, Option<T>)>>,
+
+    /// held by the transaction writing the draft, see `Draft::begin`
+    writer: Arc<WriterLock<()>>,
+}
+
+/// the configs written by the transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigKind {
+    Clash,
+    Verge,
+    Profiles,
+    Override,
+}
+
+/// the config validated and saved by `DraftTx::commit`
+pub trait ConfigData: Clone {
+    const KIND: ConfigKind;
+
+    /// the invalid draft is never applied
+    fn validate(&self) -> Result<()>;
+
+    fn save(&self) -> Result<()>;
+}
+
+/// told after each commit, such as to emit the change events
+static COMMIT_LISTENER: OnceCell<fn(ConfigKind)> = OnceCell::new();
+
+/// only the first listener is kept
+pub fn on_commit(listener: fn(ConfigKind)) {
+    let _ = COMMIT_LISTENER.set(listener);
+}
+
+impl<T: ConfigData> Draft<T> {
+    /// wait for the running transaction of the config, then start a new one
+    /// the writes of two commands never mix in the same draft
+    pub async fn begin(&self) -> DraftTx<T> {
+        let writer = self.writer.clone().lock_owned().await;
+
+        DraftTx {
+            draft: self.clone(),
+            _writer: writer,
+            committed: false,
+        }
+    }
+}
+
+/// a write to the config, one at a time for each config
+/// the draft is discarded if the transaction is dropped without the commit,
+/// so an early return with `?` leaves nothing behind
+pub struct DraftTx<T: ConfigData> {
+    draft: Draft<T>,
+    _writer: OwnedMutexGuard<()>,
+    committed: bool,
+}
+
+impl<T: ConfigData> DraftTx<T> {
+    pub fn draft(&self) -> MappedMutexGuard<T> {
+        MutexGuard::map(self.draft.inner.lock(), |inner| {
+            let (data, draft) = inner;
+            draft.get_or_insert_with(|| data.clone())
+        })
+    }
+
+    /// check the draft before the side effects of the change
+    pub fn validate(&self) -> Result<()> {
+        match self.draft.inner.lock().1.as_ref() {
+            Some(draft) => draft.validate(),
+            None => Ok(()),
+        }
+    }
+
+    /// validate the draft, save it to the file and apply it at once
+    /// nothing is applied if any step fails
+    pub fn commit(mut self) -> Result<()> {
+        {
+            let mut inner = self.draft.inner.lock();
+            if let Some(draft) = inner.1.take() {
+                draft.validate()?;
+                draft.save()?;
+                inner.0 = draft;
+            }
+        }
+        self.committed = true;
+
+        if let Some(listener) = COMMIT_LISTENER.get() {
+            listener(T::KIND);
+        }
+        Ok(())
+    }
+}
+
+impl<T: ConfigData> Drop for DraftTx<T> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.draft.inner.lock().1.take();
+        }
+    }
 }
 
+/// the local ports of a config should not be the same, 0 is not set
+pub(super) fn check_ports(ports: &[(&str, u16)]) -> Result<()> {
+    let ports = ports.iter().filter(|(_, port)| *port > 0);
+
+    for (index, (key, port)) in ports.clone().enumerate() {
+        if let Some((other, _)) = ports.clone().skip(index + 1).find(|(_, p)| p == port) {
+            anyhow::bail!("port already in use, {key} and {other} are both {port}");
+        }
+    }
+    Ok(())
+}
+
+/// the configs written by the transactions keep `draft`, `apply` and `discard` private
+/// `data` is private for all, read the config by `latest`
 macro_rules! draft_define {
-    ($id: ident) => {
+    ($id: ident $(, $vis: vis)?) => {
         impl Draft<$id> {
             #[allow(unused)]
-            pub fn data(&self) -> MappedMutexGuard<$id> {
+            fn data(&self) -> MappedMutexGuard<$id> {
                 MutexGuard::map(self.inner.lock(), |guard| &mut guard.0)
             }
 
@@ -25,7 +139,8 @@ macro_rules! draft_define {
                 })
             }
 
-            pub fn draft(&self) -> MappedMutexGuard<$id> {
+            #[allow(unused)]
+            $($vis)? fn draft(&self) -> MappedMutexGuard<$id> {
                 MutexGuard::map(self.inner.lock(), |mut inner| {
                     if inner.1.is_none() {
                         inner.1 = Some(inner.0.clone());
@@ -35,7 +150,8 @@ macro_rules! draft_define {
                 })
             }
 
-            pub fn apply(&self) -> Option<$id> {
+            #[allow(unused)]
+            $($vis)? fn apply(&self) -> Option<$id> {
                 let mut inner = self.inner.lock();
 
                 match inner.1.take() {
@@ -48,7 +164,8 @@ macro_rules! draft_define {
                 }
             }
 
-            pub fn discard(&self) -> Option<$id> {
+            #[allow(unused)]
+            $($vis)? fn discard(&self) -> Option<$id> {
                 let mut inner = self.inner.lock();
                 inner.1.take()
             }
@@ -58,6 +175,7 @@ macro_rules! draft_define {
             fn from(data: $id) -> Self {
                 Draft {
                     inner: Arc::new(Mutex::new((data, None))),
+                    writer: Arc::new(WriterLock::new(())),
                 }
             }
         }
@@ -66,8 +184,9 @@ macro_rules! draft_define {
 
 // draft_define!(IClash);
 draft_define!(IClashTemp);
+draft_define!(IOverride);
 draft_define!(IProfiles);
-draft_define!(IRuntime);
+draft_define!(IRuntime, pub);
 draft_define!(IVerge);
 
 #[test]
@@ -125,3 +244,50 @@ fn test_draft() {
 
     assert_eq!(draft.draft().enable_auto_launch, Some(false));
 }
+
+#[test]
+fn test_draft_tx() {
+    use tokio::time::{timeout, Duration};
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let draft = Draft::from(IVerge {
+        proxy_guard_duration: Some(30),
+        ..IVerge::default()
+    });
+
+    runtime.block_on(async {
+        // dropped without the commit
+        let tx = draft.begin().await;
+        tx.draft().proxy_guard_duration = Some(10);
+        assert_eq!(draft.latest().proxy_guard_duration, Some(10));
+        drop(tx);
+        assert_eq!(draft.latest().proxy_guard_duration, Some(30));
+
+        // the invalid draft is not applied
+        let tx = draft.begin().await;
+        tx.draft().proxy_guard_duration = Some(0);
+        assert!(tx.validate().is_err());
+        assert!(tx.commit().is_err());
+        assert_eq!(draft.latest().proxy_guard_duration, Some(30));
+
+        // one writer at a time
+        let tx = draft.begin().await;
+        assert!(timeout(Duration::from_millis(20), draft.begin())
+            .await
+            .is_err());
+        drop(tx);
+        assert!(timeout(Duration::from_millis(20), draft.begin())
+            .await
+            .is_ok());
+    });
+
+    assert!(check_ports(&[("a", 7890), ("b", 0), ("c", 0)]).is_ok());
+    let err = check_ports(&[("a", 7890), ("b", 9090), ("c", 7890)]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "port already in use, a and c are both 7890"
+    );
+}
@@ -0,0 +1,87 @@
+use anyhow::{bail, Result};
+use std::net::IpAddr;
+
+/// the names of the system hosts file not imported
+const SYSTEM_HOSTS: [&str; 4] = [
+    "localhost",
+    "localhost.localdomain",
+    "broadcasthost",
+    "local",
+];
+
+/// the normalized domain and ip of a static mapping of `hosts`
+/// the domain can start with `*.` or `+.`
+pub fn check_host(domain: &str, ip: &str) -> Result<(String, String)> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    let name = domain
+        .strip_prefix("*.")
+        .or_else(|| domain.strip_prefix("+."))
+        .unwrap_or(&domain);
+
+    let valid = !name.is_empty()
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid {
+        bail!("invalid host domain \"{domain}\"");
+    }
+
+    let ip = match ip.trim().parse::<IpAddr>() {
+        Ok(ip) => ip.to_string(),
+        Err(_) => bail!("invalid host ip \"{}\"", ip.trim()),
+    };
+    Ok((domain, ip))
+}
+
+/// the mappings of a hosts file, the invalid lines are skipped
+/// the later mapping of the same domain wins
+pub fn parse_hosts_file(text: &str) -> Vec<(String, String)> {
+    let mut hosts: Vec<(String, String)> = vec![];
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        let ip = match parts.next() {
+            Some(ip) => ip,
+            None => continue,
+        };
+
+        for name in parts.filter(|name| !SYSTEM_HOSTS.contains(name)) {
+            if let Ok((domain, ip)) = check_host(name, ip) {
+                hosts.retain(|(d, _)| *d != domain);
+                hosts.push((domain, ip));
+            }
+        }
+    }
+
+    hosts
+}
+
+#[test]
+fn test_parse_hosts_file() {
+    assert_eq!(
+        check_host(" Panel.Example.com. ", "1.2.3.4").unwrap(),
+        ("panel.example.com".to_string(), "1.2.3.4".to_string())
+    );
+    assert!(check_host("+.example.com", "2606:4700::1").is_ok());
+    assert!(check_host("exa mple.com", "1.2.3.4").is_err());
+    assert!(check_host("-bad.com", "1.2.3.4").is_err());
+    assert!(check_host("example.com", "1.2.3").is_err());
+
+    let text = "127.0.0.1 localhost\n\
+        # the panel\n\
+        1.2.3.4  panel.example.com  cdn.example.com # pinned\n\
+        bad line\n\
+        5.6.7.8\tpanel.example.com\n";
+    assert_eq!(
+        parse_hosts_file(text),
+        [
+            ("cdn.example.com".to_string(), "1.2.3.4".to_string()),
+            ("panel.example.com".to_string(), "5.6.7.8".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,233 @@
+use super::Config;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+/// the domestic domains kept out of the fake ip
+const DOMESTIC_FILTER: &str = "+.ir";
+
+/// the rule sending the domestic ips out directly
+const GEOIP_RULE: &str = "GEOIP,IR,DIRECT";
+
+/// the domains filtered by the sni, the handshake is reset
+const BLOCKED_SNI: [&str; 11] = [
+    "youtube.com",
+    "twitter.com",
+    "x.com",
+    "facebook.com",
+    "instagram.com",
+    "telegram.org",
+    "t.me",
+    "whatsapp.com",
+    "discord.com",
+    "reddit.com",
+    "medium.com",
+];
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct LintIssue {
+    /// such as `fake_ip_filter`, also the key of the translation
+    pub rule: String,
+
+    /// the proxy name, none for the whole config
+    pub proxy: Option<String>,
+
+    /// why it breaks
+    pub message: String,
+
+    /// how to fix it by hand
+    pub suggestion: String,
+
+    /// the fields put into the overrides by `apply_lint_fix`
+    /// none if it can not be fixed by the overrides, such as the nodes of a subscription
+    pub fix: Option<Mapping>,
+}
+
+/// lint the runtime config
+pub fn lint_runtime_config() -> Vec<LintIssue> {
+    let config = { Config::runtime().latest().config.clone() };
+    config.map_or(vec![], |config| lint_config(&config))
+}
+
+/// the known pitfalls of the config in iran
+pub fn lint_config(config: &Mapping) -> Vec<LintIssue> {
+    let mut issues = vec![];
+    issues.extend(lint_fake_ip_filter(config));
+    issues.extend(lint_geoip_rule(config));
+
+    let proxies = config
+        .get("proxies")
+        .and_then(|p| p.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.as_mapping());
+    for proxy in proxies {
+        issues.extend(lint_blocked_sni(proxy));
+        issues.extend(lint_mux(proxy));
+    }
+
+    issues
+}
+
+fn lint_fake_ip_filter(config: &Mapping) -> Option<LintIssue> {
+    let dns = config.get("dns").and_then(|d| d.as_mapping())?;
+    if dns.get("enhanced-mode").and_then(|m| m.as_str()) != Some("fake-ip") {
+        return None;
+    }
+
+    let mut filter = dns
+        .get("fake-ip-filter")
+        .and_then(|f| f.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+    let covered = filter.iter().filter_map(|f| f.as_str()).any(|f| {
+        let f = f.to_lowercase();
+        f.ends_with("*.ir") || f.ends_with("+.ir") || f.contains("category-ir")
+    });
+    if covered {
+        return None;
+    }
+
+    filter.push(DOMESTIC_FILTER.into());
+    let mut dns = dns.clone();
+    dns.insert("fake-ip-filter".into(), filter.into());
+
+    let mut fix = Mapping::new();
+    fix.insert("dns".into(), dns.into());
+
+    Some(LintIssue {
+        rule: "fake_ip_filter".into(),
+        proxy: None,
+        message: "the domestic domains get fake ips, so the sites checking the ip of the visitor and the direct rules by the ip fail".into(),
+        suggestion: format!("add `{DOMESTIC_FILTER}` to `dns.fake-ip-filter`"),
+        fix: Some(fix),
+    })
+}
+
+fn lint_geoip_rule(config: &Mapping) -> Option<LintIssue> {
+    let rules = config.get("rules").and_then(|r| r.as_sequence())?;
+    let found = rules.iter().filter_map(|r| r.as_str()).any(|rule| {
+        let rule = rule.replace(' ', "").to_uppercase();
+        rule == GEOIP_RULE || rule.starts_with(&format!("{GEOIP_RULE},"))
+    });
+    if found {
+        return None;
+    }
+
+    let mut fix = Mapping::new();
+    fix.insert(
+        "prepend-rules".into(),
+        Value::Sequence(vec![GEOIP_RULE.into()]),
+    );
+
+    Some(LintIssue {
+        rule: "geoip_ir_direct".into(),
+        proxy: None,
+        message: "the domestic sites go through the proxy, they are slow and some of them block the foreign ips".into(),
+        suggestion: format!("add the rule `{GEOIP_RULE}` before the `MATCH` rule"),
+        fix: Some(fix),
+    })
+}
+
+fn lint_blocked_sni(proxy: &Mapping) -> Option<LintIssue> {
+    let sni = ["servername", "sni"]
+        .into_iter()
+        .find_map(|key| proxy.get(key).and_then(|s| s.as_str()))?
+        .trim_end_matches('.')
+        .to_lowercase();
+    let blocked = BLOCKED_SNI
+        .iter()
+        .find(|domain| sni == **domain || sni.ends_with(&format!(".{domain}")))?;
+
+    Some(LintIssue {
+        rule: "blocked_sni".into(),
+        proxy: proxy_name(proxy),
+        message: format!("the sni `{sni}` is under the blocked `{blocked}`, the tls handshake is reset by the filtering"),
+        suggestion: "use an unblocked domain on the same server or the cdn as the sni".into(),
+        fix: None,
+    })
+}
+
+fn lint_mux(proxy: &Mapping) -> Option<LintIssue> {
+    let enabled = proxy
+        .get("smux")
+        .and_then(|s| s.as_mapping())
+        .and_then(|s| s.get("enabled"))
+        .and_then(|e| e.as_bool())
+        .unwrap_or(false);
+    if !enabled {
+        return None;
+    }
+
+    let protocol = proxy
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let flow = proxy
+        .get("flow")
+        .and_then(|f| f.as_str())
+        .unwrap_or_default();
+
+    let message = match protocol.as_str() {
+        "hysteria" | "hysteria2" | "tuic" | "wireguard" => {
+            format!("the mux does not work over the udp of `{protocol}`")
+        }
+        _ if !flow.is_empty() => {
+            format!("the mux breaks the flow `{flow}`, the connections are closed")
+        }
+        _ => return None,
+    };
+
+    Some(LintIssue {
+        rule: "mux_unsupported".into(),
+        proxy: proxy_name(proxy),
+        message,
+        suggestion: "disable `smux` of the node".into(),
+        fix: None,
+    })
+}
+
+fn proxy_name(proxy: &Mapping) -> Option<String> {
+    proxy.get("name").and_then(|n| n.as_str()).map(String::from)
+}
+
+#[test]
+fn test_lint_config() {
+    let config = serde_yaml::from_str::<Mapping>(
+        r#"
+dns:
+  enhanced-mode: fake-ip
+  fake-ip-filter: ["*.lan"]
+rules: ["DOMAIN-SUFFIX,ir,DIRECT", "MATCH,proxy"]
+proxies:
+  - {name: a, type: vless, servername: www.youtube.com, flow: xtls-rprx-vision, smux: {enabled: true}}
+  - {name: b, type: hysteria2, sni: cdn.example.com, smux: {enabled: false}}
+  - {name: c, type: vmess, servername: youtube.com.example.com, smux: {enabled: true}}
+"#,
+    )
+    .unwrap();
+
+    let issues = lint_config(&config);
+    let rules = issues.iter().map(|i| i.rule.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        rules,
+        [
+            "fake_ip_filter",
+            "geoip_ir_direct",
+            "blocked_sni",
+            "mux_unsupported"
+        ]
+    );
+    assert!(issues[2..].iter().all(|i| i.proxy.as_deref() == Some("a")));
+
+    let fix = issues[0].fix.as_ref().unwrap();
+    let filter = &fix["dns"]["fake-ip-filter"];
+    assert_eq!(filter, &Value::from(vec!["*.lan", DOMESTIC_FILTER]));
+    assert_eq!(fix["dns"]["enhanced-mode"], "fake-ip");
+
+    let config = serde_yaml::from_str::<Mapping>(
+        "{dns: {enhanced-mode: fake-ip, fake-ip-filter: ['+.ir']}, rules: ['geoip, ir, direct, no-resolve']}",
+    )
+    .unwrap();
+    assert!(lint_config(&config).is_empty());
+}
@@ -1,15 +1,37 @@
 mod clash;
 mod config;
+mod dedup;
+mod dns_preset;
 mod draft;
+mod generator;
+mod hosts;
+mod lint;
+mod node_filter;
+mod overrides;
+mod preset;
 mod prfitem;
+mod prfurl;
 mod profiles;
+mod routing_preset;
+mod rule_edit;
 mod runtime;
 mod verge;
 
 pub use self::clash::*;
 pub use self::config::*;
+pub use self::dedup::*;
+pub use self::dns_preset::*;
 pub use self::draft::*;
+pub use self::generator::*;
+pub use self::hosts::*;
+pub use self::lint::*;
+pub use self::node_filter::*;
+pub use self::overrides::*;
+pub use self::preset::*;
 pub use self::prfitem::*;
+pub use self::prfurl::*;
 pub use self::profiles::*;
+pub use self::routing_preset::*;
+pub use self::rule_edit::*;
 pub use self::runtime::*;
 pub use self::verge::*;
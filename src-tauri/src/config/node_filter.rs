@@ -0,0 +1,108 @@
+use super::Config;
+use anyhow::Result;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+use tauri::regex::Regex;
+
+/// the entries of the providers masquerading as nodes, such as the website and the traffic left
+const DEFAULT_NODE_FILTERS: [&str; 6] = [
+    "官网|官方|网址|客服",
+    "剩余流量|套餐|到期|过期|重置",
+    r"(?i)\b(expire|traffic|remaining|renew)",
+    r"(?i)(t\.me/|telegram|channel)",
+    r"(?i)(website|https?://)",
+    r"(?i)\d+\s*(days?|gb) left",
+];
+
+/// the patterns of `node_filters`, the built-in ones if not set
+pub fn node_filters() -> Vec<String> {
+    let filters = { Config::verge().latest().node_filters.clone() };
+    filters.unwrap_or_else(|| DEFAULT_NODE_FILTERS.iter().map(|f| f.to_string()).collect())
+}
+
+/// drop the proxies whose names match any pattern, also from the groups
+/// return the count of the dropped proxies, fail if a regex is invalid
+pub fn filter_nodes(config: &mut Mapping, patterns: &[String]) -> Result<usize> {
+    let regexes = patterns
+        .iter()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| Regex::new(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    if regexes.is_empty() {
+        return Ok(0);
+    }
+
+    let mut dropped = HashSet::new();
+    if let Some(proxies) = config.get_mut("proxies").and_then(|p| p.as_sequence_mut()) {
+        proxies.retain(|proxy| {
+            let name = proxy
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default();
+            let matched = regexes.iter().any(|r| r.is_match(name));
+            if matched {
+                dropped.insert(name.to_string());
+            }
+            !matched
+        });
+    }
+    if dropped.is_empty() {
+        return Ok(0);
+    }
+
+    if let Some(groups) = config
+        .get_mut("proxy-groups")
+        .and_then(|g| g.as_sequence_mut())
+    {
+        for group in groups.iter_mut().filter_map(|g| g.as_mapping_mut()) {
+            let uses = group.contains_key("use");
+            if let Some(proxies) = group.get_mut("proxies").and_then(|p| p.as_sequence_mut()) {
+                proxies.retain(|p| !matches!(p.as_str(), Some(name) if dropped.contains(name)));
+                // the group without any proxy fails the core
+                if proxies.is_empty() && !uses {
+                    proxies.push(Value::from("DIRECT"));
+                }
+            }
+        }
+    }
+
+    Ok(dropped.len())
+}
+
+#[test]
+fn test_filter_nodes() {
+    let mut config = serde_yaml::from_str::<Mapping>(
+        r#"
+proxies:
+  - {name: "官网: example.com", type: ss}
+  - {name: "剩余流量：120 GB", type: ss}
+  - {name: "Expire: 2026-12-01", type: ss}
+  - {name: "🇩🇪 DE 1", type: vless}
+  - {name: "JP 2", type: trojan}
+proxy-groups:
+  - {name: select, type: select, proxies: ["官网: example.com", "🇩🇪 DE 1", "JP 2"]}
+  - {name: info, type: select, proxies: ["剩余流量：120 GB", "Expire: 2026-12-01"]}
+  - {name: sub, type: select, use: [provider], proxies: ["Expire: 2026-12-01"]}
+"#,
+    )
+    .unwrap();
+
+    let filters = DEFAULT_NODE_FILTERS.map(String::from);
+    assert_eq!(filter_nodes(&mut config, &filters).unwrap(), 3);
+    assert_eq!(config["proxies"].as_sequence().unwrap().len(), 2);
+    assert_eq!(
+        config["proxy-groups"][0]["proxies"],
+        Value::from(vec!["🇩🇪 DE 1", "JP 2"])
+    );
+    assert_eq!(
+        config["proxy-groups"][1]["proxies"],
+        Value::from(vec!["DIRECT"])
+    );
+    assert!(config["proxy-groups"][2]["proxies"]
+        .as_sequence()
+        .unwrap()
+        .is_empty());
+
+    assert_eq!(filter_nodes(&mut config, &[]).unwrap(), 0);
+    assert!(filter_nodes(&mut config, &["(".into()]).is_err());
+}
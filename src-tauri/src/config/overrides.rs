@@ -0,0 +1,192 @@
+use super::draft::{ConfigData, ConfigKind};
+use crate::utils::{dirs, help};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Sequence, Value};
+use tauri::regex::Regex;
+
+/// the key of the keyword filters of the groups in the override
+pub const GROUP_FILTERS: &str = "group-filters";
+
+/// ### `override.yaml` schema
+/// same as the `merge` profile item
+/// managed by the app and applied after the profile chain
+#[derive(Default, Debug, Clone)]
+pub struct IOverride(pub Mapping);
+
+impl IOverride {
+    pub fn new() -> Self {
+        match dirs::override_path().and_then(|path| help::read_merge_mapping(&path)) {
+            Ok(map) => Self(map),
+            Err(err) => {
+                log::error!(target: "app", "{err}");
+                Self::template()
+            }
+        }
+    }
+
+    pub fn template() -> Self {
+        Self(Mapping::new())
+    }
+
+    pub fn save_file(&self) -> Result<()> {
+        help::save_yaml(
+            &dirs::override_path()?,
+            &self.0,
+            Some("# Override Config for Hiddify Clash Desktop"),
+        )
+    }
+
+    /// get the sequence field, such as `prepend-rules`
+    pub fn get_seq(&self, key: &str) -> Sequence {
+        self.0
+            .get(key)
+            .and_then(|val| val.as_sequence())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// set the sequence field
+    /// remove the field if the sequence is empty
+    pub fn set_seq(&mut self, key: &str, seq: Sequence) {
+        match seq.is_empty() {
+            true => self.0.remove(key),
+            false => self.0.insert(key.into(), Value::from(seq)),
+        };
+    }
+
+    /// get the mapping field, such as `hosts`
+    pub fn get_map(&self, key: &str) -> Mapping {
+        self.0
+            .get(key)
+            .and_then(|val| val.as_mapping())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// set the mapping field
+    /// remove the field if the mapping is empty
+    pub fn set_map(&mut self, key: &str, map: Mapping) {
+        match map.is_empty() {
+            true => self.0.remove(key),
+            false => self.0.insert(key.into(), Value::from(map)),
+        };
+    }
+
+    /// get the `group-filters`, the invalid ones are skipped
+    pub fn get_group_filters(&self) -> Vec<IGroupFilter> {
+        self.get_seq(GROUP_FILTERS)
+            .into_iter()
+            .filter_map(|filter| serde_yaml::from_value(filter).ok())
+            .collect()
+    }
+
+    /// set the `group-filters`
+    pub fn set_group_filters(&mut self, filters: &[IGroupFilter]) -> Result<()> {
+        let seq = filters
+            .iter()
+            .map(serde_yaml::to_value)
+            .collect::<Result<Sequence, _>>()?;
+        self.set_seq(GROUP_FILTERS, seq);
+        Ok(())
+    }
+}
+
+impl ConfigData for IOverride {
+    const KIND: ConfigKind = ConfigKind::Override;
+
+    /// the invalid group filters are not skipped silently
+    fn validate(&self) -> Result<()> {
+        for key in ["prepend-rules", GROUP_FILTERS] {
+            if !matches!(self.0.get(key), None | Some(Value::Sequence(_))) {
+                bail!("the \"{key}\" of the override should be a sequence");
+            }
+        }
+        if !matches!(self.0.get("hosts"), None | Some(Value::Mapping(_))) {
+            bail!("the \"hosts\" of the override should be a mapping");
+        }
+
+        for filter in self.get_seq(GROUP_FILTERS) {
+            let filter: IGroupFilter =
+                serde_yaml::from_value(filter).context("invalid group filter")?;
+            if filter.name.trim().is_empty() {
+                bail!("the name of the group filter is empty");
+            }
+            let _ = filter.matcher()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        self.save_file()
+    }
+}
+
+/// the proxy group holding the nodes matching the keywords
+/// the group of the same name in the profile is replaced, otherwise appended
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct IGroupFilter {
+    pub name: String,
+
+    /// the type of the appended group, default is `select`
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub gtype: Option<String>,
+
+    /// the regex of the node names kept, such as `JP|Japan|🇯🇵`, ignore the case
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<String>,
+
+    /// the regex of the node names dropped after the `include`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+}
+
+impl IGroupFilter {
+    /// whether the node name is kept, fail if a regex is invalid
+    pub fn matcher(&self) -> Result<impl Fn(&str) -> bool> {
+        let compile = |pattern: &Option<String>| match pattern.as_deref().map(str::trim) {
+            Some(pattern) if !pattern.is_empty() => Regex::new(&format!("(?i){pattern}"))
+                .with_context(|| {
+                    format!(
+                        "invalid filter \"{pattern}\" of the group \"{}\"",
+                        self.name
+                    )
+                })
+                .map(Some),
+            _ => Ok(None),
+        };
+        let include = compile(&self.include)?;
+        let exclude = compile(&self.exclude)?;
+
+        Ok(move |node: &str| {
+            !matches!(&include, Some(r) if !r.is_match(node))
+                && !matches!(&exclude, Some(r) if r.is_match(node))
+        })
+    }
+}
+
+#[test]
+fn test_validate_override() {
+    let filter = |name: &str, include: &str| IGroupFilter {
+        name: name.into(),
+        include: Some(include.into()),
+        ..IGroupFilter::default()
+    };
+
+    let mut overrides = IOverride::template();
+    overrides.set_seq("prepend-rules", vec!["DOMAIN,a.com,DIRECT".into()]);
+    overrides
+        .set_group_filters(&[filter("JP", "JP|Japan")])
+        .unwrap();
+    assert!(overrides.validate().is_ok());
+
+    overrides.set_group_filters(&[filter(" ", "JP")]).unwrap();
+    assert!(overrides.validate().is_err());
+    overrides.set_group_filters(&[filter("JP", "(JP")]).unwrap();
+    assert!(overrides.validate().is_err());
+    overrides.set_group_filters(&[]).unwrap();
+    assert!(overrides.validate().is_ok());
+
+    overrides.0.insert("hosts".into(), "a.com".into());
+    assert!(overrides.validate().is_err());
+}
@@ -0,0 +1,159 @@
+use super::{Config, IVerge};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+
+/// the format version of the preset
+const PRESET_VERSION: u32 = 1;
+
+/// the clash fields shared by the preset
+/// the `secret` and the `external-controller` are never shared
+const PRESET_CLASH_FIELDS: [&str; 7] = [
+    "mode",
+    "mixed-port",
+    "socks-port",
+    "port",
+    "allow-lan",
+    "ipv6",
+    "log-level",
+];
+
+/// the shareable app settings without the secrets,
+/// the profiles, the schedules and the machine specific settings
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ISettingsPreset {
+    pub version: u32,
+
+    #[serde(default)]
+    pub verge: IVerge,
+
+    #[serde(default)]
+    pub clash: Mapping,
+}
+
+impl ISettingsPreset {
+    /// the preset of the current settings
+    pub fn export() -> Self {
+        let verge = { Config::verge().latest().clone() };
+        let clash = { Config::clash().latest().0.clone() };
+        Self::new(&verge, &clash)
+    }
+
+    /// only the shareable fields are kept
+    fn new(verge: &IVerge, clash: &Mapping) -> Self {
+        let mut preset = IVerge::default();
+
+        macro_rules! copy {
+            ($($key: tt),*) => {
+                $(preset.$key = verge.$key.clone();)*
+            };
+        }
+
+        copy!(
+            language,
+            theme_mode,
+            theme_blur,
+            traffic_graph,
+            theme_setting
+        );
+        copy!(
+            enable_tun_mode,
+            enable_system_proxy,
+            enable_proxy_guard,
+            proxy_guard_duration,
+            system_proxy_bypass
+        );
+        copy!(
+            clash_core,
+            hotkeys,
+            auto_close_connection,
+            proxy_layout_column
+        );
+        copy!(
+            default_latency_test,
+            latency_test_urls,
+            measure_port,
+            measure_repeat,
+            measure_udp_server,
+            measure_http_version,
+            measure_user_agent,
+            measure_accept_language,
+            measure_header_order,
+            measure_random_ua
+        );
+        copy!(enable_clash_fields, enable_builtin_enhanced);
+        copy!(update_channel, notification, expire_reminder_days);
+
+        // the injected css runs in the webview, it is never shared
+        if let Some(theme) = preset.theme_setting.as_mut() {
+            theme.css_injection = None;
+        }
+
+        let clash = PRESET_CLASH_FIELDS
+            .iter()
+            .filter_map(|key| Some(((*key).into(), clash.get(*key)?.clone())))
+            .collect::<Mapping>();
+
+        Self {
+            version: PRESET_VERSION,
+            verge: preset,
+            clash,
+        }
+    }
+
+    /// the unset fields are left out
+    pub fn to_json(&self) -> Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(verge) = value.get_mut("verge").and_then(|v| v.as_object_mut()) {
+            verge.retain(|_, v| !v.is_null());
+        }
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+
+    /// the fields not shareable are dropped
+    pub fn from_json(json: &str) -> Result<Self> {
+        let preset = serde_json::from_str::<Self>(json)?;
+        if preset.version > PRESET_VERSION {
+            bail!("the preset is made by a newer version of the app");
+        }
+        Ok(Self::new(&preset.verge, &preset.clash))
+    }
+}
+
+#[test]
+fn test_settings_preset() {
+    let verge = IVerge {
+        language: Some("fa".into()),
+        measure_repeat: Some(3),
+        web_ui_list: Some(vec!["http://127.0.0.1:9090/ui?secret=xxx".into()]),
+        app_singleton_port: Some(33331),
+        theme_setting: Some(super::IVergeTheme {
+            primary_color: Some("#000000".into()),
+            css_injection: Some("body { display: none }".into()),
+            ..Default::default()
+        }),
+        ..IVerge::default()
+    };
+    let clash =
+        serde_yaml::from_str::<Mapping>("mode: global\nmixed-port: 7890\nsecret: xxx").unwrap();
+
+    let json = ISettingsPreset::new(&verge, &clash).to_json().unwrap();
+    assert!(!json.contains("secret"));
+    assert!(!json.contains("null"));
+    assert!(!json.contains("css_injection"));
+
+    let preset = ISettingsPreset::from_json(&json).unwrap();
+    assert_eq!(preset.verge.language, Some("fa".into()));
+    assert_eq!(preset.verge.measure_repeat, Some(3));
+    assert_eq!(preset.clash.len(), 2);
+
+    // the secrets are dropped from the shared preset too
+    let json = r#"{"version":1,"verge":{"web_ui_list":["x"],"app_singleton_port":1,"theme_setting":{"css_injection":"*{}"}},"clash":{"secret":"x","external-controller":"0.0.0.0:9090"}}"#;
+    let preset = ISettingsPreset::from_json(json).unwrap();
+    assert!(preset.verge.web_ui_list.is_none());
+    assert!(preset.verge.theme_setting.unwrap().css_injection.is_none());
+    assert!(preset.verge.app_singleton_port.is_none());
+    assert!(preset.clash.is_empty());
+
+    assert!(ISettingsPreset::from_json(r#"{"version":2}"#).is_err());
+}
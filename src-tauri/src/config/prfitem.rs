@@ -1,14 +1,22 @@
-use crate::utils::{dirs, help, tmpl};
+use crate::utils::{app_state::AppState, dirs, help, tmpl};
 use anyhow::{bail, Context, Result};
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
+use std::collections::HashMap;
 use std::fs;
+use std::path::PathBuf;
 use sysproxy::Sysproxy;
-use tauri::regex::Regex;
 
 
-use super::Config;
+use super::{
+    filter_nodes, generate_basic_profile, node_filters, normalize_profile_url,
+    profile_redirect_policy, Config, IVergeRenameRule,
+};
+
+/// the dropped file larger than it is not a profile
+const MAX_IMPORT_SIZE: u64 = 10 * 1024 * 1024;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PrfItem {
@@ -44,6 +52,22 @@ pub struct PrfItem {
     /// updated time
     pub updated: Option<usize>,
 
+    /// how the last refresh was downloaded
+    /// enum value: direct | system | proxy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downloaded_via: Option<String>,
+
+    /// the validators of the remote response for the conditional refresh
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+
+    /// the count of the nodes dropped by `filter_nodes` on the last import or update
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered: Option<usize>,
+
     /// some options of the item
     #[serde(skip_serializing_if = "Option::is_none")]
     pub option: Option<PrfOption>,
@@ -86,6 +110,35 @@ pub struct PrfOption {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update_interval: Option<u64>,
+
+    /// for `remote` profile
+    /// retry through the clash core after the direct failed
+    /// enum value: auto | always | never
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_via_proxy: Option<String>,
+
+    /// for `remote` profile's http request
+    /// such as the token required by the panel, `User-Agent` here wins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_headers: Option<HashMap<String, String>>,
+
+    /// the expire time in seconds set by hand
+    /// for the subscription without `subscription-userinfo`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expire: Option<usize>,
+
+    /// the uid of the `script` item transforming this profile before the chain
+    /// run in the quickjs without the file system and the network
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+
+    /// rename the nodes on loading, after the global `rename_rules`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename_rules: Option<Vec<IVergeRenameRule>>,
+
+    /// drop the nodes matching the `node_filters`, such as the ads of the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter_nodes: Option<bool>,
 }
 
 impl PrfOption {
@@ -96,6 +149,12 @@ impl PrfOption {
                 a.with_proxy = b.with_proxy.or(a.with_proxy);
                 a.self_proxy = b.self_proxy.or(a.self_proxy);
                 a.update_interval = b.update_interval.or(a.update_interval);
+                a.download_via_proxy = b.download_via_proxy.or(a.download_via_proxy);
+                a.request_headers = b.request_headers.or(a.request_headers);
+                a.expire = b.expire.or(a.expire);
+                a.script = b.script.or(a.script);
+                a.rename_rules = b.rename_rules.or(a.rename_rules);
+                a.filter_nodes = b.filter_nodes.or(a.filter_nodes);
                 Some(a)
             }
             t @ _ => t.0.or(t.1),
@@ -115,6 +174,10 @@ impl Default for PrfItem {
             selected: None,
             extra: None,
             updated: None,
+            downloaded_via: None,
+            etag: None,
+            last_modified: None,
+            filtered: None,
             option: None,
             file_data: None,
         }
@@ -175,6 +238,10 @@ impl PrfItem {
             extra: None,
             option: None,
             updated: Some(chrono::Local::now().timestamp() as usize),
+            downloaded_via: None,
+            etag: None,
+            last_modified: None,
+            filtered: None,
             file_data: Some(file_data.unwrap_or(tmpl::ITEM_LOCAL.into())),
         })
     }
@@ -187,62 +254,110 @@ impl PrfItem {
         desc: Option<String>,
         option: Option<PrfOption>,
     ) -> Result<PrfItem> {
+        let url = normalize_profile_url(url)?;
+        match Self::from_url_modified(&url, name, desc, option, None).await? {
+            Some(item) => Ok(item),
+            None => bail!("the remote profile is not modified"),
+        }
+    }
+
+    /// ## Remote type
+    /// send the validators of the cached item
+    /// return none if the server replies `304 Not Modified`
+    pub async fn from_url_modified(
+        url: &str,
+        name: Option<String>,
+        desc: Option<String>,
+        option: Option<PrfOption>,
+        cached: Option<&PrfItem>,
+    ) -> Result<Option<PrfItem>> {
         let opt_ref = option.as_ref();
+        let filter = opt_ref.map_or(false, |o| o.filter_nodes.unwrap_or(false));
         let with_proxy = opt_ref.map_or(false, |o| o.with_proxy.unwrap_or(false));
         let self_proxy = opt_ref.map_or(false, |o| o.self_proxy.unwrap_or(false));
-        let user_agent = opt_ref.map_or(None, |o| o.user_agent.clone());
+        let user_agent = opt_ref.and_then(|o| o.user_agent.clone());
+        let via_proxy = opt_ref.and_then(|o| o.download_via_proxy.clone());
+        let headers = opt_ref.and_then(|o| o.request_headers.clone());
+        let headers = Self::header_map(headers.unwrap_or_default())?;
+
+        // `self_proxy` is the same as `always` if not set
+        let via_proxy = match via_proxy {
+            Some(via_proxy) => via_proxy,
+            None if self_proxy => "always".into(),
+            None => "auto".into(),
+        };
+        let first = match with_proxy {
+            true => "system",
+            false => "direct",
+        };
+        let routes = match via_proxy.as_str() {
+            "auto" => vec![first, "proxy"],
+            "always" => vec!["proxy"],
+            "never" => vec![first],
+            mode => bail!("invalid download_via_proxy \"{mode}\""),
+        };
 
-        let mut builder = reqwest::ClientBuilder::new().no_proxy();
+        let version = AppState::global().version();
+        let version = format!("hiddify-desktop/{version}");
+        let user_agent = user_agent.unwrap_or(version);
 
-        // 使用软件自己的代理
-        if self_proxy {
-            let port = Config::clash().data().get_mixed_port();
+        let mut last_err = None;
+        let mut fetched = None;
 
-            let proxy_scheme = format!("http://127.0.0.1:{port}");
+        for via in routes {
+            let result = async {
+                let mut request = Self::remote_client(via, &user_agent, &headers)?.get(url);
+                if let Some(etag) = cached.and_then(|item| item.etag.as_ref()) {
+                    request = request.header(header::IF_NONE_MATCH, etag);
+                }
+                if let Some(modified) = cached.and_then(|item| item.last_modified.as_ref()) {
+                    request = request.header(header::IF_MODIFIED_SINCE, modified);
+                }
+                let resp = request.send().await?;
 
-            if let Ok(proxy) = reqwest::Proxy::http(&proxy_scheme) {
-                builder = builder.proxy(proxy);
-            }
-            if let Ok(proxy) = reqwest::Proxy::https(&proxy_scheme) {
-                builder = builder.proxy(proxy);
-            }
-            if let Ok(proxy) = reqwest::Proxy::all(&proxy_scheme) {
-                builder = builder.proxy(proxy);
-            }
-        }
-        // 使用系统代理
-        else if with_proxy {
-            match Sysproxy::get_system_proxy() {
-                Ok(p @ Sysproxy { enable: true, .. }) => {
-                    let proxy_scheme = format!("http://{}:{}", p.host, p.port);
-
-                    if let Ok(proxy) = reqwest::Proxy::http(&proxy_scheme) {
-                        builder = builder.proxy(proxy);
-                    }
-                    if let Ok(proxy) = reqwest::Proxy::https(&proxy_scheme) {
-                        builder = builder.proxy(proxy);
-                    }
-                    if let Ok(proxy) = reqwest::Proxy::all(&proxy_scheme) {
-                        builder = builder.proxy(proxy);
-                    }
+                let status_code = resp.status();
+                if status_code == StatusCode::NOT_MODIFIED {
+                    return Ok(resp);
+                }
+                if !StatusCode::is_success(&status_code) {
+                    bail!("failed to fetch remote profile with status {status_code}")
                 }
-                _ => {}
+                Ok(resp)
             };
-        }
 
-        let version = unsafe { dirs::APP_VERSION };
-        let version = format!("hiddify-desktop/{version}");
-        builder = builder.user_agent(user_agent.unwrap_or(version));
+            match result.await {
+                Ok(resp) => {
+                    fetched = Some((resp, via));
+                    break;
+                }
+                Err(err) => {
+                    log::warn!(target: "app", "failed to fetch the profile via {via}, {err}");
+                    last_err = Some(err);
+                }
+            }
+        }
 
-        let resp = builder.build()?.get(url).send().await?;
+        let (resp, via) = match (fetched, last_err) {
+            (Some(fetched), _) => fetched,
+            (None, Some(err)) => return Err(err),
+            (None, None) => bail!("failed to fetch remote profile"),
+        };
 
-        let status_code = resp.status();
-        if !StatusCode::is_success(&status_code) {
-            bail!("failed to fetch remote profile with status {status_code}")
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
         }
 
         let header = resp.headers();
 
+        let validator = |name: HeaderName| {
+            header
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let etag = validator(header::ETAG);
+        let last_modified = validator(header::LAST_MODIFIED);
+
         // parse the Subscription UserInfo
         let extra = match header.get("Subscription-Userinfo") {
             Some(value) => {
@@ -258,17 +373,8 @@ impl PrfItem {
             None => None,
         };
 
-        // parse the Content-Disposition
-        let filename = match header.get("Content-Disposition") {
-            Some(value) => {
-                let filename = value.to_str().unwrap_or("");
-                help::parse_str::<String>(filename, "filename=")
-            }
-            None => None,
-        };
-
         // parse the profile-update-interval
-        let option = match header.get("profile-update-interval") {
+        let update_option = match header.get("profile-update-interval") {
             Some(value) => match value.to_str().unwrap_or("").parse::<u64>() {
                 Ok(val) => Some(PrfOption {
                     update_interval: Some(val * 60), // hour -> min
@@ -278,21 +384,13 @@ impl PrfItem {
             },
             None => None,
         };
-        let re=Regex::new(r"(\?|&)name=([^&]*)(&|$)").unwrap();
-        let mut defname="Remote Profile";
-        match re.captures(url) {
-            Some(caps) => {
-                defname = caps.get(2).unwrap().as_str();        
-            }
-            None => {
-                // The regex did not match. Deal with it here!
-            }
-        }
-        
+        let disposition = header
+            .get("Content-Disposition")
+            .and_then(|value| value.to_str().ok());
+        let name = name.unwrap_or_else(|| derive_name(url, disposition));
 
         let uid = help::get_uid("r");
         let file = format!("{uid}.yaml");
-        let name = name.unwrap_or(filename.unwrap_or(defname.into()));
         let data = resp.text_with_charset("utf-8").await?;
 
         // process the charset "UTF-8 with BOM"
@@ -306,7 +404,13 @@ impl PrfItem {
             bail!("profile does not contain `proxies` or `proxy-providers`");
         }
 
-        Ok(PrfItem {
+        // only counted here, the nodes are dropped on loading the profile
+        let filtered = match filter {
+            true => Some(filter_nodes(&mut yaml.clone(), &node_filters())?),
+            false => None,
+        };
+
+        Ok(Some(PrfItem {
             uid: Some(uid),
             itype: Some("remote".into()),
             name: Some(name),
@@ -315,10 +419,84 @@ impl PrfItem {
             url: Some(url.into()),
             selected: None,
             extra,
-            option,
+            // the option of the request wins the one of the response
+            option: PrfOption::merge(update_option, option),
             updated: Some(chrono::Local::now().timestamp() as usize),
+            downloaded_via: Some(via.into()),
+            etag,
+            last_modified,
+            filtered,
             file_data: Some(data.into()),
-        })
+        }))
+    }
+
+    /// the client of the remote profile
+    /// `proxy` uses the clash core and `system` uses the system proxy
+    /// each redirect is validated the same as the subscription url
+    fn remote_client(via: &str, user_agent: &str, headers: &HeaderMap) -> Result<reqwest::Client> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .no_proxy()
+            .redirect(profile_redirect_policy());
+
+        let proxy_scheme = match via {
+            "proxy" => {
+                let port = Config::clash().latest().get_mixed_port();
+                Some(format!("http://127.0.0.1:{port}"))
+            }
+            "system" => match Sysproxy::get_system_proxy() {
+                Ok(p @ Sysproxy { enable: true, .. }) => {
+                    Some(format!("http://{}:{}", p.host, p.port))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(proxy_scheme) = proxy_scheme {
+            builder = builder.proxy(reqwest::Proxy::all(&proxy_scheme)?);
+        }
+
+        Ok(builder
+            .user_agent(user_agent)
+            .default_headers(headers.clone())
+            .build()?)
+    }
+
+    fn header_map(headers: HashMap<String, String>) -> Result<HeaderMap> {
+        let mut map = HeaderMap::new();
+
+        for (name, value) in headers.into_iter() {
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .with_context(|| format!("invalid request header name \"{name}\""))?;
+            let value = HeaderValue::from_str(value.trim())
+                .with_context(|| format!("invalid request header value of \"{name}\""))?;
+            map.insert(name, value);
+        }
+
+        Ok(map)
+    }
+
+    /// ## Local type
+    /// create a new item from the dropped file
+    /// the clash profile, the share links and the base64 subscription are accepted
+    pub fn from_file(path: &str) -> Result<PrfItem> {
+        let path = PathBuf::from(path);
+        let size = fs::metadata(&path)
+            .with_context(|| format!("failed to read the file \"{}\"", path.display()))?
+            .len();
+        if size > MAX_IMPORT_SIZE {
+            bail!("the file is too large to import");
+        }
+
+        let data = fs::read_to_string(&path).context("failed to read the file as text")?;
+        let file_data = convert_imported(&data)?;
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or("Imported".into());
+        let desc = "Imported from the file".to_string();
+        Self::from_local(name, desc, Some(file_data))
     }
 
     /// ## Merge type (enhance)
@@ -338,6 +516,10 @@ impl PrfItem {
             extra: None,
             option: None,
             updated: Some(chrono::Local::now().timestamp() as usize),
+            downloaded_via: None,
+            etag: None,
+            last_modified: None,
+            filtered: None,
             file_data: Some(tmpl::ITEM_MERGE.into()),
         })
     }
@@ -359,6 +541,10 @@ impl PrfItem {
             extra: None,
             option: None,
             updated: Some(chrono::Local::now().timestamp() as usize),
+            downloaded_via: None,
+            etag: None,
+            last_modified: None,
+            filtered: None,
             file_data: Some(tmpl::ITEM_SCRIPT.into()),
         })
     }
@@ -385,3 +571,93 @@ impl PrfItem {
         fs::write(path, data.as_bytes()).context("failed to save the file")
     }
 }
+
+/// the `name=` parameter of the url, the filename of the `Content-Disposition`, or the url host
+fn derive_name(url: &str, disposition: Option<&str>) -> String {
+    let url = reqwest::Url::parse(url).ok();
+    let param = url.as_ref().and_then(|url| {
+        url.query_pairs()
+            .find(|(key, _)| key == "name")
+            .map(|(_, value)| value.to_string())
+    });
+
+    // `filename*=UTF-8''...` is preferred to `filename=`
+    let filename = disposition.and_then(|value| {
+        let encoded = help::parse_str::<String>(value, "filename*=")
+            .and_then(|name| Some(name.split_once("''")?.1.to_string()))
+            .map(|name| help::percent_decode(&name));
+        encoded.or_else(|| help::parse_str::<String>(value, "filename="))
+    });
+    let filename = filename.map(|name| {
+        let name = name.trim_matches('"');
+        let stem = [".yaml", ".yml", ".txt"]
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext));
+        stem.unwrap_or(name).to_string()
+    });
+
+    let host = url
+        .as_ref()
+        .and_then(|url| url.host_str())
+        .map(|host| host.trim_start_matches("www.").to_string());
+
+    [param, filename, host]
+        .into_iter()
+        .flatten()
+        .map(|name| name.trim().to_string())
+        .find(|name| !name.is_empty())
+        .unwrap_or_else(|| "Remote Profile".into())
+}
+
+/// keep the clash profile as it is,
+/// generate the profile from the share links or the base64 subscription
+fn convert_imported(data: &str) -> Result<String> {
+    let data = data.trim_start_matches('\u{feff}');
+
+    if let Ok(config) = serde_yaml::from_str::<Mapping>(data) {
+        let keys = ["proxies", "proxy-providers", "proxy-groups"];
+        if keys.iter().any(|key| config.contains_key(*key)) {
+            return Ok(data.to_string());
+        }
+        bail!("the yaml file is not a clash profile");
+    }
+
+    generate_basic_profile(data.trim(), "global")
+        .context("unknown file format, neither a clash profile nor the share links")
+}
+
+#[test]
+fn test_derive_name() {
+    let url = "https://www.sub.example.com/api?token=1";
+    assert_eq!(derive_name(url, None), "sub.example.com");
+    assert_eq!(
+        derive_name(
+            &format!("{url}&name=My%20Sub"),
+            Some("attachment; filename=x.yaml")
+        ),
+        "My Sub"
+    );
+    assert_eq!(
+        derive_name(url, Some("attachment; filename=\"Clash.yaml\"")),
+        "Clash"
+    );
+    assert_eq!(
+        derive_name(
+            url,
+            Some("attachment; filename=a.yml; filename*=UTF-8''%E8%AE%A2%E9%98%85.yml")
+        ),
+        "订阅"
+    );
+    assert_eq!(derive_name("not a url", None), "Remote Profile");
+}
+
+#[test]
+fn test_convert_imported() {
+    let yaml = "proxies:\n  - {name: a, type: ss, server: a.com, port: 8388}\n";
+    assert_eq!(convert_imported(yaml).unwrap(), yaml);
+    assert!(convert_imported("mode: rule\n").is_err());
+
+    let links = "ss://YWVzLTI1Ni1nY206cGFzcw@a.com:8388#a\n";
+    assert!(convert_imported(links).unwrap().contains("server: a.com"));
+    assert!(convert_imported("just some notes").is_err());
+}
@@ -0,0 +1,179 @@
+use super::Config;
+use reqwest::Url;
+use std::fmt;
+use std::net::IpAddr;
+
+/// the reason a subscription url is refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileUrlError {
+    Malformed(String),
+    /// such as `file`, `jar` or `ftp`
+    Scheme(String),
+    /// `profile_tls_only` is set but the url is `http`
+    TlsRequired,
+    /// the loopback, the unspecified or the link-local address
+    LocalHost(String),
+}
+
+impl fmt::Display for ProfileUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(err) => write!(f, "the profile url is malformed, {err}"),
+            Self::Scheme(scheme) => write!(f, "the profile url scheme `{scheme}` is not allowed"),
+            Self::TlsRequired => write!(f, "the profile url must be https"),
+            Self::LocalHost(host) => write!(f, "the profile url host `{host}` is not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileUrlError {}
+
+/// validate the imported subscription url by `profile_tls_only`
+/// return the normalized one to store
+pub fn normalize_profile_url(url: &str) -> Result<String, ProfileUrlError> {
+    let tls_only = { Config::verge().latest().profile_tls_only.unwrap_or(false) };
+    check_profile_url(url, tls_only)
+}
+
+/// the redirects of the subscription are checked the same as the url
+/// at most 10 hops, the same as the default policy
+pub fn profile_redirect_policy() -> reqwest::redirect::Policy {
+    let tls_only = { Config::verge().latest().profile_tls_only.unwrap_or(false) };
+    redirect_policy(tls_only)
+}
+
+fn redirect_policy(tls_only: bool) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 10 {
+            return attempt.error("too many redirects");
+        }
+        match check_profile_url(attempt.url().as_str(), tls_only) {
+            Ok(_) => attempt.follow(),
+            Err(err) => attempt.error(err),
+        }
+    })
+}
+
+fn check_profile_url(url: &str, tls_only: bool) -> Result<String, ProfileUrlError> {
+    let mut url =
+        Url::parse(url.trim()).map_err(|err| ProfileUrlError::Malformed(err.to_string()))?;
+
+    match url.scheme() {
+        "https" => {}
+        "http" if !tls_only => {}
+        "http" => return Err(ProfileUrlError::TlsRequired),
+        scheme => return Err(ProfileUrlError::Scheme(scheme.into())),
+    }
+
+    let host = match url.host_str() {
+        Some(host) => host.to_string(),
+        None => return Err(ProfileUrlError::Malformed("the host is empty".into())),
+    };
+    // the ip hosts are normalized by the parser, such as `2130706433` to `127.0.0.1`
+    let local = match host
+        .trim_matches(|c| c == '[' || c == ']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => is_local(ip),
+        Err(_) => {
+            let name = host.trim_end_matches('.');
+            name == "localhost" || name.ends_with(".localhost")
+        }
+    };
+    if local {
+        return Err(ProfileUrlError::LocalHost(host));
+    }
+
+    // the fragment is never sent
+    url.set_fragment(None);
+    Ok(url.into())
+}
+
+fn is_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_unspecified() || ip.is_link_local(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback() || ip.is_unspecified() || (ip.segments()[0] & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+#[test]
+fn test_profile_url() {
+    let check = |url: &str| check_profile_url(url, false);
+
+    assert_eq!(
+        check("  HTTPS://Example.COM:443/sub?token=1#frag "),
+        Ok("https://example.com/sub?token=1".into())
+    );
+    assert_eq!(
+        check("http://sub.example.com:8080/a"),
+        Ok("http://sub.example.com:8080/a".into())
+    );
+    assert_eq!(
+        check_profile_url("http://example.com/a", true),
+        Err(ProfileUrlError::TlsRequired)
+    );
+
+    assert_eq!(
+        check("file:///etc/passwd"),
+        Err(ProfileUrlError::Scheme("file".into()))
+    );
+    assert_eq!(
+        check("jar:http://example.com/a.jar!/sub"),
+        Err(ProfileUrlError::Scheme("jar".into()))
+    );
+    assert!(matches!(
+        check("example.com/sub"),
+        Err(ProfileUrlError::Malformed(_))
+    ));
+
+    for url in [
+        "http://localhost:25500/sub",
+        "http://api.localhost/sub",
+        "http://localhost./sub",
+        "http://127.0.0.2/sub",
+        "http://0.0.0.0/sub",
+        "http://169.254.169.254/latest",
+        "http://[::1]/sub",
+        "http://[::ffff:127.0.0.1]/sub",
+        "http://2130706433/sub",
+    ] {
+        assert!(
+            matches!(check(url), Err(ProfileUrlError::LocalHost(_))),
+            "{url}"
+        );
+    }
+    assert!(check("http://192.168.1.2/sub").is_ok());
+}
+
+#[test]
+fn test_profile_redirect() {
+    use warp::Filter;
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        // the first url is checked before the request
+        let routes = warp::path("sub").map(|| {
+            let uri = warp::http::Uri::from_static("http://127.0.0.1:9090/configs");
+            warp::redirect::found(uri)
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .redirect(redirect_policy(false))
+            .build()
+            .unwrap();
+        let err = client
+            .get(format!("http://{addr}/sub"))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_redirect());
+    });
+}
@@ -1,9 +1,11 @@
+use super::draft::{ConfigData, ConfigKind};
 use super::prfitem::PrfItem;
+use super::prfurl::normalize_profile_url;
 use crate::utils::{dirs, help};
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
-use std::{fs, io::Write};
+use std::{collections::HashSet, fs, io::Write};
 
 /// Define the `profiles.yaml` schema
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
@@ -147,12 +149,36 @@ impl IProfiles {
             self.items = Some(vec![]);
         }
 
+        item.name = item.name.map(|name| self.unique_name(&name));
         self.items.as_mut().map(|items| items.push(item));
-        self.save_file()
+        Ok(())
+    }
+
+    /// `name (2)` and so on if the name is taken
+    fn unique_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| {
+            self.items
+                .iter()
+                .flatten()
+                .any(|item| item.name.as_deref() == Some(candidate))
+        };
+
+        match taken(name) {
+            false => name.into(),
+            true => (2..)
+                .map(|index| format!("{name} ({index})"))
+                .find(|candidate| !taken(candidate))
+                .unwrap_or_else(|| name.into()),
+        }
     }
 
     /// update the item value
-    pub fn patch_item(&mut self, uid: String, item: PrfItem) -> Result<()> {
+    /// the changed url is checked the same as a new one
+    pub fn patch_item(&mut self, uid: String, mut item: PrfItem) -> Result<()> {
+        if let Some(url) = item.url.as_ref() {
+            item.url = Some(normalize_profile_url(url)?);
+        }
+
         let mut items = self.items.take().unwrap_or(vec![]);
 
         for mut each in items.iter_mut() {
@@ -168,7 +194,7 @@ impl IProfiles {
                 patch!(each, item, option);
 
                 self.items = Some(items);
-                return self.save_file();
+                return Ok(());
             }
         }
 
@@ -177,7 +203,7 @@ impl IProfiles {
     }
 
     /// be used to update the remote item
-    /// only patch `updated` `extra` `file_data`
+    /// only patch `updated` `extra` `downloaded_via` the validators `filtered` and `file_data`
     pub fn update_item(&mut self, uid: String, mut item: PrfItem) -> Result<()> {
         if self.items.is_none() {
             self.items = Some(vec![]);
@@ -193,6 +219,10 @@ impl IProfiles {
                 if each.uid == some_uid {
                     each.extra = item.extra;
                     each.updated = item.updated;
+                    each.downloaded_via = item.downloaded_via;
+                    each.etag = item.etag;
+                    each.last_modified = item.last_modified;
+                    each.filtered = item.filtered;
 
                     // save the file data
                     // move the field value after save
@@ -217,7 +247,7 @@ impl IProfiles {
             }
         }
 
-        self.save_file()
+        Ok(())
     }
 
     /// delete item
@@ -257,24 +287,53 @@ impl IProfiles {
         }
 
         self.items = Some(items);
-        self.save_file()?;
         Ok(current == uid)
     }
 
     /// 获取current指向的配置内容
     pub fn current_mapping(&self) -> Result<Mapping> {
         match (self.current.as_ref(), self.items.as_ref()) {
-            (Some(current), Some(items)) => {
-                if let Some(item) = items.iter().find(|e| e.uid.as_ref() == Some(current)) {
-                    let file_path = match item.file.as_ref() {
-                        Some(file) => dirs::app_profiles_dir()?.join(file),
-                        None => bail!("failed to get the file field"),
-                    };
-                    return Ok(help::read_merge_mapping(&file_path)?);
-                }
-                bail!("failed to find the current profile \"uid:{current}\"");
-            }
+            (Some(current), Some(_)) => self.item_mapping(current),
             _ => Ok(Mapping::new()),
         }
     }
+
+    /// the mapping of the profile
+    pub fn item_mapping(&self, uid: &String) -> Result<Mapping> {
+        if let Some(items) = self.items.as_ref() {
+            if let Some(item) = items.iter().find(|e| e.uid.as_ref() == Some(uid)) {
+                let file_path = match item.file.as_ref() {
+                    Some(file) => dirs::app_profiles_dir()?.join(file),
+                    None => bail!("failed to get the file field"),
+                };
+                return Ok(help::read_merge_mapping(&file_path)?);
+            }
+        }
+        bail!("failed to find the profile \"uid:{uid}\"");
+    }
+}
+
+impl ConfigData for IProfiles {
+    const KIND: ConfigKind = ConfigKind::Profiles;
+
+    fn validate(&self) -> Result<()> {
+        let mut uids = HashSet::new();
+        let items = self.items.iter().flatten();
+        for uid in items.filter_map(|item| item.uid.as_ref()) {
+            if !uids.insert(uid) {
+                bail!("duplicate profile uid \"{uid}\"");
+            }
+        }
+
+        match self.current.as_ref() {
+            Some(current) if !uids.contains(current) => {
+                bail!("failed to find the current profile \"{current}\"")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        self.save_file()
+    }
 }
@@ -0,0 +1,135 @@
+use crate::utils::tmpl;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Sequence, Value};
+
+/// the name, the title and the template of the bundled presets
+const ROUTING_PRESETS: [(&str, &str, &str); 3] = [
+    ("iran", "Iran direct-domestic", tmpl::ROUTING_IRAN),
+    ("russia", "Russia direct-domestic", tmpl::ROUTING_RUSSIA),
+    ("china", "China direct-domestic", tmpl::ROUTING_CHINA),
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RoutingPresetInfo {
+    pub name: String,
+
+    pub title: String,
+
+    pub rules: Vec<String>,
+}
+
+/// the change of `prepend-rules` in the override by a preset
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct RoutingPlan {
+    /// the rules of the preset not in the override yet
+    pub added: Vec<String>,
+
+    /// the rules of the other presets taken out
+    pub removed: Vec<String>,
+
+    /// the new `prepend-rules`, the rules of the preset first
+    #[serde(skip)]
+    pub rules: Sequence,
+}
+
+pub fn routing_presets() -> Vec<RoutingPresetInfo> {
+    ROUTING_PRESETS
+        .iter()
+        .map(|(name, title, _)| RoutingPresetInfo {
+            name: name.to_string(),
+            title: title.to_string(),
+            rules: preset_rules(name).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// the rules of the bundled template, none for `none`
+pub fn preset_rules(name: &str) -> Result<Vec<String>> {
+    if name == "none" {
+        return Ok(vec![]);
+    }
+    let template = match ROUTING_PRESETS.iter().find(|(n, _, _)| *n == name) {
+        Some((_, _, template)) => template,
+        None => bail!("invalid routing preset \"{name}\""),
+    };
+
+    let template = serde_yaml::from_str::<Mapping>(template)
+        .with_context(|| format!("invalid template of the routing preset \"{name}\""))?;
+    Ok(template
+        .get("prepend-rules")
+        .and_then(|r| r.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|r| r.as_str().map(String::from))
+        .collect())
+}
+
+/// replace the rules of the presets in `prepend-rules` by the ones of the preset,
+/// the rules added by hand are kept after them
+pub fn plan_routing_preset(name: &str, current: &Sequence) -> Result<RoutingPlan> {
+    let rules = preset_rules(name)?;
+    let all = ROUTING_PRESETS
+        .iter()
+        .flat_map(|(name, _, _)| preset_rules(name).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let is_preset =
+        |rule: &Value| matches!(rule.as_str(), Some(rule) if all.iter().any(|r| r == rule));
+
+    let existing = current
+        .iter()
+        .filter_map(|r| r.as_str())
+        .collect::<Vec<_>>();
+    let added = rules
+        .iter()
+        .filter(|r| !existing.contains(&r.as_str()))
+        .cloned()
+        .collect();
+    let mut removed = existing
+        .iter()
+        .filter(|r| all.iter().any(|a| a == *r) && !rules.iter().any(|n| n == *r))
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>();
+    removed.dedup();
+
+    let mut new_rules = rules.into_iter().map(Value::from).collect::<Sequence>();
+    new_rules.extend(current.iter().filter(|r| !is_preset(r)).cloned());
+
+    Ok(RoutingPlan {
+        added,
+        removed,
+        rules: new_rules,
+    })
+}
+
+#[test]
+fn test_routing_preset() {
+    let presets = routing_presets();
+    assert_eq!(presets.len(), 3);
+    assert!(presets.iter().all(|p| !p.rules.is_empty()));
+    assert!(presets[0]
+        .rules
+        .contains(&"GEOIP,IR,DIRECT,no-resolve".into()));
+    assert!(preset_rules("mars").is_err());
+
+    let current = Sequence::from(vec![
+        Value::from("PROCESS-NAME,game.exe,DIRECT"),
+        Value::from("GEOIP,IR,DIRECT,no-resolve"),
+        Value::from("GEOIP,private,DIRECT,no-resolve"),
+    ]);
+    let plan = plan_routing_preset("russia", &current).unwrap();
+    assert!(plan.added.contains(&"GEOIP,RU,DIRECT,no-resolve".into()));
+    assert!(!plan
+        .added
+        .contains(&"GEOIP,private,DIRECT,no-resolve".into()));
+    assert_eq!(plan.removed, ["GEOIP,IR,DIRECT,no-resolve"]);
+    assert_eq!(plan.rules[0], "DOMAIN-SUFFIX,ru,DIRECT");
+    assert_eq!(plan.rules.last().unwrap(), "PROCESS-NAME,game.exe,DIRECT");
+
+    let plan = plan_routing_preset("none", &plan.rules).unwrap();
+    assert!(plan.added.is_empty());
+    assert_eq!(
+        plan.rules,
+        vec![Value::from("PROCESS-NAME,game.exe,DIRECT")]
+    );
+}
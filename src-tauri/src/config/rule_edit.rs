@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Sequence;
+use std::net::IpAddr;
+
+/// the rule types with a payload, `MATCH` and the logic ones are checked alone
+const RULE_TYPES: [&str; 22] = [
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "DOMAIN-REGEX",
+    "GEOSITE",
+    "GEOIP",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-SUFFIX",
+    "IP-ASN",
+    "SRC-GEOIP",
+    "SRC-IP-CIDR",
+    "SRC-PORT",
+    "DST-PORT",
+    "IN-PORT",
+    "IN-TYPE",
+    "PROCESS-NAME",
+    "PROCESS-PATH",
+    "PROCESS-NAME-REGEX",
+    "NETWORK",
+    "RULE-SET",
+    "SUB-RULE",
+];
+
+const LOGIC_TYPES: [&str; 3] = ["AND", "OR", "NOT"];
+
+/// the options after the target
+const RULE_OPTIONS: [&str; 2] = ["no-resolve", "src"];
+
+/// an edit of the custom rules, the `prepend-rules` of the override
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum RuleEdit {
+    /// insert before the index, or after it if `after`
+    /// append if the index is none
+    Add {
+        rule: String,
+        index: Option<usize>,
+        after: bool,
+    },
+    Update {
+        index: usize,
+        rule: String,
+    },
+    /// the rule at `from` ends up at `to`
+    Move {
+        from: usize,
+        to: usize,
+    },
+    Delete {
+        index: usize,
+    },
+}
+
+/// apply the edit, the added rules are checked against the targets
+pub fn edit_rules(rules: &mut Sequence, edit: RuleEdit, targets: &[String]) -> Result<()> {
+    let check_index = |index: usize, len: usize| match index < len {
+        true => Ok(index),
+        false => bail!("the rule index {index} is out of the {len} rules"),
+    };
+
+    match edit {
+        RuleEdit::Add { rule, index, after } => {
+            let rule = check_rule(&rule, targets)?;
+            let index = match index {
+                Some(index) => check_index(index, rules.len())? + after as usize,
+                None => rules.len(),
+            };
+            rules.insert(index, rule.into());
+        }
+        RuleEdit::Update { index, rule } => {
+            let index = check_index(index, rules.len())?;
+            rules[index] = check_rule(&rule, targets)?.into();
+        }
+        RuleEdit::Move { from, to } => {
+            let from = check_index(from, rules.len())?;
+            let to = check_index(to, rules.len())?;
+            let rule = rules.remove(from);
+            rules.insert(to, rule);
+        }
+        RuleEdit::Delete { index } => {
+            rules.remove(check_index(index, rules.len())?);
+        }
+    }
+    Ok(())
+}
+
+/// the normalized rule, such as `DOMAIN-SUFFIX,example.com,proxy`
+pub fn check_rule(rule: &str, targets: &[String]) -> Result<String> {
+    let rule = rule.trim();
+    let (rtype, rest) = rule.split_once(',').unwrap_or((rule, ""));
+    let rtype = rtype.trim().to_uppercase();
+
+    // the payload of the logic rules is in the parentheses
+    let (payload, rest) = if rtype == "MATCH" {
+        (None, rest)
+    } else if LOGIC_TYPES.contains(&rtype.as_str()) {
+        let rest = rest.trim_start();
+        match (rest.starts_with('('), rest.rfind(')')) {
+            (true, Some(end)) => (Some(&rest[..=end]), rest[end + 1..].trim_start_matches(',')),
+            _ => bail!("invalid payload of the rule \"{rule}\""),
+        }
+    } else if RULE_TYPES.contains(&rtype.as_str()) {
+        match rest.split_once(',') {
+            Some((payload, rest)) => (Some(payload.trim()), rest),
+            None => bail!("the rule \"{rule}\" has no target"),
+        }
+    } else {
+        bail!("invalid rule type \"{rtype}\"");
+    };
+
+    let mut parts = rest.split(',').map(str::trim);
+    let target = parts.next().unwrap_or_default();
+    if target.is_empty() {
+        bail!("the rule \"{rule}\" has no target");
+    }
+    if !targets.iter().any(|t| t == target) {
+        bail!("invalid rule target \"{target}\"");
+    }
+    let options = parts.collect::<Vec<_>>();
+    if let Some(option) = options.iter().find(|o| !RULE_OPTIONS.contains(o)) {
+        bail!("invalid option \"{option}\" of the rule \"{rule}\"");
+    }
+
+    if let Some(payload) = payload {
+        check_payload(&rtype, payload)?;
+    }
+
+    let mut normalized = vec![rtype.as_str()];
+    normalized.extend(payload);
+    normalized.push(target);
+    normalized.extend(options);
+    Ok(normalized.join(","))
+}
+
+fn check_payload(rtype: &str, payload: &str) -> Result<()> {
+    let valid = match rtype {
+        _ if payload.is_empty() => false,
+        "IP-CIDR" | "IP-CIDR6" | "SRC-IP-CIDR" | "IP-SUFFIX" => match payload.split_once('/') {
+            Some((ip, len)) => match (ip.parse::<IpAddr>(), len.parse::<u8>()) {
+                (Ok(IpAddr::V4(_)), Ok(len)) => len <= 32,
+                (Ok(IpAddr::V6(_)), Ok(len)) => len <= 128,
+                _ => false,
+            },
+            None => false,
+        },
+        "SRC-PORT" | "DST-PORT" | "IN-PORT" => payload.split('/').all(|range| {
+            range
+                .split('-')
+                .all(|port| port.trim().parse::<u16>().is_ok())
+        }),
+        "IP-ASN" => payload.parse::<u32>().is_ok(),
+        "NETWORK" => matches!(payload.to_lowercase().as_str(), "tcp" | "udp"),
+        // the paths and the names of the processes may have the spaces
+        "PROCESS-NAME" | "PROCESS-PATH" | "PROCESS-NAME-REGEX" => true,
+        _ => !payload.contains(char::is_whitespace),
+    };
+    if !valid {
+        bail!("invalid payload \"{payload}\" of the rule type \"{rtype}\"");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_edit_rules() {
+    use serde_yaml::Value;
+
+    let targets = ["DIRECT", "REJECT", "proxy"].map(String::from);
+
+    assert_eq!(
+        check_rule(" domain-suffix, example.com ,proxy", &targets).unwrap(),
+        "DOMAIN-SUFFIX,example.com,proxy"
+    );
+    assert_eq!(
+        check_rule("IP-CIDR,10.0.0.0/8,DIRECT,no-resolve", &targets).unwrap(),
+        "IP-CIDR,10.0.0.0/8,DIRECT,no-resolve"
+    );
+    assert_eq!(
+        check_rule("AND,((DOMAIN,a.com),(NETWORK,UDP)),REJECT", &targets).unwrap(),
+        "AND,((DOMAIN,a.com),(NETWORK,UDP)),REJECT"
+    );
+    assert_eq!(check_rule("match,proxy", &targets).unwrap(), "MATCH,proxy");
+    assert!(check_rule("DOMAIN-SUFFIX,example.com,unknown", &targets).is_err());
+    assert!(check_rule("DOMAIN-SUFFIX,example.com", &targets).is_err());
+    assert!(check_rule("HOST,example.com,proxy", &targets).is_err());
+    assert!(check_rule("IP-CIDR,10.0.0.0/33,DIRECT", &targets).is_err());
+    assert!(check_rule("DST-PORT,443-x,DIRECT", &targets).is_err());
+    assert!(check_rule("GEOIP,IR,DIRECT,resolve", &targets).is_err());
+
+    let mut rules = Sequence::from(vec![Value::from("MATCH,proxy")]);
+    let add = |rule: &str, index: Option<usize>, after: bool| RuleEdit::Add {
+        rule: rule.into(),
+        index,
+        after,
+    };
+    edit_rules(&mut rules, add("GEOIP,IR,DIRECT", Some(0), false), &targets).unwrap();
+    edit_rules(
+        &mut rules,
+        add("DOMAIN,a.com,REJECT", Some(0), true),
+        &targets,
+    )
+    .unwrap();
+    edit_rules(&mut rules, add("DST-PORT,22,DIRECT", None, false), &targets).unwrap();
+    assert_eq!(
+        rules,
+        [
+            "GEOIP,IR,DIRECT",
+            "DOMAIN,a.com,REJECT",
+            "MATCH,proxy",
+            "DST-PORT,22,DIRECT"
+        ]
+        .map(Value::from)
+    );
+
+    edit_rules(&mut rules, RuleEdit::Move { from: 3, to: 0 }, &targets).unwrap();
+    edit_rules(&mut rules, RuleEdit::Delete { index: 3 }, &targets).unwrap();
+    let update = RuleEdit::Update {
+        index: 1,
+        rule: "GEOIP,CN,DIRECT".into(),
+    };
+    edit_rules(&mut rules, update, &targets).unwrap();
+    assert_eq!(
+        rules,
+        [
+            "DST-PORT,22,DIRECT",
+            "GEOIP,CN,DIRECT",
+            "DOMAIN,a.com,REJECT"
+        ]
+        .map(Value::from)
+    );
+
+    assert!(edit_rules(&mut rules, RuleEdit::Delete { index: 3 }, &targets).is_err());
+    assert!(edit_rules(&mut rules, add("MATCH,proxy", Some(5), false), &targets).is_err());
+    assert_eq!(rules.len(), 3);
+}
@@ -16,6 +16,49 @@ impl IRuntime {
         Self::default()
     }
 
+    /// get the names of the proxies and groups
+    /// which can be used as the rule target
+    pub fn get_targets(&self) -> Vec<String> {
+        let mut targets = vec!["DIRECT".to_string(), "REJECT".to_string()];
+
+        if let Some(config) = self.config.as_ref() {
+            ["proxy-groups", "proxies"].into_iter().for_each(|key| {
+                config
+                    .get(key)
+                    .and_then(|val| val.as_sequence())
+                    .map(|seq| {
+                        seq.iter()
+                            .filter_map(|item| item.get("name")?.as_str())
+                            .for_each(|name| targets.push(name.to_string()))
+                    });
+            });
+        }
+
+        targets
+    }
+
+    /// the first select group and its members, the main group of most profiles
+    pub fn first_select_group(&self) -> Option<(String, Vec<String>)> {
+        let group = self
+            .config
+            .as_ref()?
+            .get("proxy-groups")?
+            .as_sequence()?
+            .iter()
+            .filter_map(|group| group.as_mapping())
+            .find(|group| group.get("type").and_then(|t| t.as_str()) == Some("select"))?;
+
+        let name = group.get("name")?.as_str()?.to_string();
+        let nodes = group
+            .get("proxies")
+            .and_then(|p| p.as_sequence())
+            .into_iter()
+            .flatten()
+            .filter_map(|node| node.as_str().map(String::from))
+            .collect();
+        Some((name, nodes))
+    }
+
     // 这里只更改 allow-lan | ipv6 | log-level
     pub fn patch_config(&mut self, patch: Mapping) {
         if let Some(config) = self.config.as_mut() {
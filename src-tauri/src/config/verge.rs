@@ -1,5 +1,6 @@
+use super::draft::{check_ports, ConfigData, ConfigKind};
 use crate::utils::{dirs, help};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
 /// ### `verge.yaml` schema
@@ -31,9 +32,36 @@ pub struct IVerge {
     /// can the app auto startup
     pub enable_auto_launch: Option<bool>,
 
+    /// only the https subscription urls can be imported
+    pub profile_tls_only: Option<bool>,
+
+    /// the localhost api for the external control, applied after restart
+    pub enable_control_api: Option<bool>,
+
+    /// default 33332
+    pub control_api_port: Option<u16>,
+
+    /// the bearer token of the control api, generated on the first start
+    pub control_api_token: Option<String>,
+
+    /// the lan websocket server of the companion app, applied after restart
+    /// the channel is plain `ws://` and not encrypted, only enable it on a trusted network
+    pub enable_remote_control: Option<bool>,
+
+    /// default 33333
+    pub remote_control_port: Option<u16>,
+
+    /// the sensitive commands granted to the webview,
+    /// `service`, `autostart`, `core_permission` and `hooks`
+    /// only edited in the file, the patches from the webview are ignored
+    pub command_capabilities: Option<Vec<String>>,
+
     /// not show the window on launch
     pub enable_silent_start: Option<bool>,
 
+    /// wait for the seconds before starting when launched on login
+    pub auto_launch_delay: Option<u64>,
+
     /// set system proxy
     pub enable_system_proxy: Option<bool>,
 
@@ -66,6 +94,77 @@ pub struct IVerge {
     /// 默认的延迟测试连接
     pub default_latency_test: Option<String>,
 
+    /// latency test urls, tried in order until one succeeds
+    pub latency_test_urls: Option<Vec<String>>,
+
+    /// the local port used by the measurement listener (clash meta only)
+    pub measure_port: Option<u16>,
+
+    /// the number of runs for each measurement, default is 3
+    pub measure_repeat: Option<u32>,
+
+    /// the udp echo server for the udp throughput test, format: `host:port`
+    pub measure_udp_server: Option<String>,
+
+    /// force the http version of the download test
+    /// `auto` or `http1` or `http2`, http3 is not supported by reqwest yet
+    pub measure_http_version: Option<String>,
+
+    /// the user agent of the measurement requests, a browser one by default
+    pub measure_user_agent: Option<String>,
+
+    /// the `accept-language` of the measurement requests
+    pub measure_accept_language: Option<String>,
+
+    /// the order of the measurement request headers, such as `["user-agent", "accept"]`
+    pub measure_header_order: Option<Vec<String>>,
+
+    /// pick a random browser user agent for each run
+    pub measure_random_ua: Option<bool>,
+
+    /// the seconds between the full tests of the same proxy, default is 60
+    /// the last result is reused within it, the failed ones can be retested at once
+    pub measure_min_interval: Option<u64>,
+
+    /// `cloudflare`, `speedtest`, `custom` or `latency`, the server of the throughput test,
+    /// default is `cloudflare`
+    /// the nearest speedtest.net server is picked by `speedtest_location`
+    pub measure_download_backend: Option<String>,
+
+    /// the download url of the `custom` backend
+    pub measure_download_url: Option<String>,
+
+    /// the upload url of the `custom` backend, the upload is skipped if not set
+    pub measure_upload_url: Option<String>,
+
+    /// only the fastest proxies by the latency are tested for the throughput in a batch
+    /// the rest are saved with the latency only, 0 means all
+    pub measure_throughput_top: Option<usize>,
+
+    /// the data budget of the throughput tests in a batch, in MB, 0 means no limit
+    /// the full measurements are downgraded to the latency only when exceeded
+    pub measure_scan_budget: Option<u64>,
+
+    /// the data budget of the throughput tests in a month, in MB, 0 means no limit
+    pub measure_monthly_budget: Option<u64>,
+
+    /// stop the download test once the rate is stable to save the data, default is false
+    /// the throttling after the first seconds may be missed
+    pub enable_measure_early_stop: Option<bool>,
+
+    /// run the upload test in the full measurement, default is false
+    pub enable_measure_upload: Option<bool>,
+
+    /// the number of the concurrent upload connections, default is 4
+    pub measure_upload_connections: Option<u32>,
+
+    /// the location to pick the speedtest servers from
+    /// `proxy_exit` or `local_geoip` or `manual`, default is `local_geoip`
+    pub speedtest_location: Option<String>,
+
+    /// the coordinates of the `manual` location, `[latitude, longitude]`
+    pub speedtest_coordinates: Option<[f64; 2]>,
+
     /// 支持关闭字段过滤，避免meta的新字段都被过滤掉，默认为真
     pub enable_clash_fields: Option<bool>,
 
@@ -74,6 +173,71 @@ pub struct IVerge {
 
     /// proxy 页面布局 列数
     pub proxy_layout_column: Option<i32>,
+
+    /// switch clash mode or profile on a timetable
+    pub mode_schedule: Option<Vec<IVergeSchedule>>,
+
+    /// the release feeds of the app update, tried in order
+    pub update_endpoints: Option<Vec<String>>,
+
+    /// always check and download the app update through the clash core
+    /// or else the core is only tried after the direct failed
+    pub update_via_proxy: Option<bool>,
+
+    /// `stable` or `beta` or `nightly`
+    pub update_channel: Option<String>,
+
+    /// the triggers of the os notifications
+    pub notification: Option<IVergeNotification>,
+
+    /// remind the subscription expiry before the days, default is 3
+    pub expire_reminder_days: Option<u32>,
+
+    /// the seconds for a switched profile to reach a node before it's rolled back
+    /// default is 15, 0 disables the probe
+    pub activation_probe_timeout: Option<u64>,
+
+    /// the local ports pinned to the nodes (clash meta only)
+    pub node_listeners: Option<Vec<IVergeListener>>,
+
+    /// run the commands or post the webhooks on the events
+    /// the new commands from the webview require the capability `hooks`
+    pub hooks: Option<Vec<IVergeHook>>,
+
+    /// notify on the patterns of the core logs, the built-in rules if none
+    pub log_alerts: Option<Vec<IVergeLogAlert>>,
+
+    /// rename the nodes of all the profiles, before the rules of the profile
+    pub rename_rules: Option<Vec<IVergeRenameRule>>,
+
+    /// the patterns of the node names dropped from the profiles with `filter_nodes`
+    /// the built-in ones if none
+    pub node_filters: Option<Vec<String>>,
+
+    /// the dns preset in the override, set by `apply_dns_preset`
+    pub dns_preset: Option<IDnsPreset>,
+
+    /// the `load-balance` group of the fastest nodes, set by `create_load_balance`
+    pub load_balance: Option<IVergeLoadBalance>,
+
+    /// keep the previous content of the config files on saving, default is true
+    pub enable_config_backup: Option<bool>,
+
+    /// offer to import the share links and the subscriptions copied to the clipboard
+    pub enable_clipboard_watcher: Option<bool>,
+
+    /// the allowed schemes of the clipboard watcher, such as `vmess` and `https`
+    pub clipboard_schemes: Option<Vec<String>>,
+
+    /// run in the tray without the webview to save the memory,
+    /// the window is created on demand and destroyed on closing
+    pub enable_lite_mode: Option<bool>,
+
+    /// warn when the memory of the core exceeds it, in MB, 0 means no limit
+    pub core_memory_limit: Option<u64>,
+
+    /// `notify` or `restart` when the core exceeds the memory limit, default is `notify`
+    pub core_memory_policy: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
@@ -92,6 +256,133 @@ pub struct IVergeTheme {
     pub css_injection: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct IVergeNotification {
+    /// default is true
+    pub core_crash: Option<bool>,
+
+    /// the success or failure of the profile auto update
+    /// default is false
+    pub profile_update: Option<bool>,
+
+    /// the subscription is nearing the quota or the expiry
+    /// default is true
+    pub subscription: Option<bool>,
+
+    /// the measurement of the proxies is done
+    /// default is false
+    pub measurement: Option<bool>,
+
+    /// the rules of the core logs are triggered
+    /// default is true
+    pub log_alert: Option<bool>,
+
+    /// the switched profile did not work and the previous one is back
+    /// default is true
+    pub profile_rollback: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeSchedule {
+    /// local time to trigger, format: `HH:MM`
+    pub time: String,
+
+    /// 1 for monday ... 7 for sunday
+    /// trigger every day if none
+    pub weekdays: Option<Vec<u32>>,
+
+    /// `rule` or `global` or `direct` or `script`
+    pub mode: Option<String>,
+
+    /// the profile uid to activate
+    pub profile: Option<String>,
+
+    /// default is true
+    pub enable: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeListener {
+    /// the proxy or the group name
+    pub node: String,
+
+    /// the local mixed port, socks and http
+    pub port: u16,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeHook {
+    /// `core_started` or `core_crashed` or `profile_switched` or `quota_exceeded` or `log_alert`
+    pub event: String,
+
+    /// run by the shell, the variables are passed as `HIDDIFY_EVENT` and so on
+    pub command: Option<String>,
+
+    /// post the payload to the url
+    pub webhook: Option<String>,
+
+    /// the template of the webhook body, such as `{"text":"{{message}}"}`
+    /// default is the json of all the variables
+    pub payload: Option<String>,
+
+    /// default is true
+    pub enable: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeRenameRule {
+    /// `replace` by the regex, `flag` prefixes the country flag, `strip_ads` removes the links and the channels
+    pub kind: String,
+
+    /// the regex of `replace`
+    pub find: Option<String>,
+
+    /// the replacement of `replace`, such as `$1`
+    pub replace: Option<String>,
+
+    /// default is true
+    pub enable: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeLoadBalance {
+    /// the group name
+    pub name: String,
+
+    /// `round-robin`, `consistent-hashing` or `sticky-sessions`, default is `round-robin`
+    pub strategy: Option<String>,
+
+    /// the most members, default is 5
+    pub size: Option<usize>,
+
+    /// the members by the throughput, the fastest first
+    /// refreshed after each scan
+    pub members: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct IVergeLogAlert {
+    /// the title of the notification
+    pub name: String,
+
+    /// the substring of the log line, or the regex if `regex`
+    pub pattern: String,
+
+    pub regex: Option<bool>,
+
+    /// the matched lines in the window to trigger, default is 1
+    pub threshold: Option<u32>,
+
+    /// the seconds of the window, also the cooldown after triggered, default is 60
+    pub window: Option<u64>,
+
+    /// the hint of the notification, such as switching the dns preset
+    pub suggestion: Option<String>,
+
+    /// default is true
+    pub enable: Option<bool>,
+}
+
 impl IVerge {
     pub fn new() -> Self {
         match dirs::verge_path().and_then(|path| help::read_yaml::<IVerge>(&path)) {
@@ -146,7 +437,14 @@ impl IVerge {
         patch!(enable_tun_mode);
         patch!(enable_service_mode);
         patch!(enable_auto_launch);
+        patch!(profile_tls_only);
+        patch!(enable_control_api);
+        patch!(control_api_port);
+        patch!(control_api_token);
+        patch!(enable_remote_control);
+        patch!(remote_control_port);
         patch!(enable_silent_start);
+        patch!(auto_launch_delay);
         patch!(enable_system_proxy);
         patch!(enable_proxy_guard);
         patch!(system_proxy_bypass);
@@ -159,9 +457,64 @@ impl IVerge {
 
         patch!(auto_close_connection);
         patch!(default_latency_test);
+        patch!(latency_test_urls);
+        patch!(measure_port);
+        patch!(measure_repeat);
+        patch!(measure_udp_server);
+        patch!(measure_http_version);
+        patch!(measure_user_agent);
+        patch!(measure_accept_language);
+        patch!(measure_header_order);
+        patch!(measure_random_ua);
+        patch!(measure_min_interval);
+        patch!(measure_download_backend);
+        patch!(measure_download_url);
+        patch!(measure_upload_url);
+        patch!(measure_throughput_top);
+        patch!(measure_scan_budget);
+        patch!(measure_monthly_budget);
+        patch!(enable_measure_early_stop);
+        patch!(enable_measure_upload);
+        patch!(measure_upload_connections);
+        patch!(speedtest_location);
+        patch!(speedtest_coordinates);
         patch!(enable_builtin_enhanced);
         patch!(proxy_layout_column);
         patch!(enable_clash_fields);
+        patch!(mode_schedule);
+        patch!(update_endpoints);
+        patch!(update_via_proxy);
+        patch!(update_channel);
+        patch!(notification);
+        patch!(expire_reminder_days);
+        patch!(activation_probe_timeout);
+        patch!(node_listeners);
+        patch!(hooks);
+        patch!(log_alerts);
+        patch!(rename_rules);
+        patch!(node_filters);
+        patch!(enable_config_backup);
+        patch!(enable_clipboard_watcher);
+        patch!(clipboard_schemes);
+        patch!(enable_lite_mode);
+        patch!(core_memory_limit);
+        patch!(core_memory_policy);
+    }
+
+    /// the port of the measurement listener
+    pub fn get_measure_port(&self) -> u16 {
+        match self.measure_port {
+            Some(port) if port > 0 => port,
+            _ => 7895,
+        }
+    }
+
+    /// 在初始化前尝试拿到开机自启的延迟
+    pub fn get_auto_launch_delay() -> u64 {
+        match dirs::verge_path().and_then(|path| help::read_yaml::<IVerge>(&path)) {
+            Ok(config) => config.auto_launch_delay.unwrap_or(0),
+            Err(_) => 0,
+        }
     }
 
     /// 在初始化前尝试拿到单例端口的值
@@ -177,3 +530,29 @@ impl IVerge {
         }
     }
 }
+
+impl ConfigData for IVerge {
+    const KIND: ConfigKind = ConfigKind::Verge;
+
+    fn validate(&self) -> Result<()> {
+        if let Some(core) = self.clash_core.as_deref() {
+            if core != "clash" && core != "clash-meta" {
+                bail!("invalid clash core name \"{core}\"");
+            }
+        }
+        if self.proxy_guard_duration == Some(0) {
+            bail!("the proxy guard duration should be at least 1 second");
+        }
+
+        check_ports(&[
+            ("app_singleton_port", self.app_singleton_port.unwrap_or(0)),
+            ("control_api_port", self.control_api_port.unwrap_or(0)),
+            ("remote_control_port", self.remote_control_port.unwrap_or(0)),
+            ("measure_port", self.measure_port.unwrap_or(0)),
+        ])
+    }
+
+    fn save(&self) -> Result<()> {
+        self.save_file()
+    }
+}
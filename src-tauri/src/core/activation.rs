@@ -0,0 +1,117 @@
+use super::notification::{self, NotifyTrigger};
+use super::{clash_api, delay, handle, CoreManager};
+use crate::config::{Config, ConfigType, IRuntime};
+use crate::utils::dirs;
+use anyhow::{anyhow, bail, Result};
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+/// the probe timeout if not set (s)
+const DEFAULT_PROBE_TIMEOUT: u64 = 15;
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// load the config of the switched profile into the core as a transaction
+/// if the core refuses it or no node of the main group works within the probe timeout,
+/// the config loaded before is put back and the error is returned,
+/// so the caller discards the profiles draft
+pub async fn activate() -> Result<()> {
+    let previous = { Config::runtime().latest().clone() };
+
+    let result = match CoreManager::global().update_config().await {
+        Ok(_) => match probe_timeout() {
+            Some(limit) => probe(limit).await,
+            None => Ok(()),
+        },
+        Err(err) => Err(err),
+    };
+
+    let err = match result {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+    log::error!(target: "app", "the profile failed to activate, {err}");
+
+    if previous.config.is_none() {
+        return Err(err);
+    }
+    if let Err(rollback_err) = rollback(previous).await {
+        log::error!(target: "app", "failed to roll back the core config, {rollback_err}");
+        return Err(err.context("failed to roll back to the previous profile"));
+    }
+
+    let name = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles
+            .get_current()
+            .and_then(|uid| profiles.get_item(&uid).ok())
+            .and_then(|item| item.name.clone())
+            .unwrap_or_default()
+    };
+    let body = format!("The profile \"{name}\" did not work, the previous one is restored");
+    notification::notify(NotifyTrigger::ProfileRollback, "Hiddify", &body);
+    handle::Handle::notice_message("set_config::error", format!("{body}\n{err}"));
+
+    Err(err.context("the profile is rolled back"))
+}
+
+/// none if the probe is disabled
+fn probe_timeout() -> Option<Duration> {
+    let secs = { Config::verge().latest().activation_probe_timeout };
+    match secs.unwrap_or(DEFAULT_PROBE_TIMEOUT) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// retry until the core answers and a node of the main group works
+async fn probe(limit: Duration) -> Result<()> {
+    let deadline = Instant::now() + limit;
+    let mut last_err = anyhow!("the health probe timed out");
+
+    while Instant::now() < deadline {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match timeout(left, probe_once()).await {
+            Ok(Ok(_)) => return Ok(()),
+            Ok(Err(err)) => last_err = err,
+            Err(_) => break,
+        }
+        sleep(PROBE_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+    }
+
+    bail!(
+        "the health probe failed in {}s, {last_err}",
+        limit.as_secs()
+    )
+}
+
+async fn probe_once() -> Result<()> {
+    clash_api::get_version().await?;
+
+    // the profiles without a select group are only checked by the core
+    let current = { Config::runtime().latest().first_select_group() };
+    let group = match current {
+        Some((group, _)) => group,
+        None => return Ok(()),
+    };
+    let url = delay::test_urls().into_iter().next().unwrap_or_default();
+    let delays = clash_api::get_group_delay(&group, &url, delay::DELAY_TIMEOUT).await?;
+
+    match delays.values().any(|delay| *delay > 0) {
+        true => Ok(()),
+        false => bail!("no node of the group \"{group}\" works"),
+    }
+}
+
+/// put the config loaded before the activation back into the core
+async fn rollback(previous: IRuntime) -> Result<()> {
+    log::info!(target: "app", "roll back the core config");
+
+    *Config::runtime().draft() = previous;
+    let path = Config::generate_file(ConfigType::Run)?;
+    let path = dirs::path_to_str(&path)?;
+    clash_api::put_configs(path).await?;
+
+    handle::Handle::refresh_clash();
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use super::clash_api;
+use super::measure::MeasureMode;
+use super::measure_batch::{MeasureBatch, MeasureBatches};
+use super::rank::{self, RankStrategy};
+use super::CoreManager;
+use crate::config::Config;
+use crate::utils::app_state::AppState;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// the status shown by the dashboard in one call
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct AppStatus {
+    pub app_version: String,
+
+    /// `running`, `not_responding` if the sidecar is alive but the api fails, or `stopped`
+    pub core_state: String,
+
+    /// the version reported by the running core
+    pub core_version: Option<String>,
+
+    /// `clash` or `clash-meta`
+    pub clash_core: Option<String>,
+
+    /// the uid of the current profile
+    pub profile: Option<String>,
+
+    pub profile_name: Option<String>,
+
+    /// `rule`, `global` or `direct`
+    pub mode: Option<String>,
+
+    /// the selected node of each group
+    pub selected: BTreeMap<String, String>,
+
+    pub system_proxy: bool,
+
+    pub tun_mode: bool,
+
+    /// none on the first call or if the core is not running
+    pub rate: Option<TrafficRate>,
+
+    pub last_measurement: Option<MeasureSummary>,
+}
+
+/// the traffic since the last call (bytes/s)
+#[derive(Default, Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct TrafficRate {
+    pub up: u64,
+
+    pub down: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MeasureSummary {
+    pub id: String,
+
+    pub mode: MeasureMode,
+
+    pub total: usize,
+
+    pub measured: usize,
+
+    pub failed: usize,
+
+    /// by the throughput for the full batches, by the latency for the others
+    pub best: Option<String>,
+
+    pub updated: usize,
+}
+
+/// the traffic totals of the last call
+struct TrafficSample {
+    time: Instant,
+    up: u64,
+    down: u64,
+}
+
+fn last_sample() -> &'static Mutex<Option<TrafficSample>> {
+    static SAMPLE: OnceCell<Mutex<Option<TrafficSample>>> = OnceCell::new();
+    SAMPLE.get_or_init(|| Mutex::new(None))
+}
+
+pub async fn get_app_status() -> AppStatus {
+    let (clash_core, system_proxy, tun_mode) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.clash_core.clone(),
+            verge.enable_system_proxy.unwrap_or(false),
+            verge.enable_tun_mode.unwrap_or(false),
+        )
+    };
+    let (profile, profile_name) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let current = profiles.get_current();
+        let name = current
+            .as_ref()
+            .and_then(|uid| profiles.get_item(uid).ok())
+            .and_then(|item| item.name.clone());
+        (current, name)
+    };
+    let mode = { Config::clash().latest().0.get("mode").cloned() };
+
+    let (version, proxies, connections) = futures::join!(
+        clash_api::get_version(),
+        clash_api::get_proxies(),
+        clash_api::get_connections()
+    );
+
+    let core_state = match (&version, CoreManager::global().core_pid()) {
+        (Ok(_), _) => "running",
+        (Err(_), Some(_)) => "not_responding",
+        (Err(_), None) => "stopped",
+    };
+    let selected = proxies.map_or(BTreeMap::new(), |proxies| selected_nodes(&proxies));
+    let rate = match connections {
+        Ok(connections) => {
+            let total = |key: &str| connections.get(key).and_then(|t| t.as_u64());
+            match (total("uploadTotal"), total("downloadTotal")) {
+                (Some(up), Some(down)) => {
+                    let sample = TrafficSample {
+                        time: Instant::now(),
+                        up,
+                        down,
+                    };
+                    let mut last = last_sample().lock();
+                    let rate = last.as_ref().and_then(|last| traffic_rate(last, &sample));
+                    *last = Some(sample);
+                    rate
+                }
+                _ => None,
+            }
+        }
+        Err(_) => None,
+    };
+
+    let batch = MeasureBatches::global().get_all().into_iter().last();
+
+    AppStatus {
+        app_version: AppState::global().version().into(),
+        core_state: core_state.into(),
+        core_version: version.ok(),
+        clash_core,
+        profile,
+        profile_name,
+        mode: mode.and_then(|mode| mode.as_str().map(String::from)),
+        selected,
+        system_proxy,
+        tun_mode,
+        rate,
+        last_measurement: batch.map(summarize),
+    }
+}
+
+/// the `now` of the groups in `GET /proxies`
+pub(super) fn selected_nodes(proxies: &serde_json::Value) -> BTreeMap<String, String> {
+    proxies
+        .get("proxies")
+        .and_then(|p| p.as_object())
+        .into_iter()
+        .flatten()
+        .filter(|(_, proxy)| proxy.get("all").is_some())
+        .filter_map(|(name, proxy)| Some((name.clone(), proxy.get("now")?.as_str()?.into())))
+        .collect()
+}
+
+/// none if the samples are too close, the totals are reset by restarting the core
+fn traffic_rate(last: &TrafficSample, now: &TrafficSample) -> Option<TrafficRate> {
+    let secs = now.time.duration_since(last.time).as_secs_f64();
+    if secs < 0.1 {
+        return None;
+    }
+    Some(TrafficRate {
+        up: (now.up.saturating_sub(last.up) as f64 / secs) as u64,
+        down: (now.down.saturating_sub(last.down) as f64 / secs) as u64,
+    })
+}
+
+fn summarize(batch: MeasureBatch) -> MeasureSummary {
+    let strategy = match batch.mode {
+        MeasureMode::Full => RankStrategy::ThroughputFirst,
+        _ => RankStrategy::LatencyFirst,
+    };
+    let failed = batch.failed(None).len();
+    let best = rank::rank(batch.results.clone(), strategy)
+        .into_iter()
+        .find(|proxy| proxy.score > 0.0)
+        .map(|proxy| proxy.name);
+
+    MeasureSummary {
+        id: batch.id,
+        mode: batch.mode,
+        total: batch.names.len(),
+        measured: batch.results.len(),
+        failed,
+        best,
+        updated: batch.updated,
+    }
+}
+
+#[test]
+fn test_app_status() {
+    use std::time::Duration;
+
+    let proxies = serde_json::json!({
+        "proxies": {
+            "GLOBAL": {"type": "Selector", "now": "proxy", "all": ["proxy", "DIRECT"]},
+            "proxy": {"type": "Selector", "now": "a", "all": ["a", "b"]},
+            "auto": {"type": "URLTest", "all": ["a", "b"]},
+            "a": {"type": "Vless"},
+        }
+    });
+    let selected = selected_nodes(&proxies);
+    assert_eq!(selected.len(), 2);
+    assert_eq!(selected["proxy"], "a");
+
+    let start = Instant::now();
+    let sample = |secs: u64, up: u64, down: u64| TrafficSample {
+        time: start + Duration::from_secs(secs),
+        up,
+        down,
+    };
+    let rate = traffic_rate(&sample(0, 1000, 5000), &sample(2, 3000, 25000));
+    assert_eq!(
+        rate,
+        Some(TrafficRate {
+            up: 1000,
+            down: 10000
+        })
+    );
+    // the core was restarted
+    let rate = traffic_rate(&sample(0, 1000, 5000), &sample(1, 10, 20));
+    assert_eq!(rate, Some(TrafficRate { up: 0, down: 0 }));
+    assert!(traffic_rate(&sample(1, 0, 0), &sample(1, 10, 10)).is_none());
+}
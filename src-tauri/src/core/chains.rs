@@ -0,0 +1,127 @@
+use super::clash_api;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// the part of the clash connection used here
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection {
+    #[serde(default)]
+    upload: u64,
+
+    #[serde(default)]
+    download: u64,
+
+    /// from the node to the outermost group
+    #[serde(default)]
+    chains: Vec<String>,
+
+    #[serde(default)]
+    rule: String,
+
+    #[serde(default)]
+    rule_payload: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Connections {
+    #[serde(default)]
+    connections: Option<Vec<Connection>>,
+}
+
+/// the connections going the same way
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ConnectionChain {
+    /// such as `DomainSuffix` or `Match`
+    pub rule: String,
+
+    /// such as `google.com`, empty for `Match`
+    pub rule_payload: String,
+
+    /// from the outermost group to the innermost
+    pub groups: Vec<String>,
+
+    /// the proxy at the end, such as `DIRECT`
+    pub node: String,
+
+    pub count: usize,
+
+    /// the total bytes
+    pub upload: u64,
+    pub download: u64,
+}
+
+/// aggregate the active connections by the rule, the groups and the node
+/// sorted by the traffic
+pub async fn get_connection_chains() -> Result<Vec<ConnectionChain>> {
+    let data = clash_api::get_connections().await?;
+    let data = serde_json::from_value::<Connections>(data)?;
+
+    Ok(aggregate(data.connections.unwrap_or_default()))
+}
+
+fn aggregate(connections: Vec<Connection>) -> Vec<ConnectionChain> {
+    let mut chains: HashMap<(String, String, Vec<String>), ConnectionChain> = HashMap::new();
+
+    for conn in connections.into_iter() {
+        let key = (conn.rule, conn.rule_payload, conn.chains);
+
+        let chain = chains.entry(key.clone()).or_insert_with(|| {
+            let mut groups = key.2.clone();
+            groups.reverse();
+            let node = groups.pop().unwrap_or_default();
+
+            ConnectionChain {
+                rule: key.0,
+                rule_payload: key.1,
+                groups,
+                node,
+                ..ConnectionChain::default()
+            }
+        });
+
+        chain.count += 1;
+        chain.upload += conn.upload;
+        chain.download += conn.download;
+    }
+
+    let mut chains = chains.into_values().collect::<Vec<ConnectionChain>>();
+    chains.sort_by(|a, b| {
+        (b.upload + b.download)
+            .cmp(&(a.upload + a.download))
+            .then(b.count.cmp(&a.count))
+    });
+    chains
+}
+
+#[test]
+fn test_aggregate() {
+    let data = serde_json::json!({
+        "downloadTotal": 0,
+        "uploadTotal": 0,
+        "connections": [
+            { "upload": 10, "download": 100, "chains": ["node1", "Auto", "Proxy"], "rule": "DomainSuffix", "rulePayload": "google.com" },
+            { "upload": 20, "download": 200, "chains": ["node1", "Auto", "Proxy"], "rule": "DomainSuffix", "rulePayload": "google.com" },
+            { "upload": 1, "download": 1, "chains": ["DIRECT"], "rule": "GeoIP", "rulePayload": "IR" },
+            { "upload": 5, "download": 5, "chains": ["node2", "Proxy"], "rule": "Match", "rulePayload": "" }
+        ]
+    });
+    let data = serde_json::from_value::<Connections>(data).unwrap();
+    let chains = aggregate(data.connections.unwrap());
+
+    assert_eq!(chains.len(), 3);
+    assert_eq!(chains[0].groups, vec!["Proxy", "Auto"]);
+    assert_eq!(chains[0].node, "node1");
+    assert_eq!(
+        (chains[0].count, chains[0].upload, chains[0].download),
+        (2, 30, 300)
+    );
+    assert_eq!(chains[1].node, "node2");
+    assert_eq!(chains[2].node, "DIRECT");
+    assert!(chains[2].groups.is_empty());
+
+    let data = serde_json::json!({ "connections": null });
+    let data = serde_json::from_value::<Connections>(data).unwrap();
+    assert!(aggregate(data.connections.unwrap_or_default()).is_empty());
+}
@@ -1,23 +1,25 @@
+use super::clash_socket;
 use crate::config::Config;
-use anyhow::{bail, Result};
-use reqwest::header::HeaderMap;
+use anyhow::{anyhow, bail, Result};
+use futures::future::BoxFuture;
+use reqwest::{header::HeaderMap, Method, Url};
+use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
 use std::collections::HashMap;
 
 /// PUT /configs
 /// path 是绝对路径
 pub async fn put_configs(path: &str) -> Result<()> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/configs");
+    let client = clash_client_info()?;
+    let url = client.url(&["configs"])?;
 
     let mut data = HashMap::new();
     data.insert("path", path);
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let builder = client.put(&url).headers(headers).json(&data);
-    let response = builder.send().await?;
+    let body = serde_json::to_vec(&data)?;
+    let (status, _) = client.send(Method::PUT, url, Some(body)).await?;
 
-    match response.status().as_u16() {
+    match status {
         204 => Ok(()),
         status @ _ => {
             bail!("failed to put configs with status \"{status}\"")
@@ -25,20 +27,286 @@ pub async fn put_configs(path: &str) -> Result<()> {
     }
 }
 
+/// GET /configs
+/// 内核当前生效的配置
+pub async fn get_configs() -> Result<serde_json::Value> {
+    let client = clash_client_info()?;
+    let url = client.url(&["configs"])?;
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => Ok(serde_json::from_slice::<serde_json::Value>(&body)?),
+        status => {
+            bail!("failed to get configs with status \"{status}\"")
+        }
+    }
+}
+
 /// PATCH /configs
 pub async fn patch_configs(config: &Mapping) -> Result<()> {
-    let (url, headers) = clash_client_info()?;
-    let url = format!("{url}/configs");
+    let client = clash_client_info()?;
+    let url = client.url(&["configs"])?;
 
-    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-    let builder = client.patch(&url).headers(headers.clone()).json(config);
-    builder.send().await?;
+    let body = serde_json::to_vec(config)?;
+    client.send(Method::PATCH, url, Some(body)).await?;
     Ok(())
 }
 
+/// GET /proxies/{name}/delay
+/// 返回延迟毫秒数
+pub async fn get_proxy_delay(name: &str, test_url: &str, timeout: u64) -> Result<u64> {
+    let client = clash_client_info()?;
+
+    let mut url = client.url(&["proxies", name, "delay"])?;
+    url.query_pairs_mut()
+        .append_pair("timeout", &timeout.to_string())
+        .append_pair("url", test_url);
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+    let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+
+    match (status, body.get("delay").and_then(|d| d.as_u64())) {
+        (200, Some(delay)) => Ok(delay),
+        _ => {
+            let message = body.get("message").and_then(|m| m.as_str()).unwrap_or("");
+            bail!("failed to test delay of \"{name}\" with status \"{status}\" {message}")
+        }
+    }
+}
+
+/// GET /group/{name}/delay of clash-meta
+/// the delays of the nodes tested at once, the failed ones are missing
+pub async fn get_group_delay(
+    group: &str,
+    test_url: &str,
+    timeout: u64,
+) -> Result<HashMap<String, u64>> {
+    let client = clash_client_info()?;
+
+    let mut url = client.url(&["group", group, "delay"])?;
+    url.query_pairs_mut()
+        .append_pair("timeout", &timeout.to_string())
+        .append_pair("url", test_url);
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => Ok(serde_json::from_slice::<HashMap<String, u64>>(&body)?),
+        status => {
+            bail!("failed to test delay of the group \"{group}\" with status \"{status}\"")
+        }
+    }
+}
+
+/// PUT /proxies/{group}
+/// 切换group选中的节点
+pub async fn put_proxy(group: &str, name: &str) -> Result<()> {
+    let client = clash_client_info()?;
+    let url = client.url(&["proxies", group])?;
+
+    let mut data = HashMap::new();
+    data.insert("name", name);
+
+    let body = serde_json::to_vec(&data)?;
+    let (status, _) = client.send(Method::PUT, url, Some(body)).await?;
+
+    match status {
+        204 => Ok(()),
+        status => {
+            bail!("failed to select \"{name}\" in \"{group}\" with status \"{status}\"")
+        }
+    }
+}
+
+/// GET /proxies
+/// 包含core健康检查的延迟历史
+pub async fn get_proxies() -> Result<serde_json::Value> {
+    let client = clash_client_info()?;
+    let url = client.url(&["proxies"])?;
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => Ok(serde_json::from_slice::<serde_json::Value>(&body)?),
+        status => {
+            bail!("failed to get the proxies with status \"{status}\"")
+        }
+    }
+}
+
+/// GET /proxies/{group}
+/// 获取group当前选中的节点
+pub async fn get_proxy_now(group: &str) -> Result<Option<String>> {
+    let client = clash_client_info()?;
+    let url = client.url(&["proxies", group])?;
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => {
+            let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+            Ok(body.get("now").and_then(|n| n.as_str()).map(|n| n.into()))
+        }
+        status => {
+            bail!("failed to get the proxy \"{group}\" with status \"{status}\"")
+        }
+    }
+}
+
+/// the selector api of the core, replaced by a mock in the tests
+pub trait ClashApi: Send + Sync {
+    fn get_proxy_now<'a>(&'a self, group: &'a str) -> BoxFuture<'a, Result<Option<String>>>;
+
+    fn put_proxy<'a>(&'a self, group: &'a str, name: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// the http api of the running core
+pub struct HttpClashApi;
+
+impl ClashApi for HttpClashApi {
+    fn get_proxy_now<'a>(&'a self, group: &'a str) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(get_proxy_now(group))
+    }
+
+    fn put_proxy<'a>(&'a self, group: &'a str, name: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(put_proxy(group, name))
+    }
+}
+
+/// GET /connections
+pub async fn get_connections() -> Result<serde_json::Value> {
+    let client = clash_client_info()?;
+    let url = client.url(&["connections"])?;
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => Ok(serde_json::from_slice::<serde_json::Value>(&body)?),
+        status => {
+            bail!("failed to get the connections with status \"{status}\"")
+        }
+    }
+}
+
+/// GET /version
+/// the version of the running core
+pub async fn get_version() -> Result<String> {
+    let client = clash_client_info()?;
+    let url = client.url(&["version"])?;
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => {
+            let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+            match body.get("version").and_then(|v| v.as_str()) {
+                Some(version) => Ok(version.into()),
+                None => bail!("failed to get the version of the core"),
+            }
+        }
+        status => {
+            bail!("failed to get the version with status \"{status}\"")
+        }
+    }
+}
+
+/// GET /rules
+pub async fn get_rules() -> Result<serde_json::Value> {
+    let client = clash_client_info()?;
+    let url = client.url(&["rules"])?;
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+
+    match status {
+        200 => Ok(serde_json::from_slice::<serde_json::Value>(&body)?),
+        status => {
+            bail!("failed to get the rules with status \"{status}\"")
+        }
+    }
+}
+
+/// GET /dns/query
+/// 仅clash meta支持，返回解析到的ip
+pub async fn dns_query(name: &str, qtype: &str) -> Result<Vec<String>> {
+    let client = clash_client_info()?;
+
+    let mut url = client.url(&["dns", "query"])?;
+    url.query_pairs_mut()
+        .append_pair("name", name)
+        .append_pair("type", qtype);
+
+    let (status, body) = client.send(Method::GET, url, None).await?;
+    if status != 200 {
+        bail!("failed to query \"{name}\" with status \"{status}\"");
+    }
+
+    // only the A and AAAA records
+    let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+    Ok(body
+        .get("Answer")
+        .and_then(|a| a.as_array())
+        .map_or(vec![], |answer| {
+            answer
+                .iter()
+                .filter(|a| matches!(a.get("type").and_then(|t| t.as_u64()), Some(1 | 28)))
+                .filter_map(|a| a.get("data").and_then(|d| d.as_str()))
+                .map(|d| d.to_string())
+                .collect()
+        }))
+}
+
+/// clash服务地址和请求头
+struct ClashClient {
+    server: String,
+    headers: HeaderMap,
+
+    /// unix socket 或 named pipe，优先于tcp端口
+    socket: Option<String>,
+}
+
+impl ClashClient {
+    /// 拼接路径，并对节点名称进行编码
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.server)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("failed to parse the clash server"))?
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// 返回状态码和body
+    async fn send(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<Vec<u8>>,
+    ) -> Result<(u16, Vec<u8>)> {
+        if let Some(socket) = self.socket.as_ref() {
+            let path = match url.query() {
+                Some(query) => format!("{}?{query}", url.path()),
+                None => url.path().to_string(),
+            };
+            let body = body.as_deref();
+            return clash_socket::request(socket, method.as_str(), &path, &self.headers, body)
+                .await;
+        }
+
+        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+        let mut builder = client.request(method, url).headers(self.headers.clone());
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status().as_u16();
+        Ok((status, response.bytes().await?.to_vec()))
+    }
+}
+
 /// 根据clash info获取clash服务地址和请求头
-fn clash_client_info() -> Result<(String, HeaderMap)> {
-    let client = { Config::clash().data().get_client_info() };
+fn clash_client_info() -> Result<ClashClient> {
+    let client = { Config::clash().latest().get_client_info() };
 
     let server = format!("http://{}", client.server);
 
@@ -50,7 +318,11 @@ fn clash_client_info() -> Result<(String, HeaderMap)> {
         headers.insert("Authorization", secret);
     }
 
-    Ok((server, headers))
+    Ok(ClashClient {
+        server,
+        headers,
+        socket: client.socket,
+    })
 }
 
 /// 缩短clash的日志
@@ -64,51 +336,242 @@ pub fn parse_log(log: String) -> String {
     return log;
 }
 
-/// 缩短clash -t的错误输出
-/// 仅适配 clash p核 8-26、clash meta 1.13.1
-pub fn parse_check_output(log: String) -> String {
-    let t = log.find("time=");
-    let m = log.find("msg=");
-    let mr = log.rfind('"');
+/// the level of an issue of `clash -t`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckLevel {
+    Warning,
+    Error,
+    Fatal,
+}
+
+impl CheckLevel {
+    /// `error`, `ERR`, `ERROR[0000]` and so on, none for the info and the debug
+    fn parse(level: &str) -> Option<Self> {
+        match level.to_lowercase().as_str() {
+            "warn" | "warning" | "wrn" => Some(Self::Warning),
+            "error" | "err" => Some(Self::Error),
+            "fatal" | "ftl" | "panic" => Some(Self::Fatal),
+            _ => None,
+        }
+    }
+}
+
+/// an issue of `clash -t`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CoreCheckIssue {
+    pub level: CheckLevel,
 
-    if let (Some(_), Some(m), Some(mr)) = (t, m, mr) {
-        let e = match log.find("level=error msg=") {
-            Some(e) => e + 17,
-            None => m + 5,
-        };
+    pub message: String,
 
-        if mr > m {
-            return (&log[e..mr]).to_owned();
+    /// the location in the config, such as `proxy 0` or `outbounds[0].server`
+    pub field_path: Option<String>,
+}
+
+impl std::fmt::Display for CoreCheckIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.field_path.as_ref() {
+            Some(path) => write!(f, "{path}: {}", self.message),
+            None => write!(f, "{}", self.message),
         }
     }
+}
+
+/// the prefixes of the locations in the messages
+const CHECK_PATHS: [&str; 12] = [
+    "proxy",
+    "rule",
+    "dns",
+    "listener",
+    "tun",
+    "sniffer",
+    "hosts",
+    "ntp",
+    "inbound",
+    "outbound",
+    "route",
+    "experimental",
+];
 
-    let l = log.find("error=");
-    let r = log.find("path=").or(Some(log.len()));
+/// 解析 -t 的输出，每行一个问题，忽略 info 和 debug
+/// 支持 clash p核 的 logrus 格式、clash meta 和 mihomo 的 `ERR [Config]` 格式、sing-box 的 `FATAL[0000]` 格式
+/// 解析不出时整个输出作为一个错误
+pub fn parse_check_output(log: String) -> Vec<CoreCheckIssue> {
+    let mut issues: Vec<CoreCheckIssue> = vec![];
 
-    if let (Some(l), Some(r)) = (l, r) {
-        return (&log[(l + 6)..(r - 1)]).to_owned();
+    for (level, message) in log.lines().filter_map(parse_check_line) {
+        let issue = check_issue(level, &message);
+        if !issues.contains(&issue) {
+            issues.push(issue);
+        }
+    }
+
+    if issues.is_empty() && !log.trim().is_empty() {
+        issues.push(check_issue(CheckLevel::Error, log.trim()));
     }
+    issues
+}
 
-    log
+fn parse_check_line(line: &str) -> Option<(CheckLevel, String)> {
+    let line = line.trim();
+    parse_logfmt_line(line)
+        .or_else(|| parse_text_line(line))
+        .or_else(|| parse_sing_box_line(line))
+}
+
+/// time="2022-11-18T20:42:58+08:00" level=error msg="proxy 0: ..."
+fn parse_logfmt_line(line: &str) -> Option<(CheckLevel, String)> {
+    let fields = parse_logfmt(line);
+    let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+
+    let message = field("msg")?;
+    let level = CheckLevel::parse(field("level")?)?;
+    Some((level, message.clone()))
+}
+
+/// 20:43:49 ERR [Config] configuration file test failed error=proxy 0: ... path=xxx
+fn parse_text_line(line: &str) -> Option<(CheckLevel, String)> {
+    let mut parts = line.splitn(3, ' ');
+    let time = parts.next()?;
+    if !time.contains(':') || !time.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let level = CheckLevel::parse(parts.next()?)?;
+
+    let message = parts.next()?.trim();
+    let message = match message.strip_prefix('[') {
+        Some(rest) => rest.split_once("] ").map_or(message, |(_, m)| m),
+        None => message,
+    };
+    let message = match message.split_once("error=") {
+        Some((_, error)) => error.split(" path=").next().unwrap_or(error),
+        None => message,
+    };
+    Some((level, message.trim().trim_matches('"').to_string()))
+}
+
+/// +0800 2024-01-02 10:00:00 FATAL[0000] decode config at ./config.json: ...
+fn parse_sing_box_line(line: &str) -> Option<(CheckLevel, String)> {
+    let (head, message) = line.split_once("] ")?;
+    let (head, counter) = head.rsplit_once('[')?;
+    if counter.is_empty() || !counter.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let level = CheckLevel::parse(head.rsplit(' ').next()?)?;
+    Some((level, message.trim().to_string()))
+}
+
+/// the `key=value` pairs, the quoted values are unescaped
+fn parse_logfmt(line: &str) -> Vec<(String, String)> {
+    let mut fields = vec![];
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            continue;
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+
+        fields.push((key.trim_start_matches('"').to_string(), value));
+    }
+
+    fields
+}
+
+/// split `proxy 0: 'alpn' expected type 'string'` into the path and the message
+fn check_issue(level: CheckLevel, message: &str) -> CoreCheckIssue {
+    let mut message = message.trim_start_matches("Parse config error: ").trim();
+    if let Some(rest) = message.strip_prefix("decode config at ") {
+        message = rest.split_once(": ").map_or(rest, |(_, m)| m);
+    }
+
+    if let Some((path, rest)) = message.split_once(": ") {
+        let lower = path.to_lowercase();
+        if CHECK_PATHS.iter().any(|p| lower.starts_with(p)) {
+            return CoreCheckIssue {
+                level,
+                message: rest.to_string(),
+                field_path: Some(path.trim_end_matches(" error").to_string()),
+            };
+        }
+    }
+
+    CoreCheckIssue {
+        level,
+        message: message.to_string(),
+        field_path: None,
+    }
 }
 
 #[test]
 fn test_parse_check_output() {
+    // clash premium
     let str1 = r#"xxxx\n time="2022-11-18T20:42:58+08:00" level=error msg="proxy 0: 'alpn' expected type 'string', got unconvertible type '[]interface {}'""#;
-    let str2 = r#"20:43:49 ERR [Config] configuration file test failed error=proxy 0: unsupport proxy type: hysteria path=xxx"#;
     let str3 = r#"
     "time="2022-11-18T21:38:01+08:00" level=info msg="Start initial configuration in progress"
     time="2022-11-18T21:38:01+08:00" level=error msg="proxy 0: 'alpn' expected type 'string', got unconvertible type '[]interface {}'"
     configuration file xxx\n
     "#;
-
     let res1 = parse_check_output(str1.into());
+    assert_eq!(res1, parse_check_output(str3.into()));
+    assert_eq!(
+        res1,
+        [CoreCheckIssue {
+            level: CheckLevel::Error,
+            message: "'alpn' expected type 'string', got unconvertible type '[]interface {}'"
+                .into(),
+            field_path: Some("proxy 0".into()),
+        }]
+    );
+
+    // mihomo, all the lines
+    let str2 = r#"20:43:49 WRN [Config] deprecated field "tun.device"
+20:43:49 ERR [Config] configuration file test failed error=proxy 0: unsupport proxy type: hysteria path=xxx
+time="2024-01-02T10:00:00+08:00" level=fatal msg="Parse config error: rules[3] [GEOIP,XX] error: unknown code \"XX\"""#;
     let res2 = parse_check_output(str2.into());
-    let res3 = parse_check_output(str3.into());
+    assert_eq!(res2.len(), 3);
+    assert_eq!(res2[0].level, CheckLevel::Warning);
+    assert_eq!(
+        res2[1].to_string(),
+        "proxy 0: unsupport proxy type: hysteria"
+    );
+    assert_eq!(res2[2].level, CheckLevel::Fatal);
+    assert_eq!(res2[2].field_path.as_deref(), Some("rules[3] [GEOIP,XX]"));
+    assert_eq!(res2[2].message, "unknown code \"XX\"");
 
-    println!("res1: {res1}");
-    println!("res2: {res2}");
-    println!("res3: {res3}");
+    // sing-box
+    let str4 = "+0800 2024-01-02 10:00:00 FATAL[0000] decode config at ./config.json: outbounds[0].server: missing\nINFO[0000] done";
+    let res4 = parse_check_output(str4.into());
+    assert_eq!(res4.len(), 1);
+    assert_eq!(res4[0].level, CheckLevel::Fatal);
+    assert_eq!(res4[0].field_path.as_deref(), Some("outbounds[0].server"));
+    assert_eq!(res4[0].message, "missing");
 
-    assert_eq!(res1, res3);
+    let res5 = parse_check_output("unknown [".into());
+    assert_eq!(res5[0].message, "unknown [");
+    assert!(parse_check_output(" \n".into()).is_empty());
 }
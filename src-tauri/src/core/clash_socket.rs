@@ -0,0 +1,146 @@
+use anyhow::{bail, Context, Result};
+use reqwest::header::HeaderMap;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// send the http request to the controller over the unix socket or the named pipe
+/// the connection is closed after each request
+/// returns the status and the body
+pub async fn request(
+    socket: &str,
+    method: &str,
+    path: &str,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+) -> Result<(u16, Vec<u8>)> {
+    let request = encode_request(method, path, headers, body);
+
+    #[cfg(unix)]
+    let stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .context(format!("failed to connect the controller \"{socket}\""))?;
+
+    #[cfg(windows)]
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(socket)
+        .context(format!("failed to connect the controller \"{socket}\""))?;
+
+    parse_response(&exchange(stream, &request).await?)
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    request: &[u8],
+) -> Result<Vec<u8>> {
+    stream.write_all(request).await?;
+    stream.flush().await?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response).await?;
+    Ok(response)
+}
+
+fn encode_request(method: &str, path: &str, headers: &HeaderMap, body: Option<&[u8]>) -> Vec<u8> {
+    let mut head = format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n");
+    for (name, value) in headers.iter() {
+        if let Ok(value) = value.to_str() {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+
+    let body = body.unwrap_or_default();
+    head.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+
+    let mut request = head.into_bytes();
+    request.extend_from_slice(body);
+    request
+}
+
+/// the body is sized by the content length or the chunks
+fn parse_response(data: &[u8]) -> Result<(u16, Vec<u8>)> {
+    let end = match data.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => end,
+        None => bail!("invalid response of the controller"),
+    };
+    let head = String::from_utf8_lossy(&data[..end]);
+    let body = &data[end + 4..];
+
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .context("invalid status of the controller")?;
+
+    let mut chunked = false;
+    let mut length = None;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_lowercase().as_str() {
+                "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+                "content-length" => length = value.parse::<usize>().ok(),
+                _ => {}
+            }
+        }
+    }
+
+    if chunked {
+        return Ok((status, decode_chunked(body)?));
+    }
+    match length {
+        Some(length) => Ok((status, body[..length.min(body.len())].to_vec())),
+        None => Ok((status, body.to_vec())),
+    }
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>> {
+    let mut body = vec![];
+
+    loop {
+        let line = match data.windows(2).position(|w| w == b"\r\n") {
+            Some(line) => line,
+            None => bail!("invalid chunk of the controller"),
+        };
+        let size = String::from_utf8_lossy(&data[..line]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).context("invalid chunk size")?;
+
+        data = &data[line + 2..];
+        if size == 0 {
+            break;
+        }
+        if data.len() < size {
+            bail!("truncated chunk of the controller");
+        }
+        body.extend_from_slice(&data[..size]);
+        data = data.get(size + 2..).unwrap_or_default();
+    }
+    Ok(body)
+}
+
+#[test]
+fn test_parse_response() {
+    let mut headers = HeaderMap::new();
+    headers.insert("Authorization", "Bearer xxx".parse().unwrap());
+    let request = encode_request("PUT", "/proxies/a%20b", &headers, Some(b"{}"));
+    assert_eq!(
+        String::from_utf8(request).unwrap(),
+        "PUT /proxies/a%20b HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nauthorization: Bearer xxx\r\nContent-Length: 2\r\n\r\n{}"
+    );
+
+    let sized = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 11\r\n\r\n{\"now\":\"a\"}";
+    assert_eq!(
+        parse_response(sized).unwrap(),
+        (200, b"{\"now\":\"a\"}".to_vec())
+    );
+
+    let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n{\"now\r\n6\r\n\":\"a\"}\r\n0\r\n\r\n";
+    assert_eq!(
+        parse_response(chunked).unwrap(),
+        (200, b"{\"now\":\"a\"}".to_vec())
+    );
+
+    let empty = b"HTTP/1.1 204 No Content\r\n\r\n";
+    assert_eq!(parse_response(empty).unwrap(), (204, vec![]));
+    assert!(parse_response(b"HTTP/1.1 200").is_err());
+}
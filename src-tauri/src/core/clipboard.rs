@@ -0,0 +1,203 @@
+use super::handle::Handle;
+use crate::config::Config;
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::ClipboardManager;
+use tokio::time::{sleep, Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// the copied text must stay the same for the duration
+const DEBOUNCE: Duration = Duration::from_millis(800);
+
+/// the default allowlist of `clipboard_schemes`
+/// the `https` urls are only offered if they look like the subscriptions
+pub const DEFAULT_SCHEMES: [&str; 8] = [
+    "vmess",
+    "vless",
+    "ss",
+    "trojan",
+    "hysteria2",
+    "tuic",
+    "clash",
+    "https",
+];
+
+/// the hints in the path or the query of the subscription urls
+const SUBSCRIPTION_HINTS: [&str; 5] = ["sub", "token", "clash", "link", "api/v1/client"];
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ClipboardOffer {
+    /// `share_links` or `subscription`
+    pub kind: String,
+
+    /// the share links, or the only subscription url
+    pub links: Vec<String>,
+}
+
+pub struct ClipboardWatcher {
+    /// the text seen last and since when
+    pending: Arc<Mutex<Option<(String, Instant)>>>,
+
+    /// the text offered last, not offered again
+    offered: Arc<Mutex<Option<String>>>,
+}
+
+impl ClipboardWatcher {
+    pub fn global() -> &'static ClipboardWatcher {
+        static WATCHER: OnceCell<ClipboardWatcher> = OnceCell::new();
+
+        WATCHER.get_or_init(|| ClipboardWatcher {
+            pending: Arc::new(Mutex::new(None)),
+            offered: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// launch the watcher loop, it's idle unless `enable_clipboard_watcher` is set
+    pub fn init(&'static self) -> Result<()> {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                sleep(POLL_INTERVAL).await;
+
+                let enable = { Config::verge().latest().enable_clipboard_watcher };
+                if !enable.unwrap_or(false) {
+                    *self.pending.lock() = None;
+                    continue;
+                }
+                if let Some(text) = read_text() {
+                    self.check(text);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn check(&self, text: String) {
+        let mut pending = self.pending.lock();
+        let since = match pending.as_ref() {
+            Some((last, since)) if last == &text => *since,
+            _ => {
+                *pending = Some((text, Instant::now()));
+                return;
+            }
+        };
+        if since.elapsed() < DEBOUNCE {
+            return;
+        }
+
+        let mut offered = self.offered.lock();
+        if offered.as_ref() == Some(&text) {
+            return;
+        }
+        *offered = Some(text.clone());
+
+        if let Some(offer) = detect_offer(&text, &schemes()) {
+            log::info!(target: "app", "found the {} in the clipboard", offer.kind);
+            Handle::clipboard_offer(offer);
+        }
+    }
+}
+
+fn read_text() -> Option<String> {
+    let app_handle = { Handle::global().app_handle.lock().clone() }?;
+    app_handle.clipboard_manager().read_text().ok().flatten()
+}
+
+fn schemes() -> Vec<String> {
+    let schemes = { Config::verge().latest().clipboard_schemes.clone() };
+    schemes.unwrap_or_else(|| DEFAULT_SCHEMES.iter().map(|s| s.to_string()).collect())
+}
+
+/// the share links in the lines, or a single subscription url
+fn detect_offer(text: &str, schemes: &[String]) -> Option<ClipboardOffer> {
+    let allowed = |line: &str| {
+        let (scheme, rest) = line.split_once("://")?;
+        let scheme = scheme.to_lowercase();
+        match schemes.iter().any(|s| s.eq_ignore_ascii_case(&scheme)) && !rest.is_empty() {
+            true => Some(scheme),
+            false => None,
+        }
+    };
+
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>();
+
+    if let [line] = lines.as_slice() {
+        let subscription = match allowed(line).as_deref() {
+            Some("http" | "https") => is_subscription(line),
+            Some("clash") => true,
+            _ => false,
+        };
+        if subscription {
+            return Some(ClipboardOffer {
+                kind: "subscription".into(),
+                links: vec![line.to_string()],
+            });
+        }
+    }
+
+    let links = lines
+        .into_iter()
+        .filter(|line| allowed(line).filter(|s| !is_url_scheme(s)).is_some())
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>();
+
+    match links.is_empty() {
+        true => None,
+        false => Some(ClipboardOffer {
+            kind: "share_links".into(),
+            links,
+        }),
+    }
+}
+
+fn is_url_scheme(scheme: &str) -> bool {
+    matches!(scheme, "http" | "https" | "clash")
+}
+
+fn is_subscription(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    let path = lower.split_once("://").map_or("", |(_, rest)| rest);
+    let path = path.split_once('/').map_or("", |(_, path)| path);
+    SUBSCRIPTION_HINTS.iter().any(|hint| path.contains(hint))
+}
+
+#[test]
+fn test_detect_offer() {
+    let schemes = DEFAULT_SCHEMES
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+
+    let offer = detect_offer(
+        "vless://uuid@a.com:443#de\n\nss://abc@b.com:8388\n",
+        &schemes,
+    );
+    assert_eq!(offer.as_ref().map(|o| o.kind.as_str()), Some("share_links"));
+    assert_eq!(offer.unwrap().links.len(), 2);
+
+    let offer = detect_offer(
+        " https://panel.com/api/v1/client/subscribe?token=x ",
+        &schemes,
+    );
+    assert_eq!(
+        offer,
+        Some(ClipboardOffer {
+            kind: "subscription".into(),
+            links: vec!["https://panel.com/api/v1/client/subscribe?token=x".into()],
+        })
+    );
+
+    assert_eq!(detect_offer("https://github.com/", &schemes), None);
+    assert_eq!(detect_offer("hello world", &schemes), None);
+
+    let schemes = vec!["vmess".to_string()];
+    assert_eq!(detect_offer("vless://uuid@a.com:443", &schemes), None);
+}
@@ -1,10 +1,14 @@
-use super::{clash_api, logger::Logger};
+use super::hook::{self, HookEvent};
+use super::notification::{self, NotifyTrigger};
+use super::{clash_api, log_alert::LogAlerts, logger::Logger};
+use crate::enhance;
 use crate::log_err;
-use crate::{config::*, utils::dirs};
+use crate::{config::*, utils::dirs, utils::help};
 use anyhow::{bail, Context, Result};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
-use std::{fs, io::Write, sync::Arc, time::Duration};
+use serde_yaml::Mapping;
+use std::{env::temp_dir, fs, io::Write, path::PathBuf, sync::Arc, time::Duration};
 use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
 use tauri::api::process::{Command, CommandChild, CommandEvent};
 use tokio::time::sleep;
@@ -27,23 +31,25 @@ impl CoreManager {
         })
     }
 
+    /// the core is started in the background, the setup does not wait for it
     pub fn init(&self) -> Result<()> {
-        // kill old clash process
-        let _ = dirs::clash_pid_path()
-            .and_then(|path| fs::read(path).map(|p| p.to_vec()).context(""))
-            .and_then(|pid| String::from_utf8_lossy(&pid).parse().context(""))
-            .map(|pid| {
-                let mut system = System::new();
-                system.refresh_all();
-                system.process(Pid::from_u32(pid)).map(|proc| {
-                    if proc.name().contains("clash") {
-                        log::debug!(target: "app", "kill old clash process");
-                        proc.kill();
-                    }
+        tauri::async_runtime::spawn(async {
+            // kill old clash process
+            // scanning the processes is slow, so it's not on the startup path
+            let _ = dirs::clash_pid_path()
+                .and_then(|path| fs::read(path).map(|p| p.to_vec()).context(""))
+                .and_then(|pid| String::from_utf8_lossy(&pid).parse().context(""))
+                .map(|pid| {
+                    let mut system = System::new();
+                    system.refresh_process(Pid::from_u32(pid));
+                    system.process(Pid::from_u32(pid)).map(|proc| {
+                        if proc.name().contains("clash") {
+                            log::debug!(target: "app", "kill old clash process");
+                            proc.kill();
+                        }
+                    });
                 });
-            });
 
-        tauri::async_runtime::spawn(async {
             // 启动clash
             log_err!(Self::global().run_core().await);
         });
@@ -54,7 +60,50 @@ impl CoreManager {
     /// 检查配置是否正确
     pub fn check_config(&self) -> Result<()> {
         let config_path = Config::generate_file(ConfigType::Check)?;
-        let config_path = dirs::path_to_str(&config_path)?;
+
+        if let Some(output) = Self::run_check(&config_path)? {
+            let issues = clash_api::parse_check_output(output.clone());
+            let mut errors = issues
+                .iter()
+                .filter(|issue| issue.level >= clash_api::CheckLevel::Error)
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>();
+            if errors.is_empty() {
+                errors = issues.iter().map(|issue| issue.to_string()).collect();
+            }
+            Logger::global().set_log(output);
+            bail!("{}", errors.join("\n"));
+        }
+
+        Ok(())
+    }
+
+    /// 用 -t 检查还未启用的配置，不影响运行中的核心
+    /// 返回所有的问题，通过时为空
+    pub fn test_config(&self, uid: &String) -> Result<Vec<clash_api::CoreCheckIssue>> {
+        Config::profiles().latest().get_item(uid)?;
+
+        let (config, _, _) = enhance::enhance_profile(Some(uid));
+        self.test_enhanced(&config)
+    }
+
+    /// 用 -t 检查已经生成的配置
+    pub fn test_enhanced(&self, config: &Mapping) -> Result<Vec<clash_api::CoreCheckIssue>> {
+        let config_path = temp_dir().join(TEST_CONFIG);
+        help::save_yaml(
+            &config_path,
+            config,
+            Some("# Generated by Hiddify Clash Desktop"),
+        )?;
+
+        Ok(Self::run_check(&config_path)?
+            .map(clash_api::parse_check_output)
+            .unwrap_or_default())
+    }
+
+    /// 运行 clash -t，失败时返回输出
+    fn run_check(config_path: &PathBuf) -> Result<Option<String>> {
+        let config_path = dirs::path_to_str(config_path)?;
 
         let clash_core = { Config::verge().latest().clash_core.clone() };
         let clash_core = clash_core.unwrap_or("clash-meta".into());
@@ -66,13 +115,10 @@ impl CoreManager {
             .args(["-t", "-d", app_dir, "-f", config_path])
             .output()?;
 
-        if !output.status.success() {
-            let error = clash_api::parse_check_output(output.stdout.clone());
-            Logger::global().set_log(output.stdout);
-            bail!("{error}");
+        match output.status.success() {
+            true => Ok(None),
+            false => Ok(Some(output.stdout)),
         }
-
-        Ok(())
     }
 
     /// 启动核心
@@ -121,7 +167,10 @@ impl CoreManager {
                 })()
                 .await
                 {
-                    Ok(_) => return Ok(()),
+                    Ok(_) => {
+                        hook::fire(HookEvent::CoreStarted, vec![]);
+                        return Ok(());
+                    }
                     Err(err) => {
                         // 修改这个值，免得stop出错
                         *self.use_service_mode.lock() = false;
@@ -160,6 +209,7 @@ impl CoreManager {
             <Result<()>>::Ok(())
         })());
 
+        let pid = cmd_child.pid();
         let mut sidecar = self.sidecar.lock();
         *sidecar = Some(cmd_child);
         drop(sidecar);
@@ -174,19 +224,34 @@ impl CoreManager {
                         } else {
                             log::info!(target: "app", "[clash]: {line}");
                         };
+                        LogAlerts::global().feed(&line);
                         Logger::global().set_log(line);
                     }
                     CommandEvent::Stderr(err) => {
                         // let stdout = clash_api::parse_log(err.clone());
                         log::error!(target: "app", "[clash]: {err}");
+                        LogAlerts::global().feed(&err);
                         Logger::global().set_log(err);
                     }
                     CommandEvent::Error(err) => {
                         log::error!(target: "app", "[clash]: {err}");
                         Logger::global().set_log(err);
                     }
-                    CommandEvent::Terminated(_) => {
+                    CommandEvent::Terminated(payload) => {
                         log::info!(target: "app", "clash core terminated");
+
+                        // the sidecar is taken before stopping by the app
+                        let crashed = {
+                            let sidecar = CoreManager::global().sidecar.lock();
+                            sidecar.as_ref().map(|child| child.pid()) == Some(pid)
+                        };
+                        if crashed {
+                            let code = payload.code.unwrap_or(-1);
+                            let body = format!("The clash core exited with code {code}");
+                            notification::notify(NotifyTrigger::CoreCrash, "Hiddify", &body);
+                            hook::fire(HookEvent::CoreCrashed, vec![("message", body)]);
+                        }
+
                         let _ = CoreManager::global().recover_core();
                         break;
                     }
@@ -195,9 +260,15 @@ impl CoreManager {
             }
         });
 
+        hook::fire(HookEvent::CoreStarted, vec![]);
         Ok(())
     }
 
+    /// the pid of the sidecar, none in the service mode
+    pub fn core_pid(&self) -> Option<u32> {
+        self.sidecar.lock().as_ref().map(|child| child.pid())
+    }
+
     /// 重启内核
     pub fn recover_core(&'static self) -> Result<()> {
         // 服务模式不管
@@ -261,7 +332,8 @@ impl CoreManager {
 
         log::debug!(target: "app", "change core to `{clash_core}`");
 
-        Config::verge().draft().clash_core = Some(clash_core);
+        let tx = Config::verge().begin().await;
+        tx.draft().clash_core = Some(clash_core);
 
         // 更新配置
         Config::generate()?;
@@ -273,13 +345,12 @@ impl CoreManager {
 
         match self.run_core().await {
             Ok(_) => {
-                Config::verge().apply();
+                log_err!(tx.commit());
                 Config::runtime().apply();
-                log_err!(Config::verge().latest().save_file());
                 Ok(())
             }
             Err(err) => {
-                Config::verge().discard();
+                drop(tx);
                 Config::runtime().discard();
                 Err(err)
             }
@@ -0,0 +1,186 @@
+use crate::config::Config;
+use crate::log_err;
+use crate::utils::{dirs, help};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// the traffic of the throughput tests
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DataUsage {
+    /// such as `2024-05`
+    pub month: String,
+
+    /// the bytes of the month
+    pub month_bytes: u64,
+
+    /// the bytes since the last batch started, not saved
+    #[serde(skip)]
+    pub scan_bytes: u64,
+
+    /// the bytes of the last full test, the estimate of the next one
+    pub last_cost: u64,
+
+    /// the estimates of the running full tests, not saved
+    #[serde(skip)]
+    pub reserved: u64,
+}
+
+impl DataUsage {
+    fn roll(&mut self, month: &str) {
+        if self.month != month {
+            self.month = month.into();
+            self.month_bytes = 0;
+        }
+    }
+
+    /// the reason if the next full test would exceed the budgets (bytes)
+    fn exceeds(&self, scan_budget: Option<u64>, month_budget: Option<u64>) -> Option<String> {
+        let over = |used: u64, budget: Option<u64>| {
+            budget
+                .filter(|budget| *budget > 0)
+                .filter(|budget| used + self.reserved + self.last_cost > *budget)
+                .is_some()
+        };
+
+        if over(self.scan_bytes, scan_budget) {
+            return Some("the throughput test was skipped for the data budget of the scan".into());
+        }
+        if over(self.month_bytes, month_budget) {
+            return Some("the throughput test was skipped for the data budget of the month".into());
+        }
+        None
+    }
+
+    /// hold the estimate for the next full test if it fits the budgets
+    fn reserve(
+        &mut self,
+        scan_budget: Option<u64>,
+        month_budget: Option<u64>,
+    ) -> Result<u64, String> {
+        match self.exceeds(scan_budget, month_budget) {
+            Some(reason) => Err(reason),
+            None => {
+                self.reserved += self.last_cost;
+                Ok(self.last_cost)
+            }
+        }
+    }
+}
+
+/// the estimate held for a running full test, released on drop
+/// the bytes are recorded by `DataBudget::record` as they are read
+pub struct BudgetReservation(u64);
+
+impl Drop for BudgetReservation {
+    fn drop(&mut self) {
+        let mut usage = DataBudget::global().usage.lock();
+        if let Some(usage) = usage.as_mut() {
+            usage.reserved = usage.reserved.saturating_sub(self.0);
+        }
+    }
+}
+
+/// the data usage of the measurements, saved in `measure_usage.yaml`
+pub struct DataBudget {
+    /// loaded on the first use
+    usage: Arc<Mutex<Option<DataUsage>>>,
+}
+
+impl DataBudget {
+    pub fn global() -> &'static DataBudget {
+        static BUDGET: OnceCell<DataBudget> = OnceCell::new();
+
+        BUDGET.get_or_init(|| DataBudget {
+            usage: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn load() -> DataUsage {
+        match dirs::measure_usage_path().and_then(|path| help::read_yaml(&path)) {
+            Ok(usage) => usage,
+            Err(err) => {
+                log::debug!(target: "app", "failed to read the measurement data usage, {err}");
+                DataUsage::default()
+            }
+        }
+    }
+
+    pub fn get_usage(&self) -> DataUsage {
+        let mut usage = self.usage.lock();
+        let usage = usage.get_or_insert_with(Self::load);
+        usage.roll(&current_month());
+        usage.clone()
+    }
+
+    pub fn record(&self, bytes: u64) {
+        let mut usage = self.usage.lock();
+        let usage = usage.get_or_insert_with(Self::load);
+        usage.roll(&current_month());
+        usage.month_bytes += bytes;
+        usage.scan_bytes += bytes;
+        log_err!(dirs::measure_usage_path().and_then(|path| help::save_yaml(&path, usage, None)));
+    }
+
+    pub fn set_last_cost(&self, bytes: u64) {
+        let mut usage = self.usage.lock();
+        usage.get_or_insert_with(Self::load).last_cost = bytes;
+    }
+
+    /// reset the usage of the scan
+    pub fn start_scan(&self) {
+        let mut usage = self.usage.lock();
+        usage.get_or_insert_with(Self::load).scan_bytes = 0;
+    }
+
+    /// check and hold the budget of a full test at once, so the concurrent tests can't all pass
+    /// the reason to skip the throughput test, by `measure_scan_budget` and `measure_monthly_budget`
+    pub fn reserve(&self) -> Result<BudgetReservation, String> {
+        let (scan_budget, month_budget) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (verge.measure_scan_budget, verge.measure_monthly_budget)
+        };
+        let mb = |budget: Option<u64>| budget.map(|budget| budget * 1024 * 1024);
+
+        let mut usage = self.usage.lock();
+        let usage = usage.get_or_insert_with(Self::load);
+        usage.roll(&current_month());
+        usage
+            .reserve(mb(scan_budget), mb(month_budget))
+            .map(BudgetReservation)
+    }
+}
+
+fn current_month() -> String {
+    chrono::Local::now().format("%Y-%m").to_string()
+}
+
+#[test]
+fn test_data_budget() {
+    let mut usage = DataUsage {
+        month: "2024-05".into(),
+        month_bytes: 900,
+        scan_bytes: 300,
+        last_cost: 100,
+        reserved: 0,
+    };
+
+    assert_eq!(usage.exceeds(None, None), None);
+    assert_eq!(usage.exceeds(Some(0), Some(0)), None);
+    assert_eq!(usage.exceeds(Some(400), Some(1000)), None);
+    assert!(usage.exceeds(Some(399), None).unwrap().contains("scan"));
+    assert!(usage.exceeds(None, Some(999)).unwrap().contains("month"));
+
+    // the running tests hold their estimates
+    assert_eq!(usage.reserve(Some(450), None), Ok(100));
+    assert!(usage.reserve(Some(450), None).unwrap_err().contains("scan"));
+    usage.reserved = 0;
+
+    usage.roll("2024-05");
+    assert_eq!(usage.month_bytes, 900);
+    usage.roll("2024-06");
+    assert_eq!(usage.month_bytes, 0);
+    assert_eq!(usage.exceeds(None, Some(999)), None);
+}
@@ -0,0 +1,64 @@
+use super::clash_api;
+use crate::config::Config;
+use anyhow::{bail, Result};
+
+/// used when the user does not set any test url
+const DEFAULT_TEST_URLS: [&str; 3] = [
+    "http://www.gstatic.com/generate_204",
+    "http://cp.cloudflare.com/generate_204",
+    "http://www.msftconnecttest.com/connecttest.txt",
+];
+
+/// the timeout for each test url (ms)
+pub const DELAY_TIMEOUT: u64 = 5000;
+
+/// get the latency test urls by order
+pub fn test_urls() -> Vec<String> {
+    let (urls, default_url) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.latency_test_urls.clone(),
+            verge.default_latency_test.clone(),
+        )
+    };
+
+    let urls = urls
+        .unwrap_or_default()
+        .into_iter()
+        .chain(default_url)
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect::<Vec<String>>();
+
+    match urls.is_empty() {
+        true => DEFAULT_TEST_URLS.iter().map(|s| s.to_string()).collect(),
+        false => urls,
+    }
+}
+
+/// test the proxy delay with the test urls by order
+/// return the first successful result
+/// the `url` set for a group in the proxies page is tried alone
+pub async fn test_delay(name: &str, url: Option<String>) -> Result<u64> {
+    let urls = match url.filter(|url| !url.trim().is_empty()) {
+        Some(url) => vec![url],
+        None => test_urls(),
+    };
+    let mut last_err = None;
+
+    for url in urls.iter() {
+        match clash_api::get_proxy_delay(name, url, DELAY_TIMEOUT).await {
+            Ok(delay) => return Ok(delay),
+            Err(err) => {
+                log::debug!(target: "app", "delay test with `{url}` failed, {err}");
+                last_err = Some(err);
+            }
+        }
+    }
+
+    match last_err {
+        Some(err) => Err(err),
+        None => bail!("no latency test url"),
+    }
+}
@@ -0,0 +1,319 @@
+use super::measure::{self, MeasureInfo, MeasureStats};
+use super::measure_cache::MeasureCache;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use futures::StreamExt;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use std::collections::HashMap;
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration, Instant};
+
+/// the timeout of each tcp connect
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+const CONNECT_COUNT: usize = 4;
+
+/// the endpoints tested at the same time by tcping
+const TCPING_PARALLEL: usize = 16;
+
+/// no tcp listener behind these types
+const UDP_TYPES: [&str; 4] = ["hysteria", "hysteria2", "tuic", "wireguard"];
+
+/// the download is stopped at the time or the size
+const DOWNLOAD_DURATION: Duration = Duration::from_secs(10);
+const DOWNLOAD_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// the server of the proxy in the profile
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ProxyEndpoint {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub ptype: String,
+
+    pub server: String,
+    pub port: u16,
+}
+
+/// the result of the test from the local machine without the proxy
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DirectResult {
+    /// `host:port`
+    pub target: String,
+
+    /// tcp connect statistics (ms)
+    pub latency: Option<MeasureStats>,
+
+    /// the ratio of the failed connects
+    pub loss: f64,
+
+    /// download speed of the https page on the target (bytes/s)
+    /// none if the target serves no page
+    pub download: Option<u64>,
+
+    /// the error message if all the connects failed
+    pub error: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DirectReport {
+    /// the host of the subscription url
+    pub panel: Option<DirectResult>,
+
+    /// the server of the proxy
+    pub node: Option<DirectResult>,
+
+    /// the last measurement through the proxy for comparison
+    /// slow here but fine directly means the node is overloaded
+    pub proxied: Option<MeasureInfo>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct TcpingResult {
+    pub name: String,
+
+    /// `host:port`
+    pub target: String,
+
+    /// tcp connect statistics (ms)
+    pub latency: Option<MeasureStats>,
+
+    /// the ratio of the failed connects
+    pub loss: f64,
+
+    /// all the connects failed
+    pub blocked: bool,
+
+    /// the reason if skipped, such as the udp based proxy
+    pub error: Option<String>,
+}
+
+/// test the panel of the profile and the server of the proxy directly
+/// it tells whether the isp throttles the server or the node is overloaded
+pub async fn test_direct(uid: &str, proxy: Option<&str>) -> Result<DirectReport> {
+    let (url, endpoints) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let item = profiles.get_item(&uid.to_string())?;
+
+        let endpoints = match proxy.is_some() {
+            true => profile_endpoints(&serde_yaml::from_str::<Mapping>(&item.read_file()?)?),
+            false => vec![],
+        };
+        (item.url.clone(), endpoints)
+    };
+
+    let panel = match url.as_ref().and_then(|url| Url::parse(url).ok()) {
+        Some(url) => {
+            let host = url.host_str().unwrap_or_default().to_string();
+            let port = url.port_or_known_default().unwrap_or(443);
+            let page = format!("{}://{}/", url.scheme(), url.authority());
+            Some(test_target(&host, port, &page).await)
+        }
+        None => None,
+    };
+
+    let node = match proxy {
+        Some(name) => match endpoints.iter().find(|endpoint| endpoint.name == name) {
+            Some(endpoint) => {
+                let (server, port) = (&endpoint.server, endpoint.port);
+                let page = format!("https://{}/", host_port(server, port));
+                Some(test_target(server, port, &page).await)
+            }
+            None => bail!("failed to find the server of the proxy \"{name}\""),
+        },
+        None => None,
+    };
+
+    let proxied = proxy.and_then(|name| {
+        let infos = MeasureCache::global().get(uid);
+        infos.into_iter().find(|info| info.name == name)
+    });
+
+    Ok(DirectReport {
+        panel,
+        node,
+        proxied,
+    })
+}
+
+/// tcp connect to the server of each proxy in the profile without the core
+/// the same server is only tested once
+pub async fn tcping_nodes(uid: &str) -> Result<Vec<TcpingResult>> {
+    let endpoints = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let item = profiles.get_item(&uid.to_string())?;
+        profile_endpoints(&serde_yaml::from_str::<Mapping>(&item.read_file()?)?)
+    };
+
+    let mut targets = endpoints
+        .iter()
+        .filter(|endpoint| !UDP_TYPES.contains(&endpoint.ptype.as_str()))
+        .map(|endpoint| host_port(&endpoint.server, endpoint.port))
+        .collect::<Vec<String>>();
+    targets.sort();
+    targets.dedup();
+
+    let samples = futures::stream::iter(targets)
+        .map(|target| async move {
+            let samples = tcp_connect_samples(&target, CONNECT_COUNT).await;
+            (target, samples)
+        })
+        .buffer_unordered(TCPING_PARALLEL)
+        .collect::<HashMap<String, (Vec<u64>, usize)>>()
+        .await;
+
+    Ok(endpoints
+        .into_iter()
+        .map(|endpoint| {
+            let target = host_port(&endpoint.server, endpoint.port);
+            let mut result = TcpingResult {
+                name: endpoint.name,
+                target,
+                ..TcpingResult::default()
+            };
+
+            match samples.get(&result.target) {
+                Some((samples, failed)) => {
+                    result.latency = MeasureStats::from_samples(samples);
+                    result.loss = *failed as f64 / CONNECT_COUNT as f64;
+                    result.blocked = samples.is_empty();
+                }
+                None => result.error = Some(format!("{} is not tcp based", endpoint.ptype)),
+            }
+            result
+        })
+        .collect())
+}
+
+async fn test_target(host: &str, port: u16, page: &str) -> DirectResult {
+    let target = host_port(host, port);
+    let (samples, failed) = tcp_connect_samples(&target, CONNECT_COUNT).await;
+
+    let mut result = DirectResult {
+        latency: MeasureStats::from_samples(&samples),
+        loss: failed as f64 / CONNECT_COUNT as f64,
+        target,
+        ..DirectResult::default()
+    };
+
+    if samples.is_empty() {
+        result.error = Some("all the tcp connects failed".into());
+        return result;
+    }
+
+    result.download = match download_page(page).await {
+        Ok(speed) => Some(speed),
+        Err(err) => {
+            log::debug!(target: "app", "failed to download `{page}` directly, {err}");
+            None
+        }
+    };
+    result
+}
+
+/// the connect time (ms) of the successful ones and the failed count
+pub async fn tcp_connect_samples(target: &str, count: usize) -> (Vec<u64>, usize) {
+    let mut samples = vec![];
+
+    for _ in 0..count {
+        let start = Instant::now();
+        if let Ok(Ok(_)) = timeout(CONNECT_TIMEOUT, TcpStream::connect(target)).await {
+            samples.push(start.elapsed().as_millis() as u64);
+        }
+    }
+
+    let failed = count - samples.len();
+    (samples, failed)
+}
+
+/// the certificate is not verified
+/// the node usually serves a fake site with its own certificate
+async fn download_page(page: &str) -> Result<u64> {
+    let client = reqwest::ClientBuilder::new()
+        .no_proxy()
+        .danger_accept_invalid_certs(true)
+        .default_headers(measure::request_headers()?)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()?;
+
+    let start = Instant::now();
+    let mut resp = timeout(DOWNLOAD_DURATION, client.get(page).send()).await??;
+    let mut bytes = 0;
+
+    loop {
+        let left = DOWNLOAD_DURATION.saturating_sub(start.elapsed());
+        match timeout(left, resp.chunk()).await {
+            Ok(Ok(Some(chunk))) => {
+                bytes += chunk.len() as u64;
+                if bytes >= DOWNLOAD_MAX_BYTES {
+                    break;
+                }
+            }
+            Ok(Err(err)) if bytes == 0 => return Err(err.into()),
+            _ => break,
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    if bytes == 0 || elapsed <= 0.0 {
+        bail!("nothing downloaded");
+    }
+    Ok((bytes as f64 / elapsed) as u64)
+}
+
+/// the ipv6 address is wrapped by the brackets
+fn host_port(host: &str, port: u16) -> String {
+    match host.contains(':') {
+        true => format!("[{host}]:{port}"),
+        false => format!("{host}:{port}"),
+    }
+}
+
+/// the servers of the proxies in the profile
+pub fn profile_endpoints(config: &Mapping) -> Vec<ProxyEndpoint> {
+    config
+        .get("proxies")
+        .and_then(|val| val.as_sequence())
+        .map_or(vec![], |seq| {
+            seq.iter()
+                .filter_map(|item| {
+                    let port = item.get("port")?;
+                    // the port may be quoted
+                    let port = port
+                        .as_u64()
+                        .or_else(|| port.as_str()?.parse().ok())
+                        .and_then(|port| u16::try_from(port).ok())?;
+
+                    Some(ProxyEndpoint {
+                        name: item.get("name")?.as_str()?.into(),
+                        ptype: item.get("type")?.as_str()?.into(),
+                        server: item.get("server")?.as_str()?.into(),
+                        port,
+                    })
+                })
+                .collect()
+        })
+}
+
+#[test]
+fn test_profile_endpoints() {
+    let config = r#"
+proxies:
+  - { name: a, type: vless, server: example.com, port: 443 }
+  - { name: b, type: trojan, server: "::1", port: "8443" }
+  - { name: c, type: ss, server: 1.2.3.4, port: 70000 }
+  - { name: d, type: ss }
+"#;
+    let config = serde_yaml::from_str::<Mapping>(config).unwrap();
+    let endpoints = profile_endpoints(&config);
+
+    assert_eq!(endpoints.len(), 2);
+    assert_eq!(endpoints[0].server, "example.com");
+    assert_eq!(endpoints[1].port, 8443);
+    assert_eq!(host_port(&endpoints[1].server, 8443), "[::1]:8443");
+    assert_eq!(host_port("example.com", 443), "example.com:443");
+}
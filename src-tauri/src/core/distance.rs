@@ -0,0 +1,122 @@
+/// the mean radius of the earth (km)
+const EARTH_RADIUS: f64 = 6371.0088;
+
+/// the wgs-84 ellipsoid
+const WGS84_A: f64 = 6378137.0;
+const WGS84_F: f64 = 1.0 / 298.257223563;
+
+/// the servers within the radius (km) are in the same metro area
+pub const METRO_RADIUS: f64 = 50.0;
+
+/// (latitude, longitude) in degrees
+pub type Coordinate = (f64, f64);
+
+/// the great-circle distance (km), up to 0.5% error
+pub fn haversine(a: Coordinate, b: Coordinate) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.1 - a.1).to_radians();
+
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().min(1.0).asin()
+}
+
+/// the distance on the wgs-84 ellipsoid (km) by the vincenty formula
+/// fall back to `haversine` for the nearly antipodal points where it doesn't converge
+pub fn vincenty(a: Coordinate, b: Coordinate) -> f64 {
+    let wgs84_b = (1.0 - WGS84_F) * WGS84_A;
+    let l = (b.1 - a.1).to_radians();
+    let u1 = ((1.0 - WGS84_F) * a.0.to_radians().tan()).atan();
+    let u2 = ((1.0 - WGS84_F) * b.0.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            return 0.0;
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha.powi(2);
+        // on the equator
+        let cos_2sigma_m = match cos2_alpha != 0.0 {
+            true => cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha,
+            false => 0.0,
+        };
+        let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+
+        let prev = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+        if (lambda - prev).abs() > 1e-12 {
+            continue;
+        }
+
+        let u_sq = cos2_alpha * (WGS84_A.powi(2) - wgs84_b.powi(2)) / wgs84_b.powi(2);
+        let big_a =
+            1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+        let big_b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+        let delta_sigma = big_b
+            * sin_sigma
+            * (cos_2sigma_m
+                + big_b / 4.0
+                    * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                        - big_b / 6.0
+                            * cos_2sigma_m
+                            * (-3.0 + 4.0 * sin_sigma.powi(2))
+                            * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+
+        return wgs84_b * big_a * (sigma - delta_sigma) / 1000.0;
+    }
+
+    haversine(a, b)
+}
+
+/// group the points within the radius (km) of the first point of a group
+/// return the group index of each point, the groups are in the order of the points
+pub fn cluster(points: &[Coordinate], radius: f64) -> Vec<usize> {
+    let mut centers: Vec<Coordinate> = vec![];
+    points
+        .iter()
+        .map(
+            |point| match centers.iter().position(|c| haversine(*c, *point) <= radius) {
+                Some(index) => index,
+                None => {
+                    centers.push(*point);
+                    centers.len() - 1
+                }
+            },
+        )
+        .collect()
+}
+
+#[test]
+fn test_distance() {
+    // flinders peak to buninyong, from the vincenty paper
+    let a = (-37.95103342, 144.42486789);
+    let b = (-37.65282114, 143.92649554);
+    assert!((vincenty(a, b) - 54.972271).abs() < 0.001);
+    assert!((haversine(a, b) - 54.972271).abs() < 54.972271 * 0.005);
+    assert_eq!(vincenty(a, a), 0.0);
+
+    // nearly antipodal
+    let far = vincenty((0.0, 0.0), (0.5, 179.7));
+    assert!(far > 19000.0 && far < 20100.0);
+
+    let berlin = (52.52, 13.405);
+    let potsdam = (52.39, 13.065);
+    let frankfurt = (50.11, 8.682);
+    let groups = cluster(&[berlin, frankfurt, potsdam, frankfurt], METRO_RADIUS);
+    assert_eq!(groups, vec![0, 1, 0, 1]);
+}
@@ -0,0 +1,280 @@
+use super::handle::Handle;
+use crate::config::Config;
+use anyhow::{bail, Context, Result};
+use reqwest::{header, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// the timeout of connecting and of the whole request
+/// the file download only limits the connecting
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// the min interval of the progress events
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// `direct` tries the mirrors without the proxy
+/// `auto` retries them through the clash core after all failed directly
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadRoute {
+    Direct,
+    Proxy,
+    Auto,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadTask {
+    /// the id in the progress events
+    pub id: String,
+
+    /// the mirrors tried in order
+    pub urls: Vec<String>,
+
+    pub path: PathBuf,
+
+    /// the hex sha256 of the file
+    pub sha256: Option<String>,
+
+    pub route: DownloadRoute,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub id: String,
+    pub url: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+/// download the file from the mirrors
+/// the partial file `{path}.part` is resumed if the server supports the range
+pub async fn download(task: &DownloadTask) -> Result<()> {
+    let part = part_path(&task.path);
+    let mut last_err = None;
+
+    for proxy in routes(task.route) {
+        let client = client(proxy.as_deref(), None)?;
+
+        for url in task.urls.iter() {
+            let result = async {
+                download_part(&client, url, &part, task).await?;
+                verify_file(&part, task.sha256.as_deref())
+            };
+
+            match result.await {
+                Ok(_) => {
+                    fs::rename(&part, &task.path).await?;
+                    return Ok(());
+                }
+                Err(err) => {
+                    let via = proxy.as_deref().unwrap_or("direct");
+                    log::warn!(target: "app", "failed to download `{url}` via {via}, {err}");
+                    last_err = Some(err);
+                }
+            }
+        }
+    }
+
+    match last_err {
+        Some(err) => Err(err),
+        None => bail!("no download url"),
+    }
+}
+
+/// fetch the json from the mirrors
+pub async fn fetch_json<T: DeserializeOwned>(urls: &[String], route: DownloadRoute) -> Result<T> {
+    let mut last_err = None;
+
+    for proxy in routes(route) {
+        let client = client(proxy.as_deref(), Some(REQUEST_TIMEOUT))?;
+
+        for url in urls.iter() {
+            let result = async {
+                let resp = client.get(url).send().await?.error_for_status()?;
+                Ok::<T, anyhow::Error>(resp.json::<T>().await?)
+            };
+
+            match result.await {
+                Ok(data) => return Ok(data),
+                Err(err) => {
+                    let via = proxy.as_deref().unwrap_or("direct");
+                    log::warn!(target: "app", "failed to fetch `{url}` via {via}, {err}");
+                    last_err = Some(err);
+                }
+            }
+        }
+    }
+
+    match last_err {
+        Some(err) => Err(err),
+        None => bail!("no fetch url"),
+    }
+}
+
+/// the local mixed port of the clash core
+pub fn local_proxy() -> String {
+    let port = { Config::clash().latest().get_mixed_port() };
+    format!("http://127.0.0.1:{port}")
+}
+
+fn routes(route: DownloadRoute) -> Vec<Option<String>> {
+    match route {
+        DownloadRoute::Direct => vec![None],
+        DownloadRoute::Proxy => vec![Some(local_proxy())],
+        DownloadRoute::Auto => vec![None, Some(local_proxy())],
+    }
+}
+
+fn client(proxy: Option<&str>, timeout: Option<Duration>) -> Result<reqwest::Client> {
+    let version = crate::utils::app_state::AppState::global().version();
+
+    let mut builder = reqwest::ClientBuilder::new()
+        .no_proxy()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .user_agent(format!("hiddify-desktop/{version}"));
+
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+async fn download_part(
+    client: &reqwest::Client,
+    url: &str,
+    part: &Path,
+    task: &DownloadTask,
+) -> Result<()> {
+    let offset = match fs::metadata(part).await {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(header::RANGE, format!("bytes={offset}-"));
+    }
+    let mut resp = request.send().await?;
+
+    // the partial file is already complete
+    if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return Ok(());
+    }
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("failed to download with status {status}");
+    }
+
+    // it starts over if the server ignores the range
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { offset } else { 0 };
+    let total = resp.content_length().map(|len| len + downloaded);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part)
+        .await?;
+
+    let mut last_emit = Instant::now();
+    while let Some(chunk) = resp.chunk().await? {
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            emit_progress(task, url, downloaded, total);
+        }
+    }
+    file.flush().await?;
+    emit_progress(task, url, downloaded, total);
+
+    if let Some(total) = total {
+        if downloaded < total {
+            bail!("the download is incomplete, {downloaded}/{total} bytes");
+        }
+    }
+    Ok(())
+}
+
+fn emit_progress(task: &DownloadTask, url: &str, downloaded: u64, total: Option<u64>) {
+    Handle::download_progress(DownloadProgress {
+        id: task.id.clone(),
+        url: url.into(),
+        downloaded,
+        total,
+    });
+}
+
+/// the broken file is removed so the next mirror starts over
+fn verify_file(path: &Path, sha256: Option<&str>) -> Result<()> {
+    let expected = match sha256 {
+        Some(expected) => expected.trim().to_lowercase(),
+        None => return Ok(()),
+    };
+
+    let actual = file_sha256(path)?;
+    if actual != expected {
+        let _ = std::fs::remove_file(path);
+        bail!("the checksum mismatched, expected {expected} but got {actual}");
+    }
+    Ok(())
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open the file \"{}\"", path.display()))?;
+
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[test]
+fn test_verify_file() {
+    let path = std::env::temp_dir().join(format!("downloader-{}", std::process::id()));
+    std::fs::write(&path, b"abc").unwrap();
+
+    let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    assert!(verify_file(&path, None).is_ok());
+    assert!(verify_file(&path, Some(&sha256.to_uppercase())).is_ok());
+    assert!(path.exists());
+
+    assert!(verify_file(&path, Some("00")).is_err());
+    assert!(!path.exists());
+
+    assert_eq!(
+        part_path(Path::new("a/b.dat")),
+        PathBuf::from("a/b.dat.part")
+    );
+}
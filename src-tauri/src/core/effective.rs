@@ -0,0 +1,137 @@
+use super::clash_api;
+use crate::config::{Config, RUNTIME_CONFIG};
+use crate::utils::dirs;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_yaml::Mapping;
+use std::fs;
+
+/// the fields of `/configs` compared with the runtime config
+const EFFECTIVE_FIELDS: [&str; 10] = [
+    "mode",
+    "mixed-port",
+    "port",
+    "socks-port",
+    "redir-port",
+    "tproxy-port",
+    "allow-lan",
+    "bind-address",
+    "ipv6",
+    "log-level",
+];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FieldMismatch {
+    pub field: String,
+
+    /// the value in the runtime config
+    pub expected: Value,
+
+    /// the value reported by the core
+    pub actual: Value,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct EffectiveConfig {
+    /// the yaml handed to the core after the profile, the merge and the overrides
+    pub runtime: String,
+
+    /// the keys appeared in the chain, not all of them take effect
+    pub exists_keys: Vec<String>,
+
+    /// `GET /configs`, none if the core is not reachable
+    pub live: Option<Value>,
+
+    pub live_error: Option<String>,
+
+    /// the fields the core is not running with
+    pub mismatches: Vec<FieldMismatch>,
+}
+
+/// the runtime config and the live state of the core
+pub async fn get_effective_config() -> Result<EffectiveConfig> {
+    let (config, exists_keys) = {
+        let runtime = Config::runtime();
+        let runtime = runtime.latest();
+        (runtime.config.clone(), runtime.exists_keys.clone())
+    };
+
+    let runtime = read_runtime_file().or_else(|err| {
+        log::warn!(target: "app", "{err}");
+        let config = config.clone().unwrap_or_default();
+        serde_yaml::to_string(&config).context("failed to convert config to yaml")
+    })?;
+
+    let mut effective = EffectiveConfig {
+        runtime,
+        exists_keys,
+        ..EffectiveConfig::default()
+    };
+
+    match clash_api::get_configs().await {
+        Ok(live) => {
+            effective.mismatches = compare_fields(&config.unwrap_or_default(), &live);
+            effective.live = Some(live);
+        }
+        Err(err) => effective.live_error = Some(format!("{err:#}")),
+    }
+
+    Ok(effective)
+}
+
+/// the file read by the core
+fn read_runtime_file() -> Result<String> {
+    let path = dirs::app_home_dir()?.join(RUNTIME_CONFIG);
+    fs::read_to_string(&path).context(format!("failed to read the file \"{}\"", path.display()))
+}
+
+/// the unset fields are skipped, since the core reports the defaults for them
+fn compare_fields(config: &Mapping, live: &Value) -> Vec<FieldMismatch> {
+    EFFECTIVE_FIELDS
+        .iter()
+        .filter_map(|field| {
+            let expected = serde_json::to_value(config.get(*field)?).ok()?;
+            let actual = live.get(*field).cloned().unwrap_or(Value::Null);
+
+            match same_value(&expected, &actual) {
+                true => None,
+                false => Some(FieldMismatch {
+                    field: field.to_string(),
+                    expected,
+                    actual,
+                }),
+            }
+        })
+        .collect()
+}
+
+/// the mode is reported as `Rule` by some cores, and the ports may be strings in the yaml
+fn same_value(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::String(a), Value::String(b)) => a.eq_ignore_ascii_case(b),
+        (Value::String(a), Value::Number(b)) => a.trim() == b.to_string(),
+        _ => expected == actual,
+    }
+}
+
+#[test]
+fn test_compare_fields() {
+    let config = serde_yaml::from_str::<Mapping>(
+        "mode: rule\nmixed-port: '7890'\nallow-lan: true\nlog-level: debug\nproxies: []",
+    )
+    .unwrap();
+    let live = serde_json::json!({
+        "mode": "Rule",
+        "mixed-port": 7890,
+        "port": 0,
+        "allow-lan": false,
+        "log-level": "debug",
+    });
+
+    let mismatches = compare_fields(&config, &live);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].field, "allow-lan");
+    assert_eq!(mismatches[0].expected, Value::Bool(true));
+    assert_eq!(mismatches[0].actual, Value::Bool(false));
+}
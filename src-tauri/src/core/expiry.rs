@@ -0,0 +1,149 @@
+use super::notification::{self, NotifyTrigger};
+use crate::config::{Config, PrfItem};
+use anyhow::Result;
+use chrono::Local;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+const DEFAULT_REMINDER_DAYS: u32 = 3;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExpiringProfile {
+    pub uid: String,
+    pub name: String,
+
+    /// unix timestamp in seconds
+    pub expire: usize,
+
+    /// negative if expired
+    pub days_left: i64,
+}
+
+pub struct ExpiryReminder {
+    /// the uid and the day (unix timestamp / 86400) of the last reminder
+    /// remind each profile once a day
+    reminded: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl ExpiryReminder {
+    pub fn global() -> &'static ExpiryReminder {
+        static REMINDER: OnceCell<ExpiryReminder> = OnceCell::new();
+
+        REMINDER.get_or_init(|| ExpiryReminder {
+            reminded: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// launch the reminder loop
+    pub fn init(&'static self) -> Result<()> {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                self.check();
+                sleep(CHECK_INTERVAL).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// remind the profiles expiring within the days of `expire_reminder_days`
+    pub fn check(&self) {
+        let today = Local::now().timestamp() / 86400;
+        let profiles = expiring_profiles(reminder_days());
+        let mut reminded = self.reminded.lock();
+
+        for profile in profiles {
+            if reminded.get(&profile.uid) == Some(&today) {
+                continue;
+            }
+            reminded.insert(profile.uid.clone(), today);
+
+            let body = match profile.days_left {
+                days if days < 0 => "The subscription has expired".into(),
+                0 => "The subscription expires today".into(),
+                days => format!("The subscription expires in {days} days"),
+            };
+            notification::notify(NotifyTrigger::Subscription, &profile.name, &body);
+        }
+    }
+}
+
+/// the days before the expiry to remind
+pub fn reminder_days() -> u32 {
+    let days = { Config::verge().latest().expire_reminder_days };
+    days.unwrap_or(DEFAULT_REMINDER_DAYS)
+}
+
+/// the profiles expiring within the days, including the expired ones
+/// sorted by the expire time
+pub fn expiring_profiles(days: u32) -> Vec<ExpiringProfile> {
+    let now = Local::now().timestamp();
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+
+    let mut list = profiles.get_items().map_or(vec![], |items| {
+        items
+            .iter()
+            .filter_map(|item| {
+                let expire = profile_expire(item)?;
+                let days_left = days_left(expire, now);
+                if days_left > days as i64 {
+                    return None;
+                }
+
+                Some(ExpiringProfile {
+                    uid: item.uid.clone()?,
+                    name: item.name.clone().unwrap_or_default(),
+                    expire,
+                    days_left,
+                })
+            })
+            .collect::<Vec<ExpiringProfile>>()
+    });
+
+    list.sort_by_key(|profile| profile.expire);
+    list
+}
+
+/// the expire time set in the profile option wins over the `subscription-userinfo`
+fn profile_expire(item: &PrfItem) -> Option<usize> {
+    let option = item.option.as_ref().and_then(|option| option.expire);
+    let extra = item.extra.as_ref().map(|extra| extra.expire);
+
+    option.or(extra).filter(|expire| *expire > 0)
+}
+
+/// the whole days left, rounded down
+fn days_left(expire: usize, now: i64) -> i64 {
+    (expire as i64 - now).div_euclid(86400)
+}
+
+#[test]
+fn test_days_left() {
+    let now = 1_700_000_000;
+    assert_eq!(days_left(now as usize + 86400 * 3, now), 3);
+    assert_eq!(days_left(now as usize + 86400 * 3 - 1, now), 2);
+    assert_eq!(days_left(now as usize + 10, now), 0);
+    assert_eq!(days_left(now as usize - 10, now), -1);
+
+    let mut item = PrfItem::default();
+    assert_eq!(profile_expire(&item), None);
+
+    item.extra = Some(crate::config::PrfExtra {
+        expire: 100,
+        ..Default::default()
+    });
+    assert_eq!(profile_expire(&item), Some(100));
+
+    item.option = Some(crate::config::PrfOption {
+        expire: Some(200),
+        ..Default::default()
+    });
+    assert_eq!(profile_expire(&item), Some(200));
+}
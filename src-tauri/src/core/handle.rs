@@ -1,3 +1,8 @@
+use super::clipboard::ClipboardOffer;
+use super::downloader::DownloadProgress;
+use super::link_import::LinkImport;
+use super::log_alert::LogAlert;
+use super::state_events::{ProfilesDelta, ProxyDelta, StateEvents, VergeDelta};
 use super::tray::Tray;
 use crate::log_err;
 use anyhow::{bail, Result};
@@ -41,13 +46,14 @@ impl Handle {
         if let Some(window) = Self::global().get_window() {
             log_err!(window.emit("verge://refresh-verge-config", "yes"));
         }
+        StateEvents::global().verge_changed();
     }
 
-    #[allow(unused)]
     pub fn refresh_profiles() {
         if let Some(window) = Self::global().get_window() {
             log_err!(window.emit("verge://refresh-profiles-config", "yes"));
         }
+        StateEvents::global().profiles_changed();
     }
 
     pub fn notice_message<S: Into<String>, M: Into<String>>(status: S, msg: M) {
@@ -56,6 +62,62 @@ impl Handle {
         }
     }
 
+    /// `downloading` or `installed` or `error`
+    pub fn update_status<S: Into<String>>(status: S) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://update-status", status.into()));
+        }
+    }
+
+    pub fn download_progress(progress: DownloadProgress) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://download-progress", progress));
+        }
+    }
+
+    /// the share links or the subscription found in the clipboard
+    pub fn clipboard_offer(offer: ClipboardOffer) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://clipboard-offer", offer));
+        }
+    }
+
+    /// the deep link waiting for the confirmation
+    /// not sent before the window is created, the window reads them by `get_deep_link_imports`
+    pub fn deep_link_import(import: LinkImport) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://deep-link-import", import));
+        }
+    }
+
+    /// a rule of the core logs is triggered
+    pub fn log_alert(alert: LogAlert) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://log-alert", alert));
+        }
+    }
+
+    /// the changed items of the profiles after `subscribe_state`
+    pub fn profiles_delta(delta: ProfilesDelta) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://state-profiles", delta));
+        }
+    }
+
+    /// the changed fields of the verge config after `subscribe_state`
+    pub fn verge_delta(delta: VergeDelta) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://state-verge", delta));
+        }
+    }
+
+    /// the node selected by the app after `subscribe_state`
+    pub fn proxy_delta(delta: ProxyDelta) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://state-proxy", delta));
+        }
+    }
+
     pub fn update_systray() -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
         if app_handle.is_none() {
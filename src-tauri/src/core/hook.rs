@@ -0,0 +1,268 @@
+use crate::config::{Config, IVergeHook};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+/// stop the command or the webhook after the duration
+const HOOK_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookEvent {
+    CoreStarted,
+    CoreCrashed,
+    ProfileSwitched,
+    QuotaExceeded,
+    LogAlert,
+}
+
+impl HookEvent {
+    /// the same as `IVergeHook.event`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CoreStarted => "core_started",
+            Self::CoreCrashed => "core_crashed",
+            Self::ProfileSwitched => "profile_switched",
+            Self::QuotaExceeded => "quota_exceeded",
+            Self::LogAlert => "log_alert",
+        }
+    }
+
+    fn parse(event: &str) -> Option<Self> {
+        [
+            Self::CoreStarted,
+            Self::CoreCrashed,
+            Self::ProfileSwitched,
+            Self::QuotaExceeded,
+            Self::LogAlert,
+        ]
+        .into_iter()
+        .find(|e| e.as_str() == event)
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct HookResult {
+    /// the rendered body of the webhook
+    pub payload: Option<String>,
+
+    /// the variables passed to the command as `HIDDIFY_*`
+    pub variables: BTreeMap<String, String>,
+
+    /// false for the dry run
+    pub executed: bool,
+
+    /// the stdout of the command and the response of the webhook
+    pub output: Option<String>,
+
+    pub error: Option<String>,
+}
+
+/// run the enabled hooks of the event in the background
+pub fn fire(event: HookEvent, vars: Vec<(&str, String)>) {
+    let hooks = { Config::verge().latest().hooks.clone() };
+    let hooks = hooks
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|hook| hook.event == event.as_str() && hook.enable.unwrap_or(true))
+        .collect::<Vec<IVergeHook>>();
+    if hooks.is_empty() {
+        return;
+    }
+
+    let vars = variables(event, vars);
+    tauri::async_runtime::spawn(async move {
+        for hook in hooks.iter() {
+            if let Some(err) = run(hook, &vars).await.error {
+                log::error!(target: "app", "failed to run the hook of {}, {err}", event.as_str());
+            }
+        }
+    });
+}
+
+/// fire `profile_switched` with the current profile
+pub fn fire_profile_switched() {
+    let (uid, name) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let uid = profiles.get_current().unwrap_or_default();
+        let name = profiles
+            .get_item(&uid)
+            .ok()
+            .and_then(|item| item.name.clone())
+            .unwrap_or_default();
+        (uid, name)
+    };
+
+    let message = format!("The profile is switched to {name}");
+    fire(
+        HookEvent::ProfileSwitched,
+        vec![("profile", name), ("uid", uid), ("message", message)],
+    );
+}
+
+/// run the hook with the sample variables
+/// the dry run only renders the payload
+pub async fn test(hook: IVergeHook, dry_run: bool) -> Result<HookResult> {
+    let event = match HookEvent::parse(&hook.event) {
+        Some(event) => event,
+        None => bail!("invalid hook event \"{}\"", hook.event),
+    };
+
+    let vars = variables(
+        event,
+        vec![
+            ("profile", "Sample Profile".into()),
+            ("message", "This is a test of the hook".into()),
+        ],
+    );
+
+    match dry_run {
+        true => Ok(HookResult {
+            payload: hook.webhook.as_ref().map(|_| render_payload(&hook, &vars)),
+            variables: vars,
+            ..HookResult::default()
+        }),
+        false => Ok(run(&hook, &vars).await),
+    }
+}
+
+/// the `event` and the `time` are always set
+fn variables(event: HookEvent, vars: Vec<(&str, String)>) -> BTreeMap<String, String> {
+    let mut map = BTreeMap::new();
+    map.insert("event".into(), event.as_str().into());
+    map.insert("time".into(), chrono::Local::now().to_rfc3339());
+    for (key, value) in vars.into_iter() {
+        map.insert(key.into(), value);
+    }
+    map
+}
+
+async fn run(hook: &IVergeHook, vars: &BTreeMap<String, String>) -> HookResult {
+    let payload = hook.webhook.as_ref().map(|_| render_payload(hook, vars));
+    let mut result = HookResult {
+        payload: payload.clone(),
+        variables: vars.clone(),
+        executed: true,
+        ..HookResult::default()
+    };
+
+    let command = hook.command.as_ref().filter(|c| !c.trim().is_empty());
+    let webhook = hook.webhook.as_ref().filter(|w| !w.trim().is_empty());
+
+    let outcome = async {
+        if command.is_none() && webhook.is_none() {
+            bail!("the hook has no command or webhook");
+        }
+
+        let mut output = vec![];
+        if let Some(command) = command {
+            output.push(run_command(command, vars).await?);
+        }
+        if let Some(url) = webhook {
+            output.push(post_webhook(url, payload.unwrap_or_default()).await?);
+        }
+        Ok(output.join("\n"))
+    };
+
+    match timeout(HOOK_TIMEOUT, outcome).await {
+        Ok(Ok(output)) => result.output = Some(output),
+        Ok(Err(err)) => result.error = Some(err.to_string()),
+        Err(_) => result.error = Some("timeout".into()),
+    }
+    result
+}
+
+/// the variables are passed as the env instead of the command line
+/// since the profile names come from the subscriptions
+async fn run_command(command: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command).creation_flags(CREATE_NO_WINDOW);
+        cmd
+    };
+
+    for (key, value) in vars.iter() {
+        cmd.env(format!("HIDDIFY_{}", key.to_uppercase()), value);
+    }
+
+    let output = cmd.kill_on_drop(true).output().await?;
+    if !output.status.success() {
+        let status = output.status;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("the command exited with {status}, {}", stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+async fn post_webhook(url: &str, payload: String) -> Result<String> {
+    let mut request = reqwest::Client::new().post(url).timeout(HOOK_TIMEOUT);
+    if serde_json::from_str::<serde_json::Value>(&payload).is_ok() {
+        request = request.header(reqwest::header::CONTENT_TYPE, "application/json");
+    }
+
+    let resp = request.body(payload).send().await?;
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        bail!("the webhook responded with status \"{status}\"");
+    }
+    Ok(body)
+}
+
+/// replace the `{{name}}` in the template, the values are escaped as the json strings
+/// the default payload is the json of all the variables
+fn render_payload(hook: &IVergeHook, vars: &BTreeMap<String, String>) -> String {
+    let template = match hook.payload.as_ref().filter(|p| !p.trim().is_empty()) {
+        Some(template) => template,
+        None => return serde_json::to_string(vars).unwrap_or_default(),
+    };
+
+    vars.iter().fold(template.clone(), |payload, (key, value)| {
+        let escaped = serde_json::to_string(value).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len() - 1];
+        payload.replace(&format!("{{{{{key}}}}}"), escaped)
+    })
+}
+
+#[test]
+fn test_render_payload() {
+    let mut vars = BTreeMap::new();
+    vars.insert("event".into(), "profile_switched".into());
+    vars.insert("profile".into(), "my \"home\"".into());
+
+    let mut hook = IVergeHook {
+        event: "profile_switched".into(),
+        webhook: Some("http://127.0.0.1:8123/api/webhook/x".into()),
+        ..IVergeHook::default()
+    };
+    assert_eq!(
+        render_payload(&hook, &vars),
+        r#"{"event":"profile_switched","profile":"my \"home\""}"#
+    );
+
+    hook.payload = Some(r#"{"text":"{{event}}: {{profile}} {{missing}}"}"#.into());
+    assert_eq!(
+        render_payload(&hook, &vars),
+        r#"{"text":"profile_switched: my \"home\" {{missing}}"}"#
+    );
+
+    assert_eq!(
+        HookEvent::parse("quota_exceeded"),
+        Some(HookEvent::QuotaExceeded)
+    );
+    assert_eq!(HookEvent::parse("failover"), None);
+}
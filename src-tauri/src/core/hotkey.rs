@@ -86,6 +86,7 @@ impl Hotkey {
             "toggle_tun_mode" => || feat::toggle_tun_mode(),
             "enable_tun_mode" => || feat::enable_tun_mode(),
             "disable_tun_mode" => || feat::disable_tun_mode(),
+            "switch_to_previous_node" => || feat::switch_to_previous_node(),
 
             _ => bail!("invalid function \"{func}\""),
         };
@@ -0,0 +1,142 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::api::file::{ArchiveFormat, Extract};
+use tauri::AppHandle;
+
+/// the dir the archive is extracted into
+/// next to the installed app, so the new one is moved in by renaming
+const STAGING_DIR: &str = ".hiddify-update";
+
+/// install the verified update archive of the tauri bundler
+/// `.AppImage.tar.gz` on linux, `.app.tar.gz` on macos and `.msi.zip` on windows
+pub fn install(app_handle: &AppHandle, archive: &Path) -> Result<()> {
+    let target = install_path(app_handle)?;
+    let staging = match target.parent() {
+        Some(dir) if !cfg!(target_os = "windows") => dir.join(STAGING_DIR),
+        _ => std::env::temp_dir().join(STAGING_DIR),
+    };
+
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging)?;
+
+    let result = extract(archive, &staging).and_then(|_| replace(app_handle, &staging, &target));
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn extract(archive: &Path, into: &Path) -> Result<()> {
+    #[cfg(target_os = "windows")]
+    let format = ArchiveFormat::Zip;
+    #[cfg(not(target_os = "windows"))]
+    let format = ArchiveFormat::Tar(Some(tauri::api::file::Compression::Gz));
+
+    Extract::from_cursor(fs::File::open(archive)?, format).extract_into(into)?;
+    Ok(())
+}
+
+/// the file or the bundle replaced by the update
+#[allow(unused_variables)]
+fn install_path(app_handle: &AppHandle) -> Result<PathBuf> {
+    let exe = tauri::utils::platform::current_exe()?;
+
+    // `/Applications/Hiddify.app/Contents/MacOS/hiddify-desktop`
+    #[cfg(target_os = "macos")]
+    return exe
+        .ancestors()
+        .nth(3)
+        .filter(|path| path.extension().is_some_and(|ext| ext == "app"))
+        .map(PathBuf::from)
+        .ok_or(anyhow::anyhow!("the app is not in a bundle"));
+
+    // only the AppImage can be replaced
+    #[cfg(target_os = "linux")]
+    return app_handle
+        .env()
+        .appimage
+        .map(PathBuf::from)
+        .ok_or(anyhow::anyhow!(
+            "the update can only be installed into the AppImage"
+        ));
+
+    #[cfg(target_os = "windows")]
+    return Ok(exe);
+}
+
+/// the first file or dir with the extension in the extracted archive
+fn find_extracted(dir: &Path, extension: &str) -> Result<PathBuf> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == extension) {
+            return Ok(path);
+        }
+    }
+    bail!("no .{extension} found in the update")
+}
+
+/// the current one is put back if the new one failed to move in
+#[cfg(target_os = "linux")]
+fn replace(_: &AppHandle, staging: &Path, target: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tauri::api::file::Move;
+
+    let app_image = find_extracted(staging, "AppImage")?;
+    fs::set_permissions(&app_image, fs::Permissions::from_mode(0o755))?;
+
+    let backup = staging.join("current.AppImage");
+    Move::from_source(&app_image)
+        .replace_using_temp(&backup)
+        .to_dest(target)?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn replace(_: &AppHandle, staging: &Path, target: &Path) -> Result<()> {
+    use tauri::api::file::Move;
+
+    let bundle = find_extracted(staging, "app")?;
+
+    let backup = staging.join("current.app");
+    Move::from_source(&bundle)
+        .replace_using_temp(&backup)
+        .to_dest(target)?;
+
+    let _ = std::process::Command::new("touch").arg(target).status();
+    Ok(())
+}
+
+/// run the installer, which relaunches the app after it finishes
+#[cfg(target_os = "windows")]
+fn replace(app_handle: &AppHandle, staging: &Path, target: &Path) -> Result<()> {
+    use std::process::Command;
+
+    // the staging dir is removed while the installer runs
+    let extracted = find_extracted(staging, "exe").or(find_extracted(staging, "msi"))?;
+    let installer = std::env::temp_dir().join(extracted.file_name().unwrap_or_default());
+    fs::rename(&extracted, &installer)?;
+
+    if installer.extension().is_some_and(|ext| ext == "exe") {
+        Command::new(installer).spawn()?;
+        return Ok(());
+    }
+
+    let args = {
+        let config = app_handle.config();
+        config.tauri.updater.windows.install_mode.msiexec_args()
+    };
+    let root = std::env::var("SYSTEMROOT").unwrap_or("C:\\Windows".into());
+    let powershell = format!("{root}\\System32\\WindowsPowerShell\\v1.0\\powershell.exe");
+
+    // the same as the tauri updater
+    Command::new(powershell)
+        .args(["-NoProfile", "-windowstyle", "hidden"])
+        .args(["Start-Process", "-Wait", "-FilePath"])
+        .arg("$env:SYSTEMROOT\\System32\\msiexec.exe")
+        .args(["-ArgumentList", "/i,"])
+        .arg(format!("\"\"\"{}\"\"\"", installer.display()))
+        .arg(format!(", {}, /promptrestart;", args.join(", ")))
+        .arg("Start-Process")
+        .arg(format!("\"{}\"", target.display()))
+        .spawn()?;
+    Ok(())
+}
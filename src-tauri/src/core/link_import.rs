@@ -0,0 +1,154 @@
+use super::handle::Handle;
+use crate::config::{normalize_profile_url, Config, PrfItem};
+use crate::feat;
+use crate::utils::help;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// the unanswered prompts are dropped after
+const PENDING_TTL: Duration = Duration::from_secs(600);
+
+/// the links opened over it are refused until the user answers
+const MAX_PENDING: usize = 5;
+
+/// the profile of a deep link, not downloaded until confirmed
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct LinkImport {
+    pub id: String,
+
+    /// the name in the link, or the one of the profile after the download
+    pub name: Option<String>,
+
+    pub url: String,
+
+    /// the origin shown to the user
+    pub host: String,
+}
+
+/// the deep links waiting for the user
+pub struct LinkImports {
+    pending: Arc<Mutex<Vec<(LinkImport, Instant)>>>,
+}
+
+impl LinkImports {
+    pub fn global() -> &'static LinkImports {
+        static IMPORTS: OnceCell<LinkImports> = OnceCell::new();
+
+        IMPORTS.get_or_init(|| LinkImports {
+            pending: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    /// check the url of `clash://install-config?url=...&name=...`
+    /// and ask the user to confirm with `verge://deep-link-import`
+    /// kept in the pending for the window created after the link was opened
+    pub fn prepare(&self, link: &str) -> Result<LinkImport> {
+        let (url, name) = parse_link(link)?;
+        let url = normalize_profile_url(&url)?;
+        let host = Url::parse(&url)?.host_str().unwrap_or_default().to_string();
+
+        let import = {
+            let mut pending = self.pending.lock();
+            pending.retain(|(_, time)| time.elapsed() < PENDING_TTL);
+
+            // the same link opened again is asked once
+            match pending.iter().find(|(import, _)| import.url == url) {
+                Some((import, _)) => import.clone(),
+                None if pending.len() >= MAX_PENDING => {
+                    bail!("too many deep links are waiting for the confirmation")
+                }
+                None => {
+                    let import = LinkImport {
+                        id: help::get_uid("l"),
+                        name,
+                        url,
+                        host,
+                    };
+                    pending.push((import.clone(), Instant::now()));
+                    import
+                }
+            }
+        };
+        log::info!(target: "app", "the deep link of \"{}\" is waiting for the confirmation", import.host);
+
+        Handle::deep_link_import(import.clone());
+        Ok(import)
+    }
+
+    pub fn get_pending(&self) -> Vec<LinkImport> {
+        let mut pending = self.pending.lock();
+        pending.retain(|(_, time)| time.elapsed() < PENDING_TTL);
+        pending.iter().map(|(import, _)| import.clone()).collect()
+    }
+
+    /// download, import and activate the profile if accepted, drop it otherwise
+    pub async fn confirm(&self, id: &str, accept: bool) -> Result<()> {
+        let import = {
+            let mut pending = self.pending.lock();
+            pending.retain(|(_, time)| time.elapsed() < PENDING_TTL);
+            match pending.iter().position(|(import, _)| import.id == id) {
+                Some(index) => pending.remove(index).0,
+                None => bail!("the deep link import \"{id}\" is expired"),
+            }
+        };
+        if !accept {
+            return Ok(());
+        }
+
+        let item = PrfItem::from_url(&import.url, import.name, None, None).await?;
+        let uid = item.uid.clone().unwrap_or_default();
+        {
+            let tx = Config::profiles().begin().await;
+            tx.draft().append_item(item)?;
+            tx.commit()?;
+        }
+        feat::switch_profile(uid).await
+    }
+}
+
+/// the url and the name of the link
+fn parse_link(link: &str) -> Result<(String, Option<String>)> {
+    let link = Url::parse(link.trim())?;
+    if link.host_str() != Some("install-config") {
+        bail!("the deep link is not `install-config`");
+    }
+
+    let param = |key: &str| {
+        link.query_pairs()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    match param("url") {
+        Some(url) => Ok((url, param("name"))),
+        None => bail!("the deep link has no url"),
+    }
+}
+
+#[test]
+fn test_parse_link() {
+    let (url, name) = parse_link(
+        "clash://install-config?url=https%3A%2F%2Fsub.example.com%2Fs%3Ftoken%3D1&name=My%20Sub",
+    )
+    .unwrap();
+    assert_eq!(url, "https://sub.example.com/s?token=1");
+    assert_eq!(name.as_deref(), Some("My Sub"));
+
+    let (url, name) = parse_link("clash://install-config?url=https://a.com/all.yml").unwrap();
+    assert_eq!(url, "https://a.com/all.yml");
+    assert_eq!(name, None);
+
+    let (url, name) =
+        parse_link("clashy://install-config?url=https://a.com/x/clash/all.yml&name=all_a.com")
+            .unwrap();
+    assert_eq!(url, "https://a.com/x/clash/all.yml");
+    assert_eq!(name.as_deref(), Some("all_a.com"));
+
+    assert!(parse_link("clash://install-config?name=x").is_err());
+    assert!(parse_link("clash://other?url=https://a.com").is_err());
+}
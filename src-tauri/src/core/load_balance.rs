@@ -0,0 +1,109 @@
+use super::measure::MeasureInfo;
+use super::measure_cache::MeasureCache;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// the default of the most members
+pub const DEFAULT_SIZE: usize = 5;
+
+/// the core balances the members evenly,
+/// so the nodes slower than this ratio of the fastest one are left out
+const MIN_RATIO: f64 = 0.25;
+
+const STRATEGIES: [&str; 3] = ["round-robin", "consistent-hashing", "sticky-sessions"];
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct BalanceMember {
+    pub name: String,
+
+    /// the median download speed (bytes/s)
+    pub download: u64,
+
+    /// the share of the total throughput, 0 - 100
+    pub weight: f64,
+}
+
+pub fn check_strategy(strategy: &str) -> Result<()> {
+    if !STRATEGIES.contains(&strategy) {
+        bail!("invalid load balance strategy \"{strategy}\"");
+    }
+    Ok(())
+}
+
+/// the members by the measurements of the current profile
+pub fn current_members(size: usize) -> Vec<BalanceMember> {
+    let current = { Config::profiles().latest().get_current() };
+    match current {
+        Some(profile) => balance_members(&MeasureCache::global().get(&profile), size),
+        None => vec![],
+    }
+}
+
+/// the fastest nodes by the download speed, up to the size
+pub fn balance_members(infos: &[MeasureInfo], size: usize) -> Vec<BalanceMember> {
+    let mut measured = infos
+        .iter()
+        .filter(|info| info.error.is_none())
+        .filter_map(|info| Some((info.name.clone(), info.download.as_ref()?.median)))
+        .filter(|(_, download)| *download > 0)
+        .collect::<Vec<_>>();
+    measured.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let fastest = match measured.first() {
+        Some((_, download)) => *download as f64,
+        None => return vec![],
+    };
+    measured.retain(|(_, download)| *download as f64 >= fastest * MIN_RATIO);
+    measured.truncate(size.max(1));
+
+    let total = measured.iter().map(|(_, d)| *d as f64).sum::<f64>();
+    measured
+        .into_iter()
+        .map(|(name, download)| BalanceMember {
+            name,
+            download,
+            weight: (download as f64 / total * 10000.0).round() / 100.0,
+        })
+        .collect()
+}
+
+#[test]
+fn test_balance_members() {
+    use super::measure::MeasureStats;
+
+    let info = |name: &str, download: Option<u64>| MeasureInfo {
+        name: name.into(),
+        download: download.map(|median| MeasureStats {
+            median,
+            p90: median,
+            std_dev: 0.0,
+        }),
+        ..MeasureInfo::default()
+    };
+    let infos = vec![
+        info("a", Some(1_000_000)),
+        info("b", Some(3_000_000)),
+        info("c", Some(800_000)),
+        info("slow", Some(100_000)),
+        info("latency only", None),
+        MeasureInfo {
+            error: Some("timeout".into()),
+            ..info("failed", Some(9_000_000))
+        },
+    ];
+
+    let members = balance_members(&infos, 5);
+    let names = members.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["b", "a", "c"]);
+    assert_eq!(members[0].weight, 62.5);
+    assert_eq!(members[2].weight, 16.67);
+
+    let members = balance_members(&infos, 2);
+    assert_eq!(members.len(), 2);
+    assert_eq!(members[0].weight, 75.0);
+    assert!(balance_members(&infos[4..], 5).is_empty());
+
+    assert!(check_strategy("round-robin").is_ok());
+    assert!(check_strategy("least-load").is_err());
+}
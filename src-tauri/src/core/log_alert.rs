@@ -0,0 +1,197 @@
+use super::handle::Handle;
+use super::hook::{self, HookEvent};
+use super::notification::{self, NotifyTrigger};
+use crate::config::{Config, IVergeLogAlert};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::regex::Regex;
+
+/// a triggered rule, emitted as `verge://log-alert`
+#[derive(Debug, Clone, Serialize)]
+pub struct LogAlert {
+    pub name: String,
+
+    /// the matched lines in the window
+    pub count: u32,
+
+    /// the last matched line
+    pub line: String,
+
+    pub suggestion: Option<String>,
+}
+
+#[derive(Default)]
+struct RuleState {
+    hits: VecDeque<Instant>,
+
+    /// the cooldown after triggered
+    muted_until: Option<Instant>,
+}
+
+/// match the core logs against the rules of `log_alerts`
+pub struct LogAlerts {
+    /// by the rule name
+    states: Arc<Mutex<HashMap<String, RuleState>>>,
+
+    /// by the pattern, none if the regex is invalid
+    regexes: Arc<Mutex<HashMap<String, Option<Regex>>>>,
+}
+
+impl LogAlerts {
+    pub fn global() -> &'static LogAlerts {
+        static ALERTS: OnceCell<LogAlerts> = OnceCell::new();
+
+        ALERTS.get_or_init(|| LogAlerts {
+            states: Arc::new(Mutex::new(HashMap::new())),
+            regexes: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// feed a line of the core logs
+    pub fn feed(&self, line: &str) {
+        let rules = get_rules();
+        let now = Instant::now();
+
+        for rule in rules.iter().filter(|r| r.enable.unwrap_or(true)) {
+            if !self.is_match(rule, line) {
+                continue;
+            }
+
+            let count = {
+                let mut states = self.states.lock();
+                let state = states.entry(rule.name.clone()).or_default();
+                record(state, now, rule.threshold.unwrap_or(1), window(rule))
+            };
+            if let Some(count) = count {
+                alert(rule, line, count);
+            }
+        }
+    }
+
+    fn is_match(&self, rule: &IVergeLogAlert, line: &str) -> bool {
+        if !rule.regex.unwrap_or(false) {
+            return line.contains(&rule.pattern);
+        }
+
+        let mut regexes = self.regexes.lock();
+        let regex = regexes.entry(rule.pattern.clone()).or_insert_with(|| {
+            Regex::new(&rule.pattern)
+                .map_err(
+                    |err| log::warn!(target: "app", "invalid log alert \"{}\", {err}", rule.name),
+                )
+                .ok()
+        });
+        matches!(regex, Some(regex) if regex.is_match(line))
+    }
+}
+
+/// the rules of `log_alerts`, the built-in rules if not set
+pub fn get_rules() -> Vec<IVergeLogAlert> {
+    let rules = { Config::verge().latest().log_alerts.clone() };
+    rules.unwrap_or_else(default_rules)
+}
+
+fn default_rules() -> Vec<IVergeLogAlert> {
+    vec![
+        IVergeLogAlert {
+            name: "DNS resolve failed".into(),
+            pattern: r"(?i)dns resolve failed|\[DNS\].*(failed|timeout)".into(),
+            regex: Some(true),
+            threshold: Some(10),
+            window: Some(60),
+            suggestion: Some("The DNS is unstable, try another DNS preset".into()),
+            enable: Some(true),
+        },
+        IVergeLogAlert {
+            name: "Connections reset".into(),
+            pattern: "connection reset by peer".into(),
+            regex: Some(false),
+            threshold: Some(20),
+            window: Some(60),
+            suggestion: Some("The node may be filtered, try a reality or a cdn node".into()),
+            enable: Some(true),
+        },
+    ]
+}
+
+fn window(rule: &IVergeLogAlert) -> Duration {
+    Duration::from_secs(rule.window.unwrap_or(60).max(1))
+}
+
+/// the count if the threshold is reached in the window
+fn record(state: &mut RuleState, now: Instant, threshold: u32, window: Duration) -> Option<u32> {
+    if matches!(state.muted_until, Some(until) if now < until) {
+        return None;
+    }
+
+    state.hits.push_back(now);
+    while let Some(hit) = state.hits.front() {
+        match now.duration_since(*hit) > window {
+            true => state.hits.pop_front(),
+            false => break,
+        };
+    }
+
+    let count = state.hits.len() as u32;
+    if count < threshold.max(1) {
+        return None;
+    }
+
+    state.hits.clear();
+    state.muted_until = Some(now + window);
+    Some(count)
+}
+
+fn alert(rule: &IVergeLogAlert, line: &str, count: u32) {
+    log::info!(target: "app", "log alert \"{}\" is triggered", rule.name);
+
+    let body = rule
+        .suggestion
+        .clone()
+        .unwrap_or_else(|| format!("Matched {count} times: {line}"));
+    notification::notify(NotifyTrigger::LogAlert, &rule.name, &body);
+
+    hook::fire(
+        HookEvent::LogAlert,
+        vec![
+            ("rule", rule.name.clone()),
+            ("count", count.to_string()),
+            ("line", line.into()),
+            ("message", body),
+        ],
+    );
+
+    Handle::log_alert(LogAlert {
+        name: rule.name.clone(),
+        count,
+        line: line.into(),
+        suggestion: rule.suggestion.clone(),
+    });
+}
+
+#[test]
+fn test_record() {
+    let mut state = RuleState::default();
+    let start = Instant::now();
+    let window = Duration::from_secs(60);
+    let at = |secs: u64| start + Duration::from_secs(secs);
+
+    assert_eq!(record(&mut state, at(0), 3, window), None);
+    assert_eq!(record(&mut state, at(10), 3, window), None);
+    // the first hit is out of the window
+    assert_eq!(record(&mut state, at(65), 3, window), None);
+    assert_eq!(record(&mut state, at(66), 3, window), Some(3));
+    // muted in the cooldown
+    assert_eq!(record(&mut state, at(100), 1, window), None);
+    assert_eq!(record(&mut state, at(127), 1, window), Some(1));
+
+    let rules = default_rules();
+    assert!(rules.iter().all(|r| Regex::new(&r.pattern).is_ok()));
+    let dns = Regex::new(&rules[0].pattern).unwrap();
+    assert!(dns.is_match("[DNS] resolve example.com failed: timeout"));
+    assert!(!dns.is_match("[TCP] 127.0.0.1:5000 --> example.com:443 match Match"));
+}
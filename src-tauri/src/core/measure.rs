@@ -0,0 +1,1357 @@
+use super::clash_api::{self, ClashApi, HttpClashApi};
+use super::data_budget::DataBudget;
+use super::speedtest::{self, SpeedtestClient, SpeedtestServer};
+use super::{delay, measure_cache::MeasureCache, socks, trace};
+use crate::{config::Config, enhance::MEASURE_GROUP, log_err};
+use anyhow::{bail, Result};
+use futures::stream::StreamExt;
+use measure_backend::{
+    BackendSettings, ChunkSource, HttpTransport, MeasureTarget, ReqwestTransport, UploadBody,
+};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use tauri::async_runtime::Mutex as TokioMutex;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, Instant};
+
+/// the default number of runs for each proxy
+const DEFAULT_REPEAT: u32 = 3;
+
+/// the default seconds between the full tests of the same proxy
+const DEFAULT_MIN_INTERVAL: u64 = 60;
+
+/// stop the download test after the duration
+const DOWNLOAD_DURATION: Duration = Duration::from_secs(10);
+
+/// the download is saturated if the rates of the last slots are stable, 3 seconds
+const STABLE_SLOTS: usize = 6;
+
+/// the max deviation of the stable rates from their mean
+const STABLE_TOLERANCE: f64 = 0.1;
+
+/// stop the upload test after the duration
+const UPLOAD_DURATION: Duration = Duration::from_secs(10);
+
+/// the default number of the concurrent upload connections
+const DEFAULT_UPLOAD_CONNECTIONS: u32 = 4;
+
+/// the chunk streamed by the upload connections
+const UPLOAD_CHUNK: usize = 64 * 1024;
+
+/// the slot length of the download rate timeline
+const TIMELINE_SLOT: Duration = Duration::from_millis(500);
+
+/// stop the udp test after the duration
+const UDP_DURATION: Duration = Duration::from_secs(5);
+
+/// the payload size of each udp packet
+const UDP_PAYLOAD: usize = 1200;
+
+/// the max number of the udp packets waiting for the echo
+const UDP_WINDOW: usize = 32;
+
+/// the in-flight packets are taken as lost after the timeout
+const UDP_RECV_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// the browser user agents used by the measurement requests
+const BROWSER_USER_AGENTS: [&str; 3] = [
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/118.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:109.0) Gecko/20100101 Firefox/118.0",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Safari/605.1.15",
+];
+
+const BROWSER_ACCEPT: &str = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+/// the header order of the chromium
+const DEFAULT_HEADER_ORDER: [&str; 3] = ["user-agent", "accept", "accept-language"];
+
+/// the peak rate is taken from the first slots
+const THROTTLE_HEAD_SLOTS: usize = 4;
+
+/// the clamp should last at least these slots
+const THROTTLE_MIN_SLOTS: usize = 6;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MeasureMode {
+    /// latency only
+    Latency,
+    /// latency and download throughput
+    Full,
+    /// latency and udp throughput against the echo server
+    Udp,
+}
+
+/// the result of a single run
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct SpeedMeasurement {
+    /// latency (ms)
+    pub latency: Option<u64>,
+
+    /// download speed (bytes/s)
+    pub download: Option<u64>,
+
+    /// download rate of each slot (bytes/s)
+    pub timeline: Vec<u64>,
+
+    /// the clamp rate if throttling is detected (bytes/s)
+    pub throttle_rate: Option<u64>,
+
+    /// the negotiated http version of the download, such as `HTTP/2.0`
+    pub http_version: Option<String>,
+
+    /// upload speed of all the connections (bytes/s)
+    pub upload: Option<u64>,
+
+    /// the echoed udp payload rate (bytes/s)
+    pub udp: Option<u64>,
+
+    /// the ratio of the udp packets without echo
+    pub udp_loss: Option<f64>,
+
+    /// the error message if failed
+    pub error: Option<String>,
+}
+
+/// the aggregate statistics of the successful samples
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct MeasureStats {
+    pub median: u64,
+    pub p90: u64,
+    pub std_dev: f64,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct MeasureInfo {
+    /// proxy name
+    pub name: String,
+
+    /// the latency test url
+    pub test_url: Option<String>,
+
+    /// the throughput test url
+    pub download_url: Option<String>,
+
+    /// the upload test url, set if `enable_measure_upload`
+    pub upload_url: Option<String>,
+
+    /// the reason the throughput test was skipped, such as the data budget
+    pub throughput_skipped: Option<String>,
+
+    /// the speedtest server of the throughput test
+    pub server: Option<SpeedtestServer>,
+
+    /// the exit of the proxy seen by speedtest.net
+    pub client: Option<SpeedtestClient>,
+
+    /// the udp echo server
+    pub udp_server: Option<String>,
+
+    /// the results of each run, without the warm-up one
+    pub samples: Vec<SpeedMeasurement>,
+
+    /// latency statistics (ms)
+    pub latency: Option<MeasureStats>,
+
+    /// download speed statistics (bytes/s)
+    pub download: Option<MeasureStats>,
+
+    /// upload speed statistics (bytes/s)
+    pub upload: Option<MeasureStats>,
+
+    /// fast start then sustained clamp found in any run
+    pub throttle_detected: bool,
+
+    /// median of the clamp rates (bytes/s)
+    pub throttle_rate: Option<u64>,
+
+    /// udp throughput statistics (bytes/s)
+    pub udp: Option<MeasureStats>,
+
+    /// the last error message if all the runs failed
+    pub error: Option<String>,
+
+    /// measured time
+    pub updated: usize,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct MeasureCompare {
+    pub a: MeasureInfo,
+    pub b: MeasureInfo,
+
+    /// `b.latency - a.latency` (ms)
+    pub latency_delta: Option<i64>,
+
+    /// `b.jitter - a.jitter` (ms)
+    pub jitter_delta: Option<f64>,
+
+    /// `b.download - a.download` (bytes/s)
+    pub download_delta: Option<i64>,
+
+    /// `b.udp - a.udp` (bytes/s)
+    pub udp_delta: Option<i64>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct MeasureBenchmark {
+    /// the node the test url goes through
+    pub node: String,
+
+    /// the same test urls without the proxy
+    pub direct: MeasureInfo,
+
+    pub proxy: MeasureInfo,
+
+    /// the extra latency of the proxy (%)
+    pub latency_overhead: Option<f64>,
+
+    /// the lost download speed of the proxy (%)
+    pub download_overhead: Option<f64>,
+}
+
+pub struct Measure {
+    /// the hidden selector can only be used by one measurement at a time
+    lock: Arc<TokioMutex<()>>,
+
+    /// set on exit, all the running measurements stop as soon as possible
+    cancelled: Arc<AtomicBool>,
+
+    /// the start time of the last full test of each proxy
+    last_full: Arc<Mutex<HashMap<String, Instant>>>,
+
+    transport: Arc<dyn HttpTransport>,
+}
+
+impl Measure {
+    pub fn global() -> &'static Measure {
+        static MEASURE: OnceCell<Measure> = OnceCell::new();
+
+        MEASURE.get_or_init(Measure::new)
+    }
+
+    fn new() -> Measure {
+        Measure::with_transport(Arc::new(ReqwestTransport {
+            builder: transport_client,
+            http_version: || Config::verge().latest().measure_http_version.clone(),
+        }))
+    }
+
+    fn with_transport(transport: Arc<dyn HttpTransport>) -> Measure {
+        Measure {
+            lock: Arc::new(TokioMutex::new(())),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            last_full: Arc::new(Mutex::new(HashMap::new())),
+            transport,
+        }
+    }
+
+    /// stop all the measurements, used on exit
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// record the full test of the proxy
+    /// return the time to wait if the last one is within the interval
+    fn check_full_interval(&self, name: &str, interval: Duration) -> Option<Duration> {
+        let now = Instant::now();
+        let mut last_full = self.last_full.lock();
+        last_full.retain(|_, start| now.saturating_duration_since(*start) < interval);
+
+        match last_full.get(name) {
+            Some(start) => Some(interval - now.saturating_duration_since(*start)),
+            None => {
+                last_full.insert(name.into(), now);
+                None
+            }
+        }
+    }
+
+    /// the failed full test does not hold the interval, so it can be retested at once
+    fn release_full_interval(&self, name: &str) {
+        self.last_full.lock().remove(name);
+    }
+
+    /// measure a single proxy against the target of `measure_download_backend`
+    /// use the same test url for all the runs
+    pub async fn measure(&self, name: &str, mode: MeasureMode, test_url: &str) -> MeasureInfo {
+        self.measure_with(name, mode, test_url, None).await
+    }
+
+    /// the target is picked for the proxy if not given
+    async fn measure_with(
+        &self,
+        name: &str,
+        mode: MeasureMode,
+        test_url: &str,
+        target: Option<&MeasureTarget>,
+    ) -> MeasureInfo {
+        let (repeat, udp_server, min_interval, upload) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.measure_repeat,
+                verge.measure_udp_server.clone(),
+                verge.measure_min_interval,
+                verge.enable_measure_upload.unwrap_or(false),
+            )
+        };
+        let min_interval = Duration::from_secs(min_interval.unwrap_or(DEFAULT_MIN_INTERVAL));
+        let repeat = repeat.unwrap_or(DEFAULT_REPEAT).clamp(1, 10);
+
+        // downgrade to the latency only if the data budget would be exceeded
+        let budget = DataBudget::global();
+        let (mut reservation, skipped) = match mode {
+            MeasureMode::Full => match budget.reserve() {
+                Ok(reservation) => (Some(reservation), None),
+                Err(reason) => (None, Some(reason)),
+            },
+            _ => (None, None),
+        };
+        let mut mode = match skipped {
+            Some(_) => MeasureMode::Latency,
+            None => mode,
+        };
+
+        let mut info = MeasureInfo {
+            name: name.into(),
+            test_url: Some(test_url.into()),
+            udp_server: match mode {
+                MeasureMode::Udp => udp_server,
+                _ => None,
+            },
+            throughput_skipped: skipped,
+            ..MeasureInfo::default()
+        };
+
+        if mode == MeasureMode::Udp && info.udp_server.is_none() {
+            info.error = Some("the udp echo server is not set".into());
+            return info;
+        }
+        if mode == MeasureMode::Full {
+            // the result of the last one is reused, or the throughput is skipped
+            if let Some(wait) = self.check_full_interval(name, min_interval) {
+                let last = MeasureCache::global()
+                    .get_proxy(name)
+                    .filter(|last| is_reusable(last, target, min_interval));
+                if let Some(last) = last {
+                    return last;
+                }
+
+                info.throughput_skipped = Some(format!(
+                    "the full test of \"{name}\" ran recently, retry in {}s",
+                    wait.as_secs() + 1
+                ));
+                mode = MeasureMode::Latency;
+                reservation = None;
+            }
+        }
+        if mode == MeasureMode::Full {
+            let target = match target {
+                Some(target) => Ok(target.clone()),
+                None => pick_target(name).await,
+            };
+            match target {
+                Ok(target) => info.set_target(target, upload),
+                Err(err) => {
+                    self.release_full_interval(name);
+                    info.error = Some(err.to_string());
+                    return info;
+                }
+            }
+            if info.download_url.is_none() {
+                self.release_full_interval(name);
+                info.throughput_skipped = Some("the measurement backend is latency only".into());
+                mode = MeasureMode::Latency;
+                reservation = None;
+            }
+        }
+
+        // warm-up, the connection to the node is established here
+        // so its result is not counted
+        let _ = clash_api::get_proxy_delay(name, test_url, delay::DELAY_TIMEOUT).await;
+
+        // the bytes of this measurement only, others may run at the same time
+        let mut cost = 0;
+        for _ in 0..repeat {
+            if self.is_cancelled() {
+                break;
+            }
+            let run = self.run_once(
+                name,
+                test_url,
+                info.download_url.as_deref(),
+                info.upload_url.as_deref(),
+                info.udp_server.as_deref(),
+            );
+            let (sample, bytes) = run.await;
+            info.samples.push(sample);
+            cost += bytes;
+        }
+
+        if mode == MeasureMode::Full {
+            budget.set_last_cost(cost);
+        }
+        drop(reservation);
+
+        info.summarize(mode);
+        if mode == MeasureMode::Full && info.download.is_none() {
+            self.release_full_interval(name);
+        }
+        log_err!(MeasureCache::global().update(&info));
+        info
+    }
+
+    /// measure the test urls without the proxy, not cached
+    /// the download is against the same target as the proxy
+    async fn measure_direct(
+        &self,
+        mode: MeasureMode,
+        test_url: &str,
+        target: Option<&MeasureTarget>,
+    ) -> MeasureInfo {
+        let repeat = { Config::verge().latest().measure_repeat };
+        let repeat = repeat.unwrap_or(DEFAULT_REPEAT).clamp(1, 10);
+
+        let download_url = target
+            .and_then(|target| target.download_url.clone())
+            .filter(|_| mode == MeasureMode::Full);
+        let mode = match mode {
+            MeasureMode::Full if download_url.is_none() => MeasureMode::Latency,
+            mode => mode,
+        };
+
+        let mut info = MeasureInfo {
+            name: "DIRECT".into(),
+            test_url: Some(test_url.into()),
+            download_url: download_url.clone(),
+            server: target.and_then(|target| target.server.clone()),
+            ..MeasureInfo::default()
+        };
+
+        // warm-up, the same as the proxy
+        let _ = self.direct_delay(test_url).await;
+
+        for _ in 0..repeat {
+            if self.is_cancelled() {
+                break;
+            }
+
+            let mut sample = SpeedMeasurement::default();
+            match self.direct_delay(test_url).await {
+                Ok(delay) => sample.latency = Some(delay),
+                Err(err) => sample.error = Some(err.to_string()),
+            }
+
+            if let Some(url) = download_url.as_deref().filter(|_| sample.error.is_none()) {
+                match self.fetch(url, false).await {
+                    Ok((speed, timeline, version, _)) => {
+                        sample.download = Some(speed);
+                        sample.http_version = Some(version);
+                        sample.throttle_rate = detect_throttle(&timeline);
+                        sample.timeline = timeline;
+                    }
+                    Err(err) => sample.error = Some(err.to_string()),
+                }
+            }
+            info.samples.push(sample);
+        }
+
+        info.summarize(mode);
+        info
+    }
+
+    /// a single run of the measurement
+    /// return the result and the bytes of the download and the upload
+    async fn run_once(
+        &self,
+        name: &str,
+        test_url: &str,
+        download_url: Option<&str>,
+        upload_url: Option<&str>,
+        udp_server: Option<&str>,
+    ) -> (SpeedMeasurement, u64) {
+        let mut sample = SpeedMeasurement::default();
+        let mut cost = 0;
+
+        match clash_api::get_proxy_delay(name, test_url, delay::DELAY_TIMEOUT).await {
+            Ok(delay) => sample.latency = Some(delay),
+            Err(err) => {
+                sample.error = Some(err.to_string());
+                return (sample, cost);
+            }
+        }
+
+        if let Some(url) = download_url {
+            match self.download(name, url).await {
+                Ok((speed, timeline, version, bytes)) => {
+                    cost += bytes;
+                    sample.download = Some(speed);
+                    sample.http_version = Some(version);
+                    sample.throttle_rate = detect_throttle(&timeline);
+                    sample.timeline = timeline;
+                }
+                Err(err) => sample.error = Some(err.to_string()),
+            }
+        }
+
+        if let Some(url) = upload_url.filter(|_| sample.error.is_none()) {
+            match self.through(name, self.upload(url)).await {
+                Ok((speed, bytes)) => {
+                    cost += bytes;
+                    sample.upload = Some(speed);
+                }
+                Err(err) => sample.error = Some(err.to_string()),
+            }
+        }
+
+        if let Some(server) = udp_server {
+            match self.udp_throughput(name, server).await {
+                Ok((speed, loss)) => {
+                    sample.udp = Some(speed);
+                    sample.udp_loss = Some(loss);
+                }
+                Err(err) => sample.error = Some(err.to_string()),
+            }
+        }
+
+        (sample, cost)
+    }
+
+    /// measure two proxies back to back with the same test url
+    /// and the same throughput target, picked for the first one
+    pub async fn compare(&self, a: &str, b: &str, mode: MeasureMode) -> Result<MeasureCompare> {
+        let test_url = match delay::test_urls().into_iter().next() {
+            Some(url) => url,
+            None => bail!("no latency test url"),
+        };
+        let target = match mode {
+            MeasureMode::Full => Some(pick_target(a).await?),
+            _ => None,
+        };
+
+        let a = self.measure_with(a, mode, &test_url, target.as_ref()).await;
+        let b = self.measure_with(b, mode, &test_url, target.as_ref()).await;
+
+        let latency = a.latency.clone().zip(b.latency.clone());
+        let download = a.download.clone().zip(b.download.clone());
+        let udp = a.udp.clone().zip(b.udp.clone());
+
+        Ok(MeasureCompare {
+            latency_delta: latency
+                .clone()
+                .map(|(a, b)| b.median as i64 - a.median as i64),
+            jitter_delta: latency.map(|(a, b)| b.std_dev - a.std_dev),
+            download_delta: download.map(|(a, b)| b.median as i64 - a.median as i64),
+            udp_delta: udp.map(|(a, b)| b.median as i64 - a.median as i64),
+            a,
+            b,
+        })
+    }
+
+    /// measure the same test urls without the proxy and through the current node
+    /// the current node is the one the latency test url goes through
+    /// the throughput target is picked for the node and used by both
+    pub async fn benchmark(&self, mode: MeasureMode) -> Result<MeasureBenchmark> {
+        if mode == MeasureMode::Udp {
+            bail!("the udp mode is not supported by the benchmark");
+        }
+        let test_url = match delay::test_urls().into_iter().next() {
+            Some(url) => url,
+            None => bail!("no latency test url"),
+        };
+
+        let trace = trace::trace_domain(&test_url).await?;
+        let node = match trace.chain.last() {
+            Some(node) if node != "DIRECT" && node != "REJECT" => node.clone(),
+            _ => bail!("the test url \"{test_url}\" does not go through a proxy"),
+        };
+
+        let target = match mode {
+            MeasureMode::Full => Some(pick_target(&node).await?),
+            _ => None,
+        };
+
+        let direct = self.measure_direct(mode, &test_url, target.as_ref()).await;
+        let proxy = self
+            .measure_with(&node, mode, &test_url, target.as_ref())
+            .await;
+
+        let latency = direct.latency.clone().zip(proxy.latency.clone());
+        let download = direct.download.clone().zip(proxy.download.clone());
+
+        Ok(MeasureBenchmark {
+            node,
+            latency_overhead: latency.and_then(|(d, p)| overhead(d.median, p.median)),
+            download_overhead: download
+                .and_then(|(d, p)| overhead(d.median, p.median))
+                .map(|o| -o),
+            direct,
+            proxy,
+        })
+    }
+
+    /// download through the hidden selector
+    /// return the speed, the rate timeline (bytes/s), the negotiated http version and the bytes
+    async fn download(&self, name: &str, url: &str) -> Result<(u64, Vec<u64>, String, u64)> {
+        self.through(name, self.fetch(url, true)).await
+    }
+
+    /// run the requests to the measure listener through the proxy
+    /// the hidden selector is restored after
+    pub async fn through<T>(&self, name: &str, run: impl Future<Output = Result<T>>) -> Result<T> {
+        self.through_with(&HttpClashApi, name, run).await
+    }
+
+    async fn through_with<T>(
+        &self,
+        api: &impl ClashApi,
+        name: &str,
+        run: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let _guard = self.lock.lock().await;
+
+        let prev = self.select(api, name).await?;
+        let result = run.await;
+        self.restore(api, prev).await;
+        result
+    }
+
+    /// download through the measure listener, or without the proxy
+    async fn fetch(&self, url: &str, proxied: bool) -> Result<(u64, Vec<u64>, String, u64)> {
+        let early_stop = { Config::verge().latest().enable_measure_early_stop };
+        let (speed, timeline, version, bytes) = self
+            .fetch_body(url, proxied, early_stop.unwrap_or(false))
+            .await?;
+        DataBudget::global().record(bytes);
+        Ok((speed, timeline, version, bytes))
+    }
+
+    /// return the speed, the rate timeline, the http version and the bytes read
+    async fn fetch_body(
+        &self,
+        url: &str,
+        proxied: bool,
+        early_stop: bool,
+    ) -> Result<(u64, Vec<u64>, String, u64)> {
+        let timeout = DOWNLOAD_DURATION + Duration::from_secs(5);
+
+        let start = Instant::now();
+        let (version, mut body) = self.transport.get(url, proxied, timeout).await?;
+        let (speed, timeline, bytes) = self.read_body(body.as_mut(), start, early_stop).await?;
+        Ok((speed, timeline, version, bytes))
+    }
+
+    /// read the body until the end or `DOWNLOAD_DURATION` since the start
+    /// return the speed, the timeline of the rates and the bytes read
+    /// stop as soon as the rate is stable if `early_stop`, the stable rate is the speed then
+    async fn read_body(
+        &self,
+        body: &mut dyn ChunkSource,
+        start: Instant,
+        early_stop: bool,
+    ) -> Result<(u64, Vec<u64>, u64)> {
+        let mut bytes = 0;
+        let mut timeline = vec![];
+        let mut slot_bytes = 0;
+        let mut slot_start = Instant::now();
+
+        while start.elapsed() < DOWNLOAD_DURATION {
+            if self.is_cancelled() {
+                bail!("the measurement is cancelled");
+            }
+            match body.next_chunk().await? {
+                Some(len) => {
+                    bytes += len as u64;
+                    slot_bytes += len as u64;
+                }
+                None => break,
+            }
+
+            let slot = slot_start.elapsed();
+            if slot >= TIMELINE_SLOT {
+                timeline.push((slot_bytes as f64 / slot.as_secs_f64()) as u64);
+                slot_bytes = 0;
+                slot_start = Instant::now();
+
+                if let Some(rate) = stable_rate(&timeline).filter(|_| early_stop) {
+                    return Ok((rate, timeline, bytes));
+                }
+            }
+        }
+
+        let secs = start.elapsed().as_secs_f64().max(0.001);
+        Ok(((bytes as f64 / secs) as u64, timeline, bytes))
+    }
+
+    /// stream to the url through the measure listener on the concurrent connections
+    /// until `UPLOAD_DURATION`, return the speed of all the connections and the bytes
+    async fn upload(&self, url: &str) -> Result<(u64, u64)> {
+        let connections = { Config::verge().latest().measure_upload_connections };
+        let connections = connections
+            .unwrap_or(DEFAULT_UPLOAD_CONNECTIONS)
+            .clamp(1, 16);
+
+        let timeout = UPLOAD_DURATION + Duration::from_secs(5);
+
+        let sent = Arc::new(AtomicU64::new(0));
+        let start = Instant::now();
+        let deadline = start + UPLOAD_DURATION;
+        let jobs = (0..connections).map(|_| {
+            let body = self.upload_body(sent.clone(), deadline);
+            self.transport.post(url, body, timeout)
+        });
+        let results = futures::future::join_all(jobs).await;
+
+        // the server may close the connection at its size limit,
+        // what was sent before is still counted
+        let bytes = sent.load(Ordering::SeqCst);
+        DataBudget::global().record(bytes);
+        if bytes == 0 {
+            match results.into_iter().find_map(|res| res.err()) {
+                Some(err) => return Err(err),
+                None => bail!("nothing was uploaded"),
+            }
+        }
+
+        let secs = start.elapsed().as_secs_f64().max(0.001);
+        Ok(((bytes as f64 / secs) as u64, bytes))
+    }
+
+    /// stream the chunks until the deadline or the cancellation
+    /// the bytes are counted as the connection takes them
+    fn upload_body(&self, sent: Arc<AtomicU64>, deadline: Instant) -> UploadBody {
+        static ZEROS: [u8; UPLOAD_CHUNK] = [0; UPLOAD_CHUNK];
+
+        let cancelled = self.cancelled.clone();
+        let chunks = futures::stream::unfold((), move |_| {
+            let sent = sent.clone();
+            let cancelled = cancelled.clone();
+            async move {
+                if Instant::now() >= deadline || cancelled.load(Ordering::SeqCst) {
+                    return None;
+                }
+                sent.fetch_add(UPLOAD_CHUNK as u64, Ordering::SeqCst);
+                Some((Ok::<_, std::io::Error>(&ZEROS[..]), ()))
+            }
+        });
+        chunks.boxed()
+    }
+
+    /// the time to the response headers without the proxy (ms)
+    /// a new connection each time, the same as the delay test of the core
+    async fn direct_delay(&self, test_url: &str) -> Result<u64> {
+        let timeout = Duration::from_millis(delay::DELAY_TIMEOUT);
+
+        let start = Instant::now();
+        self.transport.get(test_url, false, timeout).await?;
+        Ok(start.elapsed().as_millis() as u64)
+    }
+
+    /// select the proxy in the hidden selector
+    /// return the previous selection
+    async fn select(&self, api: &impl ClashApi, name: &str) -> Result<Option<String>> {
+        let prev = api.get_proxy_now(MEASURE_GROUP).await.ok().flatten();
+        api.put_proxy(MEASURE_GROUP, name).await?;
+        Ok(prev)
+    }
+
+    /// restore the selection of the hidden selector
+    async fn restore(&self, api: &impl ClashApi, prev: Option<String>) {
+        if let Some(prev) = prev {
+            log_err!(api.put_proxy(MEASURE_GROUP, &prev).await);
+        }
+    }
+
+    /// send udp packets to the echo server through the hidden selector
+    /// keep at most `UDP_WINDOW` packets waiting for the echo
+    /// return the echoed payload rate (bytes/s) and the loss ratio
+    async fn udp_throughput(&self, name: &str, server: &str) -> Result<(u64, f64)> {
+        self.through(name, self.udp_echo(server)).await
+    }
+
+    async fn udp_echo(&self, server: &str) -> Result<(u64, f64)> {
+        let port = { Config::verge().latest().get_measure_port() };
+        let relay = socks::udp_associate(([127, 0, 0, 1], port).into(), proxy_auth());
+        let (_control, relay) = relay.await?;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        socket.connect(relay).await?;
+
+        let packet = socks::encode_udp(server, &[0u8; UDP_PAYLOAD])?;
+        let mut buf = vec![0u8; packet.len() + 512];
+
+        let (mut sent, mut received, mut bytes) = (0u64, 0u64, 0u64);
+        let mut inflight = 0;
+        let start = Instant::now();
+
+        while start.elapsed() < UDP_DURATION {
+            if self.is_cancelled() {
+                bail!("the measurement is cancelled");
+            }
+            while inflight < UDP_WINDOW {
+                socket.send(&packet).await?;
+                sent += 1;
+                inflight += 1;
+            }
+
+            match timeout(UDP_RECV_TIMEOUT, socket.recv(&mut buf)).await {
+                Ok(res) => {
+                    if let Some(payload) = socks::decode_udp(&buf[..res?]) {
+                        received += 1;
+                        bytes += payload.len() as u64;
+                        inflight = inflight.saturating_sub(1);
+                    }
+                }
+                // the packets in flight are lost
+                Err(_) => inflight = 0,
+            }
+        }
+
+        if received == 0 {
+            bail!("no udp echo from \"{server}\"");
+        }
+
+        let secs = start.elapsed().as_secs_f64().max(0.001);
+        let loss = 1.0 - received.min(sent) as f64 / sent as f64;
+        Ok(((bytes as f64 / secs) as u64, loss))
+    }
+}
+
+/// the throughput target of `measure_download_backend` for the proxy
+async fn pick_target(name: &str) -> Result<MeasureTarget> {
+    let settings = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        BackendSettings {
+            backend: verge.measure_download_backend.clone(),
+            download_url: verge.measure_download_url.clone(),
+            upload_url: verge.measure_upload_url.clone(),
+        }
+    };
+    let backend =
+        measure_backend::from_settings(settings, |proxy| Box::pin(speedtest::pick_server(proxy)))?;
+
+    match backend.target(name).await {
+        Ok(target) => Ok(target),
+        Err(err) => bail!("failed to pick the throughput target, {err}"),
+    }
+}
+
+/// `(value - base) / base` (%)
+fn overhead(base: u64, value: u64) -> Option<f64> {
+    match base {
+        0 => None,
+        _ => Some((value as f64 - base as f64) / base as f64 * 100.0),
+    }
+}
+
+/// the client sending the requests to the measure listener
+pub fn proxy_client() -> Result<reqwest::ClientBuilder> {
+    let port = { Config::verge().latest().get_measure_port() };
+    let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{port}"))?;
+    let proxy = match proxy_auth() {
+        Some((user, pass)) => proxy.basic_auth(&user, &pass),
+        None => proxy,
+    };
+
+    Ok(reqwest::ClientBuilder::new()
+        .proxy(proxy)
+        .default_headers(request_headers()?))
+}
+
+/// the client of the measurement, through the measure listener if proxied
+fn transport_client(proxied: bool) -> Result<reqwest::ClientBuilder> {
+    match proxied {
+        true => proxy_client(),
+        false => Ok(reqwest::ClientBuilder::new()
+            .no_proxy()
+            .default_headers(request_headers()?)),
+    }
+}
+
+/// the first credential of `authentication` in the running config
+/// which is required by the listener if set
+fn proxy_auth() -> Option<(String, String)> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+
+    runtime
+        .config
+        .as_ref()?
+        .get("authentication")?
+        .as_sequence()?
+        .iter()
+        .filter_map(|item| item.as_str()?.split_once(':'))
+        .map(|(user, pass)| (user.to_string(), pass.to_string()))
+        .next()
+}
+
+/// the headers of the measurement requests
+/// look like a browser to avoid being fingerprinted
+pub fn request_headers() -> Result<HeaderMap> {
+    let (user_agent, language, order, random) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.measure_user_agent.clone(),
+            verge.measure_accept_language.clone(),
+            verge.measure_header_order.clone(),
+            verge.measure_random_ua.unwrap_or(false),
+        )
+    };
+
+    let user_agent = match user_agent {
+        _ if random => {
+            let index = chrono::Local::now().timestamp_subsec_nanos() as usize;
+            BROWSER_USER_AGENTS[index % BROWSER_USER_AGENTS.len()].to_string()
+        }
+        Some(user_agent) => user_agent,
+        None => BROWSER_USER_AGENTS[0].to_string(),
+    };
+    let language = language.unwrap_or("en-US,en;q=0.9".into());
+
+    build_headers(&user_agent, &language, order.as_ref())
+}
+
+/// insert the headers by the order
+/// the ones not in the order are appended
+fn build_headers(
+    user_agent: &str,
+    language: &str,
+    order: Option<&Vec<String>>,
+) -> Result<HeaderMap> {
+    let values = [
+        (header::USER_AGENT, user_agent),
+        (header::ACCEPT, BROWSER_ACCEPT),
+        (header::ACCEPT_LANGUAGE, language),
+    ];
+
+    let mut order = order
+        .map(|order| order.iter().map(|name| name.to_lowercase()).collect())
+        .unwrap_or(DEFAULT_HEADER_ORDER.map(|name| name.to_string()).to_vec());
+    for (name, _) in values.iter() {
+        if !order.contains(&name.to_string()) {
+            order.push(name.to_string());
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    for name in order.iter() {
+        if let Some((key, value)) = values.iter().find(|(key, _)| key.as_str() == name) {
+            headers.insert(key.clone(), HeaderValue::from_str(value)?);
+        }
+    }
+
+    Ok(headers)
+}
+
+impl MeasureInfo {
+    /// the upload url is kept only if `enable_measure_upload`
+    fn set_target(&mut self, target: MeasureTarget, upload: bool) {
+        self.download_url = target.download_url;
+        self.upload_url = target.upload_url.filter(|_| upload);
+        self.server = target.server;
+        self.client = target.client;
+    }
+
+    /// the statistics of the samples
+    fn summarize(&mut self, mode: MeasureMode) {
+        let latency: Vec<u64> = self.samples.iter().filter_map(|s| s.latency).collect();
+        let download: Vec<u64> = self.samples.iter().filter_map(|s| s.download).collect();
+        let upload: Vec<u64> = self.samples.iter().filter_map(|s| s.upload).collect();
+        let udp: Vec<u64> = self.samples.iter().filter_map(|s| s.udp).collect();
+        self.latency = MeasureStats::from_samples(&latency);
+        self.download = MeasureStats::from_samples(&download);
+        self.upload = MeasureStats::from_samples(&upload);
+        self.udp = MeasureStats::from_samples(&udp);
+
+        let throttle: Vec<u64> = self
+            .samples
+            .iter()
+            .filter_map(|s| s.throttle_rate)
+            .collect();
+        self.throttle_rate = median(&throttle);
+        self.throttle_detected = self.throttle_rate.is_some();
+
+        let failed = match mode {
+            MeasureMode::Full => self.download.is_none(),
+            MeasureMode::Udp => self.udp.is_none(),
+            MeasureMode::Latency => self.latency.is_none(),
+        };
+        if failed {
+            self.error = self.samples.iter().rev().find_map(|s| s.error.clone());
+        }
+
+        self.updated = chrono::Local::now().timestamp() as usize;
+    }
+}
+
+impl MeasureStats {
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        Some(Self {
+            median: median(samples)?,
+            p90: percentile(samples, 90)?,
+            std_dev: std_dev(samples)?,
+        })
+    }
+}
+
+/// the full result measured within the interval,
+/// against the same url if the target is pinned such as by `compare`
+fn is_reusable(last: &MeasureInfo, target: Option<&MeasureTarget>, interval: Duration) -> bool {
+    let now = chrono::Local::now().timestamp() as usize;
+    last.download.is_some()
+        && last.updated + interval.as_secs() as usize >= now
+        && target.map_or(true, |target| target.download_url == last.download_url)
+}
+
+/// median of the samples
+pub fn median(samples: &[u64]) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+
+    let mid = sorted.len() / 2;
+    match sorted.len() % 2 {
+        0 => Some((sorted[mid - 1] + sorted[mid]) / 2),
+        _ => Some(sorted[mid]),
+    }
+}
+
+/// nearest-rank percentile of the samples
+pub fn percentile(samples: &[u64], p: usize) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+
+    let rank = (p * sorted.len() + 99) / 100;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// population standard deviation of the samples
+pub fn std_dev(samples: &[u64]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let len = samples.len() as f64;
+    let mean = samples.iter().sum::<u64>() as f64 / len;
+    let var = samples
+        .iter()
+        .map(|s| (*s as f64 - mean).powi(2))
+        .sum::<f64>()
+        / len;
+    Some(var.sqrt())
+}
+
+/// the mean of the last `STABLE_SLOTS` rates if all of them are within
+/// `STABLE_TOLERANCE` of it, the download is saturated then
+pub fn stable_rate(timeline: &[u64]) -> Option<u64> {
+    if timeline.len() < STABLE_SLOTS {
+        return None;
+    }
+
+    let window = &timeline[timeline.len() - STABLE_SLOTS..];
+    let mean = window.iter().sum::<u64>() as f64 / STABLE_SLOTS as f64;
+    let stable = window
+        .iter()
+        .all(|rate| (*rate as f64 - mean).abs() <= mean * STABLE_TOLERANCE);
+
+    match stable && mean > 0.0 {
+        true => Some(mean as u64),
+        false => None,
+    }
+}
+
+/// detect the throttling by the download rate timeline
+/// a fast start and then a sustained and stable clamp
+/// return the clamp rate
+pub fn detect_throttle(timeline: &[u64]) -> Option<u64> {
+    if timeline.len() < THROTTLE_HEAD_SLOTS + THROTTLE_MIN_SLOTS {
+        return None;
+    }
+
+    let peak = *timeline[..THROTTLE_HEAD_SLOTS].iter().max()?;
+    let limit = peak / 2;
+
+    // the clamp must last until the end
+    let start = timeline.iter().rposition(|rate| *rate >= limit)? + 1;
+    let tail = timeline[start..].to_vec();
+    if tail.len() < THROTTLE_MIN_SLOTS {
+        return None;
+    }
+
+    let mean = tail.iter().sum::<u64>() / tail.len() as u64;
+    let stable = std_dev(&tail)? <= mean as f64 * 0.25;
+
+    match mean > 0 && stable {
+        true => Some(mean),
+        false => None,
+    }
+}
+
+#[test]
+fn test_build_headers() {
+    let names = |headers: HeaderMap| {
+        headers
+            .keys()
+            .map(|key| key.to_string())
+            .collect::<Vec<String>>()
+    };
+
+    let headers = build_headers("ua", "fa-IR", None).unwrap();
+    assert_eq!(headers["user-agent"], "ua");
+    assert_eq!(headers["accept-language"], "fa-IR");
+    assert_eq!(names(headers), DEFAULT_HEADER_ORDER.to_vec());
+
+    let order = vec!["Accept-Language".into(), "foo".into(), "user-agent".into()];
+    let headers = build_headers("ua", "en", Some(&order)).unwrap();
+    assert_eq!(names(headers), ["accept-language", "user-agent", "accept"]);
+
+    assert!(build_headers("ua\n", "en", None).is_err());
+}
+
+#[test]
+fn test_detect_throttle() {
+    let steady = vec![100; 20];
+    let throttled = vec![
+        50, 800, 900, 850, 300, 200, 210, 205, 195, 200, 198, 202, 200, 199,
+    ];
+    let late = vec![
+        800, 900, 850, 870, 860, 880, 200, 210, 205, 200, 200, 200, 200,
+    ];
+    let noisy = vec![
+        50, 800, 900, 850, 300, 20, 400, 5, 300, 10, 350, 202, 200, 199,
+    ];
+    let slow_start = vec![10, 50, 100, 200, 400, 800, 800, 800, 800, 800, 800, 800];
+
+    assert_eq!(detect_throttle(&steady), None);
+    assert_eq!(detect_throttle(&throttled), Some(210));
+    assert_eq!(detect_throttle(&late), Some(202));
+    assert_eq!(detect_throttle(&noisy), None);
+    assert_eq!(detect_throttle(&slow_start), None);
+    assert_eq!(detect_throttle(&[900, 100, 100]), None);
+}
+
+#[test]
+fn test_stable_rate() {
+    assert_eq!(stable_rate(&[100, 100, 100]), None);
+    assert_eq!(
+        stable_rate(&[10, 50, 100, 105, 95, 100, 98, 102]),
+        Some(100)
+    );
+    assert_eq!(stable_rate(&[100, 105, 95, 100, 98, 150]), None);
+    assert_eq!(stable_rate(&[0; 8]), None);
+}
+
+#[test]
+fn test_statistics() {
+    assert_eq!(median(&[]), None);
+    assert_eq!(median(&[3, 1, 2]), Some(2));
+    assert_eq!(median(&[4, 1, 2, 3]), Some(2));
+
+    assert_eq!(percentile(&[], 90), None);
+    assert_eq!(percentile(&[7], 90), Some(7));
+    assert_eq!(percentile(&(1..=10).collect::<Vec<u64>>(), 90), Some(9));
+    assert_eq!(
+        percentile(&(1..=20).rev().collect::<Vec<u64>>(), 90),
+        Some(18)
+    );
+
+    assert_eq!(std_dev(&[]), None);
+    assert_eq!(std_dev(&[5, 5, 5]), Some(0.0));
+    assert_eq!(std_dev(&[2, 4, 4, 4, 5, 5, 7, 9]), Some(2.0));
+
+    assert_eq!(overhead(0, 10), None);
+    assert_eq!(overhead(100, 150), Some(50.0));
+    assert_eq!(overhead(200, 150), Some(-25.0));
+}
+
+#[test]
+fn test_read_body() {
+    use futures::future::BoxFuture;
+
+    struct MockBody(Vec<usize>);
+
+    impl ChunkSource for MockBody {
+        fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<usize>>> {
+            Box::pin(async move {
+                match self.0.is_empty() {
+                    true => Ok(None),
+                    false => Ok(Some(self.0.remove(0))),
+                }
+            })
+        }
+    }
+
+    let measure = Measure::new();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut body = MockBody(vec![1000; 10]);
+    let start = Instant::now() - Duration::from_secs(1);
+    let (speed, _, bytes) = runtime
+        .block_on(measure.read_body(&mut body, start, false))
+        .unwrap();
+    assert!(speed > 0 && speed <= 10_000);
+    assert_eq!(bytes, 10_000);
+    assert!(body.0.is_empty());
+
+    measure.cancel();
+    let mut body = MockBody(vec![1000]);
+    assert!(runtime
+        .block_on(measure.read_body(&mut body, Instant::now(), false))
+        .is_err());
+}
+
+#[test]
+fn test_fetch_body() {
+    use futures::future::BoxFuture;
+
+    struct MockBody(usize);
+
+    impl ChunkSource for MockBody {
+        fn next_chunk(&mut self) -> BoxFuture<'_, Result<Option<usize>>> {
+            Box::pin(async move {
+                match self.0 {
+                    0 => Ok(None),
+                    _ => {
+                        self.0 -= 1;
+                        Ok(Some(1000))
+                    }
+                }
+            })
+        }
+    }
+
+    /// a body of 10 chunks for the `ok` urls
+    struct MockTransport;
+
+    impl HttpTransport for MockTransport {
+        fn get<'a>(
+            &'a self,
+            url: &'a str,
+            _: bool,
+            _: Duration,
+        ) -> BoxFuture<'a, Result<(String, Box<dyn ChunkSource>)>> {
+            Box::pin(async move {
+                match url.starts_with("ok") {
+                    true => Ok((
+                        "HTTP/2.0".into(),
+                        Box::new(MockBody(10)) as Box<dyn ChunkSource>,
+                    )),
+                    false => bail!("failed to download with status \"404 Not Found\""),
+                }
+            })
+        }
+
+        fn post<'a>(&'a self, _: &'a str, _: UploadBody, _: Duration) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move { Ok(()) })
+        }
+    }
+
+    let measure = Measure::with_transport(Arc::new(MockTransport));
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let (speed, _, version, bytes) = runtime
+        .block_on(measure.fetch_body("ok", true, false))
+        .unwrap();
+    assert!(speed > 0);
+    assert_eq!(version, "HTTP/2.0");
+    assert_eq!(bytes, 10_000);
+
+    assert!(runtime
+        .block_on(measure.fetch_body("missing", true, false))
+        .is_err());
+}
+
+#[test]
+fn test_through_restore() {
+    use futures::future::BoxFuture;
+
+    /// the selections of the groups
+    struct MockApi(Mutex<HashMap<String, String>>);
+
+    impl ClashApi for MockApi {
+        fn get_proxy_now<'a>(&'a self, group: &'a str) -> BoxFuture<'a, Result<Option<String>>> {
+            Box::pin(async move { Ok(self.0.lock().get(group).cloned()) })
+        }
+
+        fn put_proxy<'a>(&'a self, group: &'a str, name: &'a str) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                self.0.lock().insert(group.into(), name.into());
+                Ok(())
+            })
+        }
+    }
+
+    let api = MockApi(Mutex::new(HashMap::new()));
+    api.0.lock().insert(MEASURE_GROUP.into(), "DIRECT".into());
+    let measure = Measure::new();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let now = runtime
+        .block_on(measure.through_with(&api, "de", api.get_proxy_now(MEASURE_GROUP)))
+        .unwrap();
+    assert_eq!(now.as_deref(), Some("de"));
+    assert_eq!(api.0.lock()[MEASURE_GROUP], "DIRECT");
+
+    let failed = runtime.block_on(measure.through_with(&api, "us", async {
+        Err::<(), _>(anyhow::anyhow!("failed to download"))
+    }));
+    assert!(failed.is_err());
+    assert_eq!(api.0.lock()[MEASURE_GROUP], "DIRECT");
+}
+
+#[test]
+fn test_full_interval() {
+    let measure = Measure::new();
+    let interval = Duration::from_secs(60);
+
+    assert_eq!(measure.check_full_interval("de", interval), None);
+    assert!(measure.check_full_interval("de", interval).is_some());
+    assert_eq!(measure.check_full_interval("us", interval), None);
+    assert_eq!(measure.check_full_interval("de", Duration::ZERO), None);
+
+    assert!(measure.check_full_interval("de", interval).is_some());
+    measure.release_full_interval("de");
+    assert_eq!(measure.check_full_interval("de", interval), None);
+
+    let last = MeasureInfo {
+        download: Some(MeasureStats::default()),
+        download_url: Some("http://a".into()),
+        updated: chrono::Local::now().timestamp() as usize,
+        ..MeasureInfo::default()
+    };
+    let target = |url: &str| MeasureTarget {
+        download_url: Some(url.into()),
+        ..MeasureTarget::default()
+    };
+    assert!(is_reusable(&last, None, interval));
+    assert!(is_reusable(&last, Some(&target("http://a")), interval));
+    assert!(!is_reusable(&last, Some(&target("http://b")), interval));
+
+    let old = MeasureInfo {
+        updated: last.updated - 120,
+        ..last.clone()
+    };
+    assert!(!is_reusable(&old, None, interval));
+}
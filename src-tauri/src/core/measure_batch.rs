@@ -0,0 +1,370 @@
+use super::data_budget::DataBudget;
+use super::measure::{Measure, MeasureInfo, MeasureMode};
+use super::measure_queue::{MeasurePriority, MeasureQueue};
+use crate::config::Config;
+use crate::utils::{dirs, help};
+use crate::{feat, log_err};
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// the number of the batches kept, the oldest is dropped first
+const BATCH_LIMIT: usize = 10;
+
+/// the measurement of a group of proxies, checkpointed after each proxy
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MeasureBatch {
+    pub id: String,
+
+    /// the profile uid
+    pub profile: Option<String>,
+
+    pub mode: MeasureMode,
+
+    /// all the proxies of the batch
+    pub names: Vec<String>,
+
+    /// the finished ones
+    pub results: Vec<MeasureInfo>,
+
+    pub created: usize,
+
+    pub updated: usize,
+}
+
+impl MeasureBatch {
+    /// the proxies not measured yet
+    pub fn pending(&self) -> Vec<String> {
+        self.names
+            .iter()
+            .filter(|name| !self.results.iter().any(|info| &&info.name == name))
+            .cloned()
+            .collect()
+    }
+
+    /// the measured proxies with an error, or with the median latency over the threshold
+    pub fn failed(&self, latency_threshold: Option<u64>) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|info| {
+                let slow = match (&info.latency, latency_threshold) {
+                    (Some(latency), Some(threshold)) => latency.median > threshold,
+                    (None, _) => true,
+                    _ => false,
+                };
+                info.error.is_some() || slow
+            })
+            .map(|info| info.name.clone())
+            .collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending().is_empty()
+    }
+
+    /// the results in the order of the names
+    /// the proxies not measured yet come with an error
+    pub fn ordered_results(&self) -> Vec<MeasureInfo> {
+        self.names
+            .iter()
+            .map(|name| {
+                let info = self.results.iter().find(|info| &info.name == name);
+                info.cloned().unwrap_or_else(|| MeasureInfo {
+                    name: name.clone(),
+                    error: Some("the measurement was interrupted".into()),
+                    ..MeasureInfo::default()
+                })
+            })
+            .collect()
+    }
+
+    /// replace the result of the same proxy
+    fn merge(&mut self, info: MeasureInfo) {
+        self.results.retain(|item| item.name != info.name);
+        self.results.push(info);
+        self.updated = chrono::Local::now().timestamp() as usize;
+    }
+}
+
+/// the recent batches, saved in `measure_batch.yaml`
+/// so that an interrupted batch can be resumed after restart
+pub struct MeasureBatches {
+    /// loaded on the first use
+    items: Arc<Mutex<Option<Vec<MeasureBatch>>>>,
+}
+
+impl MeasureBatches {
+    pub fn global() -> &'static MeasureBatches {
+        static BATCHES: OnceCell<MeasureBatches> = OnceCell::new();
+
+        BATCHES.get_or_init(|| MeasureBatches {
+            items: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn load() -> Vec<MeasureBatch> {
+        match dirs::measure_batch_path().and_then(|path| help::read_yaml(&path)) {
+            Ok(items) => items,
+            Err(err) => {
+                log::debug!(target: "app", "failed to read the measurement batches, {err}");
+                vec![]
+            }
+        }
+    }
+
+    /// the oldest first
+    pub fn get_all(&self) -> Vec<MeasureBatch> {
+        let mut items = self.items.lock();
+        items.get_or_insert_with(Self::load).clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<MeasureBatch> {
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+        items.iter().find(|batch| batch.id == id).cloned()
+    }
+
+    /// measure the proxies of the current profile in a new batch
+    pub async fn start(
+        &'static self,
+        names: Vec<String>,
+        mode: MeasureMode,
+    ) -> Result<MeasureBatch> {
+        let now = chrono::Local::now().timestamp() as usize;
+        let batch = MeasureBatch {
+            id: help::get_uid("b"),
+            profile: current_profile(),
+            mode,
+            names: names.clone(),
+            results: vec![],
+            created: now,
+            updated: now,
+        };
+        let id = batch.id.clone();
+
+        {
+            let mut items = self.items.lock();
+            let items = items.get_or_insert_with(Self::load);
+            items.push(batch);
+            if items.len() > BATCH_LIMIT {
+                items.drain(..items.len() - BATCH_LIMIT);
+            }
+            help::save_yaml(&dirs::measure_batch_path()?, items, None)?;
+        }
+
+        self.run(&id, names).await
+    }
+
+    /// measure the rest of the batch, the latest unfinished one by default
+    pub async fn resume(&'static self, id: Option<String>) -> Result<MeasureBatch> {
+        let batch = match id {
+            Some(id) => self.get(&id),
+            None => self
+                .get_all()
+                .into_iter()
+                .rev()
+                .find(|batch| !batch.is_finished()),
+        };
+        let batch = match batch {
+            Some(batch) => batch,
+            None => bail!("no unfinished measurement batch found"),
+        };
+        if batch.profile != current_profile() {
+            bail!("the batch \"{}\" belongs to another profile", batch.id);
+        }
+
+        self.run(&batch.id, batch.pending()).await
+    }
+
+    /// measure the failed proxies of the batch again, the results replace the old ones
+    pub async fn retest_failed(
+        &'static self,
+        id: &str,
+        latency_threshold: Option<u64>,
+    ) -> Result<MeasureBatch> {
+        let batch = match self.get(id) {
+            Some(batch) => batch,
+            None => bail!("failed to find the batch \"{id}\""),
+        };
+        if batch.profile != current_profile() {
+            bail!("the batch \"{id}\" belongs to another profile");
+        }
+
+        let failed = batch.failed(latency_threshold);
+        if failed.is_empty() {
+            return Ok(batch);
+        }
+        log::info!(target: "app", "retest {} failed proxies of the batch \"{id}\"", failed.len());
+        self.run(id, failed).await
+    }
+
+    /// the result of each proxy is saved as soon as it's finished
+    async fn run(&'static self, id: &str, names: Vec<String>) -> Result<MeasureBatch> {
+        let mode = match self.get(id) {
+            Some(batch) => batch.mode,
+            None => bail!("failed to find the batch \"{id}\""),
+        };
+
+        DataBudget::global().start_scan();
+
+        let top = Config::verge().latest().measure_throughput_top.unwrap_or(0);
+        let names = match mode {
+            MeasureMode::Full if top > 0 && names.len() > top => {
+                self.prefilter(id, names, top).await
+            }
+            _ => names,
+        };
+
+        let queue = MeasureQueue::global();
+        let jobs = names.iter().map(|name| async move {
+            let info = queue.measure(name, mode, MeasurePriority::Group).await;
+            // the cancelled ones are measured again on resume
+            if let Ok(info) = info {
+                if !Measure::global().is_cancelled() {
+                    log_err!(self.checkpoint(id, info));
+                }
+            }
+        });
+        futures::future::join_all(jobs).await;
+
+        // the members of the load balance group follow the new throughput
+        if mode == MeasureMode::Full && !Measure::global().is_cancelled() {
+            log_err!(feat::refresh_load_balance().await);
+        }
+
+        match self.get(id) {
+            Some(batch) => Ok(batch),
+            None => bail!("failed to find the batch \"{id}\""),
+        }
+    }
+
+    /// measure the latency of all the proxies in parallel first,
+    /// the rest of the fastest ones are saved with the latency only
+    async fn prefilter(&'static self, id: &str, names: Vec<String>, top: usize) -> Vec<String> {
+        let infos = MeasureQueue::global()
+            .measure_all(names, MeasureMode::Latency, MeasurePriority::Group)
+            .await;
+        if Measure::global().is_cancelled() {
+            return vec![];
+        }
+
+        let (fastest, rest) = split_fastest(infos, top);
+        for mut info in rest {
+            if info.error.is_none() {
+                info.throughput_skipped = Some(format!("not in the fastest {top} by the latency"));
+            }
+            log_err!(self.checkpoint(id, info));
+        }
+        fastest
+    }
+
+    fn checkpoint(&self, id: &str, info: MeasureInfo) -> Result<()> {
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        match items.iter_mut().find(|batch| batch.id == id) {
+            Some(batch) => batch.merge(info),
+            None => return Ok(()),
+        };
+
+        help::save_yaml(&dirs::measure_batch_path()?, items, None)
+    }
+}
+
+fn current_profile() -> Option<String> {
+    Config::profiles().latest().get_current()
+}
+
+/// the responsive proxies with the lowest median latency, up to the count
+fn split_fastest(mut infos: Vec<MeasureInfo>, count: usize) -> (Vec<String>, Vec<MeasureInfo>) {
+    let median = |info: &MeasureInfo| match (&info.error, &info.latency) {
+        (None, Some(latency)) => Some(latency.median),
+        _ => None,
+    };
+    infos.sort_by_key(|info| median(info).unwrap_or(u64::MAX));
+
+    let responsive = infos.iter().filter(|info| median(info).is_some()).count();
+    let rest = infos.split_off(count.min(responsive));
+    (infos.into_iter().map(|info| info.name).collect(), rest)
+}
+
+#[test]
+fn test_batch_results() {
+    let info = |name: &str| MeasureInfo {
+        name: name.into(),
+        ..MeasureInfo::default()
+    };
+    let mut batch = MeasureBatch {
+        id: "b1".into(),
+        profile: None,
+        mode: MeasureMode::Latency,
+        names: vec!["de".into(), "us".into(), "jp".into()],
+        results: vec![],
+        created: 0,
+        updated: 0,
+    };
+
+    batch.merge(info("jp"));
+    batch.merge(info("de"));
+    batch.merge(info("jp"));
+    assert_eq!(batch.results.len(), 2);
+    assert_eq!(batch.pending(), vec!["us".to_string()]);
+    assert!(!batch.is_finished());
+
+    let results = batch.ordered_results();
+    let names = results.iter().map(|i| i.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["de", "us", "jp"]);
+    assert!(results[0].error.is_none());
+    assert!(results[1].error.is_some());
+
+    batch.merge(info("us"));
+    assert!(batch.is_finished());
+
+    let latency = |median: u64| super::measure::MeasureStats {
+        median,
+        p90: median,
+        std_dev: 0.0,
+    };
+    batch.results[0].latency = Some(latency(900));
+    batch.results[1].latency = Some(latency(100));
+    batch.merge(MeasureInfo {
+        error: Some("timeout".into()),
+        ..info("us")
+    });
+    assert_eq!(batch.failed(None), vec!["us"]);
+    assert_eq!(batch.failed(Some(500)), vec!["de", "us"]);
+
+    batch.results[1].latency = Some(latency(600));
+    assert_eq!(batch.failed(Some(500)), vec!["de", "jp", "us"]);
+}
+
+#[test]
+fn test_split_fastest() {
+    let info = |name: &str, latency: Option<u64>| MeasureInfo {
+        name: name.into(),
+        latency: latency.map(|median| super::measure::MeasureStats {
+            median,
+            p90: median,
+            std_dev: 0.0,
+        }),
+        error: latency.is_none().then(|| "timeout".into()),
+        ..MeasureInfo::default()
+    };
+    let infos = vec![
+        info("de", Some(300)),
+        info("us", None),
+        info("jp", Some(100)),
+        info("sg", Some(200)),
+    ];
+
+    let (fastest, rest) = split_fastest(infos.clone(), 2);
+    assert_eq!(fastest, vec!["jp", "sg"]);
+    let rest = rest.iter().map(|i| i.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(rest, vec!["de", "us"]);
+
+    let (fastest, rest) = split_fastest(infos, 5);
+    assert_eq!(fastest, vec!["jp", "sg", "de"]);
+    assert_eq!(rest[0].name, "us");
+}
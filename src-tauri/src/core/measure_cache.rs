@@ -0,0 +1,386 @@
+use super::clash_api;
+use super::measure::{MeasureInfo, MeasureStats};
+use crate::config::Config;
+use crate::utils::{dirs, help};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct MeasureCacheItem {
+    /// the profile uid
+    pub profile: String,
+
+    /// the proxy name
+    pub name: String,
+
+    /// `{type}://{server}:{port}`
+    /// the result is dropped if the node behind the name is changed
+    pub fingerprint: String,
+
+    pub info: MeasureInfo,
+
+    /// the latest delays probed by the health check of the core
+    #[serde(default)]
+    pub core_history: Vec<CoreDelay>,
+}
+
+/// a delay in the history of the core, `0` means failed
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CoreDelay {
+    pub time: usize,
+    pub delay: u64,
+}
+
+/// the number of the core delays kept for each node
+const CORE_HISTORY_LIMIT: usize = 10;
+
+/// the latest measurement of each node, saved in `measure.yaml`
+pub struct MeasureCache {
+    /// loaded on the first use
+    items: Arc<Mutex<Option<Vec<MeasureCacheItem>>>>,
+}
+
+impl MeasureCache {
+    pub fn global() -> &'static MeasureCache {
+        static CACHE: OnceCell<MeasureCache> = OnceCell::new();
+
+        CACHE.get_or_init(|| MeasureCache {
+            items: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn load() -> Vec<MeasureCacheItem> {
+        match dirs::measure_path().and_then(|path| help::read_yaml(&path)) {
+            Ok(items) => items,
+            Err(err) => {
+                log::debug!(target: "app", "failed to read the measure cache, {err}");
+                vec![]
+            }
+        }
+    }
+
+    /// save the successful measurement of the proxy in the current profile
+    pub fn update(&self, info: &MeasureInfo) -> Result<()> {
+        if info.error.is_some() {
+            return Ok(());
+        }
+
+        let current = { Config::profiles().latest().get_current() };
+        let profile = match current {
+            Some(profile) => profile,
+            None => return Ok(()),
+        };
+        let fingerprint = current_fingerprints()
+            .remove(&info.name)
+            .unwrap_or_default();
+
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        // the probes of the core are kept for the same node
+        let core_history = items
+            .iter()
+            .find(|item| item.profile == profile && item.name == info.name)
+            .filter(|item| item.fingerprint == fingerprint)
+            .map_or(vec![], |item| item.core_history.clone());
+
+        items.retain(|item| !(item.profile == profile && item.name == info.name));
+        items.push(MeasureCacheItem {
+            profile,
+            name: info.name.clone(),
+            fingerprint,
+            info: info.clone(),
+            core_history,
+        });
+
+        help::save_yaml(&dirs::measure_path()?, items, None)
+    }
+
+    /// merge the delay history of the core into the current profile
+    pub fn update_core_history(&self, history: HashMap<String, Vec<CoreDelay>>) -> Result<()> {
+        let current = { Config::profiles().latest().get_current() };
+        let profile = match current {
+            Some(profile) => profile,
+            None => return Ok(()),
+        };
+        let mut fingerprints = current_fingerprints();
+
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        let mut changed = false;
+        for (name, delays) in history.into_iter() {
+            let fingerprint = fingerprints.remove(&name).unwrap_or_default();
+            let index = items
+                .iter()
+                .position(|item| item.profile == profile && item.name == name);
+
+            let index = match index {
+                Some(index) if items[index].fingerprint == fingerprint => index,
+                // the node behind the name is changed
+                index => {
+                    if let Some(index) = index {
+                        items.remove(index);
+                    }
+                    items.push(MeasureCacheItem {
+                        profile: profile.clone(),
+                        name: name.clone(),
+                        fingerprint,
+                        info: MeasureInfo {
+                            name,
+                            ..MeasureInfo::default()
+                        },
+                        core_history: vec![],
+                    });
+                    items.len() - 1
+                }
+            };
+
+            changed |= merge_core_history(&mut items[index].core_history, delays);
+        }
+
+        match changed {
+            true => help::save_yaml(&dirs::measure_path()?, items, None),
+            false => Ok(()),
+        }
+    }
+
+    /// get the cached measurements of the profile
+    /// the results of the changed nodes are skipped for the current profile
+    pub fn get(&self, profile: &str) -> Vec<MeasureInfo> {
+        let current = { Config::profiles().latest().get_current() };
+        let fingerprints = match current.as_deref() == Some(profile) {
+            true => Some(current_fingerprints()),
+            false => None,
+        };
+
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        items
+            .iter()
+            .filter(|item| item.profile == profile)
+            .filter(|item| {
+                fingerprints
+                    .as_ref()
+                    .map_or(true, |map| map.get(&item.name) == Some(&item.fingerprint))
+            })
+            .map(|item| item.merged_info())
+            .collect()
+    }
+
+    /// the cached measurement of the proxy in the current profile
+    pub fn get_proxy(&self, name: &str) -> Option<MeasureInfo> {
+        let current = { Config::profiles().latest().get_current() };
+        self.get(&current?)
+            .into_iter()
+            .find(|info| info.name == name)
+    }
+
+    /// remove the results of the deleted profile
+    pub fn remove_profile(&self, profile: &str) -> Result<()> {
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        let len = items.len();
+        items.retain(|item| item.profile != profile);
+
+        match items.len() == len {
+            true => Ok(()),
+            false => help::save_yaml(&dirs::measure_path()?, items, None),
+        }
+    }
+}
+
+impl MeasureCacheItem {
+    /// the measurement with the latency probed by the core
+    fn merged_info(&self) -> MeasureInfo {
+        let mut info = self.info.clone();
+        if self.core_history.is_empty() {
+            return info;
+        }
+
+        let mut latency = info
+            .samples
+            .iter()
+            .filter_map(|s| s.latency)
+            .collect::<Vec<u64>>();
+        latency.extend(
+            self.core_history
+                .iter()
+                .filter(|d| d.delay > 0)
+                .map(|d| d.delay),
+        );
+
+        let last = self.core_history.iter().map(|d| d.time).max();
+        info.updated = info.updated.max(last.unwrap_or_default());
+        info.latency = MeasureStats::from_samples(&latency);
+        if info.latency.is_none() && info.samples.is_empty() {
+            info.error = Some("failed by the health check of the core".into());
+        }
+        info
+    }
+}
+
+/// pull the delay history of the core into the cache
+pub async fn sync_core_history() -> Result<()> {
+    let proxies = clash_api::get_proxies().await?;
+    MeasureCache::global().update_core_history(parse_core_history(&proxies))
+}
+
+/// the history of the nodes in `GET /proxies`, the groups are skipped
+/// clash meta keeps the history of each test url in `extra`
+fn parse_core_history(data: &serde_json::Value) -> HashMap<String, Vec<CoreDelay>> {
+    let proxies = match data.get("proxies").and_then(|p| p.as_object()) {
+        Some(proxies) => proxies,
+        None => return HashMap::new(),
+    };
+
+    let history = |value: &serde_json::Value| {
+        value
+            .get("history")
+            .and_then(|h| h.as_array())
+            .map_or(vec![], |history| {
+                history
+                    .iter()
+                    .filter_map(|h| {
+                        let time = h.get("time")?.as_str()?;
+                        let time = chrono::DateTime::parse_from_rfc3339(time).ok()?;
+                        Some(CoreDelay {
+                            time: time.timestamp() as usize,
+                            delay: h.get("delay")?.as_u64()?,
+                        })
+                    })
+                    .collect::<Vec<CoreDelay>>()
+            })
+    };
+
+    proxies
+        .iter()
+        .filter(|(_, proxy)| proxy.get("all").is_none())
+        .filter_map(|(name, proxy)| {
+            let mut delays = history(proxy);
+            if let Some(extra) = proxy.get("extra").and_then(|e| e.as_object()) {
+                extra.values().for_each(|e| delays.extend(history(e)));
+            }
+            match delays.is_empty() {
+                true => None,
+                false => Some((name.clone(), delays)),
+            }
+        })
+        .collect()
+}
+
+/// keep the latest delays, return true if any is new
+fn merge_core_history(history: &mut Vec<CoreDelay>, delays: Vec<CoreDelay>) -> bool {
+    let len = history.len();
+    for delay in delays.into_iter() {
+        if !history.contains(&delay) {
+            history.push(delay);
+        }
+    }
+    let changed = history.len() != len;
+
+    history.sort_by_key(|d| d.time);
+    if history.len() > CORE_HISTORY_LIMIT {
+        history.drain(..history.len() - CORE_HISTORY_LIMIT);
+    }
+    changed
+}
+
+/// the fingerprints of the proxies in the runtime config
+fn current_fingerprints() -> HashMap<String, String> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+
+    runtime
+        .config
+        .as_ref()
+        .map(fingerprints)
+        .unwrap_or_default()
+}
+
+fn fingerprints(config: &Mapping) -> HashMap<String, String> {
+    config
+        .get("proxies")
+        .and_then(|val| val.as_sequence())
+        .map_or(HashMap::new(), |seq| {
+            seq.iter()
+                .filter_map(|item| {
+                    let name = item.get("name")?.as_str()?;
+                    let ptype = item.get("type")?.as_str()?;
+                    let server = item.get("server")?.as_str()?;
+                    let port = item.get("port")?.as_u64()?;
+                    Some((name.into(), format!("{ptype}://{server}:{port}")))
+                })
+                .collect()
+        })
+}
+
+#[test]
+fn test_fingerprints() {
+    let config = r"
+proxies:
+  - { name: a, type: vless, server: example.com, port: 443 }
+  - { name: b, type: trojan, server: 1.2.3.4, port: 8443 }
+  - { name: c, type: ss }
+";
+    let config = serde_yaml::from_str::<Mapping>(config).unwrap();
+    let map = fingerprints(&config);
+
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["a"], "vless://example.com:443");
+    assert_eq!(map["b"], "trojan://1.2.3.4:8443");
+}
+
+#[test]
+fn test_core_history() {
+    let data = serde_json::json!({
+        "proxies": {
+            "a": {
+                "type": "Vless",
+                "history": [{"time": "2023-10-01T10:00:00Z", "delay": 120}],
+                "extra": {
+                    "https://www.gstatic.com/generate_204": {
+                        "history": [{"time": "2023-10-01T10:05:00+00:00", "delay": 0}]
+                    }
+                }
+            },
+            "b": { "type": "Trojan", "history": [] },
+            "auto": {
+                "type": "URLTest",
+                "all": ["a", "b"],
+                "history": [{"time": "2023-10-01T10:00:00Z", "delay": 120}]
+            }
+        }
+    });
+
+    let mut map = parse_core_history(&data);
+    assert_eq!(map.len(), 1);
+    let delays = map.remove("a").unwrap();
+    assert_eq!(delays.len(), 2);
+    assert_eq!(delays[0].time, 1696154400);
+
+    let mut history = vec![];
+    assert!(merge_core_history(&mut history, delays.clone()));
+    assert!(!merge_core_history(&mut history, delays));
+    assert_eq!(history.len(), 2);
+
+    let item = MeasureCacheItem {
+        info: MeasureInfo {
+            name: "a".into(),
+            ..MeasureInfo::default()
+        },
+        core_history: history,
+        ..MeasureCacheItem::default()
+    };
+    let info = item.merged_info();
+    assert_eq!(info.latency.unwrap().median, 120);
+    assert_eq!(info.updated, 1696154700);
+    assert!(info.error.is_none());
+}
@@ -0,0 +1,184 @@
+use super::{delay, measure::*};
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// the max number of measurements running at the same time
+/// the throughput tests are still run one by one
+const MAX_RUNNING: usize = 4;
+
+/// the higher one runs first
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum MeasurePriority {
+    Scheduled,
+    Group,
+    Single,
+}
+
+type JobKey = (String, MeasureMode);
+
+struct MeasureJob {
+    key: JobKey,
+    priority: MeasurePriority,
+    waiters: Vec<oneshot::Sender<MeasureInfo>>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// in the order of arrival
+    pending: Vec<MeasureJob>,
+
+    /// the waiters of the running jobs
+    running: HashMap<JobKey, Vec<oneshot::Sender<MeasureInfo>>>,
+}
+
+pub struct MeasureQueue {
+    state: Arc<Mutex<QueueState>>,
+}
+
+impl MeasureQueue {
+    pub fn global() -> &'static MeasureQueue {
+        static QUEUE: OnceCell<MeasureQueue> = OnceCell::new();
+
+        QUEUE.get_or_init(|| MeasureQueue {
+            state: Arc::new(Mutex::new(QueueState::default())),
+        })
+    }
+
+    /// enqueue and wait for the result
+    pub async fn measure(
+        &'static self,
+        name: &str,
+        mode: MeasureMode,
+        priority: MeasurePriority,
+    ) -> Result<MeasureInfo> {
+        match self.enqueue(name, mode, priority).await {
+            Ok(info) => Ok(info),
+            Err(_) => bail!("the measurement of \"{name}\" was dropped"),
+        }
+    }
+
+    /// enqueue all the proxies and wait for all of them
+    pub async fn measure_all(
+        &'static self,
+        names: Vec<String>,
+        mode: MeasureMode,
+        priority: MeasurePriority,
+    ) -> Vec<MeasureInfo> {
+        let jobs = names
+            .iter()
+            .map(|name| self.measure(name, mode, priority))
+            .collect::<Vec<_>>();
+
+        futures::future::join_all(jobs)
+            .await
+            .into_iter()
+            .zip(names)
+            .map(|(res, name)| match res {
+                Ok(info) => info,
+                Err(err) => MeasureInfo {
+                    name,
+                    error: Some(err.to_string()),
+                    ..MeasureInfo::default()
+                },
+            })
+            .collect()
+    }
+
+    /// the same request for the proxy is coalesced
+    /// the pending job takes the higher priority
+    fn enqueue(
+        &'static self,
+        name: &str,
+        mode: MeasureMode,
+        priority: MeasurePriority,
+    ) -> oneshot::Receiver<MeasureInfo> {
+        let (tx, rx) = oneshot::channel();
+        let key = (name.to_string(), mode);
+
+        // the sender is dropped so the waiter gets an error
+        if Measure::global().is_cancelled() {
+            return rx;
+        }
+
+        let mut state = self.state.lock();
+        if let Some(waiters) = state.running.get_mut(&key) {
+            waiters.push(tx);
+        } else if let Some(job) = state.pending.iter_mut().find(|job| job.key == key) {
+            job.priority = job.priority.max(priority);
+            job.waiters.push(tx);
+        } else {
+            state.pending.push(MeasureJob {
+                key,
+                priority,
+                waiters: vec![tx],
+            });
+        }
+        drop(state);
+
+        self.dispatch();
+        rx
+    }
+
+    /// drop the pending jobs and stop the running ones
+    pub fn cancel(&self) {
+        Measure::global().cancel();
+        self.state.lock().pending.clear();
+    }
+
+    /// start the pending jobs by priority until the running ones are full
+    fn dispatch(&'static self) {
+        if Measure::global().is_cancelled() {
+            return;
+        }
+
+        let mut state = self.state.lock();
+
+        while state.running.len() < MAX_RUNNING {
+            let next = state
+                .pending
+                .iter()
+                .enumerate()
+                // the earlier one wins among the same priority
+                .max_by(|(ia, a), (ib, b)| a.priority.cmp(&b.priority).then(ib.cmp(ia)))
+                .map(|(index, _)| index);
+
+            let job = match next {
+                Some(index) => state.pending.remove(index),
+                None => break,
+            };
+
+            let key = job.key.clone();
+            state.running.insert(job.key, job.waiters);
+
+            tauri::async_runtime::spawn(async move {
+                let info = self.run(&key).await;
+
+                let waiters = { self.state.lock().running.remove(&key) };
+                waiters.unwrap_or_default().into_iter().for_each(|tx| {
+                    let _ = tx.send(info.clone());
+                });
+
+                self.dispatch();
+            });
+        }
+    }
+
+    async fn run(&self, key: &JobKey) -> MeasureInfo {
+        let (name, mode) = key;
+
+        match delay::test_urls().into_iter().next() {
+            Some(url) => Measure::global().measure(name, *mode, &url).await,
+            None => MeasureInfo {
+                name: name.clone(),
+                error: Some("no latency test url".into()),
+                ..MeasureInfo::default()
+            },
+        }
+    }
+}
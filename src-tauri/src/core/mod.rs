@@ -1,12 +1,56 @@
+pub mod activation;
+pub mod app_status;
+pub mod chains;
 pub mod clash_api;
+pub mod clash_socket;
+pub mod clipboard;
 mod core;
+pub mod data_budget;
+pub mod delay;
+pub mod direct;
+pub mod distance;
+pub mod downloader;
+pub mod effective;
+pub mod expiry;
 pub mod handle;
+pub mod hook;
 pub mod hotkey;
+mod installer;
+pub mod link_import;
+pub mod load_balance;
+pub mod log_alert;
 pub mod logger;
 pub mod manager;
+pub mod measure;
+pub mod measure_batch;
+pub mod measure_cache;
+pub mod measure_queue;
+pub mod notification;
+pub mod pause;
+pub mod preflight;
+pub mod netinfo;
+pub mod node_kind;
+pub mod node_meta;
+pub mod proxy_query;
+pub mod rank;
+pub mod rate_limit;
+pub mod reach;
+pub mod recent;
+pub mod region;
+pub mod remote_control;
+pub mod route_check;
+pub mod scan_summary;
+pub mod schedule;
+pub mod socks;
+pub mod speedtest;
+pub mod state_events;
 pub mod sysopt;
 pub mod timer;
+pub mod trace;
 pub mod tray;
+pub mod unlock;
+pub mod updater;
+pub mod usage;
 pub mod win_service;
 
 pub use self::core::*;
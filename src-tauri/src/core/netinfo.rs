@@ -0,0 +1,169 @@
+use super::speedtest;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::net::UdpSocket;
+use std::process::Command;
+use sysinfo::{NetworkExt, System, SystemExt};
+use tauri::regex::Regex;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+/// the interface name prefixes of vpn and tun adapters
+const TUN_PREFIXES: [&str; 8] = ["tun", "utun", "tap", "wg", "ppp", "ipsec", "meta", "clash"];
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct NetworkInfo {
+    /// the local addresses used for the outbound traffic
+    pub local_ips: Vec<String>,
+
+    pub gateway: Option<String>,
+
+    /// the interface of the default route
+    /// it is the interface address on windows
+    pub interface: Option<String>,
+
+    /// the public ip of the direct connection
+    pub public_ip: Option<String>,
+
+    pub isp: Option<String>,
+
+    /// the vpn or tun adapters found
+    pub tun_interfaces: Vec<String>,
+
+    /// whether a vpn or tun adapter is active, including the clash tun mode
+    pub tun_active: bool,
+
+    /// the error message of the public ip lookup
+    pub error: Option<String>,
+}
+
+pub async fn get_network_info() -> NetworkInfo {
+    let (gateway, interface) = default_route().unwrap_or_else(|err| {
+        log::debug!(target: "app", "failed to get the default route, {err}");
+        (None, None)
+    });
+
+    let tun_interfaces = tun_interfaces();
+    let tun_mode = { Config::verge().latest().enable_tun_mode.unwrap_or(false) };
+
+    let mut info = NetworkInfo {
+        local_ips: local_ips(),
+        gateway,
+        interface,
+        tun_active: tun_mode || !tun_interfaces.is_empty(),
+        tun_interfaces,
+        ..NetworkInfo::default()
+    };
+
+    match speedtest::fetch_client(false).await {
+        Ok(client) => {
+            info.public_ip = client.ip;
+            info.isp = client.isp;
+        }
+        Err(err) => info.error = Some(err.to_string()),
+    }
+
+    info
+}
+
+/// the local address picked by the os for the outbound traffic
+/// no packet is sent by `connect` on udp
+pub fn local_ips() -> Vec<String> {
+    ["1.1.1.1:80", "[2606:4700:4700::1111]:80"]
+        .into_iter()
+        .filter_map(|target| {
+            let bind = match target.starts_with('[') {
+                true => "[::]:0",
+                false => "0.0.0.0:0",
+            };
+            let socket = UdpSocket::bind(bind).ok()?;
+            socket.connect(target).ok()?;
+            Some(socket.local_addr().ok()?.ip().to_string())
+        })
+        .collect()
+}
+
+pub fn tun_interfaces() -> Vec<String> {
+    let mut system = System::new();
+    system.refresh_networks_list();
+
+    system
+        .networks()
+        .into_iter()
+        .filter(|(_, data)| data.total_received() > 0 || data.total_transmitted() > 0)
+        .map(|(name, _)| name.clone())
+        .filter(|name| is_tun_interface(name))
+        .collect()
+}
+
+/// guess by the name of the interface
+pub fn is_tun_interface(name: &str) -> bool {
+    let name = name.to_lowercase();
+    TUN_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// get the (gateway, interface) of the default route
+pub fn default_route() -> Result<(Option<String>, Option<String>)> {
+    #[cfg(target_os = "linux")]
+    let output = Command::new("ip")
+        .args(["route", "show", "default"])
+        .output()?;
+
+    #[cfg(target_os = "macos")]
+    let output = Command::new("route")
+        .args(["-n", "get", "default"])
+        .output()?;
+
+    #[cfg(target_os = "windows")]
+    let output = Command::new("route")
+        .args(["print", "-4", "0.0.0.0"])
+        .creation_flags(0x08000000)
+        .output()?;
+
+    if !output.status.success() {
+        bail!("failed to run the route command");
+    }
+
+    Ok(parse_route(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// parse the output of the route command
+fn parse_route(output: &str) -> (Option<String>, Option<String>) {
+    // linux: `default via 192.168.1.1 dev wlan0 proto dhcp`
+    let linux = Regex::new(r"default via (\S+) dev (\S+)").unwrap();
+    if let Some(caps) = linux.captures(output) {
+        return (Some(caps[1].into()), Some(caps[2].into()));
+    }
+
+    // macos: `gateway: 192.168.1.1` and `interface: en0`
+    let gateway = Regex::new(r"gateway:\s*(\S+)").unwrap();
+    let interface = Regex::new(r"interface:\s*(\S+)").unwrap();
+    let gateway = gateway.captures(output).map(|caps| caps[1].to_string());
+    let interface = interface.captures(output).map(|caps| caps[1].to_string());
+    if gateway.is_some() || interface.is_some() {
+        return (gateway, interface);
+    }
+
+    // windows: `0.0.0.0  0.0.0.0  192.168.1.1  192.168.1.100  25`
+    let windows = Regex::new(r"(?m)^\s*0\.0\.0\.0\s+0\.0\.0\.0\s+(\S+)\s+(\S+)").unwrap();
+    match windows.captures(output) {
+        Some(caps) => (Some(caps[1].into()), Some(caps[2].into())),
+        None => (None, None),
+    }
+}
+
+#[test]
+fn test_parse_route() {
+    let linux = "default via 192.168.1.1 dev wlan0 proto dhcp metric 600\n";
+    let macos =
+        "   route to: default\ndestination: default\n    gateway: 10.0.0.1\n  interface: en0\n";
+    let windows = "Active Routes:\nNetwork Destination        Netmask          Gateway       Interface  Metric\n          0.0.0.0          0.0.0.0      192.168.1.1    192.168.1.100     25\n";
+
+    let some = |a: &str, b: &str| (Some(a.to_string()), Some(b.to_string()));
+    assert_eq!(parse_route(linux), some("192.168.1.1", "wlan0"));
+    assert_eq!(parse_route(macos), some("10.0.0.1", "en0"));
+    assert_eq!(parse_route(windows), some("192.168.1.1", "192.168.1.100"));
+    assert_eq!(parse_route(""), (None, None));
+}
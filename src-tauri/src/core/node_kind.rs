@@ -0,0 +1,141 @@
+use crate::config::Config;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// the protocol and the transport of a node, for the filtering
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct NodeKind {
+    /// such as `vmess`, `vless`, `trojan`, `ss` or `hysteria2`
+    pub protocol: String,
+
+    /// such as `tcp`, `ws`, `grpc`, `h2` or `quic`
+    pub transport: String,
+
+    /// `reality`, `tls` or `none`
+    pub security: String,
+}
+
+/// the kind of a proxy of the clash config
+pub fn node_kind(proxy: &Mapping) -> Option<NodeKind> {
+    let text = |key: &str| {
+        proxy
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_lowercase)
+    };
+
+    let protocol = normalize_protocol(&text("type")?);
+    let quic = matches!(protocol.as_str(), "hysteria" | "hysteria2" | "tuic");
+
+    let transport = match quic {
+        true => "quic".into(),
+        false => text("network").unwrap_or_else(|| "tcp".into()),
+    };
+
+    let tls = quic || protocol == "trojan" || matches!(proxy.get("tls"), Some(Value::Bool(true)));
+    let security = match proxy.contains_key("reality-opts") {
+        true => "reality",
+        false if tls => "tls",
+        false => "none",
+    };
+
+    Some(NodeKind {
+        protocol,
+        transport,
+        security: security.into(),
+    })
+}
+
+/// the kinds of the nodes in the runtime config by the proxy name
+pub fn current_kinds() -> HashMap<String, NodeKind> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+
+    runtime
+        .config
+        .as_ref()
+        .and_then(|config| config.get("proxies"))
+        .and_then(|val| val.as_sequence())
+        .map_or(HashMap::new(), |seq| {
+            seq.iter()
+                .filter_map(|item| {
+                    let item = item.as_mapping()?;
+                    let name = item.get("name")?.as_str()?;
+                    Some((name.into(), node_kind(item)?))
+                })
+                .collect()
+        })
+}
+
+/// add the `kind` to the nodes of GET /proxies, the groups are skipped
+/// the nodes of the providers only get the protocol reported by the core
+pub fn merge_kinds(proxies: &mut serde_json::Value, kinds: &HashMap<String, NodeKind>) {
+    let proxies = match proxies.get_mut("proxies").and_then(|p| p.as_object_mut()) {
+        Some(proxies) => proxies,
+        None => return,
+    };
+
+    for (name, proxy) in proxies.iter_mut() {
+        let proxy = match proxy.as_object_mut() {
+            Some(proxy) if !proxy.contains_key("all") => proxy,
+            _ => continue,
+        };
+
+        let kind = match kinds.get(name) {
+            Some(kind) => serde_json::to_value(kind).ok(),
+            None => proxy
+                .get("type")
+                .and_then(|t| t.as_str())
+                .map(|t| serde_json::json!({ "protocol": normalize_protocol(&t.to_lowercase()) })),
+        };
+        if let Some(kind) = kind {
+            proxy.insert("kind".into(), kind);
+        }
+    }
+}
+
+/// the core reports `Shadowsocks` for the `ss` of the config
+pub fn normalize_protocol(protocol: &str) -> String {
+    match protocol {
+        "shadowsocks" => "ss".into(),
+        "shadowsocksr" => "ssr".into(),
+        protocol => protocol.into(),
+    }
+}
+
+#[test]
+fn test_node_kind() {
+    let proxy = |yaml: &str| serde_yaml::from_str::<Mapping>(yaml).unwrap();
+
+    let kind = node_kind(&proxy(
+        "{name: a, type: vless, network: grpc, tls: true, reality-opts: {public-key: k}}",
+    ))
+    .unwrap();
+    assert_eq!(kind.protocol, "vless");
+    assert_eq!(kind.transport, "grpc");
+    assert_eq!(kind.security, "reality");
+
+    let kind = node_kind(&proxy("{name: b, type: vmess, network: ws}")).unwrap();
+    assert_eq!(
+        (kind.transport.as_str(), kind.security.as_str()),
+        ("ws", "none")
+    );
+
+    let kind = node_kind(&proxy("{name: c, type: hysteria2}")).unwrap();
+    assert_eq!(
+        (kind.transport.as_str(), kind.security.as_str()),
+        ("quic", "tls")
+    );
+    assert!(node_kind(&proxy("{name: d}")).is_none());
+
+    let mut proxies = serde_json::json!({
+        "proxies": {
+            "auto": {"name": "auto", "type": "URLTest", "all": ["e"]},
+            "e": {"name": "e", "type": "Shadowsocks"}
+        }
+    });
+    merge_kinds(&mut proxies, &HashMap::new());
+    assert_eq!(proxies["proxies"]["e"]["kind"]["protocol"], "ss");
+    assert!(proxies["proxies"]["auto"].get("kind").is_none());
+}
@@ -0,0 +1,166 @@
+use super::{clash_api, node_kind, region};
+use crate::config::{node_fingerprint, Config};
+use crate::utils::{dirs, help};
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// the user data of a node
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct NodeMeta {
+    pub favorite: Option<bool>,
+
+    pub note: Option<String>,
+
+    /// shown instead of the proxy name
+    pub alias: Option<String>,
+}
+
+impl NodeMeta {
+    fn is_empty(&self) -> bool {
+        !self.favorite.unwrap_or(false)
+            && self.note.as_ref().filter(|n| !n.is_empty()).is_none()
+            && self.alias.as_ref().filter(|a| !a.is_empty()).is_none()
+    }
+}
+
+/// the metadata keyed by the node fingerprint, saved in `node_meta.yaml`
+/// so that it survives the renaming by the subscription updates
+pub struct NodeMetaStore {
+    /// loaded on the first use
+    items: Arc<Mutex<Option<BTreeMap<String, NodeMeta>>>>,
+}
+
+impl NodeMetaStore {
+    pub fn global() -> &'static NodeMetaStore {
+        static STORE: OnceCell<NodeMetaStore> = OnceCell::new();
+
+        STORE.get_or_init(|| NodeMetaStore {
+            items: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn load() -> BTreeMap<String, NodeMeta> {
+        match dirs::node_meta_path().and_then(|path| help::read_yaml(&path)) {
+            Ok(items) => items,
+            Err(err) => {
+                log::debug!(target: "app", "failed to read the node metadata, {err}");
+                BTreeMap::new()
+            }
+        }
+    }
+
+    /// the metadata of the nodes in the current profile by the proxy name
+    pub fn get_current(&self) -> HashMap<String, NodeMeta> {
+        let fingerprints = current_fingerprints();
+
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        fingerprints
+            .into_iter()
+            .filter_map(|(name, fingerprint)| Some((name, items.get(&fingerprint)?.clone())))
+            .collect()
+    }
+
+    /// set the metadata of the proxy in the current profile
+    /// the empty metadata is removed
+    pub fn set(&self, name: &str, meta: NodeMeta) -> Result<()> {
+        let fingerprint = match current_fingerprints().remove(name) {
+            Some(fingerprint) => fingerprint,
+            None => bail!("failed to find the node \"{name}\""),
+        };
+
+        let mut items = self.items.lock();
+        let items = items.get_or_insert_with(Self::load);
+
+        match meta.is_empty() {
+            true => items.remove(&fingerprint),
+            false => items.insert(fingerprint, meta),
+        };
+
+        help::save_yaml(&dirs::node_meta_path()?, items, None)
+    }
+}
+
+/// GET /proxies of the core, with the `meta`, the `region` and the `kind` of each node
+pub async fn get_proxies() -> Result<serde_json::Value> {
+    let mut proxies = clash_api::get_proxies().await?;
+    merge_meta(&mut proxies, &NodeMetaStore::global().get_current());
+    region::merge_regions(&mut proxies, &region::current_exits());
+    node_kind::merge_kinds(&mut proxies, &node_kind::current_kinds());
+    Ok(proxies)
+}
+
+fn merge_meta(proxies: &mut serde_json::Value, metas: &HashMap<String, NodeMeta>) {
+    let proxies = match proxies.get_mut("proxies").and_then(|p| p.as_object_mut()) {
+        Some(proxies) => proxies,
+        None => return,
+    };
+
+    for (name, meta) in metas.iter() {
+        if let Some(proxy) = proxies.get_mut(name).and_then(|p| p.as_object_mut()) {
+            if let Ok(meta) = serde_json::to_value(meta) {
+                proxy.insert("meta".into(), meta);
+            }
+        }
+    }
+}
+
+/// the nodes of the providers are not in the runtime config
+fn current_fingerprints() -> HashMap<String, String> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+
+    runtime
+        .config
+        .as_ref()
+        .and_then(|config| config.get("proxies"))
+        .and_then(|val| val.as_sequence())
+        .map_or(HashMap::new(), |seq| {
+            seq.iter()
+                .filter_map(|item| {
+                    let item = item.as_mapping()?;
+                    let name = item.get("name")?.as_str()?;
+                    Some((name.into(), node_fingerprint(item)?))
+                })
+                .collect()
+        })
+}
+
+#[test]
+fn test_merge_meta() {
+    let mut proxies = serde_json::json!({
+        "proxies": {
+            "de": {"name": "de", "type": "Vless"},
+            "us": {"name": "us", "type": "Trojan"}
+        }
+    });
+
+    let mut metas = HashMap::new();
+    metas.insert(
+        "de".to_string(),
+        NodeMeta {
+            favorite: Some(true),
+            alias: Some("Berlin".into()),
+            ..NodeMeta::default()
+        },
+    );
+    metas.insert("missing".to_string(), NodeMeta::default());
+
+    merge_meta(&mut proxies, &metas);
+    assert_eq!(proxies["proxies"]["de"]["meta"]["favorite"], true);
+    assert_eq!(proxies["proxies"]["de"]["meta"]["alias"], "Berlin");
+    assert!(proxies["proxies"]["us"].get("meta").is_none());
+
+    assert!(NodeMeta::default().is_empty());
+    assert!(NodeMeta {
+        note: Some(String::new()),
+        favorite: Some(false),
+        ..NodeMeta::default()
+    }
+    .is_empty());
+}
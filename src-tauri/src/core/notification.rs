@@ -0,0 +1,139 @@
+use super::handle::Handle;
+use super::hook::{self, HookEvent};
+use crate::config::{Config, IVergeNotification, PrfExtra};
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use tauri::api::notification::Notification;
+
+/// the subscription is nearing the quota if the usage reaches the ratio
+const QUOTA_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyTrigger {
+    CoreCrash,
+    ProfileUpdate,
+    Subscription,
+    Measurement,
+    LogAlert,
+    ProfileRollback,
+}
+
+impl NotifyTrigger {
+    /// the profile update and the measurement are off by default
+    fn enabled(&self) -> bool {
+        let config = { Config::verge().latest().notification.clone() };
+        let IVergeNotification {
+            core_crash,
+            profile_update,
+            subscription,
+            measurement,
+            log_alert,
+            profile_rollback,
+        } = config.unwrap_or_default();
+
+        match self {
+            Self::CoreCrash => core_crash.unwrap_or(true),
+            Self::ProfileUpdate => profile_update.unwrap_or(false),
+            Self::Subscription => subscription.unwrap_or(true),
+            Self::Measurement => measurement.unwrap_or(false),
+            Self::LogAlert => log_alert.unwrap_or(true),
+            Self::ProfileRollback => profile_rollback.unwrap_or(true),
+        }
+    }
+}
+
+/// show the os notification if the trigger is enabled
+pub fn notify(trigger: NotifyTrigger, title: &str, body: &str) {
+    if !trigger.enabled() {
+        return;
+    }
+
+    if let Err(err) = show(title, body) {
+        log::error!(target: "app", "failed to notify {trigger:?}, {err}");
+    }
+}
+
+/// show the notification regardless of the triggers
+pub fn test() -> Result<()> {
+    show("Hiddify", "The notification works")
+}
+
+fn show(title: &str, body: &str) -> Result<()> {
+    let identifier = {
+        let app_handle = Handle::global().app_handle.lock();
+        match app_handle.as_ref() {
+            Some(app_handle) => app_handle.config().tauri.bundle.identifier.clone(),
+            None => bail!("the app is not ready"),
+        }
+    };
+
+    Notification::new(identifier)
+        .title(title)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum QuotaLevel {
+    Normal,
+    Nearing,
+    Exceeded,
+}
+
+impl QuotaLevel {
+    fn of(extra: &PrfExtra) -> QuotaLevel {
+        let used = extra.upload + extra.download;
+        match extra.total {
+            0 => QuotaLevel::Normal,
+            total if used >= total => QuotaLevel::Exceeded,
+            total if used as f64 >= total as f64 * QUOTA_RATIO => QuotaLevel::Nearing,
+            _ => QuotaLevel::Normal,
+        }
+    }
+}
+
+/// the quota level of each profile uid at the last update
+/// alert once for each level, again after the traffic is reset
+fn quota_levels() -> &'static Mutex<HashMap<String, QuotaLevel>> {
+    static LEVELS: OnceCell<Mutex<HashMap<String, QuotaLevel>>> = OnceCell::new();
+    LEVELS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// warn if the subscription is nearing the quota
+/// the expiry is reminded by `ExpiryReminder`
+pub fn check_subscription(uid: &str, name: &str, extra: &PrfExtra) {
+    let level = QuotaLevel::of(extra);
+    let last = quota_levels().lock().insert(uid.into(), level);
+    if level <= last.unwrap_or(QuotaLevel::Normal) {
+        return;
+    }
+
+    if level == QuotaLevel::Exceeded {
+        let message = format!("The traffic quota of {name} is exceeded");
+        hook::fire(
+            HookEvent::QuotaExceeded,
+            vec![("profile", name.into()), ("message", message)],
+        );
+    }
+
+    let percent = (extra.upload + extra.download) * 100 / extra.total;
+    let body = format!("{percent}% of the traffic is used");
+    notify(NotifyTrigger::Subscription, name, &body);
+}
+
+#[test]
+fn test_quota_level() {
+    let extra = |used: usize, total: usize| PrfExtra {
+        download: used,
+        total,
+        ..Default::default()
+    };
+
+    assert_eq!(QuotaLevel::of(&extra(100, 0)), QuotaLevel::Normal);
+    assert_eq!(QuotaLevel::of(&extra(89, 100)), QuotaLevel::Normal);
+    assert_eq!(QuotaLevel::of(&extra(90, 100)), QuotaLevel::Nearing);
+    assert_eq!(QuotaLevel::of(&extra(120, 100)), QuotaLevel::Exceeded);
+}
@@ -0,0 +1,230 @@
+use super::handle::Handle;
+use crate::config::{Config, IVerge};
+use crate::{feat, log_err, utils::kv::KvStore};
+use anyhow::{bail, Result};
+use chrono::Local;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+/// the longest pause, in seconds
+const MAX_PAUSE: u64 = 24 * 3600;
+
+/// the tray shows the remaining time, so wake up this often
+const TICK: i64 = 30;
+
+const KV_NAMESPACE: &str = "proxy";
+
+const KV_KEY: &str = "pause";
+
+/// what is turned back on at the end of the pause
+/// saved in the kv store, a pause over a restart still ends
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PauseState {
+    /// unix timestamp in seconds
+    pub until: i64,
+    pub system_proxy: bool,
+    pub tun_mode: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PauseStatus {
+    pub until: i64,
+
+    /// seconds
+    pub remaining: i64,
+    pub system_proxy: bool,
+    pub tun_mode: bool,
+}
+
+/// turn off the system proxy (and the tun mode) for a while
+/// for the sites that only work without the proxy
+pub struct ProxyPause {
+    state: Arc<Mutex<Option<PauseState>>>,
+}
+
+impl ProxyPause {
+    pub fn global() -> &'static ProxyPause {
+        static PAUSE: OnceCell<ProxyPause> = OnceCell::new();
+
+        PAUSE.get_or_init(|| ProxyPause {
+            state: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// pick up the pause saved before the exit
+    /// an ended pause is restored in the config before the core and the sysproxy start
+    pub fn init(&'static self) -> Result<()> {
+        let state = match KvStore::global().get::<PauseState>(KV_NAMESPACE, KV_KEY)? {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+
+        if state.until > Local::now().timestamp() {
+            *self.state.lock() = Some(state.clone());
+            tauri::async_runtime::spawn(async move { self.run(state).await });
+            return Ok(());
+        }
+
+        log::info!(target: "app", "the proxy pause ended while the app was closed");
+        KvStore::global().delete(KV_NAMESPACE, KV_KEY)?;
+        // nothing else writes the config before the setup
+        let tx = tauri::async_runtime::block_on(Config::verge().begin());
+        tx.draft().patch_config(restore_patch(&state));
+        tx.commit()
+    }
+
+    /// pause for the seconds, pausing again extends the pause
+    /// the tun mode is paused only with `tun`
+    pub async fn pause(&'static self, duration: u64, tun: bool) -> Result<PauseStatus> {
+        if duration == 0 || duration > MAX_PAUSE {
+            bail!("the pause should be 1 to {MAX_PAUSE} seconds");
+        }
+
+        let (system_proxy, tun_mode) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.enable_system_proxy.unwrap_or(false),
+                verge.enable_tun_mode.unwrap_or(false),
+            )
+        };
+
+        let until = Local::now().timestamp() + duration as i64;
+        let current = self.state.lock().clone();
+        let state = match merge_pause(current.as_ref(), system_proxy, tun_mode && tun, until) {
+            Some(state) => state,
+            None => bail!("neither the system proxy nor the tun mode is enabled"),
+        };
+
+        if system_proxy || (tun_mode && tun) {
+            feat::patch_verge(IVerge {
+                enable_system_proxy: system_proxy.then_some(false),
+                enable_tun_mode: (tun_mode && tun).then_some(false),
+                ..IVerge::default()
+            })
+            .await?;
+        }
+
+        KvStore::global().set(KV_NAMESPACE, KV_KEY, &state)?;
+        *self.state.lock() = Some(state.clone());
+        log::info!(target: "app", "pause the proxy for {duration}s");
+
+        Handle::refresh_verge();
+        log_err!(Handle::update_systray_part());
+        tauri::async_runtime::spawn(async move { self.run(state).await });
+
+        self.status()
+            .ok_or_else(|| anyhow::anyhow!("the pause has ended"))
+    }
+
+    /// end the pause now and turn the proxy back on
+    pub async fn resume(&self) -> Result<()> {
+        let state = match self.state.lock().take() {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        log_err!(KvStore::global().delete(KV_NAMESPACE, KV_KEY));
+        log::info!(target: "app", "resume the proxy");
+
+        feat::patch_verge(restore_patch(&state)).await?;
+        Handle::refresh_verge();
+        log_err!(Handle::update_systray_part());
+        Ok(())
+    }
+
+    /// drop the pause without restoring, the proxy is turned on by hand
+    pub fn cancel(&self) {
+        if self.state.lock().take().is_some() {
+            log_err!(KvStore::global().delete(KV_NAMESPACE, KV_KEY));
+            log::info!(target: "app", "the proxy pause is cancelled");
+        }
+    }
+
+    /// none if not paused
+    pub fn status(&self) -> Option<PauseStatus> {
+        let state = self.state.lock();
+        let state = state.as_ref()?;
+
+        Some(PauseStatus {
+            until: state.until,
+            remaining: (state.until - Local::now().timestamp()).max(0),
+            system_proxy: state.system_proxy,
+            tun_mode: state.tun_mode,
+        })
+    }
+
+    /// wait for the end of the pause, updating the tray on the way
+    /// stops if the pause is extended, resumed or cancelled meanwhile
+    async fn run(&self, state: PauseState) {
+        loop {
+            if self.state.lock().as_ref() != Some(&state) {
+                return;
+            }
+
+            let remaining = state.until - Local::now().timestamp();
+            if remaining <= 0 {
+                break;
+            }
+            log_err!(Handle::update_systray_part());
+            sleep(Duration::from_secs(remaining.min(TICK) as u64)).await;
+        }
+
+        if let Err(err) = self.resume().await {
+            log::error!(target: "app", "failed to resume the proxy");
+            log::error!(target: "app", "{err}");
+        }
+    }
+}
+
+/// the items of the current pause stay paused when it is extended
+/// none if there is nothing to pause
+fn merge_pause(
+    current: Option<&PauseState>,
+    system_proxy: bool,
+    tun_mode: bool,
+    until: i64,
+) -> Option<PauseState> {
+    let state = PauseState {
+        until,
+        system_proxy: system_proxy || current.map_or(false, |c| c.system_proxy),
+        tun_mode: tun_mode || current.map_or(false, |c| c.tun_mode),
+    };
+    (state.system_proxy || state.tun_mode).then_some(state)
+}
+
+fn restore_patch(state: &PauseState) -> IVerge {
+    IVerge {
+        enable_system_proxy: state.system_proxy.then_some(true),
+        enable_tun_mode: state.tun_mode.then_some(true),
+        ..IVerge::default()
+    }
+}
+
+#[test]
+fn test_merge_pause() {
+    assert!(merge_pause(None, false, false, 100).is_none());
+
+    let state = merge_pause(None, true, false, 100).unwrap();
+    assert_eq!((state.system_proxy, state.tun_mode), (true, false));
+
+    // extended after the system proxy is already off
+    let paused = PauseState {
+        until: 100,
+        system_proxy: true,
+        tun_mode: true,
+    };
+    let state = merge_pause(Some(&paused), false, false, 200).unwrap();
+    assert_eq!(state.until, 200);
+    assert_eq!((state.system_proxy, state.tun_mode), (true, true));
+
+    let patch = restore_patch(&PauseState {
+        until: 0,
+        system_proxy: false,
+        tun_mode: true,
+    });
+    assert_eq!(patch.enable_system_proxy, None);
+    assert_eq!(patch.enable_tun_mode, Some(true));
+}
@@ -0,0 +1,292 @@
+use super::{clash_api::CheckLevel, clash_api::CoreCheckIssue, CoreManager};
+use crate::{config::Config, enhance};
+use anyhow::Result;
+use serde::Serialize;
+use serde_yaml::Mapping;
+use std::{collections::HashMap, fmt};
+
+/// the keys of the ports the core listens on
+const PORT_KEYS: [&str; 5] = [
+    "port",
+    "socks-port",
+    "mixed-port",
+    "redir-port",
+    "tproxy-port",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightCheck {
+    /// the profile file is a yaml mapping
+    Yaml,
+
+    /// at least one proxy or proxy provider
+    Proxies,
+
+    /// the ports are not listed twice or taken by another program
+    Ports,
+
+    /// `clash -t` passes
+    Core,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightStatus {
+    Passed,
+    Failed,
+
+    /// not run after the yaml check failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightResult {
+    pub check: PreflightCheck,
+    pub status: PreflightStatus,
+
+    /// why the check failed
+    pub messages: Vec<String>,
+}
+
+impl PreflightResult {
+    fn new(check: PreflightCheck, messages: Vec<String>) -> Self {
+        let status = match messages.is_empty() {
+            true => PreflightStatus::Passed,
+            false => PreflightStatus::Failed,
+        };
+        Self {
+            check,
+            status,
+            messages,
+        }
+    }
+}
+
+/// the checks of a profile before it is activated
+/// also the error of the refused switch
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    pub uid: String,
+    pub passed: bool,
+    pub results: Vec<PreflightResult>,
+
+    /// all the issues of `clash -t`, the warnings too
+    pub core_issues: Vec<CoreCheckIssue>,
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self
+            .results
+            .iter()
+            .filter(|result| result.status == PreflightStatus::Failed)
+            .map(|result| format!("{:?}: {}", result.check, result.messages.join(", ")))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "the profile failed the pre-flight checks, {}",
+            failed.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for PreflightReport {}
+
+/// check the profile without activating it
+pub fn preflight(uid: &String) -> Result<PreflightReport> {
+    let item = { Config::profiles().latest().get_item(uid)?.clone() };
+
+    let yaml = match item.read_file() {
+        Ok(data) => check_yaml(&data),
+        Err(err) => vec![format!("{err:#}")],
+    };
+    let mut results = vec![PreflightResult::new(PreflightCheck::Yaml, yaml)];
+    let mut core_issues = vec![];
+
+    if results[0].status == PreflightStatus::Failed {
+        for check in [
+            PreflightCheck::Proxies,
+            PreflightCheck::Ports,
+            PreflightCheck::Core,
+        ] {
+            results.push(PreflightResult {
+                check,
+                status: PreflightStatus::Skipped,
+                messages: vec![],
+            });
+        }
+    } else {
+        let (config, _, _) = enhance::enhance_profile(Some(uid));
+
+        results.push(PreflightResult::new(
+            PreflightCheck::Proxies,
+            check_proxies(&config),
+        ));
+        results.push(PreflightResult::new(
+            PreflightCheck::Ports,
+            check_ports(&config),
+        ));
+
+        let core = match CoreManager::global().test_enhanced(&config) {
+            Ok(issues) => {
+                let errors = issues
+                    .iter()
+                    .filter(|issue| issue.level >= CheckLevel::Error)
+                    .map(|issue| issue.to_string())
+                    .collect();
+                core_issues = issues;
+                errors
+            }
+            Err(err) => vec![format!("{err:#}")],
+        };
+        results.push(PreflightResult::new(PreflightCheck::Core, core));
+    }
+
+    let passed = results
+        .iter()
+        .all(|result| result.status != PreflightStatus::Failed);
+    Ok(PreflightReport {
+        uid: uid.clone(),
+        passed,
+        results,
+        core_issues,
+    })
+}
+
+/// the report as the error if any check failed
+pub fn require_preflight(uid: &String) -> Result<()> {
+    let report = preflight(uid)?;
+    if !report.passed {
+        log::warn!(target: "app", "refuse to activate the profile \"{uid}\", {report}");
+        return Err(report.into());
+    }
+    Ok(())
+}
+
+fn check_yaml(data: &str) -> Vec<String> {
+    match serde_yaml::from_str::<serde_yaml::Value>(data) {
+        Ok(value) if value.is_mapping() => vec![],
+        Ok(_) => vec!["the profile is not a yaml mapping".into()],
+        Err(err) => vec![err.to_string()],
+    }
+}
+
+fn check_proxies(config: &Mapping) -> Vec<String> {
+    let count = |key: &str| match config.get(key) {
+        Some(serde_yaml::Value::Sequence(seq)) => seq.len(),
+        Some(serde_yaml::Value::Mapping(map)) => map.len(),
+        _ => 0,
+    };
+
+    match count("proxies") + count("proxy-providers") {
+        0 => vec!["the profile has no proxy".into()],
+        _ => vec![],
+    }
+}
+
+/// the ports of the running config are held by the core itself
+fn check_ports(config: &Mapping) -> Vec<String> {
+    let running = {
+        let runtime = Config::runtime();
+        let runtime = runtime.latest();
+        runtime
+            .config
+            .as_ref()
+            .map(config_ports)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(_, port)| port)
+            .collect::<Vec<_>>()
+    };
+
+    let ports = config_ports(config);
+    let mut messages = conflicting_ports(&ports);
+    for (key, port) in ports.iter() {
+        if !running.contains(port) && !port_scanner::local_port_available(*port) {
+            messages.push(format!(
+                "the port {port} of {key} is used by another program"
+            ));
+        }
+    }
+    messages
+}
+
+/// the listening ports by the key, such as `mixed-port`, 0 is disabled
+fn config_ports(config: &Mapping) -> Vec<(String, u16)> {
+    let port = |value: &serde_yaml::Value| {
+        value
+            .as_u64()
+            .and_then(|port| u16::try_from(port).ok())
+            .filter(|port| *port > 0)
+    };
+
+    let mut ports = PORT_KEYS
+        .iter()
+        .filter_map(|key| Some((key.to_string(), port(config.get(*key)?)?)))
+        .collect::<Vec<_>>();
+
+    // such as `127.0.0.1:9090`
+    if let Some(controller) = config.get("external-controller").and_then(|c| c.as_str()) {
+        if let Some(port) = controller.rsplit(':').next().and_then(|p| p.parse().ok()) {
+            ports.push(("external-controller".into(), port));
+        }
+    }
+
+    let listeners = config.get("listeners").and_then(|l| l.as_sequence());
+    for listener in listeners.into_iter().flatten() {
+        let name = listener.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        if let Some(port) = listener.get("port").and_then(port) {
+            ports.push((format!("listener {name}"), port));
+        }
+    }
+    ports
+}
+
+fn conflicting_ports(ports: &[(String, u16)]) -> Vec<String> {
+    let mut keys = HashMap::<u16, Vec<&str>>::new();
+    for (key, port) in ports.iter() {
+        keys.entry(*port).or_default().push(key);
+    }
+
+    let mut messages = keys
+        .into_iter()
+        .filter(|(_, keys)| keys.len() > 1)
+        .map(|(port, keys)| format!("the port {port} is set by {}", keys.join(" and ")))
+        .collect::<Vec<_>>();
+    messages.sort();
+    messages
+}
+
+#[test]
+fn test_preflight_checks() {
+    let config = serde_yaml::from_str::<Mapping>(
+        r#"
+mixed-port: 7890
+socks-port: 0
+redir-port: 7890
+external-controller: 127.0.0.1:9090
+listeners:
+  - {name: node-a, type: mixed, port: 9090}
+proxy-providers:
+  sub: {type: http, url: "https://example.com"}
+"#,
+    )
+    .unwrap();
+
+    let ports = config_ports(&config);
+    assert_eq!(ports.len(), 4);
+    assert_eq!(
+        conflicting_ports(&ports),
+        vec![
+            "the port 7890 is set by mixed-port and redir-port",
+            "the port 9090 is set by external-controller and listener node-a",
+        ]
+    );
+    assert!(check_proxies(&config).is_empty());
+    assert_eq!(check_proxies(&Mapping::new()).len(), 1);
+
+    assert!(check_yaml("proxies: []").is_empty());
+    assert_eq!(check_yaml("- a").len(), 1);
+    assert_eq!(check_yaml("a: [").len(), 1);
+}
@@ -0,0 +1,396 @@
+use super::clash_api;
+use super::measure::MeasureInfo;
+use super::measure_cache::MeasureCache;
+use super::node_kind::{self, NodeKind};
+use super::node_meta::{NodeMeta, NodeMetaStore};
+use super::region::{self, NodeRegion};
+use crate::config::Config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// the default size of a page
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// the most rows of a page
+const MAX_LIMIT: usize = 500;
+
+/// the built-in proxies of the core, not the nodes of the profile
+const BUILTIN_TYPES: [&str; 5] = ["Direct", "Reject", "RejectDrop", "Compatible", "Pass"];
+
+/// the conditions are all required, the unset ones match any node
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ProxyFilter {
+    /// the keyword in the name or the alias, case insensitive
+    pub name: Option<String>,
+
+    /// the iso codes of the regions
+    pub regions: Option<Vec<String>>,
+
+    /// such as `vless` or `ss`
+    pub protocols: Option<Vec<String>>,
+
+    /// the latency range (ms), the nodes without the latency are left out if set
+    pub min_latency: Option<u64>,
+
+    pub max_latency: Option<u64>,
+
+    /// only the nodes of the group
+    pub group: Option<String>,
+
+    pub favorite: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxySortKey {
+    /// the order of the profile
+    #[default]
+    Default,
+    Name,
+    Latency,
+    Download,
+    Region,
+}
+
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ProxySort {
+    pub key: ProxySortKey,
+
+    /// the nodes without the value are last in both orders
+    pub desc: Option<bool>,
+}
+
+/// a node with the measurement and the metadata
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct ProxyRow {
+    pub name: String,
+
+    /// the type reported by the core, such as `Vless`
+    #[serde(rename = "type")]
+    pub ptype: String,
+
+    /// the kind of the nodes in the runtime config, only the protocol for the providers
+    pub protocol: String,
+
+    pub kind: Option<NodeKind>,
+
+    pub region: Option<NodeRegion>,
+
+    pub meta: Option<NodeMeta>,
+
+    /// the measured median, or the last health check of the core (ms)
+    pub latency: Option<u64>,
+
+    /// the measured median (bytes/s)
+    pub download: Option<u64>,
+
+    /// the error of the last measurement
+    pub error: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct ProxyPage {
+    /// the number of the nodes matching the filter
+    pub total: usize,
+
+    pub offset: usize,
+
+    pub items: Vec<ProxyRow>,
+}
+
+/// a page of the nodes of the running core, filtered and sorted
+pub async fn query_proxies(
+    filter: ProxyFilter,
+    sort: ProxySort,
+    offset: usize,
+    limit: usize,
+) -> Result<ProxyPage> {
+    let proxies = clash_api::get_proxies().await?;
+
+    let current = { Config::profiles().latest().get_current() };
+    let infos = match current {
+        Some(profile) => MeasureCache::global().get(&profile),
+        None => vec![],
+    };
+    let rows = proxy_rows(
+        &proxies,
+        &infos,
+        &NodeMetaStore::global().get_current(),
+        &node_kind::current_kinds(),
+    );
+
+    Ok(query(rows, &proxies, &filter, sort, offset, limit))
+}
+
+/// the nodes of GET /proxies in the order of `GLOBAL`, the groups are skipped
+pub fn proxy_rows(
+    proxies: &Value,
+    infos: &[MeasureInfo],
+    metas: &HashMap<String, NodeMeta>,
+    kinds: &HashMap<String, NodeKind>,
+) -> Vec<ProxyRow> {
+    let map = match proxies.get("proxies").and_then(|p| p.as_object()) {
+        Some(map) => map,
+        None => return vec![],
+    };
+    let infos = infos
+        .iter()
+        .map(|info| (info.name.as_str(), info))
+        .collect::<HashMap<_, _>>();
+
+    // the keys of GET /proxies are sorted by the core, the order of the profile is in `GLOBAL`
+    let mut names = group_members(proxies, "GLOBAL");
+    let rest = map
+        .keys()
+        .filter(|name| !names.contains(name))
+        .cloned()
+        .collect::<Vec<_>>();
+    names.extend(rest);
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let proxy = map.get(&name)?;
+            let ptype = proxy.get("type")?.as_str()?;
+            if proxy.get("all").is_some() || BUILTIN_TYPES.contains(&ptype) {
+                return None;
+            }
+
+            let info = infos.get(name.as_str());
+            let exit = info.and_then(|info| info.client.as_ref()?.country.as_deref());
+            let kind = kinds.get(&name).cloned();
+            let protocol = match kind.as_ref() {
+                Some(kind) => kind.protocol.clone(),
+                None => node_kind::normalize_protocol(&ptype.to_lowercase()),
+            };
+            let latency = info
+                .and_then(|info| Some(info.latency.as_ref()?.median))
+                .or_else(|| last_delay(proxy));
+
+            Some(ProxyRow {
+                region: region::resolve_region(&name, exit),
+                meta: metas.get(&name).cloned(),
+                ptype: ptype.into(),
+                protocol,
+                kind,
+                latency,
+                download: info.and_then(|info| Some(info.download.as_ref()?.median)),
+                error: info.and_then(|info| info.error.clone()),
+                name,
+            })
+        })
+        .collect()
+}
+
+/// filter, sort and page the rows
+pub fn query(
+    mut rows: Vec<ProxyRow>,
+    proxies: &Value,
+    filter: &ProxyFilter,
+    sort: ProxySort,
+    offset: usize,
+    limit: usize,
+) -> ProxyPage {
+    let members = filter
+        .group
+        .as_ref()
+        .map(|group| group_members(proxies, group));
+    let keyword = filter.name.as_ref().map(|name| name.trim().to_lowercase());
+    let contains = |list: &Option<Vec<String>>, value: Option<&str>| match list {
+        Some(list) if !list.is_empty() => {
+            matches!(value, Some(value) if list.iter().any(|v| v.eq_ignore_ascii_case(value)))
+        }
+        _ => true,
+    };
+
+    rows.retain(|row| {
+        let alias = row.meta.as_ref().and_then(|meta| meta.alias.as_ref());
+        let favorite = row.meta.as_ref().and_then(|meta| meta.favorite);
+        let named = |keyword: &String| {
+            row.name.to_lowercase().contains(keyword)
+                || alias.map_or(false, |alias| alias.to_lowercase().contains(keyword))
+        };
+
+        keyword.as_ref().map_or(true, named)
+            && contains(
+                &filter.regions,
+                row.region.as_ref().map(|r| r.code.as_str()),
+            )
+            && contains(&filter.protocols, Some(&row.protocol))
+            && match (filter.min_latency, filter.max_latency, row.latency) {
+                (None, None, _) => true,
+                (min, max, Some(latency)) => {
+                    latency >= min.unwrap_or(0) && latency <= max.unwrap_or(u64::MAX)
+                }
+                _ => false,
+            }
+            && members.as_ref().map_or(true, |m| m.contains(&row.name))
+            && filter
+                .favorite
+                .map_or(true, |f| favorite.unwrap_or(false) == f)
+    });
+
+    let desc = sort.desc.unwrap_or(false);
+    let order = |ord: Ordering| match desc {
+        true => ord.reverse(),
+        false => ord,
+    };
+    // the missing values are last in both orders
+    let by_value = |a: Option<u64>, b: Option<u64>| match (a, b) {
+        (Some(a), Some(b)) => order(a.cmp(&b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    match sort.key {
+        ProxySortKey::Default if desc => rows.reverse(),
+        ProxySortKey::Default => {}
+        ProxySortKey::Name => {
+            rows.sort_by(|a, b| order(a.name.to_lowercase().cmp(&b.name.to_lowercase())))
+        }
+        ProxySortKey::Latency => rows.sort_by(|a, b| by_value(a.latency, b.latency)),
+        ProxySortKey::Download => rows.sort_by(|a, b| by_value(a.download, b.download)),
+        ProxySortKey::Region => rows.sort_by(|a, b| {
+            let code = |row: &ProxyRow| row.region.as_ref().map(|r| r.code.clone());
+            match (code(a), code(b)) {
+                (Some(a), Some(b)) => order(a.cmp(&b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }),
+    }
+
+    let total = rows.len();
+    let items = rows
+        .into_iter()
+        .skip(offset)
+        .take(limit.clamp(1, MAX_LIMIT))
+        .collect();
+
+    ProxyPage {
+        total,
+        offset,
+        items,
+    }
+}
+
+/// the `all` of the group in GET /proxies
+fn group_members(proxies: &Value, group: &str) -> Vec<String> {
+    proxies
+        .get("proxies")
+        .and_then(|p| p.get(group))
+        .and_then(|g| g.get("all"))
+        .and_then(|all| all.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.as_str().map(String::from))
+        .collect()
+}
+
+/// the last delay in the `history` of the core, none if it failed
+fn last_delay(proxy: &Value) -> Option<u64> {
+    proxy
+        .get("history")?
+        .as_array()?
+        .last()?
+        .get("delay")?
+        .as_u64()
+        .filter(|delay| *delay > 0)
+}
+
+#[test]
+fn test_query_proxies() {
+    use super::measure::MeasureStats;
+
+    let proxies = serde_json::json!({
+        "proxies": {
+            "DIRECT": {"name": "DIRECT", "type": "Direct"},
+            "GLOBAL": {"type": "Selector", "now": "proxy", "all": ["proxy", "🇩🇪 Berlin", "US 01", "jp", "DIRECT"]},
+            "proxy": {"type": "Selector", "now": "US 01", "all": ["US 01", "jp"]},
+            "US 01": {"type": "Vless", "history": [{"delay": 0}]},
+            "jp": {"type": "Shadowsocks", "history": [{"delay": 80}]},
+            "🇩🇪 Berlin": {"type": "Trojan", "history": []},
+        }
+    });
+    let stats = |median: u64| MeasureStats {
+        median,
+        p90: median,
+        std_dev: 0.0,
+    };
+    let infos = vec![
+        MeasureInfo {
+            name: "US 01".into(),
+            latency: Some(stats(150)),
+            download: Some(stats(2_000_000)),
+            ..MeasureInfo::default()
+        },
+        MeasureInfo {
+            name: "🇩🇪 Berlin".into(),
+            latency: Some(stats(60)),
+            ..MeasureInfo::default()
+        },
+    ];
+    let mut metas = HashMap::new();
+    metas.insert(
+        "jp".to_string(),
+        NodeMeta {
+            favorite: Some(true),
+            alias: Some("Tokyo".into()),
+            ..NodeMeta::default()
+        },
+    );
+
+    let rows = proxy_rows(&proxies, &infos, &metas, &HashMap::new());
+    let names = rows.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["🇩🇪 Berlin", "US 01", "jp"]);
+    assert_eq!(rows[2].protocol, "ss");
+    assert_eq!(rows[2].latency, Some(80));
+    assert_eq!(rows[1].region.as_ref().unwrap().code, "US");
+
+    let page = |filter: ProxyFilter, key: ProxySortKey, desc: bool| {
+        let sort = ProxySort {
+            key,
+            desc: Some(desc),
+        };
+        query(rows.clone(), &proxies, &filter, sort, 0, DEFAULT_LIMIT)
+    };
+    let names = |page: ProxyPage| page.items.into_iter().map(|r| r.name).collect::<Vec<_>>();
+
+    let by_latency = page(ProxyFilter::default(), ProxySortKey::Latency, true);
+    assert_eq!(by_latency.total, 3);
+    assert_eq!(names(by_latency), ["US 01", "jp", "🇩🇪 Berlin"]);
+    let by_download = page(ProxyFilter::default(), ProxySortKey::Download, true);
+    assert_eq!(names(by_download)[0], "US 01");
+
+    let tokyo = ProxyFilter {
+        name: Some("tok".into()),
+        ..ProxyFilter::default()
+    };
+    assert_eq!(names(page(tokyo, ProxySortKey::Default, false)), ["jp"]);
+
+    let filter = ProxyFilter {
+        group: Some("proxy".into()),
+        max_latency: Some(100),
+        ..ProxyFilter::default()
+    };
+    assert_eq!(names(page(filter, ProxySortKey::Name, false)), ["jp"]);
+
+    let filter = ProxyFilter {
+        regions: Some(vec!["de".into(), "us".into()]),
+        protocols: Some(vec!["trojan".into()]),
+        ..ProxyFilter::default()
+    };
+    assert_eq!(
+        names(page(filter, ProxySortKey::Default, false)),
+        ["🇩🇪 Berlin"]
+    );
+
+    let sort = ProxySort::default();
+    let second = query(rows, &proxies, &ProxyFilter::default(), sort, 1, 1);
+    assert_eq!(second.total, 3);
+    assert_eq!(names(second), ["US 01"]);
+}
@@ -0,0 +1,161 @@
+use super::measure::MeasureInfo;
+use super::measure_cache::MeasureCache;
+use crate::config::Config;
+use serde::{Deserialize, Serialize};
+
+/// the latency (ms) scored as 0.5
+const LATENCY_REF: f64 = 200.0;
+
+/// the download speed (bytes/s) scored as 0.5
+const THROUGHPUT_REF: f64 = 1024.0 * 1024.0;
+
+/// the latency standard deviation (ms) scored as 0.5
+const JITTER_REF: f64 = 50.0;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RankStrategy {
+    LatencyFirst,
+    ThroughputFirst,
+    /// the weights of the latency, the throughput and the stability
+    Balanced {
+        latency: f64,
+        throughput: f64,
+        stability: f64,
+    },
+    /// the jitter of the measurements and the health checks of the core
+    StabilityFirst,
+}
+
+impl RankStrategy {
+    /// the other metrics break the ties of the preferred one
+    fn weights(&self) -> (f64, f64, f64) {
+        match *self {
+            RankStrategy::LatencyFirst => (0.8, 0.1, 0.1),
+            RankStrategy::ThroughputFirst => (0.1, 0.8, 0.1),
+            RankStrategy::StabilityFirst => (0.1, 0.1, 0.8),
+            RankStrategy::Balanced {
+                latency,
+                throughput,
+                stability,
+            } => (latency.max(0.0), throughput.max(0.0), stability.max(0.0)),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct RankedProxy {
+    pub name: String,
+
+    /// 0 - 100, 0 if the proxy failed
+    pub score: f64,
+
+    pub info: MeasureInfo,
+}
+
+/// rank the measured proxies of the current profile, the best first
+pub fn rank_proxies(strategy: RankStrategy) -> Vec<RankedProxy> {
+    let current = { Config::profiles().latest().get_current() };
+    match current {
+        Some(profile) => rank(MeasureCache::global().get(&profile), strategy),
+        None => vec![],
+    }
+}
+
+/// the best first
+pub fn rank(infos: Vec<MeasureInfo>, strategy: RankStrategy) -> Vec<RankedProxy> {
+    let mut ranked = infos
+        .into_iter()
+        .map(|info| RankedProxy {
+            name: info.name.clone(),
+            score: score(&info, strategy),
+            info,
+        })
+        .collect::<Vec<_>>();
+
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.name.cmp(&b.name)));
+    ranked
+}
+
+fn score(info: &MeasureInfo, strategy: RankStrategy) -> f64 {
+    let latency = match (&info.error, &info.latency) {
+        (None, Some(latency)) => latency,
+        _ => return 0.0,
+    };
+
+    let (wl, wt, ws) = strategy.weights();
+    let total = wl + wt + ws;
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    let half = |value: f64, reference: f64| reference / (reference + value);
+    let latency_score = half(latency.median as f64, LATENCY_REF);
+    let stability_score = half(latency.std_dev, JITTER_REF);
+    let throughput_score = info.download.as_ref().map_or(0.0, |download| {
+        let speed = download.median as f64;
+        speed / (speed + THROUGHPUT_REF)
+    });
+
+    let score = (wl * latency_score + wt * throughput_score + ws * stability_score) / total;
+    (score * 10000.0).round() / 100.0
+}
+
+#[test]
+fn test_rank() {
+    use super::measure::MeasureStats;
+
+    let info = |name: &str, latency: u64, std_dev: f64, download: Option<u64>| MeasureInfo {
+        name: name.into(),
+        latency: Some(MeasureStats {
+            median: latency,
+            p90: latency,
+            std_dev,
+        }),
+        download: download.map(|median| MeasureStats {
+            median,
+            p90: median,
+            std_dev: 0.0,
+        }),
+        ..MeasureInfo::default()
+    };
+    let infos = vec![
+        info("fast", 50, 40.0, Some(512 * 1024)),
+        info("wide", 300, 20.0, Some(20 * 1024 * 1024)),
+        info("steady", 150, 1.0, None),
+        MeasureInfo {
+            error: Some("timeout".into()),
+            ..info("failed", 10, 0.0, None)
+        },
+    ];
+    let names = |strategy| {
+        rank(infos.clone(), strategy)
+            .into_iter()
+            .map(|p| p.name)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        names(RankStrategy::LatencyFirst),
+        vec!["fast", "steady", "wide", "failed"]
+    );
+    assert_eq!(
+        names(RankStrategy::ThroughputFirst),
+        vec!["wide", "fast", "steady", "failed"]
+    );
+    assert_eq!(
+        names(RankStrategy::StabilityFirst),
+        vec!["steady", "wide", "fast", "failed"]
+    );
+
+    let ranked = rank(
+        infos.clone(),
+        RankStrategy::Balanced {
+            latency: 0.0,
+            throughput: 0.0,
+            stability: 0.0,
+        },
+    );
+    assert!(ranked.iter().all(|p| p.score == 0.0));
+    assert_eq!(ranked[0].name, "failed");
+}
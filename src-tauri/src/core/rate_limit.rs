@@ -0,0 +1,87 @@
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+
+/// the burst of the speedtest.net requests
+const SPEEDTEST_BURST: u32 = 3;
+
+/// one speedtest.net request is allowed every 20 seconds after the burst
+const SPEEDTEST_REFILL: Duration = Duration::from_secs(20);
+
+/// the requests to the same service, delayed when the bucket is empty
+pub struct TokenBucket {
+    capacity: f64,
+
+    /// the time to refill one token
+    refill: Duration,
+
+    /// the tokens left at the time
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, refill: Duration) -> TokenBucket {
+        TokenBucket {
+            capacity: capacity.max(1) as f64,
+            refill,
+            state: Mutex::new((capacity.max(1) as f64, Instant::now())),
+        }
+    }
+
+    /// take a token, or return the time to wait for the next one
+    fn take(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock();
+        let (tokens, last) = *state;
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        let tokens = (tokens + elapsed / self.refill.as_secs_f64()).min(self.capacity);
+
+        match tokens >= 1.0 {
+            true => {
+                *state = (tokens - 1.0, now);
+                Ok(())
+            }
+            false => {
+                *state = (tokens, now);
+                Err(self.refill.mul_f64(1.0 - tokens))
+            }
+        }
+    }
+
+    /// wait until a token is taken
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.take(Instant::now()) {
+            log::debug!(target: "app", "rate limited, wait for {}ms", wait.as_millis());
+            sleep(wait).await;
+        }
+    }
+}
+
+/// shared by all the requests to speedtest.net,
+/// so that scanning a large subscription does not get the ip banned
+pub fn speedtest() -> &'static TokenBucket {
+    static BUCKET: OnceCell<TokenBucket> = OnceCell::new();
+
+    BUCKET.get_or_init(|| TokenBucket::new(SPEEDTEST_BURST, SPEEDTEST_REFILL))
+}
+
+#[test]
+fn test_token_bucket() {
+    let bucket = TokenBucket::new(2, Duration::from_secs(10));
+    let start = bucket.state.lock().1;
+
+    assert!(bucket.take(start).is_ok());
+    assert!(bucket.take(start).is_ok());
+    assert_eq!(bucket.take(start), Err(Duration::from_secs(10)));
+
+    let later = start + Duration::from_secs(5);
+    assert_eq!(bucket.take(later), Err(Duration::from_secs(5)));
+
+    let later = start + Duration::from_secs(10);
+    assert!(bucket.take(later).is_ok());
+    assert!(bucket.take(later).is_err());
+
+    let later = start + Duration::from_secs(100);
+    assert!(bucket.take(later).is_ok());
+    assert!(bucket.take(later).is_ok());
+    assert!(bucket.take(later).is_err());
+}
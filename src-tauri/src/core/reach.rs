@@ -0,0 +1,178 @@
+use super::measure;
+use anyhow::{bail, Result};
+use reqwest::{header, Client, StatusCode, Version};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, Instant};
+
+/// the timeout for each check
+const REACH_TIMEOUT: Duration = Duration::from_secs(5);
+
+const TLS_URL: &str = "https://www.cloudflare.com/cdn-cgi/trace";
+const WEBSOCKET_URL: &str = "https://echo.websocket.org/";
+const GRPC_URL: &str = "https://www.google.com/grpc.health.v1.Health/Check";
+const QUIC_HOST: &str = "cloudflare-quic.com:443";
+
+/// a reserved version, the server must reply with a version negotiation packet
+const QUIC_PROBE_VERSION: [u8; 4] = [0x1a, 0x2a, 0x3a, 0x4a];
+
+/// the result of one transport check
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ReachResult {
+    pub ok: bool,
+
+    /// the time to get the first response (ms)
+    pub latency: Option<u64>,
+
+    pub error: Option<String>,
+}
+
+/// whether the common transports work on the current network
+/// checked directly, without the proxy
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ReachMatrix {
+    /// tls to port 443, used by trojan and vless/vmess with tls
+    pub tls: ReachResult,
+
+    /// websocket upgrade, used by the `ws` transport
+    pub websocket: ReachResult,
+
+    /// grpc over h2, used by the `grpc` transport
+    pub grpc: ReachResult,
+
+    /// quic on udp 443, used by hysteria and tuic
+    pub quic: ReachResult,
+}
+
+/// run all the checks concurrently
+pub async fn check_matrix() -> ReachMatrix {
+    let (tls, websocket, grpc, quic) = tokio::join!(
+        run(check_tls()),
+        run(check_websocket()),
+        run(check_grpc()),
+        run(check_quic())
+    );
+
+    ReachMatrix {
+        tls,
+        websocket,
+        grpc,
+        quic,
+    }
+}
+
+async fn run(check: impl Future<Output = Result<()>>) -> ReachResult {
+    let start = Instant::now();
+
+    match timeout(REACH_TIMEOUT, check).await {
+        Ok(Ok(_)) => ReachResult {
+            ok: true,
+            latency: Some(start.elapsed().as_millis() as u64),
+            error: None,
+        },
+        Ok(Err(err)) => ReachResult {
+            error: Some(err.to_string()),
+            ..ReachResult::default()
+        },
+        Err(_) => ReachResult {
+            error: Some("timeout".into()),
+            ..ReachResult::default()
+        },
+    }
+}
+
+fn direct_client() -> reqwest::ClientBuilder {
+    Client::builder()
+        .no_proxy()
+        .default_headers(measure::request_headers().unwrap_or_default())
+        .timeout(REACH_TIMEOUT)
+}
+
+async fn check_tls() -> Result<()> {
+    direct_client().build()?.head(TLS_URL).send().await?;
+    Ok(())
+}
+
+async fn check_websocket() -> Result<()> {
+    let resp = direct_client()
+        .http1_only()
+        .build()?
+        .get(WEBSOCKET_URL)
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_VERSION, "13")
+        .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+        .send()
+        .await?;
+
+    match resp.status() {
+        StatusCode::SWITCHING_PROTOCOLS => Ok(()),
+        status => bail!("unexpected status {status}"),
+    }
+}
+
+async fn check_grpc() -> Result<()> {
+    let resp = direct_client()
+        .http2_prior_knowledge()
+        .build()?
+        .post(GRPC_URL)
+        .header(header::CONTENT_TYPE, "application/grpc")
+        .header(header::TE, "trailers")
+        .body(vec![0u8; 5])
+        .send()
+        .await?;
+
+    // any h2 response means the stream went through
+    match resp.version() {
+        Version::HTTP_2 => Ok(()),
+        version => bail!("unexpected version {version:?}"),
+    }
+}
+
+async fn check_quic() -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(QUIC_HOST).await?;
+    socket.send(&quic_probe_packet()).await?;
+
+    let mut buf = [0u8; 1500];
+    let len = socket.recv(&mut buf).await?;
+
+    match is_version_negotiation(&buf[..len]) {
+        true => Ok(()),
+        false => bail!("unexpected quic response"),
+    }
+}
+
+/// a padded long header packet with a reserved version
+fn quic_probe_packet() -> Vec<u8> {
+    let mut packet = vec![0xc0];
+    packet.extend_from_slice(&QUIC_PROBE_VERSION);
+    // destination and source connection id
+    packet.push(8);
+    packet.extend_from_slice(&nanoid::nanoid!(8).into_bytes());
+    packet.push(8);
+    packet.extend_from_slice(&nanoid::nanoid!(8).into_bytes());
+    // the server ignores the initial packet smaller than 1200 bytes
+    packet.resize(1200, 0);
+    packet
+}
+
+/// long header with version 0
+fn is_version_negotiation(packet: &[u8]) -> bool {
+    packet.len() >= 7 && packet[0] & 0x80 != 0 && packet[1..5] == [0, 0, 0, 0]
+}
+
+#[test]
+fn test_quic_probe() {
+    let packet = quic_probe_packet();
+    assert_eq!(packet.len(), 1200);
+    assert_eq!(packet[0] & 0x80, 0x80);
+    assert_eq!(packet[1..5], QUIC_PROBE_VERSION);
+    assert!(!is_version_negotiation(&packet));
+
+    let reply = [0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    assert!(is_version_negotiation(&reply));
+    assert!(!is_version_negotiation(&[0x40, 0, 0, 0, 0, 0, 0]));
+    assert!(!is_version_negotiation(&[0x80, 0, 0]));
+}
@@ -0,0 +1,127 @@
+use super::clash_api;
+use super::state_events::StateEvents;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// the number of the nodes kept for each selector
+const RECENT_LIMIT: usize = 10;
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RecentNode {
+    pub name: String,
+
+    /// selected time
+    pub time: usize,
+}
+
+/// keyed by the profile uid and the selector
+type RecentMap = HashMap<(String, String), Vec<RecentNode>>;
+
+/// the recently selected nodes of the selectors in the current profile,
+/// the latest first
+pub struct RecentNodes {
+    items: Arc<Mutex<RecentMap>>,
+
+    /// the selector switched last, used by the hotkey
+    last_selector: Arc<Mutex<Option<String>>>,
+}
+
+impl RecentNodes {
+    pub fn global() -> &'static RecentNodes {
+        static RECENT: OnceCell<RecentNodes> = OnceCell::new();
+
+        RECENT.get_or_init(|| RecentNodes {
+            items: Arc::new(Mutex::new(HashMap::new())),
+            last_selector: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    pub fn get(&self, selector: &str) -> Vec<RecentNode> {
+        let key = (current_profile(), selector.to_string());
+        self.items.lock().get(&key).cloned().unwrap_or_default()
+    }
+
+    fn record(&self, selector: &str, name: &str) {
+        let key = (current_profile(), selector.to_string());
+        let mut items = self.items.lock();
+        push_recent(items.entry(key).or_default(), name);
+        *self.last_selector.lock() = Some(selector.to_string());
+    }
+
+    /// select the node and record it
+    /// the node selected before is recorded first, so that the first switch can be undone
+    pub async fn select(&self, selector: &str, name: &str) -> Result<()> {
+        if self.get(selector).is_empty() {
+            if let Ok(Some(now)) = clash_api::get_proxy_now(selector).await {
+                self.record(selector, &now);
+            }
+        }
+
+        clash_api::put_proxy(selector, name).await?;
+        self.record(selector, name);
+        StateEvents::global().proxy_selected(selector, name);
+        Ok(())
+    }
+
+    /// switch back to the node selected before the current one
+    /// the selector switched last is used by default
+    pub async fn switch_to_previous(&self, selector: Option<String>) -> Result<String> {
+        let selector = match selector.or_else(|| self.last_selector.lock().clone()) {
+            Some(selector) => selector,
+            None => bail!("no node has been switched"),
+        };
+
+        let now = clash_api::get_proxy_now(&selector).await?;
+        let previous = self
+            .get(&selector)
+            .into_iter()
+            .find(|node| Some(&node.name) != now.as_ref());
+
+        match previous {
+            Some(node) => {
+                self.select(&selector, &node.name).await?;
+                Ok(node.name)
+            }
+            None => bail!("no previous node of \"{selector}\""),
+        }
+    }
+}
+
+fn current_profile() -> String {
+    let current = { Config::profiles().latest().get_current() };
+    current.unwrap_or_default()
+}
+
+/// move the node to the front
+fn push_recent(nodes: &mut Vec<RecentNode>, name: &str) {
+    nodes.retain(|node| node.name != name);
+    nodes.insert(
+        0,
+        RecentNode {
+            name: name.into(),
+            time: chrono::Local::now().timestamp() as usize,
+        },
+    );
+    nodes.truncate(RECENT_LIMIT);
+}
+
+#[test]
+fn test_push_recent() {
+    let mut nodes = vec![];
+    for name in ["a", "b", "a", "c"] {
+        push_recent(&mut nodes, name);
+    }
+    let names = nodes.iter().map(|n| n.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["c", "a", "b"]);
+
+    for i in 0..20 {
+        push_recent(&mut nodes, &i.to_string());
+    }
+    assert_eq!(nodes.len(), RECENT_LIMIT);
+    assert_eq!(nodes[0].name, "19");
+}
@@ -0,0 +1,247 @@
+use super::measure_cache::MeasureCache;
+use crate::config::Config;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// the country of a node, for the grouping and the filtering
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct NodeRegion {
+    /// the iso code, such as `DE`
+    pub code: String,
+
+    pub flag: String,
+
+    /// the country in the name, set if the measured exit is elsewhere
+    pub named: Option<String>,
+}
+
+/// the two letter codes accepted as a token of the name
+const CODES: &[&str] = &[
+    "AE", "AR", "AT", "AU", "BE", "BG", "BR", "CA", "CH", "CL", "CN", "CZ", "DE", "DK", "EE", "ES",
+    "FI", "FR", "GB", "GR", "HK", "HU", "ID", "IE", "IL", "IN", "IR", "IS", "IT", "JP", "KR", "KZ",
+    "LT", "LU", "LV", "MO", "MX", "MY", "NL", "NO", "NZ", "PH", "PL", "PT", "RO", "RS", "RU", "SA",
+    "SE", "SG", "TH", "TR", "TW", "UA", "US", "VN", "ZA",
+];
+
+/// the country and the city names, lowercase
+const KEYWORDS: &[(&str, &str)] = &[
+    ("hong kong", "HK"),
+    ("hongkong", "HK"),
+    ("香港", "HK"),
+    ("taiwan", "TW"),
+    ("taipei", "TW"),
+    ("台湾", "TW"),
+    ("台灣", "TW"),
+    ("japan", "JP"),
+    ("tokyo", "JP"),
+    ("osaka", "JP"),
+    ("日本", "JP"),
+    ("东京", "JP"),
+    ("korea", "KR"),
+    ("seoul", "KR"),
+    ("韩国", "KR"),
+    ("singapore", "SG"),
+    ("新加坡", "SG"),
+    ("狮城", "SG"),
+    ("united states", "US"),
+    ("america", "US"),
+    ("los angeles", "US"),
+    ("san jose", "US"),
+    ("seattle", "US"),
+    ("new york", "US"),
+    ("chicago", "US"),
+    ("dallas", "US"),
+    ("miami", "US"),
+    ("美国", "US"),
+    ("canada", "CA"),
+    ("toronto", "CA"),
+    ("加拿大", "CA"),
+    ("united kingdom", "GB"),
+    ("england", "GB"),
+    ("london", "GB"),
+    ("英国", "GB"),
+    ("germany", "DE"),
+    ("frankfurt", "DE"),
+    ("berlin", "DE"),
+    ("德国", "DE"),
+    ("france", "FR"),
+    ("paris", "FR"),
+    ("法国", "FR"),
+    ("netherlands", "NL"),
+    ("amsterdam", "NL"),
+    ("荷兰", "NL"),
+    ("finland", "FI"),
+    ("helsinki", "FI"),
+    ("sweden", "SE"),
+    ("stockholm", "SE"),
+    ("switzerland", "CH"),
+    ("zurich", "CH"),
+    ("poland", "PL"),
+    ("warsaw", "PL"),
+    ("turkey", "TR"),
+    ("istanbul", "TR"),
+    ("土耳其", "TR"),
+    ("russia", "RU"),
+    ("moscow", "RU"),
+    ("俄罗斯", "RU"),
+    ("ukraine", "UA"),
+    ("iran", "IR"),
+    ("tehran", "IR"),
+    ("ایران", "IR"),
+    ("emirates", "AE"),
+    ("dubai", "AE"),
+    ("india", "IN"),
+    ("mumbai", "IN"),
+    ("印度", "IN"),
+    ("australia", "AU"),
+    ("sydney", "AU"),
+    ("澳大利亚", "AU"),
+    ("brazil", "BR"),
+    ("sao paulo", "BR"),
+    ("vietnam", "VN"),
+    ("越南", "VN"),
+    ("thailand", "TH"),
+    ("bangkok", "TH"),
+    ("malaysia", "MY"),
+    ("philippines", "PH"),
+    ("indonesia", "ID"),
+    ("italy", "IT"),
+    ("milan", "IT"),
+    ("spain", "ES"),
+    ("madrid", "ES"),
+];
+
+/// the country in the name, by the flag, the iso code or the place name
+pub fn parse_region(name: &str) -> Option<String> {
+    parse_flag(name)
+        .or_else(|| parse_code(name))
+        .or_else(|| parse_keyword(name))
+}
+
+/// the measured exit wins over the name
+pub fn resolve_region(name: &str, exit: Option<&str>) -> Option<NodeRegion> {
+    let named = parse_region(name);
+    let code = exit.map(str::to_uppercase).or_else(|| named.clone())?;
+
+    Some(NodeRegion {
+        flag: flag(&code),
+        named: named.filter(|named| *named != code),
+        code,
+    })
+}
+
+/// the exit countries of the current profile by the proxy name
+pub fn current_exits() -> HashMap<String, String> {
+    let current = { Config::profiles().latest().get_current() };
+    let infos = match current {
+        Some(profile) => MeasureCache::global().get(&profile),
+        None => return HashMap::new(),
+    };
+
+    infos
+        .into_iter()
+        .filter_map(|info| {
+            let country = info.client?.country?;
+            Some((info.name, country))
+        })
+        .collect()
+}
+
+/// add the `region` to the nodes of GET /proxies, the groups are skipped
+pub fn merge_regions(proxies: &mut serde_json::Value, exits: &HashMap<String, String>) {
+    let proxies = match proxies.get_mut("proxies").and_then(|p| p.as_object_mut()) {
+        Some(proxies) => proxies,
+        None => return,
+    };
+
+    for (name, proxy) in proxies.iter_mut() {
+        let proxy = match proxy.as_object_mut() {
+            Some(proxy) if !proxy.contains_key("all") => proxy,
+            _ => continue,
+        };
+
+        let region = resolve_region(name, exits.get(name).map(|c| c.as_str()));
+        if let Some(region) = region.and_then(|r| serde_json::to_value(r).ok()) {
+            proxy.insert("region".into(), region);
+        }
+    }
+}
+
+/// the flag emoji of the iso code
+pub fn flag(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .filter_map(|c| char::from_u32(0x1F1E6 + (c.to_ascii_uppercase() as u32 - 'A' as u32)))
+        .collect()
+}
+
+fn parse_flag(name: &str) -> Option<String> {
+    let letters = name
+        .chars()
+        .map(|c| c as u32)
+        .skip_while(|c| !(0x1F1E6..=0x1F1FF).contains(c))
+        .take_while(|c| (0x1F1E6..=0x1F1FF).contains(c))
+        .take(2)
+        .filter_map(|c| char::from_u32(c - 0x1F1E6 + 'A' as u32))
+        .collect::<String>();
+
+    match letters.len() {
+        2 => Some(normalize_code(&letters)),
+        _ => None,
+    }
+}
+
+/// such as `DE`, `DE01` or `UK-2`, only in uppercase
+fn parse_code(name: &str) -> Option<String> {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .map(|token| token.trim_end_matches(|c: char| c.is_ascii_digit()))
+        .filter(|token| token.len() == 2 && token.chars().all(|c| c.is_ascii_uppercase()))
+        .map(normalize_code)
+        .find(|code| CODES.contains(&code.as_str()))
+}
+
+fn parse_keyword(name: &str) -> Option<String> {
+    let name = name.to_lowercase();
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _)| name.contains(keyword))
+        .map(|(_, code)| code.to_string())
+}
+
+fn normalize_code(code: &str) -> String {
+    match code {
+        "UK" => "GB".into(),
+        code => code.into(),
+    }
+}
+
+#[test]
+fn test_resolve_region() {
+    assert_eq!(parse_region("🇩🇪 Germany 01").as_deref(), Some("DE"));
+    assert_eq!(parse_region("vless-DE01-ws").as_deref(), Some("DE"));
+    assert_eq!(parse_region("UK-2 | fast").as_deref(), Some("GB"));
+    assert_eq!(parse_region("香港 IPLC 03").as_deref(), Some("HK"));
+    assert_eq!(parse_region("Frankfurt #2").as_deref(), Some("DE"));
+    assert_eq!(parse_region("VL WS TLS").as_deref(), None);
+    assert_eq!(parse_region("node in us").as_deref(), None);
+
+    let region = resolve_region("🇯🇵 Tokyo", None).unwrap();
+    assert_eq!(region.code, "JP");
+    assert_eq!(region.flag, "🇯🇵");
+    assert_eq!(region.named, None);
+
+    let region = resolve_region("🇯🇵 Tokyo", Some("us")).unwrap();
+    assert_eq!(region.code, "US");
+    assert_eq!(region.named.as_deref(), Some("JP"));
+    assert!(resolve_region("node 1", None).is_none());
+
+    let mut proxies = serde_json::json!({
+        "proxies": {
+            "auto": {"name": "auto", "type": "URLTest", "all": ["SG 1"]},
+            "SG 1": {"name": "SG 1", "type": "Trojan"}
+        }
+    });
+    merge_regions(&mut proxies, &HashMap::new());
+    assert_eq!(proxies["proxies"]["SG 1"]["region"]["code"], "SG");
+    assert!(proxies["proxies"]["auto"].get("region").is_none());
+}
@@ -0,0 +1,559 @@
+use super::measure_batch::MeasureBatches;
+use super::netinfo;
+use super::recent::RecentNodes;
+use crate::config::Config;
+use crate::utils::{control_api, dirs, help, secret};
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose, Engine};
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use sysinfo::{System, SystemExt};
+use warp::ws::{Message, WebSocket};
+use warp::Filter;
+
+const DEFAULT_PORT: u16 = 33333;
+
+/// the pairing code in the qr is valid for
+const PAIRING_TTL: Duration = Duration::from_secs(300);
+
+/// the failed pairings and authentications allowed for each ip in `FAILURE_WINDOW`
+const MAX_FAILURES: u32 = 5;
+
+const FAILURE_WINDOW: Duration = Duration::from_secs(600);
+
+/// the device paired by the qr, only the hash of the token is saved
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PairedDevice {
+    pub id: String,
+
+    pub name: String,
+
+    pub token_hash: String,
+
+    pub paired: usize,
+
+    pub last_seen: Option<usize>,
+}
+
+/// the content of the qr shown on the desktop
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct PairingInfo {
+    /// `hiddify-remote://192.168.1.2:33333/pair?code=...&name=...&fp=...`
+    pub uri: String,
+
+    pub host: String,
+
+    pub port: u16,
+
+    pub code: String,
+
+    /// the sha256 of the certificate, pinned by the companion app instead of a ca
+    pub fingerprint: String,
+
+    /// the seconds until the code expires
+    pub expires_in: u64,
+}
+
+/// a request of the companion app, answered with the same id
+#[derive(Debug, Clone, Deserialize)]
+struct Request {
+    id: Option<u64>,
+
+    #[serde(flatten)]
+    message: ClientMessage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// the first message of a new device, with the code of the qr
+    Pair {
+        code: String,
+        device: String,
+    },
+    /// the first message of a paired device
+    Auth {
+        token: String,
+    },
+    Status,
+    SelectProxy {
+        selector: String,
+        name: String,
+    },
+    /// the latest batch by default
+    ScanResults {
+        batch_id: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Response {
+    id: Option<u64>,
+    ok: bool,
+    data: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+impl Response {
+    fn new(id: Option<u64>, result: Result<serde_json::Value>) -> Self {
+        match result {
+            Ok(data) => Response {
+                id,
+                ok: true,
+                data: Some(data),
+                error: None,
+            },
+            Err(err) => Response {
+                id,
+                ok: false,
+                data: None,
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
+
+/// the one-time code of the qr
+#[derive(Debug, Clone)]
+struct Pairing {
+    code: String,
+    created: Instant,
+}
+
+impl Pairing {
+    fn is_valid(&self, code: &str, now: Instant) -> bool {
+        now.duration_since(self.created) < PAIRING_TTL && constant_eq(&self.code, code)
+    }
+}
+
+/// the self-signed certificate of the websocket
+#[derive(Debug, Clone)]
+struct Identity {
+    cert: String,
+    key: String,
+}
+
+impl Identity {
+    /// generated on the first start and kept, so the paired devices keep the same pin
+    fn load_or_create() -> Result<Self> {
+        let cert_path = dirs::remote_cert_path()?;
+        let key_path = dirs::remote_key_path()?;
+        if cert_path.exists() && key_path.exists() {
+            return Ok(Identity {
+                cert: fs::read_to_string(cert_path)?,
+                key: fs::read_to_string(key_path)?,
+            });
+        }
+
+        let cert = rcgen::generate_simple_self_signed(vec!["hiddify-remote".to_string()])?;
+        let identity = Identity {
+            cert: cert.serialize_pem()?,
+            key: cert.serialize_private_key_pem(),
+        };
+        fs::write(cert_path, &identity.cert)?;
+        fs::write(key_path, &identity.key)?;
+        log::info!(target: "app", "created the certificate of the remote control");
+        Ok(identity)
+    }
+
+    /// the sha256 of the der of the certificate
+    fn fingerprint(&self) -> Result<String> {
+        let der: String = self
+            .cert
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let der = general_purpose::STANDARD.decode(der.trim())?;
+        Ok(to_hex(&Sha256::digest(der)))
+    }
+}
+
+/// the failed attempts of each ip, counted from the first one in the window
+#[derive(Debug, Default)]
+struct Failures(HashMap<IpAddr, (u32, Instant)>);
+
+impl Failures {
+    /// the time to wait if the ip has failed too many times
+    fn check(&mut self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        self.0
+            .retain(|_, (_, first)| now.saturating_duration_since(*first) < FAILURE_WINDOW);
+
+        match self.0.get(&ip) {
+            Some((count, first)) if *count >= MAX_FAILURES => {
+                Some(FAILURE_WINDOW - now.saturating_duration_since(*first))
+            }
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, ip: IpAddr, now: Instant) {
+        self.0.entry(ip).or_insert((0, now)).0 += 1;
+    }
+}
+
+/// the lan websocket server of the companion app, enabled by `enable_remote_control`
+/// the paired devices are saved in `remote_devices.yaml`
+pub struct RemoteControl {
+    pairing: Arc<Mutex<Option<Pairing>>>,
+
+    /// the failed pairings and authentications, so the codes and the tokens are not brute-forced
+    failures: Arc<Mutex<Failures>>,
+
+    /// loaded on the first use
+    devices: Arc<Mutex<Option<Vec<PairedDevice>>>>,
+
+    /// loaded on the first use
+    identity: Arc<Mutex<Option<Identity>>>,
+}
+
+impl RemoteControl {
+    pub fn global() -> &'static RemoteControl {
+        static REMOTE: OnceCell<RemoteControl> = OnceCell::new();
+
+        REMOTE.get_or_init(|| RemoteControl {
+            pairing: Arc::new(Mutex::new(None)),
+            failures: Arc::new(Mutex::new(Failures::default())),
+            devices: Arc::new(Mutex::new(None)),
+            identity: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn identity(&self) -> Result<Identity> {
+        let mut identity = self.identity.lock();
+        if identity.is_none() {
+            *identity = Some(Identity::load_or_create()?);
+        }
+        Ok(identity.clone().unwrap())
+    }
+
+    /// listen on all the interfaces over tls, applied after restart
+    /// the companion app pins the certificate by the fingerprint in the qr
+    pub fn init(&'static self) -> Result<()> {
+        let (enable, port) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (verge.enable_remote_control, verge.remote_control_port)
+        };
+        if !enable.unwrap_or(false) {
+            return Ok(());
+        }
+        let port = port.unwrap_or(DEFAULT_PORT);
+        let identity = self.identity()?;
+
+        tauri::async_runtime::spawn(async move {
+            let socket = warp::path!("v1" / "ws")
+                .and(warp::ws())
+                .and(warp::addr::remote())
+                .map(move |ws: warp::ws::Ws, addr: Option<SocketAddr>| {
+                    ws.on_upgrade(move |socket| self.session(socket, addr))
+                });
+
+            log::info!(target: "app", "the remote control listens on 0.0.0.0:{port}");
+            warp::serve(socket)
+                .tls()
+                .cert(identity.cert)
+                .key(identity.key)
+                .bind(([0, 0, 0, 0], port))
+                .await;
+        });
+        Ok(())
+    }
+
+    fn load() -> Vec<PairedDevice> {
+        match dirs::remote_devices_path().and_then(|path| help::read_yaml(&path)) {
+            Ok(devices) => devices,
+            Err(err) => {
+                log::debug!(target: "app", "failed to read the remote devices, {err}");
+                vec![]
+            }
+        }
+    }
+
+    /// the token hashes are cleared
+    pub fn get_devices(&self) -> Vec<PairedDevice> {
+        let mut devices = self.devices.lock();
+        let devices = devices.get_or_insert_with(Self::load);
+        devices
+            .iter()
+            .map(|device| PairedDevice {
+                token_hash: String::new(),
+                ..device.clone()
+            })
+            .collect()
+    }
+
+    pub fn remove_device(&self, id: &str) -> Result<()> {
+        let mut devices = self.devices.lock();
+        let devices = devices.get_or_insert_with(Self::load);
+        devices.retain(|device| device.id != id);
+        help::save_yaml(&dirs::remote_devices_path()?, devices, None)
+    }
+
+    /// a new code replaces the last one
+    pub fn start_pairing(&self) -> Result<PairingInfo> {
+        let (enable, port) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (verge.enable_remote_control, verge.remote_control_port)
+        };
+        if !enable.unwrap_or(false) {
+            bail!("the remote control is not enabled");
+        }
+        let port = port.unwrap_or(DEFAULT_PORT);
+
+        let host = netinfo::local_ips()
+            .into_iter()
+            .find(|ip| !ip.contains(':'));
+        let host = match host {
+            Some(host) => host,
+            None => bail!("failed to find the lan address"),
+        };
+
+        let fingerprint = self.identity()?.fingerprint()?;
+        let code = help::get_uid("");
+        *self.pairing.lock() = Some(Pairing {
+            code: code.clone(),
+            created: Instant::now(),
+        });
+
+        let mut uri = Url::parse(&format!("hiddify-remote://{host}:{port}/pair"))?;
+        uri.query_pairs_mut()
+            .append_pair("code", &code)
+            .append_pair("name", &System::new().host_name().unwrap_or_default())
+            .append_pair("fp", &fingerprint);
+
+        Ok(PairingInfo {
+            uri: uri.into(),
+            host,
+            port,
+            code,
+            fingerprint,
+            expires_in: PAIRING_TTL.as_secs(),
+        })
+    }
+
+    /// consume the code and return the device id and the token
+    fn pair(&self, code: &str, name: &str) -> Result<(String, String)> {
+        {
+            let mut pairing = self.pairing.lock();
+            match pairing.as_ref() {
+                Some(pairing) if pairing.is_valid(code, Instant::now()) => {}
+                _ => bail!("the pairing code is invalid or expired"),
+            }
+            *pairing = None;
+        }
+
+        let token = secret::generate_secret();
+        let device = PairedDevice {
+            id: help::get_uid("d"),
+            name: name.into(),
+            token_hash: hash_token(&token),
+            paired: chrono::Local::now().timestamp() as usize,
+            last_seen: None,
+        };
+        let id = device.id.clone();
+        log::info!(target: "app", "paired the remote device \"{name}\"");
+
+        let mut devices = self.devices.lock();
+        let devices = devices.get_or_insert_with(Self::load);
+        devices.push(device);
+        help::save_yaml(&dirs::remote_devices_path()?, devices, None)?;
+        Ok((id, token))
+    }
+
+    /// the device id of the token
+    fn authenticate(&self, token: &str) -> Option<String> {
+        let hash = hash_token(token);
+        let mut devices = self.devices.lock();
+        let devices = devices.get_or_insert_with(Self::load);
+
+        let device = devices
+            .iter_mut()
+            .find(|device| constant_eq(&device.token_hash, &hash))?;
+        device.last_seen = Some(chrono::Local::now().timestamp() as usize);
+        let id = device.id.clone();
+        crate::log_err!(
+            dirs::remote_devices_path().and_then(|path| help::save_yaml(&path, devices, None))
+        );
+        Some(id)
+    }
+
+    /// the connection is closed after a failed pairing or authentication
+    async fn session(&'static self, socket: WebSocket, addr: Option<SocketAddr>) {
+        let (mut tx, mut rx) = socket.split();
+        let mut device = None;
+
+        while let Some(Ok(message)) = rx.next().await {
+            if message.is_close() {
+                break;
+            }
+            let text = match message.to_str() {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+
+            let (response, close) = match serde_json::from_str::<Request>(text) {
+                Ok(request) => {
+                    let response = self.handle(request, addr, &mut device).await;
+                    (response, device.is_none())
+                }
+                Err(err) => (Response::new(None, Err(err.into())), device.is_none()),
+            };
+
+            let text = serde_json::to_string(&response).unwrap_or_default();
+            if tx.send(Message::text(text)).await.is_err() || close {
+                break;
+            }
+        }
+    }
+
+    async fn handle(
+        &self,
+        request: Request,
+        addr: Option<SocketAddr>,
+        device: &mut Option<String>,
+    ) -> Response {
+        let id = request.id;
+        let to_value = |value: Result<serde_json::Value>| Response::new(id, value);
+
+        let login = matches!(
+            request.message,
+            ClientMessage::Pair { .. } | ClientMessage::Auth { .. }
+        );
+        let ip = addr.map(|addr| addr.ip());
+        if let Some(ip) = ip.filter(|_| login) {
+            if let Some(wait) = self.failures.lock().check(ip, Instant::now()) {
+                log::warn!(target: "app", "refused the remote device from {ip}, too many failures");
+                return to_value(Err(anyhow::anyhow!(
+                    "too many failed attempts, retry in {}s",
+                    wait.as_secs() + 1
+                )));
+            }
+        }
+
+        let response = self.dispatch(request.message, id, device).await;
+        if let Some(ip) = ip.filter(|_| login && !response.ok) {
+            self.failures.lock().record(ip, Instant::now());
+        }
+        response
+    }
+
+    async fn dispatch(
+        &self,
+        message: ClientMessage,
+        id: Option<u64>,
+        device: &mut Option<String>,
+    ) -> Response {
+        let to_value = |value: Result<serde_json::Value>| Response::new(id, value);
+
+        match (message, device.is_some()) {
+            (ClientMessage::Pair { code, device: name }, _) => {
+                to_value(self.pair(&code, &name).map(|(device_id, token)| {
+                    *device = Some(device_id.clone());
+                    serde_json::json!({ "device_id": device_id, "token": token })
+                }))
+            }
+            (ClientMessage::Auth { token }, _) => match self.authenticate(&token) {
+                Some(device_id) => {
+                    *device = Some(device_id.clone());
+                    to_value(Ok(serde_json::json!({ "device_id": device_id })))
+                }
+                None => to_value(Err(anyhow::anyhow!("the token is invalid"))),
+            },
+            (_, false) => to_value(Err(anyhow::anyhow!("the device is not authenticated"))),
+            (ClientMessage::Status, true) => {
+                to_value(serde_json::to_value(control_api::status()).map_err(Into::into))
+            }
+            (ClientMessage::SelectProxy { selector, name }, true) => {
+                let result = RecentNodes::global().select(&selector, &name).await;
+                to_value(result.map(|_| serde_json::Value::Null))
+            }
+            (ClientMessage::ScanResults { batch_id }, true) => {
+                let batches = MeasureBatches::global();
+                let batch = match batch_id {
+                    Some(batch_id) => batches.get(&batch_id),
+                    None => batches.get_all().pop(),
+                };
+                let results = batch
+                    .map(|batch| batch.ordered_results())
+                    .unwrap_or_default();
+                to_value(serde_json::to_value(results).map_err(Into::into))
+            }
+        }
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// not stopped at the first different byte, so the codes are not guessed by the time
+fn constant_eq(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+#[test]
+fn test_remote_control() {
+    let pairing = Pairing {
+        code: "abc".into(),
+        created: Instant::now(),
+    };
+    let now = Instant::now();
+    assert!(pairing.is_valid("abc", now));
+    assert!(!pairing.is_valid("abd", now));
+    assert!(!pairing.is_valid("abc", now + PAIRING_TTL));
+
+    assert_eq!(hash_token("x"), hash_token("x"));
+    assert_ne!(hash_token("x"), hash_token("y"));
+    assert_eq!(hash_token("").len(), 64);
+    assert!(constant_eq("abc", "abc"));
+    assert!(!constant_eq("abc", "abcd"));
+
+    let cert = rcgen::generate_simple_self_signed(vec!["hiddify-remote".to_string()]).unwrap();
+    let der = cert.serialize_der().unwrap();
+    let identity = Identity {
+        cert: format!(
+            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+            general_purpose::STANDARD.encode(&der)
+        ),
+        key: String::new(),
+    };
+    assert_eq!(
+        identity.fingerprint().unwrap(),
+        to_hex(&Sha256::digest(der))
+    );
+
+    let mut failures = Failures::default();
+    let ip = IpAddr::from([192, 168, 1, 3]);
+    (0..MAX_FAILURES).for_each(|_| failures.record(ip, now));
+    assert_eq!(failures.check(ip, now), Some(FAILURE_WINDOW));
+    assert_eq!(failures.check(IpAddr::from([192, 168, 1, 4]), now), None);
+    assert_eq!(failures.check(ip, now + FAILURE_WINDOW), None);
+
+    let request: Request = serde_json::from_str(
+        r#"{"id": 3, "type": "select_proxy", "selector": "GLOBAL", "name": "de"}"#,
+    )
+    .unwrap();
+    assert_eq!(request.id, Some(3));
+    assert!(matches!(request.message, ClientMessage::SelectProxy { .. }));
+
+    let request: Request = serde_json::from_str(r#"{"type": "scan_results"}"#).unwrap();
+    assert!(matches!(
+        request.message,
+        ClientMessage::ScanResults { batch_id: None }
+    ));
+}
@@ -0,0 +1,231 @@
+use super::netinfo;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::net::IpAddr;
+use std::process::Command;
+use tauri::regex::Regex;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+/// the public address to find the interface of the default route
+const PROBE_ADDRESS: &str = "1.1.1.1";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RouteFinding {
+    /// `ok` or `warn` or `error`
+    pub level: String,
+
+    /// the stable id for the translations, such as `default_not_tun`
+    pub code: String,
+
+    pub message: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct TunRouteReport {
+    pub enabled: bool,
+
+    /// the interface used for the public address
+    pub route_interface: Option<String>,
+
+    pub tun_interfaces: Vec<String>,
+
+    pub findings: Vec<RouteFinding>,
+}
+
+impl TunRouteReport {
+    fn push(&mut self, level: &str, code: &str, message: String) {
+        self.findings.push(RouteFinding {
+            level: level.into(),
+            code: code.into(),
+            message,
+        });
+    }
+}
+
+/// check the os routing table against the tun mode
+pub fn check_tun_routes() -> TunRouteReport {
+    let enabled = { Config::verge().latest().enable_tun_mode.unwrap_or(false) };
+    let mut report = TunRouteReport {
+        enabled,
+        ..TunRouteReport::default()
+    };
+
+    if !enabled {
+        report.push("ok", "tun_disabled", "The tun mode is off".into());
+        return report;
+    }
+
+    let tun = {
+        let runtime = Config::runtime();
+        let runtime = runtime.latest();
+        runtime
+            .config
+            .as_ref()
+            .and_then(|config| config.get("tun"))
+            .and_then(|tun| tun.as_mapping())
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    if !tun_flag(&tun, "auto-route") {
+        let message = "The auto-route is off, the routes must be set by hand".into();
+        report.push("warn", "auto_route_off", message);
+    }
+    if !tun_flag(&tun, "auto-detect-interface") {
+        let message = "The auto-detect-interface is off, the outbound may loop into the tun".into();
+        report.push("warn", "auto_detect_off", message);
+    }
+
+    report.tun_interfaces = netinfo::tun_interfaces();
+    if report.tun_interfaces.is_empty() {
+        let message = "No active tun interface found, the core may fail to create it".into();
+        report.push("error", "no_tun_interface", message);
+    }
+
+    match route_interface(PROBE_ADDRESS) {
+        Ok(interface) => {
+            match netinfo::is_tun_interface(&interface) {
+                true => {
+                    let message = format!("The default route points at `{interface}`");
+                    report.push("ok", "default_tun", message);
+                }
+                false => {
+                    let message = format!("The default route points at `{interface}`, not the tun");
+                    report.push("error", "default_not_tun", message);
+                }
+            }
+            report.route_interface = Some(interface);
+        }
+        Err(err) => {
+            let message = format!("Failed to look up the route, {err}");
+            report.push("warn", "route_unknown", message);
+        }
+    }
+
+    // the lan and the excluded addresses must bypass the tun
+    let gateway = netinfo::default_route()
+        .ok()
+        .and_then(|(gateway, _)| gateway);
+    let gateway = gateway.filter(|gateway| gateway.parse::<IpAddr>().is_ok());
+    let mut bypass = exclude_addresses(&tun);
+    bypass.extend(gateway.map(|gateway| (gateway.clone(), gateway)));
+
+    for (cidr, address) in bypass {
+        if let Ok(interface) = route_interface(&address) {
+            if netinfo::is_tun_interface(&interface) {
+                let message = format!("`{cidr}` is routed into the tun `{interface}`");
+                report.push("error", "exclusion_via_tun", message);
+            }
+        }
+    }
+
+    report
+}
+
+/// the flags are added by the app, default is true
+fn tun_flag(tun: &Mapping, key: &str) -> bool {
+    tun.get(key).and_then(Value::as_bool).unwrap_or(true)
+}
+
+/// the (cidr, first address) of the route exclusions of the tun config
+fn exclude_addresses(tun: &Mapping) -> Vec<(String, String)> {
+    ["route-exclude-address", "inet4-route-exclude-address"]
+        .iter()
+        .filter_map(|key| tun.get(*key)?.as_sequence())
+        .flatten()
+        .filter_map(|cidr| {
+            let cidr = cidr.as_str()?;
+            let address = cidr.split('/').next()?;
+            address.parse::<IpAddr>().ok()?;
+            Some((cidr.to_string(), address.to_string()))
+        })
+        .collect()
+}
+
+/// the interface the os picks for the address
+fn route_interface(address: &str) -> Result<String> {
+    #[cfg(target_os = "linux")]
+    let output = Command::new("ip")
+        .args(["route", "get", address])
+        .output()?;
+
+    #[cfg(target_os = "macos")]
+    let output = Command::new("route")
+        .args(["-n", "get", address])
+        .output()?;
+
+    #[cfg(target_os = "windows")]
+    let output = {
+        let route = format!("Find-NetRoute -RemoteIPAddress {address} | Select-Object -First 1");
+        Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                &format!("({route}).InterfaceAlias"),
+            ])
+            .creation_flags(0x08000000)
+            .output()?
+    };
+
+    if !output.status.success() {
+        bail!("failed to run the route command");
+    }
+
+    match parse_route_interface(&String::from_utf8_lossy(&output.stdout)) {
+        Some(interface) => Ok(interface),
+        None => bail!("failed to parse the route of {address}"),
+    }
+}
+
+/// linux: `1.1.1.1 dev Meta table 2022 src 198.18.0.1`
+/// macos: `interface: utun3`
+/// windows: the interface alias only
+fn parse_route_interface(output: &str) -> Option<String> {
+    let linux = Regex::new(r"\bdev (\S+)").unwrap();
+    if let Some(caps) = linux.captures(output) {
+        return Some(caps[1].into());
+    }
+
+    let macos = Regex::new(r"interface:\s*(\S+)").unwrap();
+    if let Some(caps) = macos.captures(output) {
+        return Some(caps[1].into());
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Some(line) = output.lines().map(str::trim).find(|line| !line.is_empty()) {
+        return Some(line.into());
+    }
+
+    None
+}
+
+#[test]
+fn test_parse_route_interface() {
+    let linux = "1.1.1.1 dev Meta table 2022 src 198.18.0.1 uid 1000 \n    cache \n";
+    let linux_direct = "1.1.1.1 via 192.168.1.1 dev wlan0 src 192.168.1.10 uid 1000\n";
+    let macos =
+        "   route to: 1.1.1.1\ndestination: 1.0.0.0\n    gateway: 198.18.0.1\n  interface: utun5\n";
+
+    assert_eq!(parse_route_interface(linux), Some("Meta".into()));
+    assert_eq!(parse_route_interface(linux_direct), Some("wlan0".into()));
+    assert_eq!(parse_route_interface(macos), Some("utun5".into()));
+
+    let tun = r"
+auto-route: false
+route-exclude-address: [192.168.0.0/16, fd00::/8, invalid]
+";
+    let tun = serde_yaml::from_str::<Mapping>(tun).unwrap();
+    assert!(!tun_flag(&tun, "auto-route"));
+    assert!(tun_flag(&tun, "auto-detect-interface"));
+    assert_eq!(
+        exclude_addresses(&tun),
+        vec![
+            ("192.168.0.0/16".to_string(), "192.168.0.0".to_string()),
+            ("fd00::/8".to_string(), "fd00::".to_string())
+        ]
+    );
+}
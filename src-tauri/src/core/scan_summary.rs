@@ -0,0 +1,376 @@
+use super::measure::{MeasureInfo, MeasureMode};
+use super::measure_batch::MeasureBatches;
+use super::rank::{self, RankStrategy, RankedProxy};
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose, Engine};
+use chrono::TimeZone;
+use serde::{Deserialize, Serialize};
+
+/// the number of the proxies in the summary
+const SUMMARY_TOP: usize = 10;
+
+/// the longest name in the image, the names of the markdown are kept
+const NAME_WIDTH: usize = 24;
+
+/// the pixels of a glyph pixel
+const SCALE: usize = 2;
+
+/// the margin around the text (px)
+const MARGIN: usize = 16;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryFormat {
+    Markdown,
+    /// the data url of the image, such as `data:image/png;base64,...`
+    Png,
+}
+
+/// summarize the best proxies of the batch
+/// ranked by the throughput for the full batches, by the latency for the others
+pub fn render_scan_summary(batch_id: &str, format: SummaryFormat) -> Result<String> {
+    let batch = match MeasureBatches::global().get(batch_id) {
+        Some(batch) => batch,
+        None => bail!("failed to find the batch \"{batch_id}\""),
+    };
+    let strategy = match batch.mode {
+        MeasureMode::Full => RankStrategy::ThroughputFirst,
+        _ => RankStrategy::LatencyFirst,
+    };
+
+    let mut ranked = rank::rank(batch.results, strategy);
+    ranked.truncate(SUMMARY_TOP);
+    let title = format!(
+        "{} of {} proxies, {}",
+        ranked.len(),
+        batch.names.len(),
+        format_time(batch.updated)
+    );
+    let rows = summary_rows(&ranked);
+
+    match format {
+        SummaryFormat::Markdown => Ok(render_markdown(&title, &rows)),
+        SummaryFormat::Png => {
+            let png = render_png(&title, &rows);
+            Ok(format!(
+                "data:image/png;base64,{}",
+                general_purpose::STANDARD.encode(png)
+            ))
+        }
+    }
+}
+
+fn format_time(timestamp: usize) -> String {
+    match chrono::Local.timestamp_opt(timestamp as i64, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => "-".into(),
+    }
+}
+
+/// the header first
+fn summary_rows(ranked: &[RankedProxy]) -> Vec<Vec<String>> {
+    let mbps = |stats: &Option<super::measure::MeasureStats>| match stats {
+        Some(stats) => format!("{:.1}", stats.median as f64 * 8.0 / 1_000_000.0),
+        None => "-".into(),
+    };
+    let latency = |info: &MeasureInfo| match (&info.error, &info.latency) {
+        (None, Some(latency)) => latency.median.to_string(),
+        _ => "-".into(),
+    };
+
+    let header = [
+        "#",
+        "Name",
+        "Latency (ms)",
+        "Down (Mbps)",
+        "Up (Mbps)",
+        "Score",
+    ];
+    let mut rows = vec![header.iter().map(|h| h.to_string()).collect::<Vec<_>>()];
+    rows.extend(ranked.iter().enumerate().map(|(index, proxy)| {
+        vec![
+            (index + 1).to_string(),
+            proxy.name.clone(),
+            latency(&proxy.info),
+            mbps(&proxy.info.download),
+            mbps(&proxy.info.upload),
+            format!("{:.1}", proxy.score),
+        ]
+    }));
+    rows
+}
+
+fn render_markdown(title: &str, rows: &[Vec<String>]) -> String {
+    let line = |row: &Vec<String>| {
+        let cells = row
+            .iter()
+            .map(|cell| cell.replace('|', "\\|"))
+            .collect::<Vec<_>>();
+        format!("| {} |\n", cells.join(" | "))
+    };
+
+    let mut text = format!("**{title}**\n\n");
+    if let Some((header, body)) = rows.split_first() {
+        text.push_str(&line(header));
+        let align = header
+            .iter()
+            .enumerate()
+            .map(|(index, _)| if index == 1 { "---" } else { "---:" });
+        text.push_str(&format!("| {} |\n", align.collect::<Vec<_>>().join(" | ")));
+        body.iter().for_each(|row| text.push_str(&line(row)));
+    }
+    text
+}
+
+/// the rows as the aligned text lines, only the ascii glyphs of `FONT` are drawn
+fn text_lines(title: &str, rows: &[Vec<String>]) -> Vec<String> {
+    let clean = |text: &str| {
+        let text = text
+            .chars()
+            .filter(|c| c.is_ascii() && !c.is_ascii_control())
+            .collect::<String>();
+        text.trim().chars().take(NAME_WIDTH).collect::<String>()
+    };
+    let rows = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| clean(cell)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let columns = rows.first().map_or(0, |row| row.len());
+    let widths = (0..columns)
+        .map(|index| rows.iter().map(|row| row[index].len()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![clean(title), String::new()];
+    lines.extend(rows.iter().map(|row| {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(index, (cell, width))| match index {
+                1 => format!("{cell:<width$}"),
+                _ => format!("{cell:>width$}"),
+            });
+        cells.collect::<Vec<_>>().join("  ")
+    }));
+    lines
+}
+
+/// black text on white, 8-bit grayscale
+fn render_png(title: &str, rows: &[Vec<String>]) -> Vec<u8> {
+    let lines = text_lines(title, rows);
+    let (advance, line_height) = ((GLYPH_WIDTH + 1) * SCALE, (GLYPH_HEIGHT + 3) * SCALE);
+    let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let width = columns * advance + MARGIN * 2;
+    let height = lines.len() * line_height + MARGIN * 2;
+
+    let mut pixels = vec![0xffu8; width * height];
+    for (row, line) in lines.iter().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            let (x0, y0) = (MARGIN + column * advance, MARGIN + row * line_height);
+            let bits = match glyph(c) {
+                Some(bits) => bits,
+                None => continue,
+            };
+            for (gy, bits) in bits.iter().enumerate() {
+                for gx in (0..GLYPH_WIDTH).filter(|gx| bits & (0x10 >> gx) != 0) {
+                    for (dy, dx) in (0..SCALE).flat_map(|dy| (0..SCALE).map(move |dx| (dy, dx))) {
+                        let (x, y) = (x0 + gx * SCALE + dx, y0 + gy * SCALE + dy);
+                        pixels[y * width + x] = 0x20;
+                    }
+                }
+            }
+        }
+    }
+
+    encode_png(width as u32, height as u32, &pixels)
+}
+
+fn glyph(c: char) -> Option<[u8; 7]> {
+    let c = c.to_ascii_uppercase();
+    match FONT.binary_search_by_key(&c, |(key, _)| *key) {
+        Ok(index) => Some(FONT[index].1),
+        Err(_) if c == ' ' => None,
+        Err(_) => glyph('?'),
+    }
+}
+
+/// the grayscale image in the stored deflate blocks, without compression
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width as usize) {
+        raw.push(0); // no filter
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 0, 0, 0, 0]); // 8-bit grayscale
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// 5x7 glyphs, the high bit of the 5 is the left, sorted for the binary search
+/// the lowercase letters are drawn in the uppercase
+const FONT: [(char, [u8; 7]); 59] = [
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('#', [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('&', [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d]),
+    ('\'', [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('*', [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('0', [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e]),
+    ('1', [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+    ('2', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f]),
+    ('3', [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e]),
+    ('4', [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02]),
+    ('5', [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e]),
+    ('6', [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e]),
+    ('7', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e]),
+    ('9', [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c]),
+    (':', [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00]),
+    ('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+    ('=', [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00]),
+    ('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+    ('?', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('@', [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e]),
+    ('A', [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11]),
+    ('B', [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e]),
+    ('C', [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e]),
+    ('D', [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c]),
+    ('E', [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f]),
+    ('F', [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10]),
+    ('G', [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f]),
+    ('H', [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11]),
+    ('I', [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f]),
+    ('M', [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e]),
+    ('P', [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10]),
+    ('Q', [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d]),
+    ('R', [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11]),
+    ('S', [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e]),
+    ('T', [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a]),
+    ('X', [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x0a, 0x04, 0x04, 0x04, 0x04]),
+    ('Z', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f]),
+    ('[', [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e]),
+    (']', [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+#[test]
+fn test_scan_summary() {
+    use super::measure::MeasureStats;
+
+    let stats = |median: u64| MeasureStats {
+        median,
+        p90: median,
+        std_dev: 0.0,
+    };
+    let infos = vec![
+        MeasureInfo {
+            name: "🇩🇪 DE | Hetzner".into(),
+            latency: Some(stats(80)),
+            download: Some(stats(2_500_000)),
+            ..MeasureInfo::default()
+        },
+        MeasureInfo {
+            name: "us".into(),
+            error: Some("timeout".into()),
+            ..MeasureInfo::default()
+        },
+    ];
+    let ranked = rank::rank(infos, RankStrategy::ThroughputFirst);
+    let rows = summary_rows(&ranked);
+
+    let markdown = render_markdown("2 of 2 proxies", &rows);
+    let lines = markdown.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[2],
+        "| # | Name | Latency (ms) | Down (Mbps) | Up (Mbps) | Score |"
+    );
+    assert_eq!(lines[3], "| ---: | --- | ---: | ---: | ---: | ---: |");
+    assert!(lines[4].starts_with("| 1 | 🇩🇪 DE \\| Hetzner | 80 | 20.0 | - |"));
+    assert!(lines[5].starts_with("| 2 | us | - | - | - | 0.0 |"));
+
+    let text = text_lines("2 of 2 proxies", &rows);
+    assert_eq!(
+        text[3],
+        "1  DE | Hetzner            80         20.0          -   73.5"
+    );
+
+    let png = render_png("2 of 2 proxies", &rows);
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert!(png.ends_with(&[0xae, 0x42, 0x60, 0x82]));
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+}
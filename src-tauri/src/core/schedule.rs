@@ -0,0 +1,135 @@
+use crate::config::{Config, IVergeSchedule};
+use crate::{feat, log_err};
+use anyhow::{bail, Result};
+use chrono::{Datelike, Local, Timelike};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+const CLASH_MODES: [&str; 4] = ["rule", "global", "direct", "script"];
+
+pub struct Schedule {
+    /// the last minute (unix timestamp / 60) checked
+    /// avoid running the same tasks twice
+    last_minute: Arc<Mutex<Option<i64>>>,
+}
+
+impl Schedule {
+    pub fn global() -> &'static Schedule {
+        static SCHEDULE: OnceCell<Schedule> = OnceCell::new();
+
+        SCHEDULE.get_or_init(|| Schedule {
+            last_minute: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// launch the schedule loop
+    /// check the timetable at the beginning of every minute
+    pub fn init(&'static self) -> Result<()> {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let second = Local::now().second() as u64;
+                sleep(Duration::from_secs(60 - second.min(59))).await;
+                self.run_tasks();
+            }
+        });
+
+        Ok(())
+    }
+
+    /// check the schedule list before saving it
+    pub fn check(schedule: &[IVergeSchedule]) -> Result<()> {
+        for item in schedule.iter() {
+            if Self::parse_time(&item.time).is_none() {
+                bail!("invalid schedule time \"{}\"", item.time);
+            }
+
+            if let Some(weekdays) = item.weekdays.as_ref() {
+                if let Some(day) = weekdays.iter().find(|d| !(1..=7).contains(*d)) {
+                    bail!("invalid schedule weekday \"{day}\"");
+                }
+            }
+
+            if let Some(mode) = item.mode.as_ref() {
+                if !CLASH_MODES.contains(&mode.as_str()) {
+                    bail!("invalid clash mode \"{mode}\"");
+                }
+            }
+
+            if let Some(uid) = item.profile.as_ref() {
+                Config::profiles().latest().get_item(uid)?;
+            }
+
+            if item.mode.is_none() && item.profile.is_none() {
+                bail!("the schedule at \"{}\" has nothing to do", item.time);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// parse `HH:MM` to (hour, minute)
+    fn parse_time(time: &str) -> Option<(u32, u32)> {
+        let (hour, minute) = time.trim().split_once(':')?;
+        let hour = hour.parse::<u32>().ok()?;
+        let minute = minute.parse::<u32>().ok()?;
+
+        match hour < 24 && minute < 60 {
+            true => Some((hour, minute)),
+            false => None,
+        }
+    }
+
+    /// run the tasks matching the current minute
+    fn run_tasks(&self) {
+        let now = Local::now();
+
+        let minute = now.timestamp() / 60;
+        let mut last_minute = self.last_minute.lock();
+        if *last_minute == Some(minute) {
+            return;
+        }
+        *last_minute = Some(minute);
+        drop(last_minute);
+
+        let schedule = { Config::verge().latest().mode_schedule.clone() };
+        let weekday = now.weekday().number_from_monday();
+
+        schedule
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|item| item.enable.unwrap_or(true))
+            .filter(|item| Self::parse_time(&item.time) == Some((now.hour(), now.minute())))
+            .filter(|item| {
+                item.weekdays
+                    .as_ref()
+                    .map_or(true, |days| days.contains(&weekday))
+            })
+            .for_each(|item| {
+                log::info!(target: "app", "running schedule task at `{}`", item.time);
+
+                tauri::async_runtime::spawn(async move {
+                    // switch the profile first, the mode is kept in `config.yaml`
+                    if let Some(uid) = item.profile {
+                        log_err!(feat::switch_profile(uid).await);
+                    }
+                    if let Some(mode) = item.mode {
+                        feat::change_clash_mode(mode);
+                    }
+                });
+            });
+    }
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(Schedule::parse_time("08:30"), Some((8, 30)));
+    assert_eq!(Schedule::parse_time(" 23:59 "), Some((23, 59)));
+    assert_eq!(Schedule::parse_time("0:0"), Some((0, 0)));
+
+    assert_eq!(Schedule::parse_time("24:00"), None);
+    assert_eq!(Schedule::parse_time("12:60"), None);
+    assert_eq!(Schedule::parse_time("1230"), None);
+    assert_eq!(Schedule::parse_time(""), None);
+}
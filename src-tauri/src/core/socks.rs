@@ -0,0 +1,157 @@
+use anyhow::{bail, Result};
+use std::net::{IpAddr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// socks5 udp associate
+/// return the control connection and the relay address
+/// the relay is only available while the control connection is open
+pub async fn udp_associate(
+    proxy: SocketAddr,
+    auth: Option<(String, String)>,
+) -> Result<(TcpStream, SocketAddr)> {
+    let mut stream = TcpStream::connect(proxy).await?;
+
+    // no authentication, or username/password
+    match auth.is_some() {
+        true => stream.write_all(&[5, 2, 0, 2]).await?,
+        false => stream.write_all(&[5, 1, 0]).await?,
+    }
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+
+    match (reply, auth) {
+        ([5, 0], _) => {}
+        ([5, 2], Some((user, pass))) => {
+            stream.write_all(&auth_request(&user, &pass)?).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0 {
+                bail!("socks5 authentication failed");
+            }
+        }
+        _ => bail!("socks5 handshake failed"),
+    }
+
+    stream.write_all(&[5, 3, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[1] != 0 {
+        bail!("socks5 udp associate failed with code {}", head[1]);
+    }
+
+    let ip: IpAddr = match head[3] {
+        1 => {
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await?;
+            buf.into()
+        }
+        4 => {
+            let mut buf = [0u8; 16];
+            stream.read_exact(&mut buf).await?;
+            buf.into()
+        }
+        atyp => bail!("unsupported socks5 address type {atyp}"),
+    };
+    let port = stream.read_u16().await?;
+
+    // some servers reply with the unspecified address
+    let ip = match ip.is_unspecified() {
+        true => proxy.ip(),
+        false => ip,
+    };
+
+    Ok((stream, SocketAddr::new(ip, port)))
+}
+
+/// the username/password request of rfc 1929
+fn auth_request(user: &str, pass: &str) -> Result<Vec<u8>> {
+    if user.len() > 255 || pass.len() > 255 {
+        bail!("the socks5 username or password is too long");
+    }
+
+    let mut request = vec![1, user.len() as u8];
+    request.extend_from_slice(user.as_bytes());
+    request.push(pass.len() as u8);
+    request.extend_from_slice(pass.as_bytes());
+    Ok(request)
+}
+
+/// wrap the payload with the socks5 udp header
+/// the target is `host:port`
+pub fn encode_udp(target: &str, payload: &[u8]) -> Result<Vec<u8>> {
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) => (host.trim_matches(|c| c == '[' || c == ']'), port),
+        None => bail!("invalid target \"{target}\""),
+    };
+    let port = match port.parse::<u16>() {
+        Ok(port) => port,
+        Err(_) => bail!("invalid target port \"{target}\""),
+    };
+
+    let mut packet = vec![0, 0, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            packet.push(1);
+            packet.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            packet.push(4);
+            packet.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.is_empty() || host.len() > 255 {
+                bail!("invalid target host \"{target}\"");
+            }
+            packet.push(3);
+            packet.push(host.len() as u8);
+            packet.extend_from_slice(host.as_bytes());
+        }
+    }
+    packet.extend_from_slice(&port.to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    Ok(packet)
+}
+
+/// strip the socks5 udp header
+/// the fragmented packet is not supported
+pub fn decode_udp(packet: &[u8]) -> Option<&[u8]> {
+    if packet.len() < 4 || packet[2] != 0 {
+        return None;
+    }
+
+    let addr_len = match packet[3] {
+        1 => 4,
+        4 => 16,
+        3 => 1 + *packet.get(4)? as usize,
+        _ => return None,
+    };
+
+    packet.get(4 + addr_len + 2..)
+}
+
+#[test]
+fn test_udp_header() {
+    let packet = encode_udp("1.2.3.4:53", b"hi").unwrap();
+    assert_eq!(packet, [0, 0, 0, 1, 1, 2, 3, 4, 0, 53, b'h', b'i']);
+    assert_eq!(decode_udp(&packet), Some(&b"hi"[..]));
+
+    let packet = encode_udp("echo.example.com:7", b"hi").unwrap();
+    assert_eq!(packet[3..5], [3, 16]);
+    assert_eq!(decode_udp(&packet), Some(&b"hi"[..]));
+
+    let packet = encode_udp("[::1]:7", b"").unwrap();
+    assert_eq!(packet.len(), 3 + 1 + 16 + 2);
+    assert_eq!(decode_udp(&packet), Some(&b""[..]));
+
+    assert!(encode_udp("example.com", b"").is_err());
+    assert!(encode_udp("example.com:99999", b"").is_err());
+    assert!(encode_udp(":7", b"").is_err());
+
+    assert_eq!(decode_udp(&[0, 0, 1, 1, 1, 2, 3, 4, 0, 53]), None);
+    assert_eq!(decode_udp(&[0, 0, 0, 1, 1, 2]), None);
+
+    let request = auth_request("ab", "c").unwrap();
+    assert_eq!(request, [1, 2, b'a', b'b', 1, b'c']);
+    assert!(auth_request(&"a".repeat(256), "").is_err());
+}
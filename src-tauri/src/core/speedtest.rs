@@ -0,0 +1,271 @@
+use super::distance::{self, Coordinate};
+use super::measure::{proxy_client, Measure};
+use super::rate_limit;
+use crate::config::Config;
+use anyhow::{bail, Result};
+pub use measure_backend::{SpeedtestClient, SpeedtestServer};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::regex::Regex;
+
+/// the `client` node contains the public ip, the isp and the location
+const SPEEDTEST_CONFIG_URL: &str = "https://www.speedtest.net/speedtest-config.php";
+
+const SPEEDTEST_SERVERS_URL: &str = "https://www.speedtest.net/speedtest-servers-static.php";
+
+const SERVERS_TTL: Duration = Duration::from_secs(3600);
+
+/// the server list and the fetched time
+type ServerCache = (Instant, Vec<SpeedtestServer>);
+
+/// the default number of the candidate servers
+pub const DEFAULT_SERVER_COUNT: usize = 5;
+
+/// where the servers are picked around
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LocationStrategy {
+    /// the exit of the proxy, looked up through the measure listener
+    ProxyExit,
+    /// the direct connection, geolocated by speedtest.net
+    /// the mmdb of the core has no coordinates but the countries
+    LocalGeoip,
+    ManualCoordinates {
+        lat: f64,
+        lon: f64,
+    },
+}
+
+impl LocationStrategy {
+    /// `speedtest_location` and `speedtest_coordinates`
+    pub fn from_verge() -> LocationStrategy {
+        let (location, coordinates) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.speedtest_location.clone(),
+                verge.speedtest_coordinates,
+            )
+        };
+
+        match (location.as_deref(), coordinates) {
+            (Some("proxy_exit"), _) => LocationStrategy::ProxyExit,
+            (Some("manual"), Some([lat, lon])) => LocationStrategy::ManualCoordinates { lat, lon },
+            _ => LocationStrategy::LocalGeoip,
+        }
+    }
+
+    /// the proxy is required by `ProxyExit`
+    pub async fn resolve(&self, proxy: Option<&str>) -> Result<Coordinate> {
+        let client = match (self, proxy) {
+            (LocationStrategy::ManualCoordinates { lat, lon }, _) => return Ok((*lat, *lon)),
+            (LocationStrategy::ProxyExit, Some(proxy)) => {
+                Measure::global().through(proxy, fetch_client(true)).await?
+            }
+            (LocationStrategy::ProxyExit, None) => bail!("the proxy of the exit is not set"),
+            (LocationStrategy::LocalGeoip, _) => fetch_client(false).await?,
+        };
+
+        match client.location() {
+            Some(location) => Ok(location),
+            None => bail!("failed to get the client location"),
+        }
+    }
+}
+
+/// the client seen through the proxy and the nearest server by `speedtest_location`
+pub async fn pick_server(proxy: &str) -> Result<(SpeedtestClient, SpeedtestServer)> {
+    let client = Measure::global().through(proxy, fetch_client(true)).await?;
+
+    let location = match LocationStrategy::from_verge() {
+        LocationStrategy::ProxyExit => client.location(),
+        strategy => Some(strategy.resolve(None).await?),
+    };
+    let location = match location {
+        Some(location) => location,
+        None => bail!("failed to get the client location"),
+    };
+
+    let servers = servers_sorted_by_distance(fetch_servers().await?, location, 1);
+    match servers.into_iter().next() {
+        Some(server) => Ok((client, server)),
+        None => bail!("failed to find the speedtest servers"),
+    }
+}
+
+/// get the client from the speedtest config, through the measure listener if proxied
+pub async fn fetch_client(proxied: bool) -> Result<SpeedtestClient> {
+    let text = fetch_text(SPEEDTEST_CONFIG_URL, proxied).await?;
+    match parse_client(&text) {
+        Some(client) => Ok(client),
+        None => bail!("failed to find the client node"),
+    }
+}
+
+/// cached for `SERVERS_TTL`, so that a batch fetches the list once
+pub async fn fetch_servers() -> Result<Vec<SpeedtestServer>> {
+    static SERVERS: OnceCell<Mutex<Option<ServerCache>>> = OnceCell::new();
+    let cache = SERVERS.get_or_init(|| Mutex::new(None));
+
+    if let Some((time, servers)) = cache.lock().as_ref() {
+        if time.elapsed() < SERVERS_TTL {
+            return Ok(servers.clone());
+        }
+    }
+
+    let servers = parse_servers(&fetch_text(SPEEDTEST_SERVERS_URL, false).await?);
+    if servers.is_empty() {
+        bail!("failed to find the speedtest servers");
+    }
+    *cache.lock() = Some((Instant::now(), servers.clone()));
+    Ok(servers)
+}
+
+/// the nearest servers to the location, one from each metro area first
+pub async fn get_servers(
+    count: usize,
+    strategy: LocationStrategy,
+    proxy: Option<&str>,
+) -> Result<Vec<SpeedtestServer>> {
+    let location = strategy.resolve(proxy).await?;
+    let servers = fetch_servers().await?;
+    Ok(servers_sorted_by_distance(servers, location, count))
+}
+
+async fn fetch_text(url: &str, proxied: bool) -> Result<String> {
+    let builder = match proxied {
+        true => proxy_client()?,
+        false => reqwest::ClientBuilder::new().no_proxy(),
+    };
+    let client = builder.timeout(Duration::from_secs(10)).build()?;
+
+    rate_limit::speedtest().acquire().await;
+    Ok(client.get(url).send().await?.text().await?)
+}
+
+/// pick the nearest server of each metro area by the distance,
+/// then fill up with the rest if there are fewer areas than the count
+pub fn servers_sorted_by_distance(
+    mut servers: Vec<SpeedtestServer>,
+    location: Coordinate,
+    count: usize,
+) -> Vec<SpeedtestServer> {
+    for server in servers.iter_mut() {
+        server.distance = Some(distance::vincenty(location, (server.lat, server.lon)));
+    }
+    servers.sort_by(|a, b| {
+        a.distance
+            .unwrap_or_default()
+            .total_cmp(&b.distance.unwrap_or_default())
+    });
+
+    let points = servers.iter().map(|s| (s.lat, s.lon)).collect::<Vec<_>>();
+    let groups = distance::cluster(&points, distance::METRO_RADIUS);
+
+    let mut seen = vec![];
+    let (mut picked, mut rest) = (vec![], vec![]);
+    for (server, group) in servers.into_iter().zip(groups) {
+        match seen.contains(&group) {
+            true => rest.push(server),
+            false => {
+                seen.push(group);
+                picked.push(server);
+            }
+        }
+    }
+
+    picked.truncate(count);
+    let left = count - picked.len();
+    picked.extend(rest.into_iter().take(left));
+    picked.sort_by(|a, b| {
+        a.distance
+            .unwrap_or_default()
+            .total_cmp(&b.distance.unwrap_or_default())
+    });
+    picked
+}
+
+fn parse_client(text: &str) -> Option<SpeedtestClient> {
+    let node = Regex::new(r"<client\s[^>]*>").unwrap();
+    let node = node.find(text)?.as_str();
+
+    Some(SpeedtestClient {
+        ip: xml_attr(node, "ip"),
+        isp: xml_attr(node, "isp"),
+        country: xml_attr(node, "country").map(|country| country.to_uppercase()),
+        lat: xml_attr(node, "lat").and_then(|lat| lat.parse().ok()),
+        lon: xml_attr(node, "lon").and_then(|lon| lon.parse().ok()),
+    })
+}
+
+fn parse_servers(text: &str) -> Vec<SpeedtestServer> {
+    let node = Regex::new(r"<server\s[^>]*>").unwrap();
+    node.find_iter(text)
+        .filter_map(|node| {
+            let node = node.as_str();
+            Some(SpeedtestServer {
+                id: xml_attr(node, "id")?,
+                sponsor: xml_attr(node, "sponsor").unwrap_or_default(),
+                name: xml_attr(node, "name").unwrap_or_default(),
+                country: xml_attr(node, "country").unwrap_or_default(),
+                lat: xml_attr(node, "lat")?.parse().ok()?,
+                lon: xml_attr(node, "lon")?.parse().ok()?,
+                url: xml_attr(node, "url")?,
+                host: xml_attr(node, "host")?,
+                distance: None,
+            })
+        })
+        .collect()
+}
+
+fn xml_attr(node: &str, name: &str) -> Option<String> {
+    let attr = Regex::new(&format!(r#"\s{name}="([^"]*)""#)).ok()?;
+    attr.captures(node)
+        .map(|caps| caps[1].to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[test]
+fn test_servers_sorted_by_distance() {
+    let text = r#"<settings><client ip="1.2.3.4" lat="52.52" lon="13.40" isp="Telekom" country="de" />
+<servers>
+<server url="http://a/upload.php" lat="52.52" lon="13.41" name="Berlin" country="Germany" cc="DE" sponsor="A" id="1" host="a:8080" />
+<server url="http://b/upload.php" lat="52.50" lon="13.38" name="Berlin" country="Germany" cc="DE" sponsor="B" id="2" host="b:8080" />
+<server url="http://c/upload.php" lat="50.47" lon="12.37" name="Falkenstein" country="Germany" cc="DE" sponsor="Hetzner" id="3" host="c:8080" />
+<server url="http://d/upload.php" lat="53.55" lon="9.99" name="Hamburg" country="Germany" cc="DE" sponsor="D" id="4" host="d:8080" />
+<server lat="bad" lon="9.99" id="5" host="e:8080" />
+</servers></settings>"#;
+
+    let client = parse_client(text).unwrap();
+    assert_eq!(client.ip.as_deref(), Some("1.2.3.4"));
+    assert_eq!(client.location(), Some((52.52, 13.40)));
+    assert_eq!(client.country.as_deref(), Some("DE"));
+
+    let servers = parse_servers(text);
+    assert_eq!(servers.len(), 4);
+
+    let ids = |servers: Vec<SpeedtestServer>| servers.into_iter().map(|s| s.id).collect::<Vec<_>>();
+    let sorted = servers_sorted_by_distance(servers.clone(), (52.52, 13.40), 3);
+    assert_eq!(ids(sorted), vec!["1", "3", "4"]);
+
+    let sorted = servers_sorted_by_distance(servers, (52.52, 13.40), 4);
+    assert_eq!(ids(sorted.clone()), vec!["1", "2", "3", "4"]);
+    assert!(sorted[0].distance.unwrap() < 1.0);
+    assert_eq!(sorted[0].download_url(), "http://a/random4000x4000.jpg");
+
+    let manual = r#"{"type": "manual_coordinates", "lat": 50.1, "lon": 8.7}"#;
+    assert_eq!(
+        serde_json::from_str::<LocationStrategy>(manual).unwrap(),
+        LocationStrategy::ManualCoordinates {
+            lat: 50.1,
+            lon: 8.7
+        }
+    );
+
+    let node = r#"<client ip="1.2.3.4" lat="0" isp="Example ISP" isprating="3.7">"#;
+    assert_eq!(xml_attr(node, "ip"), Some("1.2.3.4".into()));
+    assert_eq!(xml_attr(node, "isp"), Some("Example ISP".into()));
+    assert_eq!(xml_attr(node, "country"), None);
+}
@@ -0,0 +1,285 @@
+use super::app_status;
+use super::clash_api;
+use super::handle::Handle;
+use crate::config::{Config, ConfigKind, IProfiles, IVerge};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// the state sent by `subscribe_state`, the deltas follow it
+#[derive(Debug, Clone, Serialize)]
+pub struct StateSnapshot {
+    /// the seq of the last delta, a gap in the seq means to subscribe again
+    pub seq: u64,
+
+    pub profiles: IProfiles,
+
+    pub verge: IVerge,
+
+    /// the selected node of each group, empty if the core is not running
+    pub selected: BTreeMap<String, String>,
+}
+
+/// `verge://state-profiles`
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct ProfilesDelta {
+    pub seq: u64,
+
+    pub current: Option<String>,
+
+    pub chain: Option<Vec<String>>,
+
+    /// the added and the changed items
+    pub changed: Vec<Value>,
+
+    /// the uids of the removed items
+    pub removed: Vec<String>,
+
+    /// the uids of all the items in order
+    pub order: Vec<String>,
+}
+
+/// `verge://state-verge`, the changed fields with the new values, null if unset
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct VergeDelta {
+    pub seq: u64,
+
+    pub changed: Map<String, Value>,
+}
+
+/// `verge://state-proxy`
+#[derive(Default, Debug, Clone, Serialize, PartialEq)]
+pub struct ProxyDelta {
+    pub seq: u64,
+
+    pub group: String,
+
+    pub now: String,
+}
+
+/// the state last sent to the webview
+struct Sent {
+    profiles: Value,
+    verge: Map<String, Value>,
+}
+
+/// emit the deltas of the state to the subscribed webview instead of being polled
+pub struct StateEvents {
+    /// none before `subscribe_state`
+    sent: Arc<Mutex<Option<Sent>>>,
+
+    seq: AtomicU64,
+}
+
+impl StateEvents {
+    pub fn global() -> &'static StateEvents {
+        static EVENTS: OnceCell<StateEvents> = OnceCell::new();
+
+        EVENTS.get_or_init(|| StateEvents {
+            sent: Arc::new(Mutex::new(None)),
+            seq: AtomicU64::new(0),
+        })
+    }
+
+    /// the handshake of the webview, the deltas are sent after it
+    pub async fn subscribe(&self) -> StateSnapshot {
+        let selected = match clash_api::get_proxies().await {
+            Ok(proxies) => app_status::selected_nodes(&proxies),
+            Err(_) => BTreeMap::new(),
+        };
+        let profiles = { Config::profiles().latest().clone() };
+        let verge = { Config::verge().latest().clone() };
+
+        let mut sent = self.sent.lock();
+        *sent = Some(Sent {
+            profiles: to_json(&profiles),
+            verge: to_object(&verge),
+        });
+
+        StateSnapshot {
+            seq: self.seq.load(Ordering::SeqCst),
+            profiles,
+            verge,
+            selected,
+        }
+    }
+
+    /// emit the changed items of the profiles
+    pub fn profiles_changed(&self) {
+        let profiles = to_json(&*Config::profiles().latest());
+
+        let delta = {
+            let mut sent = self.sent.lock();
+            let sent = match sent.as_mut() {
+                Some(sent) => sent,
+                None => return,
+            };
+            let delta = diff_profiles(&sent.profiles, &profiles);
+            sent.profiles = profiles;
+            delta
+        };
+
+        if let Some(mut delta) = delta {
+            delta.seq = self.next_seq();
+            Handle::profiles_delta(delta);
+        }
+    }
+
+    /// emit the changed fields of the verge config
+    pub fn verge_changed(&self) {
+        let verge = to_object(&*Config::verge().latest());
+
+        let changed = {
+            let mut sent = self.sent.lock();
+            let sent = match sent.as_mut() {
+                Some(sent) => sent,
+                None => return,
+            };
+            let changed = diff_object(&sent.verge, &verge);
+            sent.verge = verge;
+            changed
+        };
+
+        if !changed.is_empty() {
+            let seq = self.next_seq();
+            Handle::verge_delta(VergeDelta { seq, changed });
+        }
+    }
+
+    /// emit the node selected by the app, such as by the tray or the remote control
+    pub fn proxy_selected(&self, group: &str, now: &str) {
+        if self.sent.lock().is_none() {
+            return;
+        }
+
+        let seq = self.next_seq();
+        Handle::proxy_delta(ProxyDelta {
+            seq,
+            group: group.into(),
+            now: now.into(),
+        });
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+/// emit the changes of the committed config, see `config::on_commit`
+pub fn config_committed(kind: ConfigKind) {
+    match kind {
+        ConfigKind::Clash | ConfigKind::Override => Handle::refresh_clash(),
+        ConfigKind::Verge => StateEvents::global().verge_changed(),
+        ConfigKind::Profiles => StateEvents::global().profiles_changed(),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+fn to_object<T: Serialize>(value: &T) -> Map<String, Value> {
+    match to_json(value) {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+/// the fields of `new` different from `old`, the removed ones are null
+fn diff_object(old: &Map<String, Value>, new: &Map<String, Value>) -> Map<String, Value> {
+    let mut changed = new
+        .iter()
+        .filter(|(key, value)| old.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<Map<_, _>>();
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        changed.insert(key.clone(), Value::Null);
+    }
+    changed
+}
+
+/// none if nothing is changed
+fn diff_profiles(old: &Value, new: &Value) -> Option<ProfilesDelta> {
+    let items = |profiles: &Value| {
+        profiles
+            .get("items")
+            .and_then(|items| items.as_array())
+            .cloned()
+            .unwrap_or_default()
+    };
+    let uid = |item: &Value| {
+        item.get("uid")
+            .and_then(|uid| uid.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let (old_items, new_items) = (items(old), items(new));
+
+    let changed = new_items
+        .iter()
+        .filter(|item| !old_items.contains(item))
+        .cloned()
+        .collect::<Vec<_>>();
+    let order = new_items.iter().map(uid).collect::<Vec<_>>();
+    let removed = old_items
+        .iter()
+        .map(uid)
+        .filter(|uid| !order.contains(uid))
+        .collect::<Vec<_>>();
+
+    let same = |key: &str| old.get(key) == new.get(key);
+    let reordered = old_items.iter().map(uid).ne(order.iter().cloned());
+    if changed.is_empty() && removed.is_empty() && !reordered && same("current") && same("chain") {
+        return None;
+    }
+
+    Some(ProfilesDelta {
+        seq: 0,
+        current: new
+            .get("current")
+            .and_then(|c| c.as_str())
+            .map(String::from),
+        chain: serde_json::from_value(new.get("chain").cloned().unwrap_or_default()).ok(),
+        changed,
+        removed,
+        order,
+    })
+}
+
+#[test]
+fn test_state_diff() {
+    use serde_json::json;
+
+    let old = json!({"enable_tun_mode": false, "theme_mode": "dark", "language": "en"});
+    let new = json!({"enable_tun_mode": true, "theme_mode": "dark", "hotkeys": []});
+    let changed = diff_object(old.as_object().unwrap(), new.as_object().unwrap());
+    assert_eq!(
+        Value::Object(changed),
+        json!({"enable_tun_mode": true, "hotkeys": [], "language": null})
+    );
+
+    let old = json!({
+        "current": "a",
+        "items": [{"uid": "a", "name": "A"}, {"uid": "b", "name": "B"}, {"uid": "c"}]
+    });
+    assert!(diff_profiles(&old, &old).is_none());
+
+    let new = json!({
+        "current": "b",
+        "chain": ["m"],
+        "items": [{"uid": "b", "name": "B"}, {"uid": "a", "name": "A2"}, {"uid": "d"}]
+    });
+    let delta = diff_profiles(&old, &new).unwrap();
+    assert_eq!(delta.current.as_deref(), Some("b"));
+    assert_eq!(delta.chain, Some(vec!["m".to_string()]));
+    assert_eq!(
+        delta.changed,
+        [json!({"uid": "a", "name": "A2"}), json!({"uid": "d"})]
+    );
+    assert_eq!(delta.removed, ["c"]);
+    assert_eq!(delta.order, ["b", "a", "d"]);
+}
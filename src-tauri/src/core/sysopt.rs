@@ -1,9 +1,10 @@
-use crate::{config::Config, log_err};
-use anyhow::{anyhow, Result};
+use crate::{config::Config, log_err, utils::resolve};
+use anyhow::{anyhow, bail, Result};
 use auto_launch::{AutoLaunch, AutoLaunchBuilder};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, sync::Arc};
 use sysproxy::Sysproxy;
 use tauri::{async_runtime::Mutex as TokioMutex, utils::platform::current_exe};
 
@@ -29,6 +30,28 @@ static DEFAULT_BYPASS: &str = "localhost,127.0.0.1,::1";
 #[cfg(target_os = "macos")]
 static DEFAULT_BYPASS: &str = "127.0.0.1,localhost,<local>";
 
+#[cfg(target_os = "windows")]
+pub static BYPASS_SEPARATOR: &str = ";";
+#[cfg(not(target_os = "windows"))]
+pub static BYPASS_SEPARATOR: &str = ",";
+
+/// passed to the app by the startup entry
+pub static AUTOSTART_ARG: &str = "--autostart";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AutostartStatus {
+    /// the saved config
+    pub enabled: bool,
+
+    /// whether the startup entry exists
+    pub registered: bool,
+
+    pub start_minimized: bool,
+
+    /// seconds
+    pub delay: u64,
+}
+
 impl Sysopt {
     pub fn global() -> &'static Sysopt {
         static SYSOPT: OnceCell<Sysopt> = OnceCell::new();
@@ -192,6 +215,7 @@ impl Sysopt {
         let auto = AutoLaunchBuilder::new()
             .set_app_name(app_name)
             .set_app_path(&app_path)
+            .set_args(&[AUTOSTART_ARG])
             .build()?;
 
         // 避免在开发时将自启动关了
@@ -241,6 +265,24 @@ impl Sysopt {
         Ok(())
     }
 
+    /// check the startup entry instead of trusting the config
+    pub fn autostart_status(&self) -> Result<AutostartStatus> {
+        let registered = match self.auto_launch.lock().as_ref() {
+            Some(auto_launch) => auto_launch.is_enabled()?,
+            None => bail!("the auto launch is not initialized"),
+        };
+
+        let verge = Config::verge();
+        let verge = verge.latest();
+
+        Ok(AutostartStatus {
+            enabled: verge.enable_auto_launch.unwrap_or(false),
+            registered,
+            start_minimized: verge.enable_silent_start.unwrap_or(false),
+            delay: verge.auto_launch_delay.unwrap_or(0),
+        })
+    }
+
     /// launch a system proxy guard
     /// read config from file directly
     pub fn guard_proxy(&self) {
@@ -275,7 +317,7 @@ impl Sysopt {
                 };
 
                 // stop loop
-                if !enable || !guard {
+                if !enable || !guard || resolve::is_exiting() {
                     break;
                 }
 
@@ -302,3 +344,134 @@ impl Sysopt {
         });
     }
 }
+
+/// get the current system proxy bypass list
+pub fn get_bypass_list() -> Vec<String> {
+    let bypass = { Config::verge().latest().system_proxy_bypass.clone() };
+    bypass
+        .unwrap_or(DEFAULT_BYPASS.into())
+        .split(BYPASS_SEPARATOR)
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// check the bypass item
+/// support domain, ip, cidr and wildcards like `*.local` `192.168.*`
+pub fn check_bypass(entry: &str) -> Result<()> {
+    let entry = entry.trim();
+
+    if entry.is_empty() || entry.contains(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+        bail!("invalid bypass \"{entry}\"");
+    }
+    if entry == "<local>" || entry.parse::<IpAddr>().is_ok() {
+        return Ok(());
+    }
+
+    if let Some((ip, prefix)) = entry.split_once('/') {
+        let max = match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(_)) => 32,
+            Ok(IpAddr::V6(_)) => 128,
+            Err(_) => bail!("invalid bypass cidr \"{entry}\""),
+        };
+        return match prefix.parse::<u8>() {
+            Ok(prefix) if prefix <= max => Ok(()),
+            _ => bail!("invalid bypass cidr \"{entry}\""),
+        };
+    }
+
+    let host = entry.strip_prefix('.').unwrap_or(entry);
+    let valid = host.split('.').all(|label| {
+        !label.is_empty()
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '*')
+    });
+    if !valid {
+        bail!("invalid bypass domain \"{entry}\"");
+    }
+    Ok(())
+}
+
+/// transform the bypass item to the clash `DIRECT` rule
+/// return none if clash has no such rule
+pub fn bypass_to_rule(entry: &str) -> Option<String> {
+    let entry = entry.trim();
+
+    if let Ok(ip) = entry.parse::<IpAddr>() {
+        return Some(match ip {
+            IpAddr::V4(_) => format!("IP-CIDR,{ip}/32,DIRECT,no-resolve"),
+            IpAddr::V6(_) => format!("IP-CIDR6,{ip}/128,DIRECT,no-resolve"),
+        });
+    }
+    if let Some((ip, _)) = entry.split_once('/') {
+        return match ip.parse::<IpAddr>().ok()? {
+            IpAddr::V4(_) => Some(format!("IP-CIDR,{entry},DIRECT,no-resolve")),
+            IpAddr::V6(_) => Some(format!("IP-CIDR6,{entry},DIRECT,no-resolve")),
+        };
+    }
+
+    let suffix = entry.strip_prefix("*.").or(entry.strip_prefix('.'));
+    if let Some(suffix) = suffix.filter(|s| !s.contains('*')) {
+        return Some(format!("DOMAIN-SUFFIX,{suffix},DIRECT"));
+    }
+    if !entry.contains('*') {
+        return match entry == "<local>" {
+            true => None,
+            false => Some(format!("DOMAIN,{entry},DIRECT")),
+        };
+    }
+
+    // `192.168.*` => `192.168.0.0/16`
+    let labels = entry.split('.').collect::<Vec<&str>>();
+    let fixed = labels
+        .iter()
+        .take_while(|l| l.parse::<u8>().is_ok())
+        .count();
+    if labels.len() <= 4 && fixed > 0 && labels[fixed..].iter().all(|l| *l == "*") {
+        let mut octets = labels[..fixed].to_vec();
+        octets.resize(4, "0");
+        let cidr = format!("{}/{}", octets.join("."), fixed * 8);
+        return Some(format!("IP-CIDR,{cidr},DIRECT,no-resolve"));
+    }
+
+    None
+}
+
+#[test]
+fn test_bypass() {
+    [
+        "localhost",
+        "127.*",
+        "*.local",
+        ".lan",
+        "<local>",
+        "10.0.0.0/8",
+        "fe80::/10",
+    ]
+    .into_iter()
+    .for_each(|entry| assert!(check_bypass(entry).is_ok(), "{entry}"));
+
+    ["", "a..b", "foo bar", "a,b", "10.0.0.0/33", "x/8"]
+        .into_iter()
+        .for_each(|entry| assert!(check_bypass(entry).is_err(), "{entry}"));
+
+    assert_eq!(
+        bypass_to_rule("192.168.*"),
+        Some("IP-CIDR,192.168.0.0/16,DIRECT,no-resolve".into())
+    );
+    assert_eq!(
+        bypass_to_rule("::1"),
+        Some("IP-CIDR6,::1/128,DIRECT,no-resolve".into())
+    );
+    assert_eq!(
+        bypass_to_rule("*.local"),
+        Some("DOMAIN-SUFFIX,local,DIRECT".into())
+    );
+    assert_eq!(
+        bypass_to_rule("localhost"),
+        Some("DOMAIN,localhost,DIRECT".into())
+    );
+    assert_eq!(bypass_to_rule("<local>"), None);
+    assert_eq!(bypass_to_rule("foo*.com"), None);
+}
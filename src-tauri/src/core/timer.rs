@@ -1,3 +1,4 @@
+use super::notification::{self, NotifyTrigger};
 use crate::config::Config;
 use crate::feat;
 use anyhow::{Context, Result};
@@ -172,7 +173,28 @@ impl Timer {
     /// the task runner
     async fn async_task(uid: String) {
         log::info!(target: "app", "running timer task `{uid}`");
-        crate::log_err!(feat::update_profile(uid, None).await);
+
+        let name = {
+            let profiles = Config::profiles();
+            let profiles = profiles.latest();
+            let name = profiles
+                .get_item(&uid)
+                .ok()
+                .and_then(|item| item.name.clone());
+            name.unwrap_or(uid.clone())
+        };
+
+        match feat::update_profile(uid, None).await {
+            Ok(_) => {
+                let body = "The profile is updated";
+                notification::notify(NotifyTrigger::ProfileUpdate, &name, body);
+            }
+            Err(err) => {
+                log::error!(target: "app", "{err}");
+                let body = format!("Failed to update the profile, {err}");
+                notification::notify(NotifyTrigger::ProfileUpdate, &name, &body);
+            }
+        }
     }
 }
 
@@ -0,0 +1,299 @@
+use super::clash_api;
+use crate::config::Config;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::net::IpAddr;
+use tauri::regex::Regex;
+
+/// the max depth of the nested groups
+const MAX_CHAIN: usize = 8;
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TraceRule {
+    /// the position in the rule list
+    pub index: usize,
+
+    /// the type reported by the core, such as `DomainSuffix`
+    #[serde(rename = "type")]
+    pub rtype: String,
+
+    pub payload: String,
+
+    /// the target group or proxy
+    pub proxy: String,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DomainTrace {
+    /// the host parsed from the input
+    pub domain: String,
+
+    /// `rule` or `global` or `direct`
+    pub mode: String,
+
+    /// resolved by the core, only supported by clash meta
+    pub resolved: Vec<String>,
+
+    pub dns_error: Option<String>,
+
+    /// the apps get the fake ip instead of the real one
+    pub fake_ip: bool,
+
+    /// the first rule matched
+    pub rule: Option<TraceRule>,
+
+    /// the rules before the match which could not be checked here
+    /// such as `GeoSite` and `RuleSet`, any of them may match first
+    pub unchecked: Vec<TraceRule>,
+
+    /// from the target group to the selected node
+    pub chain: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RuleMatch {
+    Yes,
+    No,
+    Unknown,
+}
+
+/// find which rule and proxy would handle the domain or the url
+pub async fn trace_domain(input: &str) -> Result<DomainTrace> {
+    let domain = match parse_host(input) {
+        Some(domain) => domain,
+        None => bail!("invalid domain \"{input}\""),
+    };
+
+    let (mode, fake_ip) = {
+        let runtime = Config::runtime();
+        let runtime = runtime.latest();
+        let config = runtime.config.as_ref();
+
+        let mode = config
+            .and_then(|c| c.get("mode"))
+            .and_then(Value::as_str)
+            .unwrap_or("rule")
+            .to_lowercase();
+        let fake_ip = config
+            .and_then(|c| c.get("dns"))
+            .and_then(|dns| dns.get("enhanced-mode"))
+            .and_then(Value::as_str)
+            == Some("fake-ip");
+        (mode, fake_ip)
+    };
+
+    let mut trace = DomainTrace {
+        domain: domain.clone(),
+        mode: mode.clone(),
+        fake_ip,
+        ..DomainTrace::default()
+    };
+
+    match domain.parse::<IpAddr>() {
+        Ok(_) => trace.resolved = vec![domain.clone()],
+        Err(_) => match clash_api::dns_query(&domain, "A").await {
+            Ok(resolved) => trace.resolved = resolved,
+            Err(err) => trace.dns_error = Some(err.to_string()),
+        },
+    }
+
+    let target = match mode.as_str() {
+        "global" => "GLOBAL".to_string(),
+        "direct" => "DIRECT".to_string(),
+        _ => {
+            let rules = clash_api::get_rules().await?;
+            let rules = parse_rules(&rules);
+            let ips = trace
+                .resolved
+                .iter()
+                .filter_map(|ip| ip.parse::<IpAddr>().ok())
+                .collect::<Vec<IpAddr>>();
+
+            for rule in rules.into_iter() {
+                match match_rule(&rule, &domain, &ips) {
+                    RuleMatch::Yes => {
+                        trace.rule = Some(rule);
+                        break;
+                    }
+                    RuleMatch::Unknown => trace.unchecked.push(rule),
+                    RuleMatch::No => {}
+                }
+            }
+
+            match trace.rule.as_ref() {
+                Some(rule) => rule.proxy.clone(),
+                None => return Ok(trace),
+            }
+        }
+    };
+
+    // follow the selected proxy of the nested groups
+    let mut name = target;
+    for _ in 0..MAX_CHAIN {
+        trace.chain.push(name.clone());
+
+        match clash_api::get_proxy_now(&name).await {
+            Ok(Some(now)) if !trace.chain.contains(&now) => name = now,
+            _ => break,
+        }
+    }
+
+    Ok(trace)
+}
+
+/// accept `example.com`, `https://example.com/path` or `example.com:443`
+fn parse_host(input: &str) -> Option<String> {
+    let input = input.trim();
+    let rest = input.split_once("://").map_or(input, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next()?,
+        None if host.matches(':').count() == 1 => host.split(':').next()?,
+        None => host,
+    };
+
+    let host = host.trim_end_matches('.').to_lowercase();
+    match host.is_empty() {
+        true => None,
+        false => Some(host),
+    }
+}
+
+fn parse_rules(data: &serde_json::Value) -> Vec<TraceRule> {
+    data.get("rules")
+        .and_then(|rules| rules.as_array())
+        .map_or(vec![], |rules| {
+            rules
+                .iter()
+                .enumerate()
+                .map(|(index, rule)| {
+                    let field = |key: &str| {
+                        let value = rule.get(key).and_then(|v| v.as_str());
+                        value.unwrap_or_default().to_string()
+                    };
+
+                    TraceRule {
+                        index,
+                        rtype: field("type"),
+                        payload: field("payload"),
+                        proxy: field("proxy"),
+                    }
+                })
+                .collect()
+        })
+}
+
+/// the rule types are written differently by the cores
+/// such as `DomainSuffix` and `DOMAIN-SUFFIX`
+fn match_rule(rule: &TraceRule, domain: &str, ips: &[IpAddr]) -> RuleMatch {
+    let rtype = rule.rtype.to_lowercase().replace(['-', '_'], "");
+    let payload = rule.payload.to_lowercase();
+
+    let yes = |matched: bool| match matched {
+        true => RuleMatch::Yes,
+        false => RuleMatch::No,
+    };
+
+    match rtype.as_str() {
+        "domain" => yes(domain == payload),
+        "domainsuffix" => yes(domain == payload || domain.ends_with(&format!(".{payload}"))),
+        "domainkeyword" => yes(domain.contains(&payload)),
+        "domainregex" => match Regex::new(&rule.payload) {
+            Ok(regex) => yes(regex.is_match(domain)),
+            Err(_) => RuleMatch::Unknown,
+        },
+        "ipcidr" | "ipcidr6" => match ips.is_empty() {
+            true => RuleMatch::Unknown,
+            false => yes(ips.iter().any(|ip| cidr_contains(&payload, ip))),
+        },
+        "match" | "final" => RuleMatch::Yes,
+        _ => RuleMatch::Unknown,
+    }
+}
+
+fn cidr_contains(cidr: &str, ip: &IpAddr) -> bool {
+    let (network, bits) = match cidr.split_once('/') {
+        Some((network, bits)) => (network, bits.parse::<u32>().ok()),
+        None => (cidr, None),
+    };
+
+    match (network.parse::<IpAddr>(), ip) {
+        (Ok(IpAddr::V4(network)), IpAddr::V4(ip)) => {
+            let bits = bits.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+            u32::from(network) & mask == u32::from(*ip) & mask
+        }
+        (Ok(IpAddr::V6(network)), IpAddr::V6(ip)) => {
+            let bits = bits.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - bits).unwrap_or(0);
+            u128::from(network) & mask == u128::from(*ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[test]
+fn test_match_rule() {
+    assert_eq!(
+        parse_host("https://User@Example.com:8443/a?b"),
+        Some("example.com".into())
+    );
+    assert_eq!(parse_host("example.com."), Some("example.com".into()));
+    assert_eq!(parse_host("[::1]:443"), Some("::1".into()));
+    assert_eq!(parse_host("::1"), Some("::1".into()));
+    assert_eq!(parse_host(" / "), None);
+
+    let rule = |rtype: &str, payload: &str| TraceRule {
+        rtype: rtype.into(),
+        payload: payload.into(),
+        ..TraceRule::default()
+    };
+    let ips = vec!["10.1.2.3".parse::<IpAddr>().unwrap()];
+
+    let domain = "www.google.com";
+    assert_eq!(
+        match_rule(&rule("DomainSuffix", "google.com"), domain, &ips),
+        RuleMatch::Yes
+    );
+    assert_eq!(
+        match_rule(&rule("DOMAIN-SUFFIX", "gle.com"), domain, &ips),
+        RuleMatch::No
+    );
+    assert_eq!(
+        match_rule(&rule("Domain", "google.com"), domain, &ips),
+        RuleMatch::No
+    );
+    assert_eq!(
+        match_rule(&rule("DomainKeyword", "goog"), domain, &ips),
+        RuleMatch::Yes
+    );
+    assert_eq!(
+        match_rule(&rule("DomainRegex", r"^www\."), domain, &ips),
+        RuleMatch::Yes
+    );
+    assert_eq!(
+        match_rule(&rule("IPCIDR", "10.0.0.0/8"), domain, &ips),
+        RuleMatch::Yes
+    );
+    assert_eq!(
+        match_rule(&rule("IPCIDR", "10.0.0.0/16"), domain, &ips),
+        RuleMatch::No
+    );
+    assert_eq!(
+        match_rule(&rule("IPCIDR", "10.0.0.0/8"), domain, &[]),
+        RuleMatch::Unknown
+    );
+    assert_eq!(
+        match_rule(&rule("GeoSite", "google"), domain, &ips),
+        RuleMatch::Unknown
+    );
+    assert_eq!(match_rule(&rule("Match", ""), domain, &ips), RuleMatch::Yes);
+
+    let ip6 = "2001:db8::1".parse::<IpAddr>().unwrap();
+    assert!(cidr_contains("2001:db8::/32", &ip6));
+    assert!(!cidr_contains("10.0.0.0/8", &ip6));
+    assert!(cidr_contains("0.0.0.0/0", &ips[0]));
+}
@@ -1,11 +1,41 @@
+use super::{clash_api, delay, pause::ProxyPause, recent::RecentNodes};
 use crate::{cmds, config::Config, feat, utils::resolve};
 use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::HashMap;
 use tauri::{
     api, AppHandle, CustomMenuItem, Manager, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem,
     SystemTraySubmenu,
 };
 use crate::help;
 
+/// the most nodes of the current group listed in the tray
+const GROUP_NODE_LIMIT: usize = 30;
+
+/// the prefix of the ids of the node items
+const NODE_ITEM_PREFIX: &str = "group_node::";
+
+/// the pauses in the tray menu
+const PAUSE_MINUTES: [u64; 3] = [15, 60, 240];
+
+const PAUSE_ITEM_PREFIX: &str = "pause_proxy_";
+
+/// the group and the delays of the last test by the tray, none for the timeout
+type GroupDelays = (String, HashMap<String, Option<u64>>);
+
+fn group_delays() -> &'static Mutex<Option<GroupDelays>> {
+    static DELAYS: OnceCell<Mutex<Option<GroupDelays>>> = OnceCell::new();
+    DELAYS.get_or_init(|| Mutex::new(None))
+}
+
+/// the group and the nodes listed in the tray menu
+/// the items not in the menu can't be got, `get_item` panics
+fn menu_group() -> &'static Mutex<Option<(String, Vec<String>)>> {
+    static GROUP: OnceCell<Mutex<Option<(String, Vec<String>)>>> = OnceCell::new();
+    GROUP.get_or_init(|| Mutex::new(None))
+}
+
 pub struct Tray {}
 
 impl Tray {
@@ -25,6 +55,8 @@ impl Tray {
                 .add_native_item(SystemTrayMenuItem::Separator)
                 .add_item(CustomMenuItem::new("system_proxy", "系统代理"))
                 .add_item(CustomMenuItem::new("tun_mode", "TUN 模式"))
+                .add_submenu(Tray::pause_menu(zh))
+                .add_submenu(Tray::group_menu(zh))
                 .add_submenu(SystemTraySubmenu::new(
                     "打开目录",
                     SystemTrayMenu::new()
@@ -55,6 +87,8 @@ impl Tray {
                 .add_native_item(SystemTrayMenuItem::Separator)
                 .add_item(CustomMenuItem::new("system_proxy", "System Proxy"))
                 .add_item(CustomMenuItem::new("tun_mode", "Tun Mode"))
+                .add_submenu(Tray::pause_menu(zh))
+                .add_submenu(Tray::group_menu(zh))
                 .add_submenu(SystemTraySubmenu::new(
                     "Open Dir",
                     SystemTrayMenu::new()
@@ -77,6 +111,136 @@ impl Tray {
         }
     }
 
+    /// pause the proxy for a while, the resume item shows the remaining time
+    fn pause_menu(zh: bool) -> SystemTraySubmenu {
+        let mut menu = SystemTrayMenu::new();
+        for minutes in PAUSE_MINUTES {
+            let label = match (zh, minutes) {
+                (true, m) if m % 60 == 0 => format!("{} 小时", m / 60),
+                (true, m) => format!("{m} 分钟"),
+                (false, 60) => "1 Hour".into(),
+                (false, m) if m % 60 == 0 => format!("{} Hours", m / 60),
+                (false, m) => format!("{m} Minutes"),
+            };
+            menu = menu.add_item(CustomMenuItem::new(
+                format!("{PAUSE_ITEM_PREFIX}{minutes}"),
+                label,
+            ));
+        }
+
+        let title = if zh { "暂停代理" } else { "Pause Proxy" };
+        let resume = CustomMenuItem::new("resume_proxy", resume_label(None, zh)).disabled();
+        let menu = menu
+            .add_native_item(SystemTrayMenuItem::Separator)
+            .add_item(resume);
+        SystemTraySubmenu::new(title, menu)
+    }
+
+    /// the nodes of the current group with the delays of the last test
+    fn group_menu(zh: bool) -> SystemTraySubmenu {
+        let (title, test, timeout) = match zh {
+            true => ("当前分组", "测试当前分组", "超时"),
+            false => ("Current Group", "Test Current Group", "Timeout"),
+        };
+
+        let current = { Config::runtime().latest().first_select_group() };
+        let (group, mut nodes) = match current {
+            Some(current) => current,
+            None => {
+                *menu_group().lock() = None;
+                let menu = SystemTrayMenu::new()
+                    .add_item(CustomMenuItem::new("test_group", test).disabled());
+                return SystemTraySubmenu::new(title, menu);
+            }
+        };
+        nodes.truncate(GROUP_NODE_LIMIT);
+
+        let delays = group_delays().lock();
+        let delays = delays
+            .as_ref()
+            .filter(|(tested, _)| *tested == group)
+            .map(|(_, delays)| delays);
+
+        let mut menu = SystemTrayMenu::new()
+            .add_item(CustomMenuItem::new("test_group", test))
+            .add_native_item(SystemTrayMenuItem::Separator);
+        for node in nodes.iter() {
+            let delay = delays.and_then(|delays| delays.get(node).copied());
+            menu = menu.add_item(CustomMenuItem::new(
+                format!("{NODE_ITEM_PREFIX}{node}"),
+                node_label(node, delay, timeout),
+            ));
+        }
+
+        *menu_group().lock() = Some((group.clone(), nodes));
+        SystemTraySubmenu::new(group, menu)
+    }
+
+    /// test the group of the menu by the core and show the delays in the tray
+    fn test_group(app_handle: &AppHandle) {
+        let group = match menu_group().lock().as_ref() {
+            Some((group, _)) => group.clone(),
+            None => return,
+        };
+        let zh = { Config::verge().latest().language == Some("zh".into()) };
+        let timeout = if zh { "超时" } else { "Timeout" };
+        let app_handle = app_handle.clone();
+
+        tauri::async_runtime::spawn(async move {
+            let tray = app_handle.tray_handle();
+            let _ = tray.get_item("test_group").set_enabled(false);
+
+            let url = delay::test_urls().into_iter().next().unwrap_or_default();
+            match clash_api::get_group_delay(&group, &url, delay::DELAY_TIMEOUT).await {
+                Ok(tested) => {
+                    let delays = tested
+                        .into_iter()
+                        .map(|(node, delay)| (node, Some(delay).filter(|d| *d > 0)))
+                        .collect::<HashMap<_, _>>();
+
+                    // the nodes missing in the result are timed out
+                    for node in menu_nodes(&group) {
+                        let delay = delays.get(&node).copied().flatten();
+                        let label = node_label(&node, Some(delay), timeout);
+                        let item = tray.get_item(&format!("{NODE_ITEM_PREFIX}{node}"));
+                        let _ = item.set_title(label);
+                    }
+                    *group_delays().lock() = Some((group.clone(), delays));
+                }
+                Err(err) => log::error!(target: "app", "{err}"),
+            }
+
+            let _ = tray.get_item("test_group").set_enabled(true);
+            if let Ok(Some(now)) = clash_api::get_proxy_now(&group).await {
+                Tray::select_node_item(&app_handle, &group, &now);
+            }
+        });
+    }
+
+    /// select the node of the group of the menu
+    fn select_node(app_handle: &AppHandle, node: String) {
+        let group = match menu_group().lock().as_ref() {
+            Some((group, _)) => group.clone(),
+            None => return,
+        };
+        let app_handle = app_handle.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match RecentNodes::global().select(&group, &node).await {
+                Ok(_) => Tray::select_node_item(&app_handle, &group, &node),
+                Err(err) => log::error!(target: "app", "{err}"),
+            }
+        });
+    }
+
+    fn select_node_item(app_handle: &AppHandle, group: &str, now: &str) {
+        let tray = app_handle.tray_handle();
+        for node in menu_nodes(group) {
+            let item = tray.get_item(&format!("{NODE_ITEM_PREFIX}{node}"));
+            let _ = item.set_selected(node == now);
+        }
+    }
+
     pub fn update_systray(app_handle: &AppHandle) -> Result<()> {
         app_handle
             .tray_handle()
@@ -107,6 +271,7 @@ impl Tray {
         let verge = verge.latest();
         let system_proxy = verge.enable_system_proxy.as_ref().unwrap_or(&false);
         let tun_mode = verge.enable_tun_mode.as_ref().unwrap_or(&false);
+        let zh = verge.language == Some("zh".into());
 
 
         let indication_icon = if *system_proxy {
@@ -120,6 +285,11 @@ impl Tray {
         let _ = tray.get_item("system_proxy").set_selected(*system_proxy);
         let _ = tray.get_item("tun_mode").set_selected(*tun_mode);
 
+        let pause = ProxyPause::global().status();
+        let resume = tray.get_item("resume_proxy");
+        let _ = resume.set_enabled(pause.is_some());
+        let _ = resume.set_title(resume_label(pause.map(|p| p.remaining), zh));
+
         Ok(())
     }
 
@@ -132,8 +302,19 @@ impl Tray {
                 }
 
                 "open_window" => resolve::create_window(app_handle),
+                "test_group" => Tray::test_group(app_handle),
+                node if node.starts_with(NODE_ITEM_PREFIX) => {
+                    let node = node[NODE_ITEM_PREFIX.len()..].to_string();
+                    Tray::select_node(app_handle, node);
+                }
                 "system_proxy" => feat::toggle_system_proxy(),
                 "tun_mode" => feat::toggle_tun_mode(),
+                pause if pause.starts_with(PAUSE_ITEM_PREFIX) => {
+                    if let Ok(minutes) = pause[PAUSE_ITEM_PREFIX.len()..].parse() {
+                        feat::pause_proxy(minutes);
+                    }
+                }
+                "resume_proxy" => feat::resume_proxy(),
                 "open_app_dir" => crate::log_err!(cmds::open_app_dir()),
                 "open_core_dir" => crate::log_err!(cmds::open_core_dir()),
                 "open_logs_dir" => crate::log_err!(cmds::open_logs_dir()),
@@ -152,3 +333,31 @@ impl Tray {
         }
     }
 }
+
+/// the nodes of the group in the menu, empty if the menu is rebuilt for another group
+fn menu_nodes(group: &str) -> Vec<String> {
+    match menu_group().lock().as_ref() {
+        Some((listed, nodes)) if listed == group => nodes.clone(),
+        _ => vec![],
+    }
+}
+
+/// such as `Resume Proxy (12 min left)`, rounded up to the minute
+fn resume_label(remaining: Option<i64>, zh: bool) -> String {
+    let minutes = remaining.map(|secs| (secs + 59) / 60);
+    match (minutes, zh) {
+        (Some(m), true) => format!("恢复代理 (剩余 {m} 分钟)"),
+        (Some(m), false) => format!("Resume Proxy ({m} min left)"),
+        (None, true) => "恢复代理".into(),
+        (None, false) => "Resume Proxy".into(),
+    }
+}
+
+/// such as `US 01    120 ms`, the name alone if not tested
+fn node_label(node: &str, delay: Option<Option<u64>>, timeout: &str) -> String {
+    match delay {
+        Some(Some(delay)) => format!("{node}    {delay} ms"),
+        Some(None) => format!("{node}    {timeout}"),
+        None => node.into(),
+    }
+}
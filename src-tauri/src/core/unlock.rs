@@ -0,0 +1,297 @@
+use super::measure::{self, Measure};
+use anyhow::{bail, Result};
+use reqwest::{header, redirect::Policy, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tauri::regex::Regex;
+use tokio::time::Duration;
+
+/// the timeout for each service
+const UNLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// a title not made by netflix, only in some regions
+const NETFLIX_LICENSED_URL: &str = "https://www.netflix.com/title/70143836";
+/// a netflix original, in all the supported regions
+const NETFLIX_ORIGINAL_URL: &str = "https://www.netflix.com/title/80018499";
+
+const YOUTUBE_PREMIUM_URL: &str = "https://www.youtube.com/premium";
+
+const CHATGPT_URL: &str = "https://ios.chat.openai.com/";
+const CHATGPT_TRACE_URL: &str = "https://chat.openai.com/cdn-cgi/trace";
+
+const SPOTIFY_URL: &str = "https://spclient.wg.spotify.com/signup/public/v1/account";
+
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnlockStatus {
+    /// the service is available
+    Yes,
+    /// only part of the service, such as the netflix originals
+    Partial,
+    /// blocked in the region or by the ip
+    No,
+    /// the check failed, such as timeout
+    #[default]
+    Failed,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct UnlockResult {
+    /// `netflix` or `youtube_premium` or `chatgpt` or `spotify`
+    pub service: String,
+
+    pub status: UnlockStatus,
+
+    /// the region reported by the service, such as `US`
+    pub region: Option<String>,
+
+    pub error: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct UnlockMatrix {
+    /// proxy name
+    pub name: String,
+
+    pub results: Vec<UnlockResult>,
+
+    /// the error if the proxy could not be selected
+    pub error: Option<String>,
+
+    /// checked time
+    pub updated: usize,
+}
+
+/// check the services through each proxy
+/// one proxy at a time, since the hidden selector is shared with the measurement
+pub async fn check_unlock(names: Vec<String>) -> Vec<UnlockMatrix> {
+    let mut matrices = vec![];
+
+    for name in names.into_iter() {
+        if Measure::global().is_cancelled() {
+            break;
+        }
+
+        let mut matrix = UnlockMatrix {
+            name: name.clone(),
+            ..UnlockMatrix::default()
+        };
+        match Measure::global().through(&name, check_services()).await {
+            Ok(results) => matrix.results = results,
+            Err(err) => matrix.error = Some(err.to_string()),
+        }
+        matrix.updated = chrono::Local::now().timestamp() as usize;
+
+        matrices.push(matrix);
+    }
+
+    matrices
+}
+
+async fn check_services() -> Result<Vec<UnlockResult>> {
+    let client = measure::proxy_client()?
+        .timeout(UNLOCK_TIMEOUT)
+        .redirect(Policy::none())
+        .build()?;
+
+    let (netflix, youtube, chatgpt, spotify) = tokio::join!(
+        check_netflix(&client),
+        check_youtube_premium(&client),
+        check_chatgpt(&client),
+        check_spotify(&client)
+    );
+
+    Ok(vec![
+        to_result("netflix", netflix),
+        to_result("youtube_premium", youtube),
+        to_result("chatgpt", chatgpt),
+        to_result("spotify", spotify),
+    ])
+}
+
+fn to_result(service: &str, result: Result<(UnlockStatus, Option<String>)>) -> UnlockResult {
+    match result {
+        Ok((status, region)) => UnlockResult {
+            service: service.into(),
+            status,
+            region,
+            error: None,
+        },
+        Err(err) => UnlockResult {
+            service: service.into(),
+            error: Some(err.to_string()),
+            ..UnlockResult::default()
+        },
+    }
+}
+
+/// the licensed title is found in the unlocked regions,
+/// the original title only means the netflix originals
+async fn check_netflix(client: &Client) -> Result<(UnlockStatus, Option<String>)> {
+    let licensed = client.get(NETFLIX_LICENSED_URL).send().await?;
+    let region = licensed
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(netflix_region);
+
+    match licensed.status() {
+        status if status.is_success() => return Ok((UnlockStatus::Yes, Some("US".into()))),
+        status if status.is_redirection() => return Ok((UnlockStatus::Yes, region)),
+        StatusCode::FORBIDDEN => return Ok((UnlockStatus::No, None)),
+        StatusCode::NOT_FOUND => {}
+        status => bail!("unexpected status {status}"),
+    }
+
+    let original = client.get(NETFLIX_ORIGINAL_URL).send().await?;
+    let region = original
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .and_then(netflix_region);
+
+    match original.status() {
+        status if status.is_success() => Ok((UnlockStatus::Partial, Some("US".into()))),
+        status if status.is_redirection() => Ok((UnlockStatus::Partial, region)),
+        _ => Ok((UnlockStatus::No, None)),
+    }
+}
+
+/// such as `https://www.netflix.com/gb-en/title/70143836`
+fn netflix_region(location: &str) -> Option<String> {
+    let path = location
+        .split_once("netflix.com/")
+        .map_or(location, |(_, path)| path);
+    let path = path.trim_start_matches('/');
+    let region = path.split(['/', '-']).next()?;
+
+    match region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => Some(region.to_uppercase()),
+        false => None,
+    }
+}
+
+async fn check_youtube_premium(client: &Client) -> Result<(UnlockStatus, Option<String>)> {
+    let resp = client
+        .get(YOUTUBE_PREMIUM_URL)
+        .header(header::ACCEPT_LANGUAGE, "en")
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        bail!("unexpected status {}", resp.status());
+    }
+    Ok(parse_youtube_premium(&resp.text().await?))
+}
+
+fn parse_youtube_premium(body: &str) -> (UnlockStatus, Option<String>) {
+    let region = Regex::new(r#""INNERTUBE_CONTEXT_GL"\s*:\s*"([A-Z]{2})""#)
+        .ok()
+        .and_then(|re| re.captures(body))
+        .and_then(|caps| caps.get(1))
+        .map(|region| region.as_str().to_string());
+
+    match body.contains("Premium is not available in your country") {
+        true => (UnlockStatus::No, region),
+        false => (UnlockStatus::Yes, region),
+    }
+}
+
+/// the api blocks the unsupported regions and the vpn ips
+async fn check_chatgpt(client: &Client) -> Result<(UnlockStatus, Option<String>)> {
+    let body = client.get(CHATGPT_URL).send().await?.text().await?;
+    let region = match client.get(CHATGPT_TRACE_URL).send().await {
+        Ok(trace) => parse_trace_loc(&trace.text().await?),
+        Err(_) => None,
+    };
+
+    match body.contains("unsupported_country") || body.contains("VPN") {
+        true => Ok((UnlockStatus::No, region)),
+        false => Ok((UnlockStatus::Yes, region)),
+    }
+}
+
+/// the `loc=US` line of the cloudflare trace
+fn parse_trace_loc(body: &str) -> Option<String> {
+    body.lines()
+        .find_map(|line| line.strip_prefix("loc="))
+        .map(|loc| loc.trim().to_uppercase())
+        .filter(|loc| !loc.is_empty())
+}
+
+/// the signup api reports the country and whether spotify is launched there
+async fn check_spotify(client: &Client) -> Result<(UnlockStatus, Option<String>)> {
+    let form = [
+        ("birth_day", "11"),
+        ("birth_month", "11"),
+        ("birth_year", "2000"),
+        ("collect_personal_info", "undefined"),
+        ("creation_flow", ""),
+        ("creation_point", "https://www.spotify.com/us/"),
+        ("displayname", "hiddify"),
+        ("gender", "male"),
+        ("iagree", "1"),
+        ("key", "a1e486e2729f46d6bb368d6b2bcda326"),
+        ("platform", "www"),
+        ("referrer", ""),
+        ("send-email", "0"),
+        ("thirdpartyemail", "0"),
+        ("identifier_token", "AgE6YTvEzkReHNfJpO114514"),
+    ];
+    let body = client
+        .post(SPOTIFY_URL)
+        .form(&form)
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+
+    Ok(parse_spotify(&body))
+}
+
+/// the status 311 means the signup is allowed
+fn parse_spotify(body: &serde_json::Value) -> (UnlockStatus, Option<String>) {
+    let region = body
+        .get("country")
+        .and_then(|c| c.as_str())
+        .map(|c| c.to_uppercase());
+    let launched = body.get("is_country_launched").and_then(|l| l.as_bool());
+    let status = body.get("status").and_then(|s| s.as_u64());
+
+    match (status, launched) {
+        (Some(311), Some(true)) => (UnlockStatus::Yes, region),
+        _ => (UnlockStatus::No, region),
+    }
+}
+
+#[test]
+fn test_unlock_parsers() {
+    assert_eq!(
+        netflix_region("https://www.netflix.com/gb-en/title/70143836"),
+        Some("GB".into())
+    );
+    assert_eq!(netflix_region("/tr/title/70143836"), Some("TR".into()));
+    assert_eq!(
+        netflix_region("https://www.netflix.com/title/70143836"),
+        None
+    );
+
+    let body = r#"ytcfg.set({"INNERTUBE_CONTEXT_GL":"DE","INNERTUBE_CONTEXT_HL":"en"})"#;
+    assert_eq!(
+        parse_youtube_premium(body),
+        (UnlockStatus::Yes, Some("DE".into()))
+    );
+    let body = r#"{"INNERTUBE_CONTEXT_GL":"IR"} Premium is not available in your country"#;
+    assert_eq!(
+        parse_youtube_premium(body),
+        (UnlockStatus::No, Some("IR".into()))
+    );
+
+    let trace = "fl=1\nip=1.1.1.1\nloc=jp\nwarp=off";
+    assert_eq!(parse_trace_loc(trace), Some("JP".into()));
+    assert_eq!(parse_trace_loc("ip=1.1.1.1"), None);
+
+    let body = serde_json::json!({"status": 311, "country": "us", "is_country_launched": true});
+    assert_eq!(parse_spotify(&body), (UnlockStatus::Yes, Some("US".into())));
+    let body = serde_json::json!({"status": 120, "country": "IR", "is_country_launched": false});
+    assert_eq!(parse_spotify(&body), (UnlockStatus::No, Some("IR".into())));
+}
@@ -0,0 +1,246 @@
+use super::downloader::{self, DownloadRoute, DownloadTask};
+use super::handle::Handle;
+use super::installer;
+use crate::config::Config;
+use crate::utils::{app_state::AppState, resolve};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose, Engine};
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+/// the same as the `tauri.conf.json`
+/// the mirror is used where github is blocked
+const DEFAULT_ENDPOINTS: [&str; 2] = [
+    "https://github.com/hiddify/HiddifyClashDesktop/releases/download/updater/update.json",
+    "https://hub.fastgit.xyz/hiddify/HiddifyClashDesktop/releases/download/updater/update-proxy.json",
+];
+
+/// `stable` reads the endpoints as they are, the others read `update-{channel}.json`
+const UPDATE_CHANNELS: [&str; 3] = ["stable", "beta", "nightly"];
+
+/// the static update json of the tauri updater
+#[derive(Debug, Clone, Deserialize)]
+struct UpdateManifest {
+    version: String,
+    notes: Option<String>,
+    pub_date: Option<String>,
+
+    /// keyed by the updater target, such as `windows-x86_64`
+    #[serde(default)]
+    platforms: HashMap<String, UpdatePlatform>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct UpdatePlatform {
+    url: String,
+
+    /// the base64 minisign signature of the archive
+    signature: String,
+
+    /// the hex sha256 set by the mirror feeds
+    /// a broken download is removed before it is resumed from the next mirror
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AppUpdate {
+    pub version: String,
+    pub current: String,
+    pub channel: String,
+    pub notes: Option<String>,
+    pub pub_date: Option<String>,
+}
+
+/// the channel of the verge config, `stable` by default
+pub fn update_channel() -> String {
+    let channel = { Config::verge().latest().update_channel.clone() };
+    channel.unwrap_or("stable".into())
+}
+
+/// check the release feeds of the channel by order
+/// return none if the app is up to date or the release is older
+pub async fn check_update(channel: &str) -> Result<Option<AppUpdate>> {
+    let manifest = fetch_manifest(channel).await?;
+    let current = AppState::global().version().to_string();
+
+    match is_newer(&manifest.version, &current) {
+        true => Ok(Some(AppUpdate {
+            version: manifest.version,
+            current,
+            channel: channel.into(),
+            notes: manifest.notes,
+            pub_date: manifest.pub_date,
+        })),
+        false => Ok(None),
+    }
+}
+
+/// download the release of the channel from the same feeds as `check_update`
+/// the archive is verified by the updater pubkey of `tauri.conf.json` before installing
+pub async fn install_update(app_handle: AppHandle) -> Result<()> {
+    let channel = update_channel();
+    Handle::update_status("downloading");
+
+    match download_update(&app_handle, &channel).await {
+        Ok(_) => {
+            Handle::update_status("installed");
+            resolve::resolve_reset();
+            // the windows installer relaunches the app after it finishes
+            if cfg!(target_os = "windows") {
+                std::process::exit(0);
+            }
+            tauri::api::process::restart(&app_handle.env());
+            Ok(())
+        }
+        Err(err) => {
+            Handle::update_status("error");
+            Err(err)
+        }
+    }
+}
+
+async fn download_update(app_handle: &AppHandle, channel: &str) -> Result<()> {
+    let manifest = fetch_manifest(channel).await?;
+    // never downgrade after leaving the beta or nightly channel
+    let current = AppState::global().version();
+    if !is_newer(&manifest.version, current) {
+        bail!(
+            "the release {} is not newer than {current}",
+            manifest.version
+        );
+    }
+
+    let target = tauri::updater::target().ok_or(anyhow!("the platform is not supported"))?;
+    let platform = manifest.platforms.get(&target).ok_or(anyhow!(
+        "no {target} package in the release {}",
+        manifest.version
+    ))?;
+
+    let name = platform
+        .url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("update");
+    let path = std::env::temp_dir().join(format!("hiddify-{}-{name}", manifest.version));
+
+    downloader::download(&DownloadTask {
+        id: "app_update".into(),
+        urls: vec![platform.url.clone()],
+        path: path.clone(),
+        sha256: platform.sha256.clone(),
+        route: update_route(),
+    })
+    .await?;
+
+    let pubkey = app_handle.config().tauri.updater.pubkey.clone();
+    let result = std::fs::read(&path)
+        .context("failed to read the downloaded update")
+        .and_then(|data| verify_signature(&data, &platform.signature, &pubkey))
+        .and_then(|_| installer::install(app_handle, &path));
+
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// the manifest of the channel, from the first feed that answers
+async fn fetch_manifest(channel: &str) -> Result<UpdateManifest> {
+    if !UPDATE_CHANNELS.contains(&channel) {
+        bail!("invalid update channel \"{channel}\"");
+    }
+
+    let endpoints = { Config::verge().latest().update_endpoints.clone() };
+    let endpoints = endpoints
+        .filter(|list| !list.is_empty())
+        .unwrap_or(DEFAULT_ENDPOINTS.map(|s| s.to_string()).to_vec())
+        .iter()
+        .map(|url| channel_endpoint(url, channel))
+        .collect::<Vec<String>>();
+
+    downloader::fetch_json(&endpoints, update_route()).await
+}
+
+/// the pubkey and the signature are base64 of the minisign text, as the tauri updater
+fn verify_signature(data: &[u8], signature: &str, pubkey: &str) -> Result<()> {
+    let decode = |text: &str| -> Result<String> {
+        let bytes = general_purpose::STANDARD.decode(text.trim())?;
+        Ok(String::from_utf8(bytes)?)
+    };
+
+    let pubkey = PublicKey::decode(&decode(pubkey)?)
+        .map_err(|err| anyhow!("invalid updater pubkey, {err}"))?;
+    let signature = Signature::decode(&decode(signature)?)
+        .map_err(|err| anyhow!("invalid update signature, {err}"))?;
+
+    pubkey
+        .verify(data, &signature, true)
+        .map_err(|err| anyhow!("the update signature mismatched, {err}"))
+}
+
+/// always through the clash core if `update_via_proxy` is set
+/// or else it falls back to the core after the direct failed
+fn update_route() -> DownloadRoute {
+    let via_proxy = { Config::verge().latest().update_via_proxy };
+    match via_proxy.unwrap_or(false) {
+        true => DownloadRoute::Proxy,
+        false => DownloadRoute::Auto,
+    }
+}
+
+/// `update.json` of the stable channel is `update-beta.json` of the beta channel
+fn channel_endpoint(url: &str, channel: &str) -> String {
+    if channel == "stable" {
+        return url.into();
+    }
+
+    match url.rsplit_once(".json") {
+        Some((head, tail)) => format!("{head}-{channel}.json{tail}"),
+        None => url.into(),
+    }
+}
+
+/// compare the semver like versions, such as `v1.2.0`
+/// the pre-release part is ignored
+fn is_newer(latest: &str, current: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .trim()
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|part| part.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<u64>>()
+    };
+
+    parse(latest) > parse(current)
+}
+
+#[test]
+fn test_channel_endpoint() {
+    let url = "https://example.com/updater/update-proxy.json";
+    assert_eq!(channel_endpoint(url, "stable"), url);
+    assert_eq!(
+        channel_endpoint(url, "nightly"),
+        "https://example.com/updater/update-proxy-nightly.json"
+    );
+    assert_eq!(
+        channel_endpoint("https://example.com/update.json?v=1", "beta"),
+        "https://example.com/update-beta.json?v=1"
+    );
+}
+
+#[test]
+fn test_is_newer() {
+    assert!(is_newer("1.2.1", "v1.2.0"));
+    assert!(is_newer("v1.10.0", "v1.9.9"));
+    assert!(is_newer("2.0", "1.9.9"));
+
+    assert!(!is_newer("v1.2.0", "v1.2.0"));
+    assert!(!is_newer("1.2.0-beta.1", "1.2.0"));
+    assert!(!is_newer("1.1.9", "v1.2.0"));
+}
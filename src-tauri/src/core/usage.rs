@@ -0,0 +1,167 @@
+use super::notification::{self, NotifyTrigger};
+use super::CoreManager;
+use crate::config::Config;
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+use tokio::time::{sleep, Duration};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// the samples of the last 10 minutes
+const SAMPLE_LIMIT: usize = 60;
+
+/// `notify` or `restart`
+const DEFAULT_MEMORY_POLICY: &str = "notify";
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ResourceSample {
+    pub time: usize,
+
+    /// the rss in bytes, none if the core is not run by the sidecar
+    pub core_memory: Option<u64>,
+
+    /// the percentage of one cpu core, may exceed 100
+    pub core_cpu: Option<f32>,
+
+    pub app_memory: u64,
+
+    pub app_cpu: f32,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ResourceUsage {
+    pub current: Option<ResourceSample>,
+
+    /// the oldest first
+    pub history: Vec<ResourceSample>,
+
+    /// `core_memory_limit` in bytes
+    pub core_memory_limit: Option<u64>,
+}
+
+pub struct ResourceMonitor {
+    /// kept for the cpu usage between the refreshes
+    system: Arc<Mutex<System>>,
+
+    samples: Arc<Mutex<VecDeque<ResourceSample>>>,
+
+    /// warned until the memory drops below the limit
+    exceeded: Arc<Mutex<bool>>,
+}
+
+impl ResourceMonitor {
+    pub fn global() -> &'static ResourceMonitor {
+        static MONITOR: OnceCell<ResourceMonitor> = OnceCell::new();
+
+        MONITOR.get_or_init(|| ResourceMonitor {
+            system: Arc::new(Mutex::new(System::new())),
+            samples: Arc::new(Mutex::new(VecDeque::new())),
+            exceeded: Arc::new(Mutex::new(false)),
+        })
+    }
+
+    /// launch the sampling loop
+    pub fn init(&'static self) -> Result<()> {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                sleep(SAMPLE_INTERVAL).await;
+
+                let sample = self.sample();
+                let memory = sample.core_memory;
+                {
+                    let mut samples = self.samples.lock();
+                    samples.push_back(sample);
+                    while samples.len() > SAMPLE_LIMIT {
+                        samples.pop_front();
+                    }
+                }
+
+                if let Some(memory) = memory {
+                    self.check_limit(memory).await;
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn get_usage(&self) -> ResourceUsage {
+        let history = { self.samples.lock().iter().cloned().collect::<Vec<_>>() };
+        ResourceUsage {
+            current: history.last().cloned().or_else(|| Some(self.sample())),
+            history,
+            core_memory_limit: memory_limit(),
+        }
+    }
+
+    fn sample(&self) -> ResourceSample {
+        let core_pid = CoreManager::global().core_pid().map(Pid::from_u32);
+        let app_pid = sysinfo::get_current_pid().ok();
+
+        let mut system = self.system.lock();
+        let mut usage = |pid: Option<Pid>| {
+            let pid = pid?;
+            match system.refresh_process(pid) {
+                true => system
+                    .process(pid)
+                    .map(|process| (process.memory(), process.cpu_usage())),
+                false => None,
+            }
+        };
+
+        let core = usage(core_pid);
+        let app = usage(app_pid).unwrap_or_default();
+
+        ResourceSample {
+            time: chrono::Local::now().timestamp() as usize,
+            core_memory: core.map(|(memory, _)| memory),
+            core_cpu: core.map(|(_, cpu)| cpu),
+            app_memory: app.0,
+            app_cpu: app.1,
+        }
+    }
+
+    /// warn once when the core exceeds the limit, restart it if the policy is `restart`
+    async fn check_limit(&self, memory: u64) {
+        let limit = match memory_limit() {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        let exceeded = { std::mem::replace(&mut *self.exceeded.lock(), memory > limit) };
+        if memory <= limit || exceeded {
+            return;
+        }
+
+        let policy = { Config::verge().latest().core_memory_policy.clone() };
+        let policy = policy.unwrap_or(DEFAULT_MEMORY_POLICY.into());
+        let body = format!(
+            "The clash core uses {} MB of memory, over the limit of {} MB",
+            memory / 1024 / 1024,
+            limit / 1024 / 1024
+        );
+        log::warn!(target: "app", "{body}");
+        notification::notify(NotifyTrigger::CoreCrash, "Hiddify", &body);
+
+        if policy == "restart" {
+            log::info!(target: "app", "restart the core for the memory limit");
+            if let Err(err) = CoreManager::global().run_core().await {
+                log::error!(target: "app", "failed to restart the core, {err}");
+            }
+            *self.exceeded.lock() = false;
+        }
+    }
+}
+
+/// `core_memory_limit` is in MB, 0 means no limit
+fn memory_limit() -> Option<u64> {
+    let limit = { Config::verge().latest().core_memory_limit };
+    limit
+        .filter(|limit| *limit > 0)
+        .map(|limit| limit * 1024 * 1024)
+}
@@ -0,0 +1,105 @@
+use crate::config::IVergeLoadBalance;
+use serde_yaml::{Mapping, Sequence, Value};
+use std::collections::HashSet;
+
+/// append the `load-balance` group of the measured members
+/// the members missing in the profile are skipped, and the group is put first in the first `select` group
+pub fn use_load_balance(mut config: Mapping, balance: &Option<IVergeLoadBalance>) -> Mapping {
+    let balance = match balance {
+        Some(balance) => balance,
+        None => return config,
+    };
+
+    let names = config
+        .get("proxies")
+        .and_then(|p| p.as_sequence())
+        .map_or(HashSet::new(), |seq| {
+            seq.iter()
+                .filter_map(|p| p.get("name")?.as_str())
+                .collect::<HashSet<_>>()
+        });
+    let members = balance
+        .members
+        .iter()
+        .filter(|m| names.contains(m.as_str()))
+        .map(|m| Value::from(m.as_str()))
+        .collect::<Sequence>();
+    if members.is_empty() {
+        return config;
+    }
+
+    let strategy = balance.strategy.as_deref().unwrap_or("round-robin");
+    let mut group = Mapping::new();
+    group.insert("name".into(), balance.name.as_str().into());
+    group.insert("type".into(), "load-balance".into());
+    group.insert("strategy".into(), strategy.into());
+    group.insert("url".into(), "https://www.gstatic.com/generate_204".into());
+    group.insert("interval".into(), 300.into());
+    group.insert("proxies".into(), members.into());
+
+    let mut groups = config
+        .get("proxy-groups")
+        .and_then(|g| g.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+    let name = Value::from(balance.name.as_str());
+    groups.retain(|g| g.get("name") != Some(&name));
+
+    let select = groups
+        .iter_mut()
+        .filter_map(|g| g.as_mapping_mut())
+        .find(|g| g.get("type").and_then(|t| t.as_str()) == Some("select"));
+    if let Some(proxies) = select
+        .and_then(|g| g.get_mut("proxies"))
+        .and_then(|p| p.as_sequence_mut())
+    {
+        proxies.retain(|p| *p != name);
+        proxies.insert(0, name);
+    }
+
+    groups.push(group.into());
+    config.insert("proxy-groups".into(), groups.into());
+    config
+}
+
+#[test]
+fn test_load_balance() {
+    let config = serde_yaml::from_str::<Mapping>(
+        r#"
+proxies:
+  - {name: a, type: vless}
+  - {name: b, type: trojan}
+proxy-groups:
+  - {name: auto, type: url-test, proxies: [a, b]}
+  - {name: proxy, type: select, proxies: [auto, a, b]}
+"#,
+    )
+    .unwrap();
+
+    let balance = Some(IVergeLoadBalance {
+        name: "balance".into(),
+        strategy: None,
+        size: None,
+        members: vec!["b".into(), "gone".into(), "a".into()],
+    });
+    let config = use_load_balance(config, &balance);
+    let groups = config["proxy-groups"].as_sequence().unwrap();
+    assert_eq!(groups.len(), 3);
+    assert_eq!(groups[0]["proxies"], Value::from(vec!["a", "b"]));
+    assert_eq!(
+        groups[1]["proxies"],
+        Value::from(vec!["balance", "auto", "a", "b"])
+    );
+    assert_eq!(groups[2]["type"], "load-balance");
+    assert_eq!(groups[2]["strategy"], "round-robin");
+    assert_eq!(groups[2]["proxies"], Value::from(vec!["b", "a"]));
+
+    // applied again on the generated config
+    let config = use_load_balance(config, &balance);
+    let groups = config["proxy-groups"].as_sequence().unwrap();
+    assert_eq!(groups.len(), 3);
+    assert_eq!(
+        groups[1]["proxies"],
+        Value::from(vec!["balance", "auto", "a", "b"])
+    );
+}
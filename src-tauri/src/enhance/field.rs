@@ -1,7 +1,7 @@
 use serde_yaml::{Mapping, Value};
 use std::collections::HashSet;
 
-pub const HANDLE_FIELDS: [&str; 9] = [
+pub const HANDLE_FIELDS: [&str; 11] = [
     "mode",
     "port",
     "socks-port",
@@ -11,6 +11,8 @@ pub const HANDLE_FIELDS: [&str; 9] = [
     "ipv6",
     "secret",
     "external-controller",
+    "external-controller-unix", // meta
+    "external-controller-pipe", // meta
 ];
 
 pub const DEFAULT_FIELDS: [&str; 6] = [
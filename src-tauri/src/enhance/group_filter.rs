@@ -0,0 +1,113 @@
+use crate::config::IGroupFilter;
+use serde_yaml::{Mapping, Sequence, Value};
+
+/// fill the groups of the `group-filters` with the matching nodes
+/// regenerated from the proxies on every loading, so the new nodes of the subscription are included
+pub fn use_group_filters(mut config: Mapping, filters: &[IGroupFilter]) -> Mapping {
+    if filters.is_empty() {
+        return config;
+    }
+
+    let nodes = config
+        .get("proxies")
+        .and_then(|p| p.as_sequence())
+        .map_or(vec![], |seq| {
+            seq.iter()
+                .filter_map(|p| p.get("name")?.as_str().map(String::from))
+                .collect::<Vec<_>>()
+        });
+
+    let mut groups = config
+        .get("proxy-groups")
+        .and_then(|g| g.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+
+    for filter in filters.iter() {
+        let matcher = match filter.matcher() {
+            Ok(matcher) => matcher,
+            Err(err) => {
+                log::error!(target: "app", "{err}");
+                continue;
+            }
+        };
+
+        let mut proxies = nodes
+            .iter()
+            .filter(|node| matcher(node))
+            .map(|node| Value::from(node.as_str()))
+            .collect::<Sequence>();
+        // the group without any proxy fails the core
+        if proxies.is_empty() {
+            proxies.push("DIRECT".into());
+        }
+
+        let found = groups
+            .iter_mut()
+            .filter_map(|g| g.as_mapping_mut())
+            .find(|g| g.get("name").and_then(|n| n.as_str()) == Some(filter.name.as_str()));
+        match found {
+            Some(group) => {
+                group.insert("proxies".into(), proxies.into());
+            }
+            None => {
+                let gtype = filter.gtype.clone().unwrap_or("select".into());
+                let mut group = Mapping::new();
+                group.insert("name".into(), filter.name.clone().into());
+                group.insert("type".into(), gtype.as_str().into());
+                if gtype != "select" {
+                    group.insert("url".into(), "https://www.gstatic.com/generate_204".into());
+                    group.insert("interval".into(), 300.into());
+                }
+                group.insert("proxies".into(), proxies.into());
+                groups.push(group.into());
+            }
+        }
+    }
+
+    config.insert("proxy-groups".into(), groups.into());
+    config
+}
+
+#[test]
+fn test_group_filters() {
+    let config = serde_yaml::from_str::<Mapping>(
+        r#"
+proxies:
+  - {name: "🇯🇵 Tokyo 1", type: vless}
+  - {name: "japan 2 (game)", type: vless}
+  - {name: "DE 1", type: trojan}
+proxy-groups:
+  - {name: select, type: select, proxies: ["🇯🇵 Tokyo 1", "DE 1"]}
+"#,
+    )
+    .unwrap();
+
+    let filter = |name: &str, gtype: Option<&str>, include: Option<&str>, exclude: Option<&str>| {
+        IGroupFilter {
+            name: name.into(),
+            gtype: gtype.map(String::from),
+            include: include.map(String::from),
+            exclude: exclude.map(String::from),
+        }
+    };
+    let filters = [
+        filter("JP only", None, Some("JP|Japan|🇯🇵"), Some("game")),
+        filter("select", None, None, Some("^DE")),
+        filter("US auto", Some("url-test"), Some("US"), None),
+        filter("broken", None, Some("("), None),
+    ];
+
+    let config = use_group_filters(config, &filters);
+    let groups = config["proxy-groups"].as_sequence().unwrap();
+    assert_eq!(groups.len(), 3);
+    assert_eq!(
+        groups[0]["proxies"],
+        Value::from(vec!["🇯🇵 Tokyo 1", "japan 2 (game)"])
+    );
+    assert_eq!(groups[1]["name"], "JP only");
+    assert_eq!(groups[1]["type"], "select");
+    assert_eq!(groups[1]["proxies"], Value::from(vec!["🇯🇵 Tokyo 1"]));
+    assert_eq!(groups[2]["type"], "url-test");
+    assert_eq!(groups[2]["proxies"], Value::from(vec!["DIRECT"]));
+}
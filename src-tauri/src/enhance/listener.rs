@@ -0,0 +1,85 @@
+use crate::config::IVergeListener;
+use serde_yaml::{Mapping, Value};
+
+/// the name prefix of the node listeners
+pub const NODE_LISTENER_PREFIX: &str = "hiddify-node";
+
+/// add a local listener for each pinned node,
+/// so that the apps using the port always go through the node
+/// the nodes not in the profile are skipped, unless the providers may contain them
+pub fn use_node_listeners(mut config: Mapping, listeners: &[IVergeListener]) -> Mapping {
+    if listeners.is_empty() {
+        return config;
+    }
+
+    let has_providers = config
+        .get("proxy-providers")
+        .and_then(|val| val.as_mapping())
+        .filter(|map| !map.is_empty())
+        .is_some();
+
+    let names = ["proxies", "proxy-groups"]
+        .into_iter()
+        .filter_map(|key| config.get(key).and_then(|val| val.as_sequence()))
+        .flatten()
+        .filter_map(|item| item.get("name")?.as_str())
+        .map(|name| name.to_string())
+        .collect::<Vec<String>>();
+
+    let mut seq = config
+        .get("listeners")
+        .and_then(|val| val.as_sequence())
+        .cloned()
+        .unwrap_or_default();
+
+    for item in listeners.iter() {
+        let builtin = matches!(item.node.as_str(), "DIRECT" | "REJECT");
+        if !builtin && !has_providers && !names.contains(&item.node) {
+            log::warn!(target: "app", "skip the listener of the missing node \"{}\"", item.node);
+            continue;
+        }
+
+        let mut listener = Mapping::new();
+        let name = format!("{NODE_LISTENER_PREFIX}-{}", item.port);
+        listener.insert("name".into(), name.into());
+        listener.insert("type".into(), "mixed".into());
+        listener.insert("listen".into(), "127.0.0.1".into());
+        listener.insert("port".into(), item.port.into());
+        listener.insert("udp".into(), true.into());
+        listener.insert("proxy".into(), item.node.clone().into());
+        seq.push(Value::from(listener));
+    }
+
+    config.insert("listeners".into(), seq.into());
+    config
+}
+
+#[test]
+fn test_node_listeners() {
+    let config = serde_yaml::from_str::<Mapping>(
+        "proxies:\n  - name: a\nproxy-groups:\n  - name: g\n    type: select\n    proxies: [a]",
+    )
+    .unwrap();
+
+    let listeners = vec![
+        IVergeListener {
+            node: "a".into(),
+            port: 7001,
+        },
+        IVergeListener {
+            node: "missing".into(),
+            port: 7002,
+        },
+        IVergeListener {
+            node: "DIRECT".into(),
+            port: 7003,
+        },
+    ];
+
+    let config = use_node_listeners(config, &listeners);
+    let seq = config.get("listeners").unwrap().as_sequence().unwrap();
+    assert_eq!(seq.len(), 2);
+    assert_eq!(seq[0].get("name").unwrap(), "hiddify-node-7001");
+    assert_eq!(seq[0].get("proxy").unwrap(), "a");
+    assert_eq!(seq[1].get("port").unwrap(), 7003);
+}
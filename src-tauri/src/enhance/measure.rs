@@ -0,0 +1,59 @@
+use serde_yaml::{Mapping, Sequence, Value};
+
+/// the hidden selector used by the measurement
+pub const MEASURE_GROUP: &str = "hiddify-measure";
+
+/// add a hidden selector containing all the proxies
+/// and a local listener which sends all the traffic to it,
+/// so that a single proxy can be measured without touching the user's selection
+pub fn use_measure(mut config: Mapping, port: u16) -> Mapping {
+    let names = config
+        .get("proxies")
+        .and_then(|val| val.as_sequence())
+        .map_or(vec![], |seq| {
+            seq.iter()
+                .filter_map(|item| item.get("name")?.as_str())
+                .map(Value::from)
+                .collect::<Sequence>()
+        });
+
+    let providers = config
+        .get("proxy-providers")
+        .and_then(|val| val.as_mapping())
+        .map_or(vec![], |map| map.keys().cloned().collect::<Sequence>());
+
+    if names.is_empty() && providers.is_empty() {
+        return config;
+    }
+
+    let mut group = Mapping::new();
+    group.insert("name".into(), MEASURE_GROUP.into());
+    group.insert("type".into(), "select".into());
+    group.insert("hidden".into(), true.into());
+    if !names.is_empty() {
+        group.insert("proxies".into(), names.into());
+    }
+    if !providers.is_empty() {
+        group.insert("use".into(), providers.into());
+    }
+
+    let mut listener = Mapping::new();
+    listener.insert("name".into(), format!("{MEASURE_GROUP}-in").into());
+    listener.insert("type".into(), "mixed".into());
+    listener.insert("listen".into(), "127.0.0.1".into());
+    listener.insert("port".into(), port.into());
+    listener.insert("udp".into(), true.into());
+    listener.insert("proxy".into(), MEASURE_GROUP.into());
+
+    for (key, value) in [("proxy-groups", group), ("listeners", listener)] {
+        let mut seq = config
+            .get(key)
+            .and_then(|val| val.as_sequence())
+            .cloned()
+            .unwrap_or_default();
+        seq.push(value.into());
+        config.insert(key.into(), seq.into());
+    }
+
+    config
+}
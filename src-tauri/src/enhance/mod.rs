@@ -1,29 +1,139 @@
+mod balance;
 mod chain;
 mod field;
+mod group_filter;
+mod listener;
+mod measure;
 mod merge;
+mod rename;
 mod script;
 mod tun;
 
 pub(self) use self::field::*;
 
+use self::balance::*;
 use self::chain::*;
+use self::group_filter::*;
+use self::listener::*;
+use self::measure::*;
 use self::merge::*;
+use self::rename::*;
 use self::script::*;
 use self::tun::*;
-use crate::config::Config;
+use crate::config::{
+    filter_nodes, node_filters, Config, IProfiles, IVergeRenameRule, GROUP_FILTERS,
+};
+use anyhow::{bail, Result};
+use serde::Serialize;
 use serde_yaml::Mapping;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+pub use self::measure::MEASURE_GROUP;
+
 type ResultLog = Vec<(String, String)>;
 
 /// Enhance mode
 /// 返回最终配置、该配置包含的键、和script执行的结果
 pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
+    enhance_profile(None)
+}
+
+/// the output of the script of a profile
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptPreview {
+    /// the yaml transformed by the script, the original one if the script failed
+    pub config: String,
+
+    pub logs: ResultLog,
+}
+
+/// run the script of the profile on it, without the chain and the overrides
+pub fn preview_profile_script(uid: &String) -> Result<ScriptPreview> {
+    let (mapping, script) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        (profiles.item_mapping(uid)?, profile_script(&profiles, uid))
+    };
+
+    let script = match script.map(|item| item.data) {
+        Some(ChainType::Script(script)) => script,
+        _ => bail!("the profile has no script"),
+    };
+
+    let (config, logs) = use_script(script, mapping)?;
+    Ok(ScriptPreview {
+        config: serde_yaml::to_string(&config)?,
+        logs,
+    })
+}
+
+/// the names of the nodes of the profile before and after the rename rules
+/// the saved global and profile rules are used if `rules` is none
+pub fn preview_profile_rename(
+    uid: &String,
+    rules: Option<Vec<IVergeRenameRule>>,
+) -> Result<Vec<(String, String)>> {
+    let (mapping, rules) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let rules = rules.unwrap_or_else(|| rename_rules(&profiles, Some(uid)));
+        (profiles.item_mapping(uid)?, rules)
+    };
+    preview_rename(&mapping, &rules)
+}
+
+/// the global rename rules and the ones of the profile
+fn rename_rules(profiles: &IProfiles, uid: Option<&String>) -> Vec<IVergeRenameRule> {
+    let mut rules = { Config::verge().latest().rename_rules.clone() }.unwrap_or_default();
+    let item = uid.and_then(|uid| profiles.get_item(uid).ok());
+    if let Some(item_rules) = item.and_then(|item| item.option.as_ref()?.rename_rules.clone()) {
+        rules.extend(item_rules);
+    }
+    rules
+}
+
+/// drop the nodes matching the `node_filters` if the profile enables `filter_nodes`
+fn use_node_filter(mut config: Mapping, profiles: &IProfiles, uid: Option<&String>) -> Mapping {
+    let item = uid.and_then(|uid| profiles.get_item(uid).ok());
+    let enable = item.and_then(|item| item.option.as_ref()?.filter_nodes);
+    if !enable.unwrap_or(false) {
+        return config;
+    }
+
+    match filter_nodes(&mut config, &node_filters()) {
+        Ok(count) => log::info!(target: "app", "filtered {count} nodes of the profile"),
+        Err(err) => log::error!(target: "app", "failed to filter the nodes, {err}"),
+    }
+    config
+}
+
+/// the `script` item set in the option of the profile
+fn profile_script(profiles: &IProfiles, uid: &String) -> Option<ChainItem> {
+    let item = profiles.get_item(uid).ok()?;
+    let script = item.option.as_ref()?.script.as_ref()?;
+    let script = profiles.get_item(script).ok()?;
+
+    match script.itype.as_deref() {
+        Some("script") => <Option<ChainItem>>::from(script),
+        _ => None,
+    }
+}
+
+/// 用指定的配置代替current生成最终配置，用于检查还未启用的配置
+pub fn enhance_profile(uid: Option<&String>) -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     // config.yaml 的配置
     let clash_config = { Config::clash().latest().0.clone() };
 
-    let (clash_core, enable_tun, enable_builtin, enable_filter) = {
+    let (
+        clash_core,
+        enable_tun,
+        enable_builtin,
+        enable_filter,
+        measure_port,
+        node_listeners,
+        load_balance,
+    ) = {
         let verge = Config::verge();
         let verge = verge.latest();
         (
@@ -31,6 +141,9 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
             verge.enable_tun_mode.clone().unwrap_or(false),
             verge.enable_builtin_enhanced.clone().unwrap_or(true),
             verge.enable_clash_fields.clone().unwrap_or(true),
+            verge.get_measure_port(),
+            verge.node_listeners.clone().unwrap_or_default(),
+            verge.load_balance.clone(),
         )
     };
 
@@ -39,9 +152,16 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
 
-        let current = profiles.current_mapping().unwrap_or(Mapping::new());
+        let current = match uid {
+            Some(uid) => profiles.item_mapping(uid),
+            None => profiles.current_mapping(),
+        };
+        let current = current.unwrap_or(Mapping::new());
+        let uid = uid.or(profiles.current.as_ref());
+        let current = use_node_filter(current, &profiles, uid);
+        let current = use_rename(current, &rename_rules(&profiles, uid));
 
-        let chain = match profiles.chain.as_ref() {
+        let mut chain = match profiles.chain.as_ref() {
             Some(chain) => chain
                 .iter()
                 .filter_map(|uid| profiles.get_item(uid).ok())
@@ -50,6 +170,11 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
             None => vec![],
         };
 
+        // 配置自己的脚本在chain之前跑
+        if let Some(script) = uid.and_then(|uid| profile_script(&profiles, uid)) {
+            chain.insert(0, script);
+        }
+
         let valid = profiles.valid.clone().unwrap_or(vec![]);
 
         (current, chain, valid)
@@ -84,6 +209,21 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         }
     });
 
+    // 处理app管理的override
+    let (mut overrides, group_filters) = {
+        let overrides = Config::overrides();
+        let overrides = overrides.latest();
+        (overrides.0.clone(), overrides.get_group_filters())
+    };
+    overrides.remove(GROUP_FILTERS);
+    if !overrides.is_empty() {
+        exists_keys.extend(use_keys(&overrides));
+        config = use_merge(overrides, config);
+    }
+    // 按关键词筛选的组，每次都从当前的节点生成
+    config = use_group_filters(config, &group_filters);
+    config = use_load_balance(config, &load_balance);
+
     // 合并默认的config
     for (key, value) in clash_config.into_iter() {
         config.insert(key, value);
@@ -116,6 +256,12 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
 
     config = use_filter(config, &clash_fields, enable_filter);
     config = use_tun(config, enable_tun);
+
+    // 测速用的和节点的listener只有meta支持
+    if clash_core.as_deref().unwrap_or("clash-meta") == "clash-meta" {
+        config = use_measure(config, measure_port);
+        config = use_node_listeners(config, &node_listeners);
+    }
     config = use_sort(config, enable_filter);
 
     let mut exists_set = HashSet::new();
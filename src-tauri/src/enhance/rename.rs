@@ -0,0 +1,210 @@
+use crate::config::IVergeRenameRule;
+use crate::core::region;
+use anyhow::{bail, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use tauri::regex::Regex;
+
+/// the ads in the node names, such as the channels and the links
+const ADS_PATTERN: &str = r"(?i)(https?://\S+|t\.me/\S+|@\w+|(telegram|channel|join)\s*[:：]\s*\S+|(www\.)?\S+\.(com|net|org|top|xyz)\b)";
+
+/// the new names of the nodes, the duplicates get a suffix such as ` 2`
+/// fail if a regex is invalid
+pub fn rename_nodes(names: &[String], rules: &[IVergeRenameRule]) -> Result<Vec<String>> {
+    let mut renamed = names.to_vec();
+    let ads = Regex::new(ADS_PATTERN)?;
+
+    for rule in rules.iter().filter(|r| r.enable.unwrap_or(true)) {
+        match rule.kind.as_str() {
+            "replace" => {
+                let find = rule.find.as_deref().unwrap_or_default();
+                let regex = match Regex::new(find) {
+                    Ok(regex) => regex,
+                    Err(err) => bail!("invalid rename regex \"{find}\", {err}"),
+                };
+                let replace = rule.replace.as_deref().unwrap_or_default();
+                for name in renamed.iter_mut() {
+                    *name = regex.replace_all(name, replace).trim().to_string();
+                }
+            }
+            "flag" => {
+                for name in renamed.iter_mut() {
+                    if let Some(code) = region::parse_region(name) {
+                        let flag = region::flag(&code);
+                        if !name.starts_with(&flag) {
+                            *name = format!("{flag} {name}");
+                        }
+                    }
+                }
+            }
+            "strip_ads" => {
+                for name in renamed.iter_mut() {
+                    let stripped = ads.replace_all(name, "");
+                    let stripped = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+                    *name = stripped
+                        .trim_matches(|c: char| c.is_whitespace() || "|-_·,".contains(c))
+                        .to_string();
+                }
+            }
+            kind => bail!("invalid rename rule \"{kind}\""),
+        }
+    }
+
+    // keep the original name if nothing is left
+    for (name, original) in renamed.iter_mut().zip(names.iter()) {
+        if name.is_empty() {
+            *name = original.clone();
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for name in renamed.iter_mut() {
+        let mut unique = name.clone();
+        let mut index = 2;
+        while !seen.insert(unique.clone()) {
+            unique = format!("{name} {index}");
+            index += 1;
+        }
+        *name = unique;
+    }
+
+    Ok(renamed)
+}
+
+/// rename the proxies of the profile, the groups and the rules follow
+pub fn use_rename(mut config: Mapping, rules: &[IVergeRenameRule]) -> Mapping {
+    if rules.is_empty() {
+        return config;
+    }
+
+    let names = proxy_names(&config);
+    let renamed = match rename_nodes(&names, rules) {
+        Ok(renamed) => renamed,
+        Err(err) => {
+            log::error!(target: "app", "failed to rename the nodes, {err}");
+            return config;
+        }
+    };
+
+    let map = names
+        .into_iter()
+        .zip(renamed)
+        .filter(|(old, new)| old != new)
+        .collect::<HashMap<String, String>>();
+    if map.is_empty() {
+        return config;
+    }
+    let rename = |name: &mut Value| {
+        if let Some(new) = name.as_str().and_then(|n| map.get(n)) {
+            *name = new.as_str().into();
+        }
+    };
+
+    if let Some(proxies) = config.get_mut("proxies").and_then(|p| p.as_sequence_mut()) {
+        for proxy in proxies.iter_mut().filter_map(|p| p.as_mapping_mut()) {
+            for key in ["name", "dialer-proxy"] {
+                if let Some(name) = proxy.get_mut(key) {
+                    rename(name);
+                }
+            }
+        }
+    }
+
+    if let Some(groups) = config
+        .get_mut("proxy-groups")
+        .and_then(|g| g.as_sequence_mut())
+    {
+        for group in groups.iter_mut().filter_map(|g| g.as_mapping_mut()) {
+            if let Some(proxies) = group.get_mut("proxies").and_then(|p| p.as_sequence_mut()) {
+                proxies.iter_mut().for_each(rename);
+            }
+        }
+    }
+
+    // the target of `MATCH,target` or `TYPE,payload,target,no-resolve`
+    if let Some(rules) = config.get_mut("rules").and_then(|r| r.as_sequence_mut()) {
+        for rule in rules.iter_mut() {
+            let mut parts = match rule.as_str() {
+                Some(rule) => rule.split(',').map(String::from).collect::<Vec<_>>(),
+                None => continue,
+            };
+            let index = match parts[0].trim().eq_ignore_ascii_case("MATCH") {
+                true => 1,
+                false => 2,
+            };
+            if let Some(new) = parts.get(index).and_then(|t| map.get(t.trim())) {
+                parts[index] = new.clone();
+                *rule = parts.join(",").into();
+            }
+        }
+    }
+
+    config
+}
+
+fn proxy_names(config: &Mapping) -> Vec<String> {
+    config
+        .get("proxies")
+        .and_then(|p| p.as_sequence())
+        .map_or(vec![], |seq| {
+            seq.iter()
+                .filter_map(|p| p.get("name")?.as_str().map(String::from))
+                .collect()
+        })
+}
+
+/// the names of the proxies before and after the rules
+pub fn preview_rename(
+    config: &Mapping,
+    rules: &[IVergeRenameRule],
+) -> Result<Vec<(String, String)>> {
+    let names = proxy_names(config);
+    let renamed = rename_nodes(&names, rules)?;
+    Ok(names.into_iter().zip(renamed).collect())
+}
+
+#[test]
+fn test_rename() {
+    let rule = |kind: &str, find: Option<&str>, replace: Option<&str>| IVergeRenameRule {
+        kind: kind.into(),
+        find: find.map(String::from),
+        replace: replace.map(String::from),
+        enable: None,
+    };
+
+    let config = serde_yaml::from_str::<Mapping>(
+        r#"
+proxies:
+  - {name: "DE-01 | t.me/free_vpn", type: vless}
+  - {name: "DE-02 @freevpn", type: vless}
+  - {name: "hk 1", type: trojan, dialer-proxy: "DE-01 | t.me/free_vpn"}
+proxy-groups:
+  - {name: select, type: select, proxies: ["DE-01 | t.me/free_vpn", "DE-02 @freevpn", "hk 1", DIRECT]}
+rules: ["DOMAIN,a.com,hk 1", "MATCH,DE-02 @freevpn"]
+"#,
+    )
+    .unwrap();
+
+    let rules = [
+        rule("strip_ads", None, None),
+        rule("replace", Some(r"-0?(\d+)"), Some(" $1")),
+        rule("replace", Some("(?i)^hk"), Some("HK")),
+        rule("flag", None, None),
+    ];
+    let preview = preview_rename(&config, &rules).unwrap();
+    assert_eq!(preview[0].1, "🇩🇪 DE 1");
+    assert_eq!(preview[1].1, "🇩🇪 DE 2");
+    assert_eq!(preview[2].1, "🇭🇰 HK 1");
+
+    let config = use_rename(config, &rules);
+    assert_eq!(config["proxies"][2]["dialer-proxy"], "🇩🇪 DE 1");
+    assert_eq!(config["proxy-groups"][0]["proxies"][1], "🇩🇪 DE 2");
+    assert_eq!(config["proxy-groups"][0]["proxies"][3], "DIRECT");
+    assert_eq!(config["rules"][0], "DOMAIN,a.com,🇭🇰 HK 1");
+    assert_eq!(config["rules"][1], "MATCH,🇩🇪 DE 2");
+
+    let names = ["a 1".to_string(), "a 2".to_string(), "a".to_string()];
+    let renamed = rename_nodes(&names, &[rule("replace", Some(r" \d"), None)]).unwrap();
+    assert_eq!(renamed, ["a", "a 2", "a 3"]);
+    assert!(rename_nodes(&names, &[rule("replace", Some("("), None)]).is_err());
+}
@@ -10,7 +10,7 @@ use crate::config::*;
 use crate::core::*;
 use crate::core::handle::Handle;
 use crate::log_err;
-use crate::utils::help;
+use crate::utils::{backup, help};
 use anyhow::{bail, Result};
 use serde_yaml::{Mapping, Value};
 
@@ -41,10 +41,10 @@ pub fn change_clash_mode(mode: String) {
         match clash_api::patch_configs(&mapping).await {
             Ok(_) => {
                 // 更新配置
-                Config::clash().data().patch_config(mapping);
+                let tx = Config::clash().begin().await;
+                tx.draft().patch_config(mapping);
 
-                if Config::clash().data().save_config().is_ok() {
-                    handle::Handle::refresh_clash();
+                if tx.commit().is_ok() {
                     log_err!(handle::Handle::update_systray_part());
                 }
             }
@@ -53,9 +53,22 @@ pub fn change_clash_mode(mode: String) {
     });
 }
 
+/// switch back to the previous node of the selector switched last
+pub fn switch_to_previous_node() {
+    tauri::async_runtime::spawn(async move {
+        match recent::RecentNodes::global().switch_to_previous(None).await {
+            Ok(name) => {
+                log::debug!(target: "app", "switch to the previous node {name}");
+                handle::Handle::refresh_clash();
+            }
+            Err(err) => log::error!(target: "app", "{err}"),
+        }
+    });
+}
+
 // 切换系统代理
 pub fn toggle_system_proxy() {
-    let enable = Config::verge().draft().enable_system_proxy.clone();
+    let enable = Config::verge().latest().enable_system_proxy.clone();
     let enable = enable.unwrap_or(false);
 
     tauri::async_runtime::spawn(async move {
@@ -71,6 +84,19 @@ pub fn toggle_system_proxy() {
     });
 }
 
+/// pause the system proxy and the tun mode for the minutes
+pub fn pause_proxy(minutes: u64) {
+    tauri::async_runtime::spawn(async move {
+        log_err!(pause::ProxyPause::global().pause(minutes * 60, true).await);
+    });
+}
+
+pub fn resume_proxy() {
+    tauri::async_runtime::spawn(async {
+        log_err!(pause::ProxyPause::global().resume().await);
+    });
+}
+
 // 打开系统代理
 pub fn enable_system_proxy() {
     tauri::async_runtime::spawn(async {
@@ -109,7 +135,7 @@ pub fn toggle_tun_mode() {
         return;
     }
 
-    let enable = Config::verge().data().enable_tun_mode.clone();
+    let enable = Config::verge().latest().enable_tun_mode.clone();
     let enable = enable.unwrap_or(false);
 
     tauri::async_runtime::spawn(async move {
@@ -189,17 +215,19 @@ fn check_user_admin_right() -> ControlFlow<()> {
 
 /// 修改clash的配置
 pub async fn patch_clash(patch: Mapping) -> Result<()> {
-    Config::clash().draft().patch_config(patch.clone());
+    let tx = Config::clash().begin().await;
+    let old_port = { tx.draft().0.get("mixed-port").cloned() };
+    tx.draft().patch_config(patch.clone());
+    tx.validate()?;
 
     match {
         let mixed_port = patch.get("mixed-port");
         if mixed_port.is_some() {
-            let changed = mixed_port != Config::clash().data().0.get("mixed-port");
+            let changed = mixed_port != old_port.as_ref();
             // 检查端口占用
             if changed {
                 if let Some(port) = mixed_port.clone().unwrap().as_u64() {
                     if !port_scanner::local_port_available(port as u16) {
-                        Config::clash().discard();
                         bail!("port already in use");
                     }
                 }
@@ -210,6 +238,7 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
         if mixed_port.is_some()
             || patch.get("secret").is_some()
             || patch.get("external-controller").is_some()
+            || patch.get(CONTROLLER_SOCKET_KEY).is_some()
         {
             Config::generate()?;
             CoreManager::global().run_core().await?;
@@ -229,22 +258,18 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
 
         <Result<()>>::Ok(())
     } {
-        Ok(()) => {
-            Config::clash().apply();
-            Config::clash().data().save_config()?;
-            Ok(())
-        }
-        Err(err) => {
-            Config::clash().discard();
-            Err(err)
-        }
+        Ok(()) => tx.commit(),
+        // the draft is discarded with the transaction
+        Err(err) => Err(err),
     }
 }
 
 /// 修改verge的配置
 /// 一般都是一个个的修改
 pub async fn patch_verge(patch: IVerge) -> Result<()> {
-    Config::verge().draft().patch_config(patch.clone());
+    let tx = Config::verge().begin().await;
+    tx.draft().patch_config(patch.clone());
+    tx.validate()?;
 
     let tun_mode = patch.enable_tun_mode;
     let auto_launch = patch.enable_auto_launch;
@@ -284,6 +309,10 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             sysopt::Sysopt::global().guard_proxy();
         }
 
+        if let Some(enable) = patch.enable_config_backup {
+            backup::set_enabled(enable);
+        }
+
         if let Some(hotkeys) = patch.hotkeys {
             hotkey::Hotkey::global().update(hotkeys)?;
         }
@@ -297,14 +326,15 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
         <Result<()>>::Ok(())
     } {
         Ok(()) => {
-            Config::verge().apply();
-            Config::verge().data().save_file()?;
+            tx.commit()?;
+            // turned on by hand, the pause is over
+            if system_proxy == Some(true) || tun_mode == Some(true) {
+                pause::ProxyPause::global().cancel();
+            }
             Ok(())
         }
-        Err(err) => {
-            Config::verge().discard();
-            Err(err)
-        }
+        // the draft is discarded with the transaction
+        Err(err) => Err(err),
     }
 }
 
@@ -322,31 +352,469 @@ pub async fn update_profile(uid: String, option: Option<PrfOption>) -> Result<()
         } else if item.url.is_none() {
             bail!("failed to get the profile item url");
         } else {
-            Some((item.url.clone().unwrap(), item.option.clone()))
+            Some((item.url.clone().unwrap(), item.clone()))
         }
     };
 
     let should_update = match url_opt {
-        Some((url, opt)) => {
-            let merged_opt = PrfOption::merge(opt, option);
-            let item = PrfItem::from_url(&url, None, None, merged_opt).await?;
-
-            let profiles = Config::profiles();
-            let mut profiles = profiles.latest();
-            profiles.update_item(uid.clone(), item)?;
-
-            Some(uid) == profiles.get_current()
+        Some((url, cached)) => {
+            // the changed option may change the response
+            let validators = match option.is_none() {
+                true => Some(&cached),
+                false => None,
+            };
+            let merged_opt = PrfOption::merge(cached.option.clone(), option);
+            // the saved url may predate the rules or be edited by hand
+            let url = normalize_profile_url(&url)?;
+            let item = PrfItem::from_url_modified(&url, None, None, merged_opt, validators).await?;
+
+            let tx = Config::profiles().begin().await;
+            let should_update = match item {
+                Some(item) => {
+                    if let Some(extra) = item.extra.as_ref() {
+                        let name = cached.name.as_ref().unwrap_or(&uid);
+                        notification::check_subscription(&uid, name, extra);
+                    }
+                    let mut profiles = tx.draft();
+                    profiles.update_item(uid.clone(), item)?;
+                    Some(uid) == profiles.get_current()
+                }
+                // only the time is updated if not modified
+                None => {
+                    log::info!(target: "app", "the profile \"{uid}\" is not modified");
+                    tx.draft().patch_item(
+                        uid,
+                        PrfItem {
+                            updated: Some(chrono::Local::now().timestamp() as usize),
+                            ..PrfItem::default()
+                        },
+                    )?;
+                    false
+                }
+            };
+            tx.commit()?;
+            should_update
         }
         None => true,
     };
 
     if should_update {
         update_core_config().await?;
+        log_err!(handle::Handle::update_systray());
     }
 
     Ok(())
 }
 
+/// 切换当前的profile
+pub async fn switch_profile(uid: String) -> Result<()> {
+    preflight::require_preflight(&uid)?;
+
+    let tx = Config::profiles().begin().await;
+    tx.draft().patch_config(IProfiles {
+        current: Some(uid),
+        ..IProfiles::default()
+    })?;
+
+    match activation::activate().await {
+        Ok(_) => {
+            tx.commit()?;
+            handle::Handle::refresh_clash();
+            handle::Handle::refresh_profiles();
+            // the nodes of the current group in the tray
+            log_err!(handle::Handle::update_systray());
+            hook::fire_profile_switched();
+            Ok(())
+        }
+        // the draft is discarded with the transaction
+        Err(err) => Err(err),
+    }
+}
+
+/// 把 lint 的修复写入 overrides
+/// prepend-rules 只添加缺少的规则，其余字段直接覆盖
+pub async fn apply_lint_fix(rule: String) -> Result<()> {
+    let fix = match lint_runtime_config()
+        .into_iter()
+        .find(|issue| issue.rule == rule)
+        .and_then(|issue| issue.fix)
+    {
+        Some(fix) => fix,
+        None => bail!("no fix of the lint \"{rule}\""),
+    };
+
+    let tx = Config::overrides().begin().await;
+    {
+        let mut overrides = tx.draft();
+
+        for (key, value) in fix.into_iter() {
+            match key.as_str() {
+                Some("prepend-rules") => {
+                    let mut rules = overrides.get_seq("prepend-rules");
+                    let added = value.as_sequence().cloned().unwrap_or_default();
+                    rules.retain(|rule| !added.contains(rule));
+                    rules.splice(0..0, added);
+                    overrides.set_seq("prepend-rules", rules);
+                }
+                _ => {
+                    overrides.0.insert(key, value);
+                }
+            }
+        }
+    }
+
+    apply_overrides(tx).await
+}
+
+/// 把 dns 预设写入 overrides 的 dns，`none` 时移除
+pub async fn apply_dns_preset(preset: IDnsPreset) -> Result<()> {
+    let dns = preset.to_dns()?;
+
+    let tx = Config::verge().begin().await;
+    tx.draft().dns_preset = match dns {
+        Some(_) => Some(preset),
+        None => None,
+    };
+    tx.validate()?;
+    let overrides_tx = Config::overrides().begin().await;
+    {
+        let mut overrides = overrides_tx.draft();
+        match dns {
+            Some(dns) => overrides.0.insert("dns".into(), dns.into()),
+            None => overrides.0.remove("dns"),
+        };
+    }
+    overrides_tx.validate()?;
+
+    // the drafts are discarded with the transactions if the core failed to update
+    CoreManager::global().update_config().await?;
+    tx.commit()?;
+    overrides_tx.commit()?;
+    handle::Handle::refresh_verge();
+    Ok(())
+}
+
+/// 把路由预设的规则放到 overrides 的 prepend-rules 前面，替换之前的预设，`none` 时移除
+pub async fn apply_routing_preset(name: String) -> Result<RoutingPlan> {
+    let tx = Config::overrides().begin().await;
+    let plan = {
+        let mut overrides = tx.draft();
+
+        let plan = plan_routing_preset(&name, &overrides.get_seq("prepend-rules"))?;
+        overrides.set_seq("prepend-rules", plan.rules.clone());
+        plan
+    };
+
+    apply_overrides(tx).await?;
+    Ok(plan)
+}
+
+/// 修改 overrides 的 hosts，添加的映射会先校验
+pub async fn patch_hosts(add: Vec<(String, String)>, remove: Vec<String>) -> Result<()> {
+    let add = add
+        .iter()
+        .map(|(domain, ip)| check_host(domain, ip))
+        .collect::<Result<Vec<_>>>()?;
+
+    let tx = Config::overrides().begin().await;
+    {
+        let mut overrides = tx.draft();
+
+        let mut hosts = overrides.get_map("hosts");
+        for domain in remove.iter() {
+            hosts.remove(domain.trim().trim_end_matches('.').to_lowercase().as_str());
+        }
+        for (domain, ip) in add.into_iter() {
+            hosts.insert(domain.into(), ip.into());
+        }
+        overrides.set_map("hosts", hosts);
+    }
+
+    apply_overrides(tx).await
+}
+
+/// 修改 overrides 的按关键词筛选的组，正则会先校验
+pub async fn set_group_filters(filters: Vec<IGroupFilter>) -> Result<()> {
+    let tx = Config::overrides().begin().await;
+    tx.draft().set_group_filters(&filters)?;
+    apply_overrides(tx).await
+}
+
+/// 用最近测速的吞吐量生成 load-balance 组，返回成员和权重
+pub async fn create_load_balance(
+    name: String,
+    strategy: Option<String>,
+    size: Option<usize>,
+) -> Result<Vec<load_balance::BalanceMember>> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        bail!("the name of the load balance group is empty");
+    }
+    if let Some(strategy) = strategy.as_deref() {
+        load_balance::check_strategy(strategy)?;
+    }
+
+    let members = load_balance::current_members(size.unwrap_or(load_balance::DEFAULT_SIZE));
+    if members.is_empty() {
+        bail!("no node with the measured throughput, run a full scan first");
+    }
+
+    let tx = Config::verge().begin().await;
+    tx.draft().load_balance = Some(IVergeLoadBalance {
+        name,
+        strategy,
+        size,
+        members: members.iter().map(|m| m.name.clone()).collect(),
+    });
+    apply_load_balance(tx).await?;
+    Ok(members)
+}
+
+/// 移除 load-balance 组
+pub async fn remove_load_balance() -> Result<()> {
+    let tx = Config::verge().begin().await;
+    tx.draft().load_balance = None;
+    apply_load_balance(tx).await
+}
+
+/// 测速完成后按吞吐量刷新 load-balance 组的成员，成员没变就不重载配置
+pub async fn refresh_load_balance() -> Result<()> {
+    let tx = Config::verge().begin().await;
+    let balance = { tx.draft().load_balance.clone() };
+    let mut balance = match balance {
+        Some(balance) => balance,
+        None => return Ok(()),
+    };
+
+    let size = balance.size.unwrap_or(load_balance::DEFAULT_SIZE);
+    let members = load_balance::current_members(size)
+        .into_iter()
+        .map(|m| m.name)
+        .collect::<Vec<_>>();
+    // 没有吞吐量的结果时保留原来的成员
+    if members.is_empty() || members == balance.members {
+        return Ok(());
+    }
+
+    log::info!(target: "app", "refresh the members of the load balance group \"{}\"", balance.name);
+    balance.members = members;
+    tx.draft().load_balance = Some(balance);
+    apply_load_balance(tx).await
+}
+
+/// the draft is discarded with the transaction if the core failed to update
+async fn apply_load_balance(tx: DraftTx<IVerge>) -> Result<()> {
+    CoreManager::global().update_config().await?;
+    tx.commit()?;
+    handle::Handle::refresh_clash();
+    handle::Handle::refresh_verge();
+    Ok(())
+}
+
+/// 为进程添加 PROCESS-NAME 规则
+/// 同一个进程的旧规则会被替换
+pub async fn add_process_rule(process: String, target: String) -> Result<()> {
+    let process = process.trim().to_string();
+    if process.is_empty() || process.contains(',') {
+        bail!("invalid process name \"{process}\"");
+    }
+    if !Config::runtime().latest().get_targets().contains(&target) {
+        bail!("invalid rule target \"{target}\"");
+    }
+
+    let tx = Config::overrides().begin().await;
+    {
+        let mut overrides = tx.draft();
+
+        let prefix = format!("PROCESS-NAME,{process},");
+        let mut rules = overrides.get_seq("prepend-rules");
+        rules.retain(|rule| rule.as_str().map_or(true, |r| !r.starts_with(&prefix)));
+        rules.insert(0, format!("{prefix}{target}").into());
+        overrides.set_seq("prepend-rules", rules);
+    }
+
+    apply_overrides(tx).await
+}
+
+/// 编辑 overrides 的 prepend-rules，规则的目标要在当前的组和节点里
+/// 核心更新失败时不保存
+pub async fn edit_custom_rules(edit: RuleEdit) -> Result<()> {
+    let targets = { Config::runtime().latest().get_targets() };
+
+    let tx = Config::overrides().begin().await;
+    {
+        let mut overrides = tx.draft();
+
+        let mut rules = overrides.get_seq("prepend-rules");
+        edit_rules(&mut rules, edit, &targets)?;
+        overrides.set_seq("prepend-rules", rules);
+    }
+
+    apply_overrides(tx).await
+}
+
+/// 为节点添加单独的本地端口
+/// 同一个端口的旧listener会被替换
+pub async fn create_node_listener(node: String, port: u16) -> Result<()> {
+    let tx = Config::verge().begin().await;
+    let (clash_core, measure_port, mut listeners) = {
+        let verge = tx.draft();
+        (
+            verge.clash_core.clone(),
+            verge.get_measure_port(),
+            verge.node_listeners.clone().unwrap_or_default(),
+        )
+    };
+    if clash_core.as_deref().unwrap_or("clash-meta") != "clash-meta" {
+        bail!("the node listener is only supported by clash meta");
+    }
+    if !Config::runtime().latest().get_targets().contains(&node) {
+        bail!("invalid node \"{node}\"");
+    }
+
+    // 替换自己的listener时端口已被core占用
+    let mixed_port = { Config::clash().latest().get_mixed_port() };
+    let replaced = listeners.iter().any(|item| item.port == port);
+    if port == 0
+        || port == mixed_port
+        || port == measure_port
+        || (!replaced && !port_scanner::local_port_available(port))
+    {
+        bail!("port already in use");
+    }
+
+    listeners.retain(|item| item.port != port);
+    listeners.push(IVergeListener { node, port });
+    tx.draft().node_listeners = Some(listeners);
+
+    apply_node_listeners(tx).await
+}
+
+/// 删除节点的本地端口
+pub async fn remove_node_listener(port: u16) -> Result<()> {
+    let tx = Config::verge().begin().await;
+    let mut listeners = { tx.draft().node_listeners.clone().unwrap_or_default() };
+    if !listeners.iter().any(|item| item.port == port) {
+        bail!("failed to find the node listener \"{port}\"");
+    }
+
+    listeners.retain(|item| item.port != port);
+    tx.draft().node_listeners = Some(listeners);
+
+    apply_node_listeners(tx).await
+}
+
+/// the draft is discarded with the transaction if the core failed to update
+async fn apply_node_listeners(tx: DraftTx<IVerge>) -> Result<()> {
+    CoreManager::global().update_config().await?;
+    tx.commit()?;
+    handle::Handle::refresh_verge();
+    Ok(())
+}
+
+/// 修改系统代理的bypass
+/// 同时将其同步为clash的DIRECT规则
+pub async fn patch_bypass(bypass: Vec<String>) -> Result<()> {
+    let bypass = bypass
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>();
+
+    for entry in bypass.iter() {
+        sysopt::check_bypass(entry)?;
+    }
+
+    let tx = Config::verge().begin().await;
+
+    // 默认的bypass没有生成过规则
+    let customized = { tx.draft().system_proxy_bypass.is_some() };
+    let old_rules = match customized {
+        true => sysopt::get_bypass_list(),
+        false => vec![],
+    };
+    let old_rules = old_rules
+        .iter()
+        .filter_map(|entry| sysopt::bypass_to_rule(entry))
+        .collect::<Vec<String>>();
+    let new_rules = bypass
+        .iter()
+        .filter_map(|entry| sysopt::bypass_to_rule(entry));
+
+    tx.draft().system_proxy_bypass = Some(bypass.join(sysopt::BYPASS_SEPARATOR));
+    tx.validate()?;
+    let overrides_tx = Config::overrides().begin().await;
+    {
+        let mut overrides = overrides_tx.draft();
+
+        let mut rules = overrides.get_seq("prepend-rules");
+        rules.retain(|rule| {
+            rule.as_str()
+                .map_or(true, |r| !old_rules.iter().any(|o| o == r))
+        });
+        rules.extend(new_rules.map(|rule| rule.into()));
+        overrides.set_seq("prepend-rules", rules);
+    }
+    overrides_tx.validate()?;
+
+    let result = match sysopt::Sysopt::global().update_sysproxy() {
+        Ok(_) => CoreManager::global().update_config().await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(_) => {
+            tx.commit()?;
+            overrides_tx.commit()?;
+            handle::Handle::refresh_verge();
+            Ok(())
+        }
+        Err(err) => {
+            // discard the drafts before the old bypass is set back
+            drop(tx);
+            drop(overrides_tx);
+            log_err!(sysopt::Sysopt::global().update_sysproxy());
+            Err(err)
+        }
+    }
+}
+
+/// 应用分享的设置
+/// 先修改clash的端口和模式，再修改verge
+pub async fn apply_settings_preset(mut preset: ISettingsPreset) -> Result<()> {
+    // 预设不带 css_injection，保留本机的
+    if let Some(theme) = preset.verge.theme_setting.as_mut() {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        theme.css_injection = verge
+            .theme_setting
+            .as_ref()
+            .and_then(|theme| theme.css_injection.clone());
+    }
+
+    if !preset.clash.is_empty() {
+        patch_clash(preset.clash.clone()).await?;
+
+        // 模式直接切换运行中的core
+        if let Some(mode) = preset.clash.get("mode") {
+            let mut mapping = Mapping::new();
+            mapping.insert("mode".into(), mode.clone());
+            log_err!(clash_api::patch_configs(&mapping).await);
+            handle::Handle::refresh_clash();
+        }
+    }
+
+    patch_verge(preset.verge).await
+}
+
+/// 激活override的修改
+/// the draft is discarded with the transaction if the core failed to update
+async fn apply_overrides(tx: DraftTx<IOverride>) -> Result<()> {
+    tx.validate()?;
+    CoreManager::global().update_config().await?;
+    tx.commit()
+}
+
 /// 更新配置
 async fn update_core_config() -> Result<()> {
     match CoreManager::global().update_config().await {
@@ -10,20 +10,18 @@ mod enhance;
 mod feat;
 mod utils;
 mod deep_link;
-use std::sync::{Arc, Mutex};
 
 use crate::utils::{init, resolve, server, help};
 use crate::core::handle::Handle;
 use tauri::{api, SystemTray, Manager};
-use once_cell::sync::Lazy;
 use std::thread;
 use help::focus_to_main_window_if_needed;
 
-// This is not the best way to do it...
-static mut NEED_WINDOW_BE_FOCUS:Lazy<Arc<Mutex<bool>>> = Lazy::new(|| Arc::new(Mutex::new(false)));
-
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
+    // 需要在读取任何文件之前
+    let _ = utils::dirs::init_portable_flag();
+
 
     #[cfg(not(target_os = "macos"))]
     {
@@ -34,27 +32,10 @@ async fn main() -> std::io::Result<()> {
            // Set need to be focus to true, it's handled in another thread
            help::set_focus();
            
-           // Convert deep link to something that import_profile can use
-           let profile_url_and_name = help::convert_deeplink_to_url_for_import_profile(&deep_link);
-           // If deep link is invalid, we pop up a message to user
-           if profile_url_and_name.is_err(){
-               Handle::notice_message("set_config::error", "Profile url is invalid");
-               return
-           }
-           
-           // Import profile
-           let import_result = cmds::import_profile(profile_url_and_name.unwrap(), None).await;
-           // If we couldn't import profile& we pop up a message to user
-           if import_result.is_err(){
-               Handle::notice_message("set_config::error",format!("Profile url is invalid | {}", import_result.err().unwrap()));
-               return
+           // Ask the user before downloading and importing the profile
+           if let Err(err) = core::link_import::LinkImports::global().prepare(&deep_link) {
+               Handle::notice_message("set_config::error", format!("Profile url is invalid | {err}"));
            }
-   
-           // Select last profile (added profile)
-           if let Err(_) = help::select_last_profile().await{
-               Handle::notice_message("set_config::error", "Couldn't select added profile!")
-           }
-           Handle::notice_message("set_config::ok", "Profile added.");
        };
        // Register "clash" scheme
        let  deep_link_register_result = deep_link::register("clash",handler.clone()).await;
@@ -64,6 +45,14 @@ async fn main() -> std::io::Result<()> {
        }
     }
 
+    // 开机自启时延迟启动，在单例检测之前
+    if std::env::args().any(|arg| arg == core::sysopt::AUTOSTART_ARG) {
+        let delay = config::IVerge::get_auto_launch_delay();
+        if delay > 0 {
+            tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+        }
+    }
+
     // 单例检测
     if server::check_singleton().is_err() {
         println!("app exists");
@@ -77,10 +66,28 @@ async fn main() -> std::io::Result<()> {
         .system_tray(SystemTray::new())
         .setup(|app| Ok(resolve::resolve_setup(app)))
         .on_system_tray_event(core::tray::Tray::on_system_tray_event)
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(utils::authz::with_allowlist(tauri::generate_handler![
             // common
             cmds::get_sys_proxy,
+            cmds::get_bypass_list,
+            cmds::set_bypass_list,
             cmds::open_app_dir,
+            cmds::get_recovered_files,
+            cmds::get_startup_report,
+            cmds::kv_get,
+            cmds::kv_set,
+            cmds::kv_delete,
+            cmds::migrate_data_dir,
+            cmds::check_app_update,
+            cmds::install_app_update,
+            cmds::test_notification,
+            cmds::test_hook,
+            cmds::get_expiring_profiles,
+            cmds::test_direct,
+            cmds::tcping_nodes,
+            cmds::check_tun_routes,
+            cmds::get_connection_chains,
+            cmds::trace_domain,
             cmds::open_logs_dir,
             cmds::open_web_url,
             cmds::open_core_dir,
@@ -92,13 +99,81 @@ async fn main() -> std::io::Result<()> {
             cmds::get_clash_logs,
             cmds::patch_clash_config,
             cmds::change_clash_core,
+            cmds::test_proxy_delay,
+            cmds::compare_proxies,
+            cmds::benchmark_direct_vs_proxy,
+            cmds::measure_proxy,
+            cmds::measure_proxies,
+            cmds::resume_measurement_batch,
+            cmds::retest_failed,
+            cmds::get_measure_data_usage,
+            cmds::get_measurement_batches,
+            cmds::render_scan_summary,
+            cmds::start_remote_pairing,
+            cmds::get_remote_devices,
+            cmds::remove_remote_device,
+            cmds::get_cached_measurements,
+            cmds::rank_proxies,
+            cmds::select_proxy,
+            cmds::get_recent_nodes,
+            cmds::switch_to_previous_node,
+            cmds::get_node_meta,
+            cmds::set_node_meta,
+            cmds::get_proxies_with_meta,
+            cmds::query_proxies,
+            cmds::check_reachability,
+            cmds::check_unlock,
+            cmds::get_network_info,
+            cmds::get_speedtest_servers,
             cmds::get_runtime_config,
             cmds::get_runtime_yaml,
             cmds::get_runtime_exists,
             cmds::get_runtime_logs,
+            cmds::get_effective_config,
+            cmds::test_config,
+            cmds::preflight_profile,
+            cmds::preview_script_output,
+            cmds::preview_node_rename,
+            cmds::lint_config,
+            cmds::apply_lint_fix,
+            cmds::get_dns_presets,
+            cmds::apply_dns_preset,
+            cmds::get_routing_presets,
+            cmds::preview_routing_preset,
+            cmds::apply_routing_preset,
+            cmds::get_hosts,
+            cmds::add_host,
+            cmds::remove_host,
+            cmds::import_hosts_file,
+            cmds::get_group_filters,
+            cmds::set_group_filters,
+            cmds::create_load_balance,
+            cmds::remove_load_balance,
+            cmds::get_resource_usage,
+            cmds::get_app_status,
+            cmds::subscribe_state,
+            cmds::list_processes,
+            cmds::add_process_rule,
+            cmds::get_custom_rules,
+            cmds::add_custom_rule,
+            cmds::update_custom_rule,
+            cmds::move_custom_rule,
+            cmds::delete_custom_rule,
+            cmds::create_node_listener,
+            cmds::remove_node_listener,
             // verge
             cmds::get_verge_config,
             cmds::patch_verge_config,
+            cmds::pause_proxy,
+            cmds::resume_proxy,
+            cmds::get_proxy_pause,
+            cmds::export_settings_preset,
+            cmds::apply_settings_preset,
+            cmds::get_log_alerts,
+            cmds::get_schedule,
+            cmds::get_autostart_status,
+            cmds::set_autostart,
+            cmds::set_schedule,
             // cmds::update_hotkeys,
             // profile
             cmds::get_profiles,
@@ -107,7 +182,13 @@ async fn main() -> std::io::Result<()> {
             cmds::view_profile,
             cmds::patch_profile,
             cmds::create_profile,
+            cmds::generate_basic_profile,
+            cmds::find_duplicate_nodes,
+            cmds::generate_unique_profile,
             cmds::import_profile,
+            cmds::get_deep_link_imports,
+            cmds::confirm_deep_link_import,
+            cmds::import_profile_file,
             cmds::update_profile,
             cmds::delete_profile,
             cmds::read_profile_file,
@@ -116,7 +197,7 @@ async fn main() -> std::io::Result<()> {
             cmds::service::check_service,
             cmds::service::install_service,
             cmds::service::uninstall_service,
-        ]);
+        ]));
 
     #[cfg(target_os = "macos")]
     {
@@ -161,7 +242,8 @@ async fn main() -> std::io::Result<()> {
 
             if label == "main" {
                 match event {
-                    tauri::WindowEvent::CloseRequested { api, .. } => {
+                    // the window is destroyed in the lite mode
+                    tauri::WindowEvent::CloseRequested { api, .. } if !resolve::is_lite_mode() => {
                         api.prevent_close();
                         app_handle.get_window("main").map(|win| {
                             let _ = win.hide();
@@ -0,0 +1,108 @@
+use once_cell::sync::OnceCell;
+use parking_lot::RwLock;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// the version before the package info is read
+const DEFAULT_VERSION: &str = "v1.2.0";
+
+/// where the app keeps its data, decided before reading any file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HomeDir {
+    /// `~/.config/hiddify-desktop`
+    Default,
+
+    /// `.config/hiddify-desktop` next to the executable
+    Portable,
+
+    /// set by `--data-dir`
+    Custom(PathBuf),
+}
+
+/// the process-wide state of the app
+/// the values set once at the startup are kept in `OnceCell`s, the others are locked
+pub struct AppState {
+    home: OnceCell<HomeDir>,
+
+    resource_dir: RwLock<Option<PathBuf>>,
+
+    /// such as `v1.2.0`, from the package info
+    version: OnceCell<String>,
+
+    /// the main window is asked to come to the front, by the deep link or a notice
+    need_focus: AtomicBool,
+}
+
+impl AppState {
+    pub fn global() -> &'static AppState {
+        static STATE: OnceCell<AppState> = OnceCell::new();
+
+        STATE.get_or_init(AppState::new)
+    }
+
+    fn new() -> AppState {
+        AppState {
+            home: OnceCell::new(),
+            resource_dir: RwLock::new(None),
+            version: OnceCell::new(),
+            need_focus: AtomicBool::new(false),
+        }
+    }
+
+    /// only the first call takes effect, the data dir can't move while running
+    pub fn set_home(&self, home: HomeDir) {
+        let _ = self.home.set(home);
+    }
+
+    pub fn home(&self) -> HomeDir {
+        self.home.get().cloned().unwrap_or(HomeDir::Default)
+    }
+
+    pub fn set_resource_dir(&self, dir: PathBuf) {
+        *self.resource_dir.write() = Some(dir);
+    }
+
+    pub fn resource_dir(&self) -> Option<PathBuf> {
+        self.resource_dir.read().clone()
+    }
+
+    /// only the first call takes effect
+    pub fn set_version(&self, version: String) {
+        let _ = self.version.set(version);
+    }
+
+    pub fn version(&self) -> &str {
+        self.version.get().map_or(DEFAULT_VERSION, |v| v.as_str())
+    }
+
+    /// handled by the focus thread, see `help::focus_to_main_window_if_needed`
+    pub fn request_focus(&self) {
+        self.need_focus.store(true, Ordering::SeqCst);
+    }
+
+    /// true once for each request
+    pub fn take_focus_request(&self) -> bool {
+        self.need_focus.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[test]
+fn test_app_state() {
+    let state = AppState::new();
+
+    assert!(!state.take_focus_request());
+    state.request_focus();
+    state.request_focus();
+    assert!(state.take_focus_request());
+    assert!(!state.take_focus_request());
+
+    assert_eq!(state.version(), DEFAULT_VERSION);
+    state.set_version("v1.3.0".into());
+    state.set_version("v1.4.0".into());
+    assert_eq!(state.version(), "v1.3.0");
+
+    assert_eq!(state.home(), HomeDir::Default);
+    state.set_home(HomeDir::Custom("/tmp/data".into()));
+    state.set_home(HomeDir::Portable);
+    assert_eq!(state.home(), HomeDir::Custom("/tmp/data".into()));
+}
@@ -0,0 +1,167 @@
+//! The authorization of the commands invoked by the webview
+
+use crate::config::{Config, IVergeHook};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tauri::{Invoke, Runtime};
+
+/// the window allowed to invoke the commands
+const MAIN_WINDOW: &str = "main";
+
+/// the commands invoked by the webview, sorted for the binary search
+/// the commands not listed are rejected even if registered
+const WEBVIEW_COMMANDS: [&str; 34] = [
+    "change_clash_core",
+    "check_service",
+    "confirm_deep_link_import",
+    "create_profile",
+    "delete_profile",
+    "enhance_profiles",
+    "get_clash_info",
+    "get_clash_logs",
+    "get_deep_link_imports",
+    "get_profiles",
+    "get_runtime_config",
+    "get_runtime_exists",
+    "get_runtime_logs",
+    "get_runtime_yaml",
+    "get_sys_proxy",
+    "get_verge_config",
+    "grant_permission",
+    "import_profile",
+    "install_service",
+    "open_app_dir",
+    "open_core_dir",
+    "open_logs_dir",
+    "open_web_url",
+    "patch_clash_config",
+    "patch_profile",
+    "patch_profiles_config",
+    "patch_verge_config",
+    "read_profile_file",
+    "restart_sidecar",
+    "save_profile_file",
+    "test_proxy_delay",
+    "uninstall_service",
+    "update_profile",
+    "view_profile",
+];
+
+/// the sensitive operations, granted by `command_capabilities` in `verge.yaml`
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// install or uninstall the windows service
+    Service,
+    /// register the app to start with the system
+    Autostart,
+    /// set the setuid bit of the core
+    CorePermission,
+    /// run the shell commands of the hooks
+    Hooks,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Service => "service",
+            Capability::Autostart => "autostart",
+            Capability::CorePermission => "core_permission",
+            Capability::Hooks => "hooks",
+        }
+    }
+}
+
+/// check the capability of the command, the result is written to the audit log
+pub fn require(capability: Capability, command: &str) -> Result<()> {
+    let granted = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        verge.command_capabilities.clone().unwrap_or_default()
+    };
+    let capability = capability.as_str();
+
+    if !granted.iter().any(|item| item == capability) {
+        log::warn!(target: "audit", "denied `{command}`, the capability `{capability}` is not granted");
+        bail!("the command requires the capability `{capability}` in the config");
+    }
+    log::info!(target: "audit", "granted `{command}` by the capability `{capability}`");
+    Ok(())
+}
+
+/// the hook commands not in the current ones, which are run by the shell
+pub fn new_hook_commands(current: &[IVergeHook], patch: &[IVergeHook]) -> Vec<String> {
+    patch
+        .iter()
+        .filter_map(|hook| hook.command.clone())
+        .filter(|command| !command.trim().is_empty())
+        .filter(|command| {
+            !current
+                .iter()
+                .any(|hook| hook.command.as_ref() == Some(command))
+        })
+        .collect()
+}
+
+pub fn is_allowed(window: &str, command: &str) -> bool {
+    window == MAIN_WINDOW && WEBVIEW_COMMANDS.binary_search(&command).is_ok()
+}
+
+/// reject the commands out of the allowlist before the handler
+pub fn with_allowlist<R: Runtime>(
+    handler: impl Fn(Invoke<R>) + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        let window = invoke.message.window().label().to_string();
+
+        if !is_allowed(&window, &command) {
+            log::warn!(target: "audit", "rejected `{command}` from the window `{window}`");
+            invoke
+                .resolver
+                .reject(format!("the command `{command}` is not allowed"));
+            return;
+        }
+        handler(invoke)
+    }
+}
+
+#[test]
+fn test_allowlist() {
+    let mut sorted = WEBVIEW_COMMANDS.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted, WEBVIEW_COMMANDS);
+
+    assert!(is_allowed("main", "get_profiles"));
+    assert!(is_allowed("main", "install_service"));
+    assert!(!is_allowed("main", "get_secret"));
+    assert!(!is_allowed("main", "kv_get"));
+    assert!(!is_allowed("popup", "get_profiles"));
+
+    let hook = |command: Option<&str>| IVergeHook {
+        event: "core_started".into(),
+        command: command.map(String::from),
+        ..IVergeHook::default()
+    };
+    let current = vec![hook(Some("notify-send up"))];
+    assert!(new_hook_commands(&current, &[hook(Some("notify-send up")), hook(None)]).is_empty());
+    assert_eq!(
+        new_hook_commands(&current, &[hook(Some("curl x | sh"))]),
+        vec!["curl x | sh"]
+    );
+
+    // the listed commands are registered
+    let main = include_str!("../main.rs");
+    let handler = &main[main.find("generate_handler![").unwrap()..];
+    let registered = handler
+        .lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with(']'))
+        .filter_map(|line| line.strip_prefix("cmds::"))
+        .map(|line| line.rsplit("::").next().unwrap().trim_end_matches(','))
+        .collect::<Vec<_>>();
+    for command in WEBVIEW_COMMANDS {
+        assert!(registered.contains(&command), "{command}");
+    }
+}
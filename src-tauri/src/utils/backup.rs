@@ -0,0 +1,213 @@
+use super::{dirs, help};
+use anyhow::{bail, Context, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// the config file recovered from the broken content
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct RecoveredFile {
+    /// the file name, such as `verge.yaml`
+    pub file: String,
+
+    /// where the broken content is kept
+    pub broken: Option<String>,
+
+    /// false if there's no usable backup and the file is reset to the default
+    pub restored: bool,
+
+    /// the modified time of the restored backup
+    pub backup_time: Option<usize>,
+
+    /// the parse error of the broken file
+    pub error: String,
+}
+
+fn recovered() -> &'static Mutex<Vec<RecoveredFile>> {
+    static RECOVERED: OnceCell<Mutex<Vec<RecoveredFile>>> = OnceCell::new();
+    RECOVERED.get_or_init(|| Mutex::new(vec![]))
+}
+
+/// the files recovered in this run
+pub fn recovered_files() -> Vec<RecoveredFile> {
+    recovered().lock().clone()
+}
+
+/// the last good copy of the file
+pub fn backup_path(path: &Path) -> Result<PathBuf> {
+    let name = match path.file_name() {
+        Some(name) => name,
+        None => bail!("invalid file path \"{}\"", path.display()),
+    };
+    Ok(dirs::app_backup_dir()?.join(name))
+}
+
+/// the truncated or malformed yaml, not the content of the wrong type
+pub fn is_broken(content: &str) -> bool {
+    check_content(content).is_err()
+}
+
+/// the config files are yaml mappings, or the lists of the measurements
+/// the empty one is truncated by a crash, even with the version header left
+pub fn check_content(content: &str) -> Result<()> {
+    if content.trim().is_empty() {
+        bail!("the file is empty");
+    }
+
+    match serde_yaml::from_str::<serde_yaml::Value>(content)? {
+        serde_yaml::Value::Mapping(_) | serde_yaml::Value::Sequence(_) => Ok(()),
+        _ => bail!("the file is not a yaml mapping or list"),
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// whether to keep the previous content on saving
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn set_enabled(enable: bool) {
+    ENABLED.store(enable, Ordering::SeqCst);
+}
+
+/// the config files with the backups and the schema versions,
+/// not the runtime config or the profiles
+pub fn config_paths() -> Result<[PathBuf; 7]> {
+    Ok([
+        dirs::clash_path()?,
+        dirs::verge_path()?,
+        dirs::profiles_path()?,
+        dirs::override_path()?,
+        dirs::measure_path()?,
+        dirs::node_meta_path()?,
+        dirs::measure_batch_path()?,
+    ])
+}
+
+pub fn is_config_file(path: &Path) -> bool {
+    config_paths()
+        .map(|paths| paths.iter().any(|p| p == path))
+        .unwrap_or(false)
+}
+
+/// validate the config files on startup,
+/// keep a copy of the good ones and restore the broken ones
+pub fn check_files() -> Result<()> {
+    for path in config_paths()?.iter().filter(|path| path.exists()) {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        match check_content(&content) {
+            Ok(_) => crate::log_err!(backup_file(path)),
+            Err(err) => {
+                if restore_file(path, err.to_string()).is_err() {
+                    crate::log_err!(reset_file(path, err.to_string()));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// copy the good file to the backup dir
+pub fn backup_file(path: &Path) -> Result<()> {
+    let backup = backup_path(path)?;
+    if let Some(dir) = backup.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::copy(path, &backup)
+        .with_context(|| format!("failed to backup the file \"{}\"", path.display()))?;
+    Ok(())
+}
+
+/// keep the previous content before overwriting,
+/// the broken one is not kept so that the last good backup remains
+pub fn backup_previous(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    match is_broken(&fs::read_to_string(path)?) {
+        true => Ok(()),
+        false => backup_file(path),
+    }
+}
+
+/// replace the broken file with the backup and return the restored content
+pub fn restore_file(path: &Path, error: String) -> Result<String> {
+    let backup = backup_path(path)?;
+    if !backup.exists() {
+        bail!("no backup of the file \"{}\"", path.display());
+    }
+
+    let content = fs::read_to_string(&backup)?;
+    if is_broken(&content) {
+        bail!("the backup of the file \"{}\" is broken", path.display());
+    }
+
+    let backup_time = fs::metadata(&backup)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .map(|time| chrono::DateTime::<chrono::Local>::from(time).timestamp() as usize);
+
+    let broken = move_broken(path)?;
+    help::write_atomic(path, content.as_bytes())
+        .with_context(|| format!("failed to restore the file \"{}\"", path.display()))?;
+
+    log::warn!(target: "app", "the file \"{}\" is broken and restored from the backup, {error}", path.display());
+    record(path, broken, true, backup_time, error);
+    Ok(content)
+}
+
+/// move the broken file away, so that the default one is created
+fn reset_file(path: &Path, error: String) -> Result<()> {
+    let broken = move_broken(path)?;
+
+    log::warn!(target: "app", "the file \"{}\" is broken and reset to the default, {error}", path.display());
+    record(path, broken, false, None, error);
+    Ok(())
+}
+
+/// keep the broken file as `{name}.broken-{timestamp}` in the backup dir
+fn move_broken(path: &Path) -> Result<Option<String>> {
+    let backup = backup_path(path)?;
+    let name = backup.file_name().unwrap_or_default().to_string_lossy();
+    let now = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let broken = backup.with_file_name(format!("{name}.broken-{now}"));
+
+    if let Some(dir) = broken.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::rename(path, &broken)
+        .or_else(|_| fs::copy(path, &broken).and_then(|_| fs::remove_file(path)))
+        .with_context(|| format!("failed to move the broken file \"{}\"", path.display()))?;
+
+    Ok(Some(broken.to_string_lossy().to_string()))
+}
+
+fn record(path: &Path, broken: Option<String>, restored: bool, time: Option<usize>, error: String) {
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    recovered().lock().push(RecoveredFile {
+        file: file.to_string(),
+        broken,
+        restored,
+        backup_time: time,
+        error,
+    });
+}
+
+#[test]
+fn test_is_broken() {
+    assert!(!is_broken("mixed-port: 7890\nmode: rule\n"));
+    assert!(!is_broken("# schema-version: 1\n[]\n"));
+    assert!(is_broken(""));
+    assert!(is_broken(" \n\t\n"));
+    assert!(is_broken("# schema-version: 1\n"));
+    assert!(is_broken("7890"));
+    assert!(is_broken("proxies:\n  - name: \"a\n    type: ss"));
+    assert!(is_broken("mode: rule\n\tport: 1"));
+}
@@ -0,0 +1,174 @@
+use super::{backup, dirs, help};
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// the current schema version of the config files
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// the first comment line of the config files
+const VERSION_PREFIX: &str = "# schema-version: ";
+
+/// upgrade one config file from `version - 1` to `version`
+struct Migration {
+    version: u32,
+
+    /// the config file of the step
+    path: fn() -> Result<PathBuf>,
+
+    /// the description for the log
+    name: &'static str,
+
+    run: fn(&mut Value) -> Result<()>,
+}
+
+/// the ordered steps, append the new ones with the next version
+fn migrations() -> Vec<Migration> {
+    vec![Migration {
+        version: 1,
+        path: dirs::profiles_path,
+        name: "assign the uids to the old profile items",
+        run: assign_profile_uids,
+    }]
+}
+
+/// the header written by `save_yaml`
+pub fn version_header() -> String {
+    format!("{VERSION_PREFIX}{SCHEMA_VERSION}")
+}
+
+/// the files without the header are version 0
+fn read_version(content: &str) -> u32 {
+    content
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .find_map(|line| line.strip_prefix(VERSION_PREFIX))
+        .and_then(|version| version.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// replace the header of the content with the current version
+fn stamp(content: &str) -> String {
+    let content = match content.lines().next() {
+        Some(line) if line.starts_with(VERSION_PREFIX) => {
+            content.split_once('\n').map_or("", |(_, rest)| rest)
+        }
+        _ => content,
+    };
+    format!("{}\n{content}", version_header())
+}
+
+/// run the pending steps on startup, after backing up the old files
+/// the missing files are created by the templates with the current version
+pub fn migrate() -> Result<()> {
+    let mut files = vec![];
+    for path in backup::config_paths()?.into_iter().filter(|p| p.exists()) {
+        let content = fs::read_to_string(&path)?;
+        let version = read_version(&content);
+        if version < SCHEMA_VERSION {
+            files.push((path, content, version));
+        }
+    }
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let backup_dir = backup_before(files.iter().map(|(path, _, _)| path.as_path()))?;
+    log::info!(target: "app", "backup the config files to \"{}\" before migration", backup_dir.display());
+
+    let steps = migrations();
+    for (path, content, version) in files.into_iter() {
+        let mut pending = vec![];
+        for step in steps.iter().filter(|step| step.version > version) {
+            if (step.path)()? == path {
+                pending.push(step);
+            }
+        }
+
+        let content = match pending.is_empty() {
+            true => content,
+            false => {
+                let mut value = serde_yaml::from_str::<Value>(&content)?;
+                for step in pending.iter() {
+                    log::info!(target: "app", "migrate \"{}\" to v{}, {}", path.display(), step.version, step.name);
+                    (step.run)(&mut value).with_context(|| {
+                        format!(
+                            "failed to migrate \"{}\" to v{}",
+                            path.display(),
+                            step.version
+                        )
+                    })?;
+                }
+                serde_yaml::to_string(&value)?
+            }
+        };
+
+        help::write_atomic(&path, stamp(&content).as_bytes())?;
+        log::info!(target: "app", "migrated \"{}\" from v{version} to v{SCHEMA_VERSION}", path.display());
+    }
+
+    Ok(())
+}
+
+/// copy the files to `backup/migration-{timestamp}`
+fn backup_before<'a>(paths: impl Iterator<Item = &'a Path>) -> Result<PathBuf> {
+    let now = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let dir = dirs::app_backup_dir()?.join(format!("migration-{now}"));
+    fs::create_dir_all(&dir)?;
+
+    for path in paths {
+        if let Some(name) = path.file_name() {
+            fs::copy(path, dir.join(name))
+                .with_context(|| format!("failed to backup the file \"{}\"", path.display()))?;
+        }
+    }
+    Ok(dir)
+}
+
+/// v1: the very old profile items have no uid
+fn assign_profile_uids(profiles: &mut Value) -> Result<()> {
+    let items = profiles
+        .get_mut("items")
+        .and_then(|items| items.as_sequence_mut());
+
+    for item in items.into_iter().flatten() {
+        if let Some(item) = item.as_mapping_mut() {
+            if item.get("uid").filter(|uid| !uid.is_null()).is_none() {
+                item.insert("uid".into(), help::get_uid("d").into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_config_migrations() {
+    assert_eq!(read_version("# Hiddify Clash Desktop\n\nmode: rule"), 0);
+    assert_eq!(
+        read_version("# schema-version: 1\n# Hiddify\nmode: rule"),
+        1
+    );
+    assert_eq!(read_version("mode: rule\n# schema-version: 1"), 0);
+
+    let content = stamp("# schema-version: 0\nmode: rule\n");
+    assert_eq!(
+        content,
+        format!("{VERSION_PREFIX}{SCHEMA_VERSION}\nmode: rule\n")
+    );
+    assert_eq!(stamp(&content), content);
+
+    let mut profiles =
+        serde_yaml::from_str::<Value>("items:\n  - name: a\n  - uid: r1\n    name: b").unwrap();
+    assign_profile_uids(&mut profiles).unwrap();
+    let items = profiles.get("items").unwrap().as_sequence().unwrap();
+    assert!(items[0]
+        .get("uid")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .starts_with('d'));
+    assert_eq!(items[1].get("uid").unwrap(), "r1");
+}
@@ -0,0 +1,236 @@
+//! The optional localhost api for the scripts and the launcher extensions
+//! authorized by `Authorization: Bearer <control_api_token>`
+
+use super::secret;
+use crate::config::{Config, IVerge};
+use crate::core::measure::{MeasureInfo, MeasureMode};
+use crate::core::{measure_batch::MeasureBatches, recent::RecentNodes};
+use crate::{feat, log_err};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+const DEFAULT_PORT: u16 = 33332;
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiStatus {
+    pub version: String,
+
+    /// the uid of the current profile
+    pub profile: Option<String>,
+
+    pub profile_name: Option<String>,
+
+    /// `rule`, `global` or `direct`
+    pub mode: Option<String>,
+
+    pub system_proxy: bool,
+
+    pub tun_mode: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SelectProfile {
+    uid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SelectProxy {
+    selector: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Scan {
+    /// all the proxies of the current profile by default
+    names: Option<Vec<String>>,
+}
+
+/// listen on `control_api_port` if `enable_control_api`, applied after restart
+/// the token is generated on the first start
+pub fn control_server() {
+    let (enable, port, token) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.enable_control_api.unwrap_or(false),
+            verge.control_api_port.unwrap_or(DEFAULT_PORT),
+            verge.control_api_token.clone(),
+        )
+    };
+    if !enable {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let token = match token.filter(|token| !token.is_empty()) {
+            Some(token) => token,
+            None => {
+                let token = secret::generate_secret();
+                let tx = Config::verge().begin().await;
+                tx.draft().patch_config(IVerge {
+                    control_api_token: Some(token.clone()),
+                    ..IVerge::default()
+                });
+                log_err!(tx.commit());
+                token
+            }
+        };
+
+        let auth = warp::header::optional::<String>("authorization")
+            .and_then(move |header: Option<String>| {
+                let authorized = is_authorized(header.as_deref(), &token);
+                async move {
+                    match authorized {
+                        true => Ok(()),
+                        false => Err(warp::reject::custom(Unauthorized)),
+                    }
+                }
+            })
+            .untuple_one();
+
+        let status = warp::path!("v1" / "status")
+            .and(warp::get())
+            .map(|| warp::reply::json(&status()).into_response());
+        let profile = warp::path!("v1" / "profile")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(
+                |body: SelectProfile| async move { reply(feat::switch_profile(body.uid).await) },
+            );
+        let proxy = warp::path!("v1" / "proxy")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(|body: SelectProxy| async move {
+                let result = RecentNodes::global().select(&body.selector, &body.name);
+                reply(result.await)
+            });
+        let scan = warp::path!("v1" / "scan")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and_then(|body: Scan| async move { reply(scan(body.names).await) });
+
+        let routes = auth
+            .and(
+                status
+                    .or(profile)
+                    .unify()
+                    .or(proxy)
+                    .unify()
+                    .or(scan)
+                    .unify(),
+            )
+            .recover(recover);
+
+        log::info!(target: "app", "the control api listens on 127.0.0.1:{port}");
+        warp::serve(routes).bind(([127, 0, 0, 1], port)).await;
+    });
+}
+
+fn is_authorized(header: Option<&str>, token: &str) -> bool {
+    let given = match header.and_then(|header| header.strip_prefix("Bearer ")) {
+        Some(given) => given.trim().as_bytes(),
+        None => return false,
+    };
+    // compare all the bytes to not leak the length of the match
+    given.len() == token.len()
+        && given
+            .iter()
+            .zip(token.as_bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+pub fn status() -> ApiStatus {
+    let (system_proxy, tun_mode) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge.enable_system_proxy.unwrap_or(false),
+            verge.enable_tun_mode.unwrap_or(false),
+        )
+    };
+    let (profile, profile_name) = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let current = profiles.get_current();
+        let name = current
+            .as_ref()
+            .and_then(|uid| profiles.get_item(uid).ok())
+            .and_then(|item| item.name.clone());
+        (current, name)
+    };
+    let mode = { Config::clash().latest().0.get("mode").cloned() };
+
+    ApiStatus {
+        version: super::app_state::AppState::global().version().into(),
+        profile,
+        profile_name,
+        mode: mode.and_then(|mode| mode.as_str().map(String::from)),
+        system_proxy,
+        tun_mode,
+    }
+}
+
+/// the latency of the proxies in a new batch
+async fn scan(names: Option<Vec<String>>) -> Result<Vec<MeasureInfo>> {
+    let names = names.unwrap_or_else(runtime_proxies);
+    let batch = MeasureBatches::global()
+        .start(names, MeasureMode::Latency)
+        .await?;
+    Ok(batch.ordered_results())
+}
+
+fn runtime_proxies() -> Vec<String> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+    let proxies = runtime
+        .config
+        .as_ref()
+        .and_then(|config| config.get("proxies"));
+
+    proxies
+        .and_then(|proxies| proxies.as_sequence())
+        .map_or(vec![], |seq| {
+            seq.iter()
+                .filter_map(|item| Some(item.get("name")?.as_str()?.to_string()))
+                .collect()
+        })
+}
+
+/// the result as json, or the error with 400
+fn reply<T: Serialize>(result: Result<T>) -> Result<warp::reply::Response, Rejection> {
+    Ok(match result {
+        Ok(value) => warp::reply::json(&value).into_response(),
+        Err(err) => error_reply(StatusCode::BAD_REQUEST, &err.to_string()),
+    })
+}
+
+fn error_reply(status: StatusCode, message: &str) -> warp::reply::Response {
+    let body = warp::reply::json(&serde_json::json!({ "error": message }));
+    warp::reply::with_status(body, status).into_response()
+}
+
+async fn recover(err: Rejection) -> Result<warp::reply::Response, Infallible> {
+    Ok(match err.find::<Unauthorized>() {
+        Some(_) => error_reply(StatusCode::UNAUTHORIZED, "unauthorized"),
+        None if err.is_not_found() => error_reply(StatusCode::NOT_FOUND, "not found"),
+        None => error_reply(StatusCode::BAD_REQUEST, &format!("{err:?}")),
+    })
+}
+
+#[test]
+fn test_authorized() {
+    assert!(is_authorized(Some("Bearer abc"), "abc"));
+    assert!(!is_authorized(Some("Bearer abd"), "abc"));
+    assert!(!is_authorized(Some("Bearer ab"), "abc"));
+    assert!(!is_authorized(Some("abc"), "abc"));
+    assert!(!is_authorized(None, "abc"));
+}
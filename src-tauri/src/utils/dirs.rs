@@ -1,3 +1,4 @@
+use super::app_state::{AppState, HomeDir};
 use anyhow::Result;
 use std::path::PathBuf;
 use tauri::{
@@ -13,45 +14,76 @@ static APP_DIR: &str = "hiddify-desktop-dev";
 static CLASH_CONFIG: &str = "config.yaml";
 static VERGE_CONFIG: &str = "verge.yaml";
 static PROFILE_YAML: &str = "profiles.yaml";
+static OVERRIDE_YAML: &str = "override.yaml";
+static MEASURE_YAML: &str = "measure.yaml";
+static NODE_META_YAML: &str = "node_meta.yaml";
+static MEASURE_BATCH_YAML: &str = "measure_batch.yaml";
+static MEASURE_USAGE_YAML: &str = "measure_usage.yaml";
+static REMOTE_DEVICES_YAML: &str = "remote_devices.yaml";
+static REMOTE_CERT_PEM: &str = "remote_cert.pem";
+static REMOTE_KEY_PEM: &str = "remote_key.pem";
 
-static mut RESOURCE_DIR: Option<PathBuf> = None;
+/// the portable marker files next to the executable
+static PORTABLE_MARKERS: [&str; 2] = [".portable", ".config/PORTABLE"];
 
-/// portable flag
-#[allow(unused)]
-static mut PORTABLE_FLAG: bool = false;
-
-pub static mut APP_VERSION: &str = "v1.2.0";
-
-/// initialize portable flag
-#[cfg(target_os = "windows")]
-pub unsafe fn init_portable_flag() -> Result<()> {
+/// initialize the portable flag and the data dir
+/// should be called before reading any file
+pub fn init_portable_flag() -> Result<()> {
     use tauri::utils::platform::current_exe;
 
+    let args = std::env::args().collect::<Vec<String>>();
+    if let Some(dir) = parse_data_dir(&args) {
+        let dir = std::env::current_dir()?.join(dir);
+        AppState::global().set_home(HomeDir::Custom(dir));
+        return Ok(());
+    }
+
     let exe = current_exe()?;
 
     if let Some(dir) = exe.parent() {
-        let dir = PathBuf::from(dir).join(".config/PORTABLE");
-
-        if dir.exists() {
-            PORTABLE_FLAG = true;
+        let portable = PORTABLE_MARKERS
+            .iter()
+            .any(|marker| dir.join(marker).exists());
+        if portable {
+            AppState::global().set_home(HomeDir::Portable);
         }
     }
 
     Ok(())
 }
 
+/// support `--data-dir <dir>` and `--data-dir=<dir>`
+fn parse_data_dir(args: &[String]) -> Option<PathBuf> {
+    let index = args
+        .iter()
+        .position(|arg| arg == "--data-dir" || arg.starts_with("--data-dir="))?;
+
+    let dir = match args[index].split_once('=') {
+        Some((_, dir)) => dir,
+        None => args.get(index + 1)?,
+    };
+
+    match dir.trim().is_empty() {
+        true => None,
+        false => Some(PathBuf::from(dir.trim())),
+    }
+}
+
+/// the app home dir in the user home
+pub fn default_home_dir() -> Result<PathBuf> {
+    Ok(home_dir()
+        .ok_or(anyhow::anyhow!("failed to get the app home dir"))?
+        .join(".config")
+        .join(APP_DIR))
+}
+
 /// get the verge app home dir
 pub fn app_home_dir() -> Result<PathBuf> {
-    #[cfg(target_os = "windows")]
-    unsafe {
-        use tauri::utils::platform::current_exe;
-
-        if !PORTABLE_FLAG {
-            Ok(home_dir()
-                .ok_or(anyhow::anyhow!("failed to get app home dir"))?
-                .join(".config")
-                .join(APP_DIR))
-        } else {
+    use tauri::utils::platform::current_exe;
+
+    match AppState::global().home() {
+        HomeDir::Custom(dir) => Ok(dir),
+        HomeDir::Portable => {
             let app_exe = current_exe()?;
             let app_exe = dunce::canonicalize(app_exe)?;
             let app_dir = app_exe
@@ -59,13 +91,8 @@ pub fn app_home_dir() -> Result<PathBuf> {
                 .ok_or(anyhow::anyhow!("failed to get the portable app dir"))?;
             Ok(PathBuf::from(app_dir).join(".config").join(APP_DIR))
         }
+        HomeDir::Default => default_home_dir(),
     }
-
-    #[cfg(not(target_os = "windows"))]
-    Ok(home_dir()
-        .ok_or(anyhow::anyhow!("failed to get the app home dir"))?
-        .join(".config")
-        .join(APP_DIR))
 }
 
 /// get the resources dir
@@ -74,13 +101,9 @@ pub fn app_resources_dir(package_info: &PackageInfo) -> Result<PathBuf> {
         .ok_or(anyhow::anyhow!("failed to get the resource dir"))?
         .join("resources");
 
-    unsafe {
-        RESOURCE_DIR = Some(res_dir.clone());
-
-        let ver = package_info.version.to_string();
-        let ver_str = format!("v{ver}");
-        APP_VERSION = Box::leak(Box::new(ver_str));
-    }
+    let state = AppState::global();
+    state.set_resource_dir(res_dir.clone());
+    state.set_version(format!("v{}", package_info.version));
 
     Ok(res_dir)
 }
@@ -90,11 +113,21 @@ pub fn app_profiles_dir() -> Result<PathBuf> {
     Ok(app_home_dir()?.join("profiles"))
 }
 
+/// the last good copies of the config files
+pub fn app_backup_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("backup"))
+}
+
 /// logs dir
 pub fn app_logs_dir() -> Result<PathBuf> {
     Ok(app_home_dir()?.join("logs"))
 }
 
+/// the database of the key-value store
+pub fn app_kv_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("kv"))
+}
+
 pub fn clash_path() -> Result<PathBuf> {
     Ok(app_home_dir()?.join(CLASH_CONFIG))
 }
@@ -107,32 +140,51 @@ pub fn profiles_path() -> Result<PathBuf> {
     Ok(app_home_dir()?.join(PROFILE_YAML))
 }
 
-#[allow(unused)]
+pub fn override_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(OVERRIDE_YAML))
+}
+
+pub fn measure_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(MEASURE_YAML))
+}
+
+pub fn node_meta_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(NODE_META_YAML))
+}
+
+pub fn measure_batch_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(MEASURE_BATCH_YAML))
+}
+
+pub fn measure_usage_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(MEASURE_USAGE_YAML))
+}
+
+pub fn remote_devices_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(REMOTE_DEVICES_YAML))
+}
+
+pub fn remote_cert_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(REMOTE_CERT_PEM))
+}
+
+pub fn remote_key_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(REMOTE_KEY_PEM))
+}
+
 pub fn app_res_dir() -> Result<PathBuf> {
-    unsafe {
-        Ok(RESOURCE_DIR
-            .clone()
-            .ok_or(anyhow::anyhow!("failed to get the resource dir"))?)
-    }
+    AppState::global()
+        .resource_dir()
+        .ok_or(anyhow::anyhow!("failed to get the resource dir"))
 }
 
 pub fn clash_pid_path() -> Result<PathBuf> {
-    unsafe {
-        Ok(RESOURCE_DIR
-            .clone()
-            .ok_or(anyhow::anyhow!("failed to get the resource dir"))?
-            .join("clash.pid"))
-    }
+    Ok(app_res_dir()?.join("clash.pid"))
 }
 
 #[cfg(windows)]
 pub fn service_path() -> Result<PathBuf> {
-    unsafe {
-        let res_dir = RESOURCE_DIR
-            .clone()
-            .ok_or(anyhow::anyhow!("failed to get the resource dir"))?;
-        Ok(res_dir.join("hiddify-desktop-service.exe"))
-    }
+    Ok(app_res_dir()?.join("hiddify-desktop-service.exe"))
 }
 
 #[cfg(windows)]
@@ -150,6 +202,47 @@ pub fn service_log_file() -> Result<PathBuf> {
     Ok(log_file)
 }
 
+/// move the data in the default home dir to the portable one
+/// the files in the portable dir are overwritten
+/// refused with `--data-dir`, the restart could come up in another home dir
+pub fn migrate_home_dir() -> Result<()> {
+    if AppState::global().home() != HomeDir::Portable {
+        anyhow::bail!("the app is not in the portable mode");
+    }
+
+    let from = default_home_dir()?;
+    let to = app_home_dir()?;
+
+    if from == to {
+        anyhow::bail!("the portable dir is the default one");
+    }
+    if !from.exists() {
+        anyhow::bail!("no data found in \"{}\"", from.display());
+    }
+
+    copy_dir(&from, &to)?;
+    std::fs::remove_dir_all(&from)?;
+    Ok(())
+}
+
+fn copy_dir(from: &PathBuf, to: &PathBuf) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        match entry.file_type()?.is_dir() {
+            true => copy_dir(&entry.path(), &target)?,
+            false => {
+                std::fs::copy(entry.path(), target)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub fn path_to_str(path: &PathBuf) -> Result<&str> {
     let path_str = path
         .as_os_str()
@@ -157,3 +250,20 @@ pub fn path_to_str(path: &PathBuf) -> Result<&str> {
         .ok_or(anyhow::anyhow!("failed to get path from {:?}", path))?;
     Ok(path_str)
 }
+
+#[test]
+fn test_parse_data_dir() {
+    let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+
+    assert_eq!(
+        parse_data_dir(&args(&["app", "--data-dir", "/tmp/data"])),
+        Some(PathBuf::from("/tmp/data"))
+    );
+    assert_eq!(
+        parse_data_dir(&args(&["app", "--data-dir=data"])),
+        Some(PathBuf::from("data"))
+    );
+    assert_eq!(parse_data_dir(&args(&["app", "--data-dir"])), None);
+    assert_eq!(parse_data_dir(&args(&["app", "--data-dir="])), None);
+    assert_eq!(parse_data_dir(&args(&["app", "--autostart"])), None);
+}
@@ -0,0 +1,204 @@
+//! The user-visible errors of the commands
+
+use crate::config::ProfileUrlError;
+use crate::core::preflight::PreflightReport;
+use serde::Serialize;
+
+/// the known failures, translated by the frontend with the message key
+/// serialized as `PORT_IN_USE` and so on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    PortInUse,
+    CoreNotRunning,
+    ProfileNotFound,
+    ProfileInvalid,
+    ProfileFetchFailed,
+    ProfileUrlInvalid,
+    NetworkTimeout,
+    NetworkFailed,
+    PermissionDenied,
+    InvalidYaml,
+    PreflightFailed,
+    Unknown,
+}
+
+/// the prefixes of the messages bailed in the app
+const MESSAGE_CODES: [(&str, ErrorCode); 7] = [
+    ("port already in use", ErrorCode::PortInUse),
+    ("failed to get the profile item", ErrorCode::ProfileNotFound),
+    (
+        "failed to find the profile item",
+        ErrorCode::ProfileNotFound,
+    ),
+    (
+        "failed to find the current profile",
+        ErrorCode::ProfileNotFound,
+    ),
+    ("profile does not contain", ErrorCode::ProfileInvalid),
+    (
+        "failed to fetch remote profile",
+        ErrorCode::ProfileFetchFailed,
+    ),
+    (
+        "the command requires the capability",
+        ErrorCode::PermissionDenied,
+    ),
+];
+
+impl ErrorCode {
+    /// the key in `src/locales`
+    pub fn message_key(&self) -> Option<&'static str> {
+        match self {
+            Self::PortInUse => Some("error_port_in_use"),
+            Self::CoreNotRunning => Some("error_core_not_running"),
+            Self::ProfileNotFound => Some("error_profile_not_found"),
+            Self::ProfileInvalid => Some("error_profile_invalid"),
+            Self::ProfileFetchFailed => Some("error_profile_fetch_failed"),
+            Self::ProfileUrlInvalid => Some("error_profile_url_invalid"),
+            Self::NetworkTimeout => Some("error_network_timeout"),
+            Self::NetworkFailed => Some("error_network_failed"),
+            Self::PermissionDenied => Some("error_permission_denied"),
+            Self::InvalidYaml => Some("error_invalid_yaml"),
+            Self::PreflightFailed => Some("error_preflight_failed"),
+            Self::Unknown => None,
+        }
+    }
+
+    /// classify the error by the type of the source or the message
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+                // the local controller of the core refuses the connection
+                let host = err.url().and_then(|url| url.host_str());
+                let local = matches!(host, Some("127.0.0.1" | "localhost"));
+
+                if err.is_timeout() {
+                    return Self::NetworkTimeout;
+                }
+                if err.is_connect() && local {
+                    return Self::CoreNotRunning;
+                }
+                if err.is_connect() || err.is_request() {
+                    return Self::NetworkFailed;
+                }
+            }
+            if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+                if err.kind() == std::io::ErrorKind::PermissionDenied {
+                    return Self::PermissionDenied;
+                }
+            }
+            if cause.downcast_ref::<ProfileUrlError>().is_some() {
+                return Self::ProfileUrlInvalid;
+            }
+            if cause.downcast_ref::<PreflightReport>().is_some() {
+                return Self::PreflightFailed;
+            }
+            if cause.downcast_ref::<serde_yaml::Error>().is_some() {
+                return Self::InvalidYaml;
+            }
+        }
+
+        err.chain()
+            .find_map(|cause| {
+                let message = cause.to_string();
+                MESSAGE_CODES
+                    .iter()
+                    .find(|(prefix, _)| message.starts_with(prefix))
+                    .map(|(_, code)| *code)
+            })
+            .unwrap_or(Self::Unknown)
+    }
+}
+
+/// the error of the commands
+#[derive(Debug, Clone, Serialize)]
+pub struct CmdError {
+    pub code: ErrorCode,
+
+    /// the message key of the known error, or the raw message
+    pub message: String,
+
+    /// the whole chain of the error
+    pub detail: String,
+
+    /// the failed checks of the refused profile switch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<PreflightReport>,
+}
+
+impl From<anyhow::Error> for CmdError {
+    fn from(err: anyhow::Error) -> Self {
+        let code = ErrorCode::from_error(&err);
+        let message = match code.message_key() {
+            Some(key) => key.into(),
+            None => err.to_string(),
+        };
+
+        let report = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<PreflightReport>())
+            .cloned();
+
+        Self {
+            code,
+            message,
+            detail: format!("{err:#}"),
+            report,
+        }
+    }
+}
+
+/// the string literal error, such as `ret_err!`
+impl From<&str> for CmdError {
+    fn from(message: &str) -> Self {
+        Self {
+            code: ErrorCode::Unknown,
+            message: message.into(),
+            detail: message.into(),
+            report: None,
+        }
+    }
+}
+
+#[test]
+fn test_error_code() {
+    use anyhow::{anyhow, Context};
+
+    let err = anyhow!("port already in use");
+    let cmd_err = CmdError::from(err);
+    assert_eq!(cmd_err.code, ErrorCode::PortInUse);
+    assert_eq!(cmd_err.message, "error_port_in_use");
+
+    let err = anyhow!("failed to get the profile item \"uid:abc\"");
+    let err = err.context("failed to update the profile");
+    assert_eq!(ErrorCode::from_error(&err), ErrorCode::ProfileNotFound);
+    assert_eq!(
+        CmdError::from(err).detail,
+        "failed to update the profile: failed to get the profile item \"uid:abc\""
+    );
+
+    let err = serde_yaml::from_str::<serde_yaml::Mapping>("a: [")
+        .context("failed to read the file")
+        .unwrap_err();
+    assert_eq!(ErrorCode::from_error(&err), ErrorCode::InvalidYaml);
+
+    let err = anyhow!("invalid hotkey `x`");
+    let cmd_err = CmdError::from(err);
+    assert_eq!(cmd_err.code, ErrorCode::Unknown);
+    assert_eq!(cmd_err.message, "invalid hotkey `x`");
+
+    let cmd_err = serde_json::to_value(CmdError::from("the file not found")).unwrap();
+    assert_eq!(cmd_err["code"], "UNKNOWN");
+    assert!(cmd_err.get("report").is_none());
+
+    let report = PreflightReport {
+        uid: "uid".into(),
+        passed: false,
+        results: vec![],
+        core_issues: vec![],
+    };
+    let cmd_err = CmdError::from(anyhow::Error::from(report));
+    assert_eq!(cmd_err.code, ErrorCode::PreflightFailed);
+    assert_eq!(cmd_err.report.map(|r| r.uid), Some("uid".into()));
+}
@@ -2,13 +2,16 @@ use anyhow::{anyhow, bail, Context, Result};
 use nanoid::nanoid;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_yaml::{Mapping, Value};
+use std::io::Write;
+use std::path::Path;
 use std::{fs, path::PathBuf, process::Command, str::FromStr, thread};
 use tauri::{AppHandle, api};
 use std::time::Duration;
-use crate::{utils::resolve, cmds};
+use crate::utils::{app_state::AppState, backup, config_migrations, resolve};
 
 
 /// read data from yaml as struct T
+/// the broken file is restored from the last good backup
 pub fn read_yaml<T: DeserializeOwned>(path: &PathBuf) -> Result<T> {
     if !path.exists() {
         bail!("file not found \"{}\"", path.display());
@@ -17,6 +20,12 @@ pub fn read_yaml<T: DeserializeOwned>(path: &PathBuf) -> Result<T> {
     let yaml_str = fs::read_to_string(&path)
         .with_context(|| format!("failed to read the file \"{}\"", path.display()))?;
 
+    // the empty file truncated by a crash parses as null, so it is checked first
+    let yaml_str = match backup::check_content(&yaml_str) {
+        Ok(_) => yaml_str,
+        Err(err) => backup::restore_file(path, err.to_string()).unwrap_or(yaml_str),
+    };
+
     serde_yaml::from_str::<T>(&yaml_str).with_context(|| {
         format!(
             "failed to read the file with yaml format \"{}\"",
@@ -50,11 +59,46 @@ pub fn save_yaml<T: Serialize>(path: &PathBuf, data: &T, prefix: Option<&str>) -
         None => data_str,
     };
 
+    let yaml_str = match backup::is_config_file(path) {
+        true => format!("{}\n{yaml_str}", config_migrations::version_header()),
+        false => yaml_str,
+    };
+
+    if backup::is_enabled() && backup::is_config_file(path) {
+        crate::log_err!(backup::backup_previous(path));
+    }
+
     let path_str = path.as_os_str().to_string_lossy().to_string();
-    fs::write(path, yaml_str.as_bytes())
+    write_atomic(path, yaml_str.as_bytes())
         .with_context(|| format!("failed to save file \"{path_str}\""))
 }
 
+/// write to a temp file in the same dir and rename it,
+/// so that a crash never leaves a half written file
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => bail!("invalid file path \"{}\"", path.display()),
+    };
+    let tmp = path.with_file_name(format!(".{name}.tmp"));
+
+    let written = fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err.into());
+    }
+
+    // persist the rename itself
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let _ = fs::File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
 const ALPHABET: [char; 62] = [
     '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i',
     'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B',
@@ -83,6 +127,48 @@ pub fn parse_str<T: FromStr>(target: &str, key: &str) -> Option<T> {
     })
 }
 
+/// decode the `%XX` of the url, the invalid escapes are kept as they are
+pub fn percent_decode(data: &str) -> String {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| {
+            let hex = std::str::from_utf8(hex).ok()?;
+            u8::from_str_radix(hex, 16).ok()
+        });
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into()
+}
+
+/// get the sorted names of the running processes
+pub fn list_process_names() -> Vec<String> {
+    use sysinfo::{ProcessExt, System, SystemExt};
+
+    let mut system = System::new();
+    system.refresh_processes();
+
+    let mut names = system
+        .processes()
+        .values()
+        .map(|proc| proc.name().to_string())
+        .collect::<Vec<String>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
 /// open file
 /// use vscode by default
 pub fn open_file(path: PathBuf) -> Result<()> {
@@ -101,88 +187,17 @@ pub fn open_file(path: PathBuf) -> Result<()> {
     Ok(())
 }
 
-#[derive(Debug)]
-pub enum ExtractDeeplinkError{
-    InvalidInput
-}
-// pub fn extract_url_and_profile_name_from_deep_link(deep_link:&String) -> Result<(String,String),ExtractDeeplinkError>{
-//     // Sample: clash://install-config?url=https://mysite.com/all.yml&name=profilename
-//     let (url,profile) = {
-//         let pruned = deep_link.split("url=").collect::<Vec<_>>();
-//         if pruned.len() < 2 {
-//             return Err(ExtractDeeplinkError::InvalidInput)
-//         }
-//         let url_and_profile_name = pruned[1].split("&").collect::<Vec<_>>();
-//         if url_and_profile_name.len() < 2{
-//             return Err(ExtractDeeplinkError::InvalidInput)
-//         }
-//         let url = url_and_profile_name[0].to_string();
-//         let profile_name = {
-//             let splitted: Vec<_> = url_and_profile_name[1].split("=").collect();
-//             if splitted.len() < 2 {
-//                 return Err(ExtractDeeplinkError::InvalidInput)
-//             }
-//             splitted[1].to_string()
-//         };
-
-//         (url,profile_name)
-//     };
-
-//     return Ok((url,profile));
-// }
-
-pub fn convert_deeplink_to_url_for_import_profile(deep_link:&String) -> Result<String,ExtractDeeplinkError>{
-    // Sample: clash://install-config?url=https://mysite.com/all.yml&name=profilename
-    let import_profile_url_raw = {
-        let url_part:Vec<_> = deep_link.split("url=").collect();
-        if url_part.len() < 2{
-            return Err(ExtractDeeplinkError::InvalidInput)
-        }
-        url_part
-    };
-
-    // Convert url to something that import_profile functin can use
-    let import_profile_url = import_profile_url_raw[1].replace(".yml&", ".yml?");
-    Ok(import_profile_url)
-}
-
-// Focus to the main window, and back the NEED_WINDOW_BE_FOCUS to false, and wait for NEED_WINDOW_BE_FOCUS be true to do its job
+// Focus to the main window on each request of `set_focus`
 pub fn focus_to_main_window_if_needed(app_handle:&AppHandle){
     loop{
-        unsafe{
-            if *crate::NEED_WINDOW_BE_FOCUS.lock().unwrap() == true{
-                // Show main window is exist, otherwise create main window and show it
-                resolve::create_window(app_handle);
-                *crate::NEED_WINDOW_BE_FOCUS.lock().unwrap() = false;
-            }
+        if AppState::global().take_focus_request(){
+            // Show main window is exist, otherwise create main window and show it
+            resolve::create_window(app_handle);
         }
         thread::sleep(Duration::from_millis(1400));
     }
 }
 
-pub async fn select_last_profile() -> Result<(),()>{
-    match cmds::get_profiles(){
-        Ok(mut prf_config) => {
-            match prf_config.get_items(){
-                Some(profiles) => {
-                    match profiles.last(){
-                        Some(last_prf) => {
-                            prf_config.current = last_prf.uid.clone();
-
-                            if let Err(_) = cmds::patch_profiles_config(prf_config).await{
-                                return Err(())
-                            }
-                            return Ok(())
-                        },
-                        None => return Err(())
-                    }
-                },
-                None => return Err(())
-            }
-        },
-        Err(_) => return Err(())
-    }
-}
 pub fn user_has_admin_right() -> Result<bool,String>{
     //TODO: @sarina find a way to check admin right in mac os
     #[cfg(target_os = "macos")]
@@ -230,9 +245,7 @@ pub fn user_has_admin_right() -> Result<bool,String>{
 }
 
 pub fn set_focus(){
-    unsafe{
-        *crate::NEED_WINDOW_BE_FOCUS.lock().unwrap() = true;
-    }
+    AppState::global().request_focus();
 }
 
 // Kills program and its children
@@ -267,7 +280,7 @@ macro_rules! log_err {
 }
 
 /// wrap the anyhow error
-/// transform the error to String
+/// transform the error to `CmdError`
 #[macro_export]
 macro_rules! wrap_err {
     ($stat: expr) => {
@@ -275,7 +288,8 @@ macro_rules! wrap_err {
             Ok(a) => Ok(a),
             Err(err) => {
                 log::error!(target: "app", "{}", err.to_string());
-                Err(format!("{}", err.to_string()))
+                let err = anyhow::Error::from(err);
+                Err($crate::utils::error::CmdError::from(err))
             }
         }
     };
@@ -308,20 +322,3 @@ fn test_parse_value() {
     assert_eq!(parse_str::<usize>(test_1, "expire1="), None);
     assert_eq!(parse_str::<usize>(test_2, "attachment="), None);
 }
-//#[test]
-// fn test_extract_url_and_profile_name_from_deep_link(){
-//     let s = "clash://install-config?url=https://mysite.com/all.yml&name=profilename";
-//     let (url,prof_name) = extract_url_and_profile_name_from_deep_link(&s.to_string()).unwrap();
-//     assert_eq!(url,"https://mysite.com/all.yml");
-//     assert_eq!(prof_name,"profilename");
-// }
-#[test]
-fn test_convert_deeplink_to_url_for_import_profile(){
-    let s = "clashy://install-config?url=https://antyfilter.aeycia.cl/80467cf865c2ef1af111716ddf30dd29/80467cf865c2ef1af111716ddf30dd29/clash/all.yml&name=all_antyfilter.aeycia.cl";
-    let res = convert_deeplink_to_url_for_import_profile(&s.to_string());
-    if res.is_err(){
-        println!("Test failed: {:?}",res.err().unwrap())
-    }else{
-        panic!("Test successfully completed")
-    }
-}
@@ -1,5 +1,5 @@
 use crate::config::*;
-use crate::utils::{dirs, help};
+use crate::utils::{backup, config_migrations, dirs, help};
 use anyhow::Result;
 use chrono::Local;
 use log::LevelFilter;
@@ -8,6 +8,7 @@ use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Config, Logger, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use std::fs;
+use std::path::Path;
 use tauri::PackageInfo;
 
 /// initialize this instance's log file
@@ -29,7 +30,14 @@ fn init_log() -> Result<()> {
     let encode = Box::new(PatternEncoder::new(time_format));
 
     let stdout = ConsoleAppender::builder().encoder(encode.clone()).build();
-    let tofile = FileAppender::builder().encoder(encode).build(log_file)?;
+    let tofile = FileAppender::builder()
+        .encoder(encode.clone())
+        .build(log_file)?;
+    // kept across the restarts
+    let audit = FileAppender::builder()
+        .encoder(encode)
+        .append(true)
+        .build(log_dir.join("audit.log"))?;
 
     #[cfg(feature = "verge-dev")]
     let level = LevelFilter::Debug;
@@ -39,12 +47,19 @@ fn init_log() -> Result<()> {
     let config = Config::builder()
         .appender(Appender::builder().build("stdout", Box::new(stdout)))
         .appender(Appender::builder().build("file", Box::new(tofile)))
+        .appender(Appender::builder().build("audit", Box::new(audit)))
         .logger(
             Logger::builder()
                 .appenders(["file", "stdout"])
                 .additive(false)
                 .build("app", level),
         )
+        .logger(
+            Logger::builder()
+                .appenders(["audit"])
+                .additive(false)
+                .build("audit", LevelFilter::Info),
+        )
         .build(Root::builder().appender("stdout").build(LevelFilter::Info))?;
 
     log4rs::init_config(config)?;
@@ -54,11 +69,6 @@ fn init_log() -> Result<()> {
 
 /// Initialize all the files from resources
 pub fn init_config() -> Result<()> {
-    #[cfg(target_os = "windows")]
-    unsafe {
-        let _ = dirs::init_portable_flag();
-    }
-
     let _ = init_log();
 
     crate::log_err!(dirs::app_home_dir().map(|app_dir| {
@@ -73,9 +83,13 @@ pub fn init_config() -> Result<()> {
         }
     }));
 
+    // restore the broken files before creating the missing ones
+    crate::log_err!(backup::check_files());
+    crate::log_err!(config_migrations::migrate());
+
     crate::log_err!(dirs::clash_path().map(|path| {
         if !path.exists() {
-            help::save_yaml(&path, &IClashTemp::template().0, Some("# Hiddify Clash Desktop"))?;
+            IClashTemp::template().save_config()?;
         }
         <Result<()>>::Ok(())
     }));
@@ -94,6 +108,13 @@ pub fn init_config() -> Result<()> {
         <Result<()>>::Ok(())
     }));
 
+    crate::log_err!(dirs::override_path().map(|path| {
+        if !path.exists() {
+            help::save_yaml(&path, &IOverride::template().0, Some("# Hiddify Clash Desktop"))?;
+        }
+        <Result<()>>::Ok(())
+    }));
+
     Ok(())
 }
 
@@ -110,13 +131,25 @@ pub fn init_resources(package_info: &PackageInfo) -> Result<()> {
     }
 
     // copy the resource file
+    // the geo databases are large, so the unchanged ones are not copied again
     for file in ["Country.mmdb", "geoip.dat", "geosite.dat", "wintun.dll"].iter() {
         let src_path = res_dir.join(file);
         let target_path = app_dir.join(file);
-        if src_path.exists() {
+        if src_path.exists() && is_outdated(&src_path, &target_path) {
             let _ = fs::copy(src_path, target_path);
         }
     }
 
     Ok(())
 }
+
+/// the target is missing, or differs from the source by the size or the modified time
+fn is_outdated(src_path: &Path, target_path: &Path) -> bool {
+    match (fs::metadata(src_path), fs::metadata(target_path)) {
+        (Ok(src), Ok(target)) => {
+            src.len() != target.len()
+                || matches!((src.modified(), target.modified()), (Ok(s), Ok(t)) if s > t)
+        }
+        _ => true,
+    }
+}
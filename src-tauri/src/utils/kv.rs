@@ -0,0 +1,121 @@
+use super::dirs;
+use anyhow::{bail, Context, Result};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
+
+/// the most bytes of a value, the store is not for the large data
+const MAX_VALUE_SIZE: usize = 1024 * 1024;
+
+const MAX_KEY_SIZE: usize = 256;
+
+const MAX_NAMESPACE_SIZE: usize = 32;
+
+/// the small data of the features, such as the favorites, the collapsed groups
+/// or the window geometry, saved in `kv/` by the namespace
+/// the values are json, so that the webview and the rust side read the same data
+pub struct KvStore {
+    /// opened on the first use
+    db: Arc<Mutex<Option<sled::Db>>>,
+}
+
+impl KvStore {
+    pub fn global() -> &'static KvStore {
+        static STORE: OnceCell<KvStore> = OnceCell::new();
+
+        STORE.get_or_init(|| KvStore {
+            db: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn tree(&self, namespace: &str) -> Result<sled::Tree> {
+        check_namespace(namespace)?;
+
+        let db = {
+            let mut db = self.db.lock();
+            match db.as_ref() {
+                Some(db) => db.clone(),
+                None => {
+                    let opened = sled::open(dirs::app_kv_dir()?)
+                        .context("failed to open the key-value store")?;
+                    *db = Some(opened.clone());
+                    opened
+                }
+            }
+        };
+        Ok(db.open_tree(namespace)?)
+    }
+
+    /// none if the key is not set
+    pub fn get<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Result<Option<T>> {
+        check_key(key)?;
+
+        match self.tree(namespace)?.get(key)? {
+            Some(value) => {
+                let value = serde_json::from_slice(&value)
+                    .with_context(|| format!("invalid value of \"{namespace}/{key}\""))?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn set<T: Serialize>(&self, namespace: &str, key: &str, value: &T) -> Result<()> {
+        check_key(key)?;
+
+        let value = serde_json::to_vec(value)?;
+        if value.len() > MAX_VALUE_SIZE {
+            bail!("the value of \"{namespace}/{key}\" is larger than {MAX_VALUE_SIZE} bytes");
+        }
+
+        let tree = self.tree(namespace)?;
+        tree.insert(key, value)?;
+        tree.flush()?;
+        Ok(())
+    }
+
+    /// false if the key is not set
+    pub fn delete(&self, namespace: &str, key: &str) -> Result<bool> {
+        check_key(key)?;
+
+        let tree = self.tree(namespace)?;
+        let removed = tree.remove(key)?;
+        tree.flush()?;
+        Ok(removed.is_some())
+    }
+}
+
+/// such as `ui` or `proxy-groups`
+fn check_namespace(namespace: &str) -> Result<()> {
+    let valid = !namespace.is_empty()
+        && namespace.len() <= MAX_NAMESPACE_SIZE
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        bail!("invalid namespace \"{namespace}\"");
+    }
+    Ok(())
+}
+
+fn check_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > MAX_KEY_SIZE {
+        bail!("invalid key \"{key}\"");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_kv_names() {
+    assert!(check_namespace("ui").is_ok());
+    assert!(check_namespace("proxy-groups_2").is_ok());
+    assert!(check_namespace("").is_err());
+    assert!(check_namespace("UI").is_err());
+    assert!(check_namespace("../profiles").is_err());
+    assert!(check_namespace(&"a".repeat(33)).is_err());
+
+    assert!(check_key("window geometry").is_ok());
+    assert!(check_key("").is_err());
+    assert!(check_key(&"k".repeat(257)).is_err());
+}
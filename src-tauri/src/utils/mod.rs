@@ -1,7 +1,16 @@
+pub mod app_state;
+pub mod authz;
+pub mod backup;
+pub mod config_migrations;
+pub mod control_api;
 pub mod dirs;
+pub mod error;
 pub mod help;
 pub mod init;
+pub mod kv;
 pub mod resolve;
+pub mod secret;
 pub mod server;
+pub mod startup;
 pub mod tmpl;
 // mod winhelp;
@@ -1,43 +1,117 @@
 use crate::config::Config;
 use crate::log_err;
-use crate::{core::*, utils::init, utils::server};
+use crate::{
+    core::*, utils::backup, utils::control_api, utils::init, utils::server, utils::startup,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+use std::{thread, time::Duration};
 use tauri::{App, AppHandle, Manager};
 
 /// handle something when start app
+/// the steps are timed, see `startup::startup_report`
 pub fn resolve_setup(app: &mut App) {
+    startup::begin();
+
     #[cfg(target_os = "macos")]
     app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
     handle::Handle::global().init(app.app_handle());
+    crate::config::on_commit(state_events::config_committed);
+
+    startup::span("resources", || {
+        log_err!(init::init_resources(app.package_info()))
+    });
 
-    log_err!(init::init_resources(app.package_info()));
+    // a pause ended while the app was closed is restored before the sysproxy and the core
+    log_err!(pause::ProxyPause::global().init());
 
     // 启动核心
-    log_err!(Config::init_config());
-    log_err!(CoreManager::global().init());
+    startup::span("config", || log_err!(Config::init_config()));
+    let config_backup = { Config::verge().latest().enable_config_backup };
+    backup::set_enabled(config_backup.unwrap_or(true));
+    startup::span("core", || log_err!(CoreManager::global().init()));
 
     // setup a simple http server for singleton
-    server::embed_server(app.app_handle());
-
-    log_err!(tray::Tray::update_systray(&app.app_handle()));
-
-    let silent_start = { Config::verge().data().enable_silent_start.clone() };
-    if !silent_start.unwrap_or(false) {
-        create_window(&app.app_handle());
+    startup::span("server", || {
+        server::embed_server(app.app_handle());
+        control_api::control_server();
+    });
+
+    startup::span("tray", || {
+        log_err!(tray::Tray::update_systray(&app.app_handle()))
+    });
+
+    let silent_start = { Config::verge().latest().enable_silent_start.clone() };
+    if !silent_start.unwrap_or(false) && !is_lite_mode() {
+        startup::span("window", || create_window(&app.app_handle()));
     }
 
-    log_err!(sysopt::Sysopt::global().init_launch());
-    log_err!(sysopt::Sysopt::global().init_sysproxy());
+    startup::span("sysopt", || {
+        log_err!(sysopt::Sysopt::global().init_launch());
+        log_err!(sysopt::Sysopt::global().init_sysproxy());
+    });
 
     log_err!(handle::Handle::update_systray_part());
-    log_err!(hotkey::Hotkey::global().init(app.app_handle()));
-    log_err!(timer::Timer::global().init());
+    startup::span("hotkey", || {
+        log_err!(hotkey::Hotkey::global().init(app.app_handle()))
+    });
+    startup::span("timer", || log_err!(timer::Timer::global().init()));
+    log_err!(schedule::Schedule::global().init());
+    log_err!(expiry::ExpiryReminder::global().init());
+    log_err!(clipboard::ClipboardWatcher::global().init());
+    log_err!(usage::ResourceMonitor::global().init());
+    log_err!(remote_control::RemoteControl::global().init());
+
+    startup::finish();
+
+    // loaded on the first use anyway, read them early so the first page does not wait
+    startup::defer("measurement history", || {
+        let current = { Config::profiles().latest().get_current() };
+        if let Some(profile) = current {
+            measure_cache::MeasureCache::global().get(&profile);
+        }
+        measure_batch::MeasureBatches::global().get_all();
+    });
+}
+
+/// the window is not created on startup and not kept after closing
+pub fn is_lite_mode() -> bool {
+    let lite_mode = { Config::verge().latest().enable_lite_mode };
+    lite_mode.unwrap_or(false)
+}
+
+/// the max time to wait for the shutdown sequence
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+/// whether the app is exiting
+pub fn is_exiting() -> bool {
+    EXITING.load(Ordering::SeqCst)
 }
 
-/// reset system proxy
+/// the shutdown sequence, only run once
+/// reset the system proxy before stopping the core,
+/// so that it never points to a dead port even if the core hangs
 pub fn resolve_reset() {
-    log_err!(sysopt::Sysopt::global().reset_sysproxy());
-    log_err!(CoreManager::global().stop_core());
+    if EXITING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        measure_queue::MeasureQueue::global().cancel();
+        log_err!(sysopt::Sysopt::global().reset_sysproxy());
+        log_err!(CoreManager::global().stop_core());
+        let _ = tx.send(());
+    });
+
+    if rx.recv_timeout(SHUTDOWN_TIMEOUT).is_err() {
+        log::error!(target: "app", "the shutdown sequence timed out");
+    }
 }
 
 /// create main window
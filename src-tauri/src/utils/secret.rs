@@ -0,0 +1,38 @@
+//! The secret of the external-controller kept in the os keyring
+
+use anyhow::Result;
+use keyring::Entry;
+use nanoid::nanoid;
+
+const SERVICE: &str = "hiddify-desktop";
+const USER: &str = "clash-secret";
+
+/// none if not stored or the keyring is unavailable
+pub fn get_secret() -> Option<String> {
+    let entry = Entry::new(SERVICE, USER).ok()?;
+    entry
+        .get_password()
+        .ok()
+        .filter(|secret| !secret.is_empty())
+}
+
+/// skip if the same one is stored
+pub fn set_secret(secret: &str) -> Result<()> {
+    if get_secret().as_deref() == Some(secret) {
+        return Ok(());
+    }
+    Entry::new(SERVICE, USER)?.set_password(secret)?;
+    Ok(())
+}
+
+pub fn delete_secret() -> Result<()> {
+    match Entry::new(SERVICE, USER)?.delete_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// the random secret for the first run
+pub fn generate_secret() -> String {
+    nanoid!(32)
+}
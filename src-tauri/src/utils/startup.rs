@@ -0,0 +1,99 @@
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{thread, time::Instant};
+
+/// the time of a step of the startup
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct StartupSpan {
+    pub name: String,
+
+    /// since the startup (ms)
+    pub start: u64,
+
+    pub duration: u64,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct StartupReport {
+    /// the steps blocking the setup, in order
+    pub spans: Vec<StartupSpan>,
+
+    /// the time of the setup (ms)
+    pub total: u64,
+
+    /// the steps run in the background after the setup
+    pub deferred: Vec<StartupSpan>,
+}
+
+struct Startup {
+    begin: Instant,
+    report: StartupReport,
+}
+
+fn startup() -> &'static Mutex<Startup> {
+    static STARTUP: OnceCell<Mutex<Startup>> = OnceCell::new();
+    STARTUP.get_or_init(|| {
+        Mutex::new(Startup {
+            begin: Instant::now(),
+            report: StartupReport::default(),
+        })
+    })
+}
+
+/// the startup is timed from the first call
+pub fn begin() {
+    startup();
+}
+
+/// run the step of the setup and record the time
+pub fn span<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let ret = f();
+    let span = new_span(name, start);
+    startup().lock().report.spans.push(span);
+    ret
+}
+
+/// run the step in the background and record the time
+pub fn defer(name: &'static str, f: impl FnOnce() + Send + 'static) {
+    thread::spawn(move || {
+        let start = Instant::now();
+        f();
+        let span = new_span(name, start);
+        log::debug!(target: "app", "the deferred startup step \"{name}\" took {}ms", span.duration);
+        startup().lock().report.deferred.push(span);
+    });
+}
+
+/// the setup is done
+pub fn finish() {
+    let mut startup = startup().lock();
+    startup.report.total = startup.begin.elapsed().as_millis() as u64;
+
+    let report = &startup.report;
+    let slowest = report.spans.iter().max_by_key(|span| span.duration);
+    match slowest {
+        Some(slowest) => log::info!(
+            target: "app",
+            "the startup took {}ms, the slowest step is \"{}\" ({}ms)",
+            report.total,
+            slowest.name,
+            slowest.duration
+        ),
+        None => log::info!(target: "app", "the startup took {}ms", report.total),
+    }
+}
+
+pub fn startup_report() -> StartupReport {
+    startup().lock().report.clone()
+}
+
+fn new_span(name: &str, start: Instant) -> StartupSpan {
+    let begin = startup().lock().begin;
+    StartupSpan {
+        name: name.into(),
+        start: start.saturating_duration_since(begin).as_millis() as u64,
+        duration: start.elapsed().as_millis() as u64,
+    }
+}
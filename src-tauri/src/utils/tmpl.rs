@@ -34,3 +34,44 @@ function main(params) {
   return params;
 }
 ";
+
+/// routing preset, the domestic sites of iran go directly
+pub const ROUTING_IRAN: &str = "# Routing Preset: Iran
+
+prepend-rules:
+  - DOMAIN-SUFFIX,ir,DIRECT
+  - DOMAIN-SUFFIX,digikala.com,DIRECT
+  - DOMAIN-SUFFIX,aparat.com,DIRECT
+  - DOMAIN-SUFFIX,divar.ir,DIRECT
+  - DOMAIN-SUFFIX,snapp.taxi,DIRECT
+  - DOMAIN-SUFFIX,filimo.com,DIRECT
+  - DOMAIN-SUFFIX,namava.ir,DIRECT
+  - GEOIP,private,DIRECT,no-resolve
+  - GEOIP,IR,DIRECT,no-resolve
+";
+
+/// routing preset, the domestic sites of russia go directly
+pub const ROUTING_RUSSIA: &str = "# Routing Preset: Russia
+
+prepend-rules:
+  - DOMAIN-SUFFIX,ru,DIRECT
+  - DOMAIN-SUFFIX,su,DIRECT
+  - DOMAIN-SUFFIX,xn--p1ai,DIRECT
+  - DOMAIN-SUFFIX,yandex.net,DIRECT
+  - DOMAIN-SUFFIX,yandex.com,DIRECT
+  - DOMAIN-SUFFIX,vk.com,DIRECT
+  - DOMAIN-SUFFIX,userapi.com,DIRECT
+  - DOMAIN-SUFFIX,mail.ru,DIRECT
+  - GEOIP,private,DIRECT,no-resolve
+  - GEOIP,RU,DIRECT,no-resolve
+";
+
+/// routing preset, the domestic sites of china go directly
+pub const ROUTING_CHINA: &str = "# Routing Preset: China
+
+prepend-rules:
+  - DOMAIN-SUFFIX,cn,DIRECT
+  - GEOSITE,cn,DIRECT
+  - GEOIP,private,DIRECT,no-resolve
+  - GEOIP,CN,DIRECT,no-resolve
+";
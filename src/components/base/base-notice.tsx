@@ -1,3 +1,4 @@
+import i18next from "i18next";
 import { createRoot } from "react-dom/client";
 import { ReactNode, useState } from "react";
 import { Box, IconButton, Slide, Snackbar, Typography } from "@mui/material";
@@ -89,6 +90,10 @@ export const Notice: NoticeInstance = (props) => {
 
 (["info", "error", "success"] as const).forEach((type) => {
   Notice[type] = (message, duration) => {
+    // the known errors of the commands are the i18n keys
+    if (typeof message === "string" && message.startsWith("error_")) {
+      message = i18next.t(message);
+    }
     setTimeout(() => Notice({ type, message, duration }), 0);
   };
 });
@@ -0,0 +1,87 @@
+import { mutate } from "swr";
+import { useEffect, useState } from "react";
+import { useLockFn } from "ahooks";
+import { useTranslation } from "react-i18next";
+import { Typography } from "@mui/material";
+import { listen } from "@tauri-apps/api/event";
+import { confirmDeepLinkImport, getDeepLinkImports } from "@/services/cmds";
+import { BaseDialog, Notice } from "@/components/base";
+
+// ask before importing the profile of a `clash://install-config` link
+const DeepLinkDialog = () => {
+  const { t } = useTranslation();
+  const [pending, setPending] = useState<IDeepLinkImport[]>([]);
+
+  useEffect(() => {
+    // the links opened before the window was created
+    getDeepLinkImports()
+      .then((imports) => setPending(imports))
+      .catch(() => {});
+
+    const unlisten = listen<IDeepLinkImport>(
+      "verge://deep-link-import",
+      ({ payload }) =>
+        setPending((list) =>
+          list.some((item) => item.id === payload.id)
+            ? list
+            : [...list, payload]
+        )
+    );
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const current = pending[0];
+
+  const onConfirm = useLockFn(async (accept: boolean) => {
+    if (!current) return;
+    setPending((list) => list.filter((item) => item.id !== current.id));
+
+    try {
+      await confirmDeepLinkImport(current.id, accept);
+      if (accept) {
+        mutate("getProfiles");
+        Notice.success("Successfully import profile.");
+      }
+    } catch (err: any) {
+      Notice.error(err?.message || err.toString());
+    }
+  });
+
+  return (
+    <BaseDialog
+      open={!!current}
+      title={t("Import Profile from Link")}
+      contentSx={{ minWidth: 360, maxWidth: 400, userSelect: "text" }}
+      okBtn={t("Import")}
+      cancelBtn={t("Cancel")}
+      onOk={() => onConfirm(true)}
+      onCancel={() => onConfirm(false)}
+      onClose={() => onConfirm(false)}
+    >
+      {current && (
+        <>
+          {current.name && (
+            <Typography>
+              {t("Name")}: {current.name}
+            </Typography>
+          )}
+          <Typography>
+            {t("Source")}: {current.host}
+          </Typography>
+          <Typography
+            color="text.secondary"
+            variant="body2"
+            sx={{ wordBreak: "break-all" }}
+          >
+            {current.url}
+          </Typography>
+        </>
+      )}
+    </BaseDialog>
+  );
+};
+
+export default DeepLinkDialog;
@@ -17,6 +17,7 @@ import LayoutItem from "@/components/layout/layout-item";
 import LayoutControl from "@/components/layout/layout-control";
 import LayoutTraffic from "@/components/layout/layout-traffic";
 import UpdateButton from "@/components/layout/update-button";
+import DeepLinkDialog from "@/components/layout/deep-link-dialog";
 import useCustomTheme from "@/components/layout/use-custom-theme";
 import getSystem from "@/utils/get-system";
 import "dayjs/locale/zh-cn";
@@ -140,6 +141,8 @@ const Layout = () => {
               </BaseErrorBoundary>
             </div>
           </div>
+
+          <DeepLinkDialog />
         </Paper>
       </ThemeProvider>
     </SWRConfig>
@@ -58,12 +58,20 @@ export async function saveProfileFile(index: string, fileData: string) {
 }
 
 export async function importProfile(url: string) {
-  return invoke<void>("import_profile", {
+  return invoke<number>("import_profile", {
     url,
     option: { with_proxy: true },
   });
 }
 
+export async function getDeepLinkImports() {
+  return invoke<IDeepLinkImport[]>("get_deep_link_imports");
+}
+
+export async function confirmDeepLinkImport(id: string, accept: boolean) {
+  return invoke<void>("confirm_deep_link_import", { id, accept });
+}
+
 export async function updateProfile(index: string, option?: IProfileOption) {
   return invoke<void>("update_profile", { index, option });
 }
@@ -103,6 +111,11 @@ export async function patchClashConfig(payload: Partial<IConfigData>) {
   return invoke<void>("patch_clash_config", { payload });
 }
 
+/// the configured test urls are tried by order if `url` is not set
+export async function testProxyDelay(name: string, url?: string) {
+  return invoke<number>("test_proxy_delay", { name, url });
+}
+
 export async function getVergeConfig() {
   return invoke<IVergeConfig>("get_verge_config");
 }
@@ -1,4 +1,4 @@
-import { getProxyDelay } from "./api";
+import { testProxyDelay } from "./cmds";
 
 const hashKey = (name: string, group: string) => `${group ?? ""}::${name}`;
 
@@ -12,8 +12,10 @@ class DelayManager {
   // 每个分组的监听
   private groupListenerMap = new Map<string, () => void>();
 
-  setUrl(group: string, url: string) {
-    this.urlMap.set(group, url);
+  // 分组单独设置的测试链接，未设置时使用配置的链接列表
+  setUrl(group: string, url?: string) {
+    if (url) this.urlMap.set(group, url);
+    else this.urlMap.delete(group);
   }
 
   getUrl(group: string) {
@@ -74,8 +76,7 @@ class DelayManager {
 
     try {
       const url = this.getUrl(group);
-      const result = await getProxyDelay(name, url);
-      delay = result.delay;
+      delay = await testProxyDelay(name, url);
     } catch {
       delay = 1e6; // error
     }
@@ -110,11 +110,19 @@ interface IConnections {
  * Some interface for command
  */
 
+// the rejected value of the commands
+interface ICmdError {
+  code: string; // such as PORT_IN_USE, CORE_NOT_RUNNING, UNKNOWN
+  message: string; // the i18n key of the known error or the raw message
+  detail: string;
+}
+
 interface IClashInfo {
   // status: string;
   port?: number; // clash mixed port
   server?: string; // external-controller
   secret?: string;
+  socket?: string; // unix socket or named pipe, used by the backend
 }
 
 interface IProfileItem {
@@ -135,6 +143,7 @@ interface IProfileItem {
     total: number;
     expire: number;
   };
+  filtered?: number;
   option?: IProfileOption;
 }
 
@@ -143,6 +152,7 @@ interface IProfileOption {
   with_proxy?: boolean;
   self_proxy?: boolean;
   update_interval?: number;
+  filter_nodes?: boolean;
 }
 
 interface IProfilesConfig {
@@ -152,6 +162,14 @@ interface IProfilesConfig {
   items?: IProfileItem[];
 }
 
+// the profile of a deep link, downloaded after the confirmation
+interface IDeepLinkImport {
+  id: string;
+  name?: string;
+  url: string;
+  host: string;
+}
+
 interface IVergeConfig {
   language?: string;
   clash_core?: string;